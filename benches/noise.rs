@@ -0,0 +1,36 @@
+//! Compares how fast chunks generate with every kind of terrain noise, so there's something to go
+//! on when picking `world.terrain.noise`. Run it with `cargo bench --bench noise`.
+
+use cgmath::Point3;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use minecrab::{
+    settings::{TerrainNoise, WorldSettings},
+    world::{chunk::Chunk, generator, WORLD_HEIGHT},
+};
+
+/// Generates a column of chunks with every kind of noise. The rest of the world settings are
+/// the defaults.
+fn generate_column(c: &mut Criterion) {
+    let mut group = c.benchmark_group("terrain_noise");
+    group.throughput(Throughput::Elements(WORLD_HEIGHT as u64));
+
+    for &kind in TerrainNoise::ALL.iter() {
+        let mut settings = WorldSettings::default();
+        settings.terrain.noise = kind;
+        let generator = generator::from_settings(&settings);
+
+        group.bench_function(BenchmarkId::from_parameter(kind.name()), |b| {
+            b.iter(|| {
+                for y in 0..WORLD_HEIGHT {
+                    let mut chunk = Box::new(Chunk::default());
+                    generator.generate(&mut chunk, Point3::new(3, y, -2));
+                }
+            })
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, generate_column);
+criterion_main!(benches);
@@ -13,33 +13,13 @@ impl Aabb {
             && (self.min.z <= other.max.z && self.max.z >= other.min.z)
     }
 
-    /// Gets the corners of the AABB that should be checked when checking
-    /// collision with the world.
-    ///
-    /// Returns a `Vec` of all `Point3`s that cover the faces of `self` with
-    /// no more than 1 unit of distance between them.
-    pub fn get_corners(&self) -> Vec<Point3<f32>> {
-        let mut corners = Vec::new();
-
-        let mut x = self.min.x;
-        while x < self.max.x.ceil() {
-            let mut y = self.min.y;
-            while y < self.max.y.ceil() {
-                let mut z = self.min.z;
-                while z < self.max.z.ceil() {
-                    corners.push(Point3::new(
-                        x.min(self.max.x),
-                        y.min(self.max.y),
-                        z.min(self.max.z),
-                    ));
-                    z += 1.0;
-                }
-                y += 1.0;
-            }
-            x += 1.0;
-        }
-
-        corners
+    /// Returns every block cell the AABB overlaps, including the ones in the middle of it. Cells
+    /// it only touches from the outside, at its `max` side, don't count.
+    pub fn cells(&self) -> impl Iterator<Item = Point3<isize>> {
+        let min = self.min.map(|x| x.floor() as isize);
+        let max = self.max.map(|x| x.ceil() as isize - 1);
+        itertools::iproduct!(min.x..=max.x, min.y..=max.y, min.z..=max.z)
+            .map(|(x, y, z)| Point3::new(x, y, z))
     }
 }
 
@@ -51,3 +31,33 @@ impl Default for Aabb {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_every_overlapped_cell() {
+        // A player's body, 1.8 blocks high, standing on y = 10
+        let aabb = Aabb {
+            min: Point3::new(0.7, 10.0, -0.3),
+            max: Point3::new(1.3, 11.8, 0.3),
+        };
+        let cells: Vec<_> = aabb.cells().collect();
+
+        assert_eq!(cells.len(), 2 * 2 * 2);
+        for (x, y, z) in itertools::iproduct!([0, 1], [10, 11], [-1, 0]) {
+            assert!(cells.contains(&Point3::new(x, y, z)));
+        }
+    }
+
+    #[test]
+    fn leaves_out_cells_it_only_touches() {
+        let aabb = Aabb {
+            min: Point3::new(0.0, 0.0, 0.0),
+            max: Point3::new(1.0, 2.0, 1.0),
+        };
+        let cells: Vec<_> = aabb.cells().collect();
+        assert_eq!(cells, [Point3::new(0, 0, 0), Point3::new(0, 1, 0)]);
+    }
+}
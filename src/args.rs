@@ -0,0 +1,112 @@
+use std::path::PathBuf;
+
+use crate::settings::Settings;
+
+const USAGE: &str = "Usage: minecrab [options]
+
+Options:
+    --world <path>           Store the world in <path>
+    --seed <n>               Generate new chunks with seed <n>
+    --render-distance <n>    Load <n> chunks around the player in every direction
+    --fullscreen             Start in fullscreen
+    --import <file>          Read a world exported with F3+X into the world before starting
+    --metrics-csv <file>     Append the performance stats printed every second to <file>
+    --help                   Show this message";
+
+/// The command-line arguments. Everything but `--import` and `--metrics-csv` overrides a setting
+/// from `config.toml`, for this run only.
+#[derive(Debug, Default)]
+pub struct Args {
+    pub world: Option<PathBuf>,
+    pub seed: Option<u32>,
+    pub render_distance: Option<usize>,
+    pub fullscreen: bool,
+    pub import: Option<PathBuf>,
+    pub metrics_csv: Option<PathBuf>,
+}
+
+impl Args {
+    /// Parses the arguments minecrab was started with. Prints the usage and exits if they can't
+    /// be parsed, or if `--help` was passed.
+    pub fn parse() -> Self {
+        match Self::parse_from(std::env::args().skip(1)) {
+            Ok(Some(args)) => args,
+            Ok(None) => {
+                println!("{}", USAGE);
+                std::process::exit(0);
+            }
+            Err(message) => {
+                eprintln!("{}\n\n{}", message, USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    /// Returns `None` if the usage was asked for.
+    fn parse_from(mut args: impl Iterator<Item = String>) -> Result<Option<Self>, String> {
+        fn value(args: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, String> {
+            args.next().ok_or_else(|| format!("{} needs a value", flag))
+        }
+
+        fn number<T: std::str::FromStr>(string: String, flag: &str) -> Result<T, String> {
+            string
+                .parse()
+                .map_err(|_| format!("{} needs a number, not {:?}", flag, string))
+        }
+
+        let mut parsed = Self::default();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--world" => parsed.world = Some(value(&mut args, &arg)?.into()),
+                "--seed" => parsed.seed = Some(number(value(&mut args, &arg)?, &arg)?),
+                "--render-distance" => {
+                    parsed.render_distance = Some(number(value(&mut args, &arg)?, &arg)?)
+                }
+                "--fullscreen" => parsed.fullscreen = true,
+                "--import" => parsed.import = Some(value(&mut args, &arg)?.into()),
+                "--metrics-csv" => parsed.metrics_csv = Some(value(&mut args, &arg)?.into()),
+                "--help" | "-h" => return Ok(None),
+                _ => return Err(format!("Unknown argument {:?}", arg)),
+            }
+        }
+        Ok(Some(parsed))
+    }
+
+    /// Overrides the settings that were passed on the command line.
+    pub fn apply(&self, settings: &mut Settings) {
+        if let Some(world) = &self.world {
+            settings.world.path = Some(world.clone());
+        }
+        if let Some(seed) = self.seed {
+            settings.world.seed = seed;
+        }
+        if let Some(render_distance) = self.render_distance {
+            settings.graphics.render_distance = render_distance.max(1);
+        }
+        if self.fullscreen {
+            settings.window.fullscreen = true;
+        }
+    }
+
+    /// Puts back the settings from `original` that `apply` overrode, so they aren't saved to
+    /// `config.toml`. Settings that were changed while playing are kept.
+    pub fn restore(&self, settings: &mut Settings, original: &Settings) {
+        let mut overridden = original.clone();
+        self.apply(&mut overridden);
+
+        if self.world.is_some() && settings.world.path == overridden.world.path {
+            settings.world.path = original.world.path.clone();
+        }
+        if self.seed.is_some() && settings.world.seed == overridden.world.seed {
+            settings.world.seed = original.world.seed;
+        }
+        if self.render_distance.is_some()
+            && settings.graphics.render_distance == overridden.graphics.render_distance
+        {
+            settings.graphics.render_distance = original.graphics.render_distance;
+        }
+        if self.fullscreen && settings.window.fullscreen {
+            settings.window.fullscreen = original.window.fullscreen;
+        }
+    }
+}
@@ -1,13 +1,75 @@
+use std::f32::consts::FRAC_PI_4;
+
 use cgmath::{Matrix4, Point3, Rad, Vector3};
 
+/// Turns OpenGL's clip space, where depth goes from -1 at the near plane to 1 at the far plane,
+/// into wgpu's, with the depth reversed: from 1 at the near plane to 0 at the far plane.
+///
+/// Perspective crowds distant depths together, and reversing them puts those where floats are
+/// the most precise, near 0. That keeps distant faces from z-fighting. Everything drawn with depth
+/// testing has to use `Greater` comparisons, and the depth buffer is cleared to 0.
 #[rustfmt::skip]
 pub const OPENGL_TO_WGPU_MATRIX: Matrix4<f32> = Matrix4::new(
     1.0, 0.0, 0.0, 0.0,
     0.0, 1.0, 0.0, 0.0,
-    0.0, 0.0, 0.5, 0.0,
+    0.0, 0.0, -0.5, 0.0,
     0.0, 0.0, 0.5, 1.0,
 );
 
+/// A compass direction in the horizontal plane. North is towards -Z, east towards +X.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+    North,
+    NorthEast,
+}
+
+impl Direction {
+    /// The directions in order of increasing yaw, starting at a yaw of 0.
+    const BY_YAW: [Self; 8] = [
+        Self::East,
+        Self::SouthEast,
+        Self::South,
+        Self::SouthWest,
+        Self::West,
+        Self::NorthWest,
+        Self::North,
+        Self::NorthEast,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::East => "east",
+            Self::SouthEast => "southeast",
+            Self::South => "south",
+            Self::SouthWest => "southwest",
+            Self::West => "west",
+            Self::NorthWest => "northwest",
+            Self::North => "north",
+            Self::NorthEast => "northeast",
+        }
+    }
+
+    /// The world axes the direction points along, like `+x -z` for northeast.
+    pub fn axes(self) -> &'static str {
+        match self {
+            Self::East => "+x",
+            Self::SouthEast => "+x +z",
+            Self::South => "+z",
+            Self::SouthWest => "-x +z",
+            Self::West => "-x",
+            Self::NorthWest => "-x -z",
+            Self::North => "-z",
+            Self::NorthEast => "+x -z",
+        }
+    }
+}
+
 pub struct Camera {
     pub position: Point3<f32>,
     pub yaw: Rad<f32>,
@@ -31,6 +93,12 @@ impl Camera {
         )
     }
 
+    /// Returns the cardinal or ordinal direction closest to where the camera is looking.
+    pub fn facing(&self) -> Direction {
+        let octant = (self.yaw.0 / FRAC_PI_4).round() as i32;
+        Direction::BY_YAW[octant.rem_euclid(8) as usize]
+    }
+
     pub fn calculate_matrix(&self) -> Matrix4<f32> {
         Matrix4::look_to_rh(self.position, self.direction(), Vector3::unit_y())
     }
@@ -68,3 +136,31 @@ impl Projection {
             * cgmath::perspective(self.fov_y, self.aspect_ratio, self.z_near, self.z_far)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Deg, Vector4};
+
+    use super::*;
+
+    /// The depth a point straight ahead, `distance` away from the camera, ends up with.
+    fn depth(projection: &Projection, distance: f32) -> f32 {
+        let clip = projection.calculate_matrix() * Vector4::new(0.0, 0.0, -distance, 1.0);
+        clip.z / clip.w
+    }
+
+    #[test]
+    fn reverses_the_depth() {
+        let projection = Projection::new(1280, 720, Deg(45.0), 0.1, 300.0);
+        assert!((depth(&projection, 0.1) - 1.0).abs() < 1e-6);
+        assert!(depth(&projection, 300.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn tells_apart_depths_near_the_far_plane() {
+        let projection = Projection::new(1280, 720, Deg(45.0), 0.1, 300.0);
+        let further = depth(&projection, 299.0);
+        let closer = depth(&projection, 298.99);
+        assert!(closer > further, "{} <= {}", closer, further);
+    }
+}
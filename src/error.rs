@@ -0,0 +1,53 @@
+use thiserror::Error;
+
+use crate::{texture::TextureLoadError, world::block::BlockType};
+
+/// Everything that can go wrong in minecrab, split up so callers can tell errors that resolve
+/// themselves (like an outdated surface) apart from fatal ones.
+#[derive(Debug, Error)]
+pub enum MinecrabError {
+    #[error("No graphics adapter available")]
+    NoAdapter,
+    #[error("The graphics adapter can't present to this window")]
+    IncompatibleSurface,
+    #[error("Failed to acquire a graphics device")]
+    RequestDevice(#[from] wgpu::RequestDeviceError),
+    #[error("Failed to get the next frame")]
+    Surface(#[from] wgpu::SurfaceError),
+    #[error("The graphics device was lost")]
+    DeviceLost,
+
+    #[error(transparent)]
+    TextureLoad(#[from] TextureLoadError),
+    #[error("{block_type:?} uses texture {name:?}, which doesn't exist")]
+    UnknownTexture {
+        block_type: BlockType,
+        name: &'static str,
+    },
+
+    #[error("Failed to read shader {name}: {source}")]
+    ShaderRead {
+        name: &'static str,
+        source: std::io::Error,
+    },
+    #[error("Failed to compile shader {name}:\n{message}")]
+    ShaderCompile { name: &'static str, message: String },
+
+    #[error("Failed to read or write a file")]
+    Io(#[from] std::io::Error),
+    #[error("The export is from version {0} of the export format, which isn't supported")]
+    ExportVersion(u32),
+    #[error("Can't import into {0}, since there's already a world in it")]
+    ImportTarget(String),
+
+    #[error("Failed to open the world at {path}")]
+    OpenWorld { path: String, source: sled::Error },
+    #[error("Failed to access the chunk database")]
+    Database(#[from] sled::Error),
+    #[error("Failed to encode chunk")]
+    Encode(#[from] rmp_serde::encode::Error),
+    #[error("Failed to decode chunk")]
+    Decode(#[from] rmp_serde::decode::Error),
+}
+
+pub type Result<T> = std::result::Result<T, MinecrabError>;
@@ -22,4 +22,10 @@ impl<T: Vertex, I> Geometry<T, I> {
     pub fn index_count(&self) -> usize {
         self.indices.len()
     }
+
+    /// Returns how many bytes the vertex and index buffers for this geometry take up.
+    pub fn size_in_bytes(&self) -> usize {
+        self.vertices.len() * std::mem::size_of::<T>()
+            + self.indices.len() * std::mem::size_of::<I>()
+    }
 }
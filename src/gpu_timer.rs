@@ -0,0 +1,130 @@
+use std::{future::Future, pin::Pin, time::Duration};
+
+use futures::FutureExt;
+use wgpu::{Buffer, BufferAsyncError, CommandEncoder, QuerySet};
+
+use crate::render_context::RenderContext;
+
+/// The timestamps written each frame: before the world pass, between the world and HUD passes,
+/// and after the HUD pass.
+pub const TIMESTAMP_COUNT: u32 = 3;
+pub const TIMESTAMP_WORLD: u32 = 0;
+pub const TIMESTAMP_HUD: u32 = 1;
+pub const TIMESTAMP_END: u32 = 2;
+
+type MapFuture = Pin<Box<dyn Future<Output = Result<(), BufferAsyncError>> + Send>>;
+
+/// How long the GPU spent on the passes of a frame.
+#[derive(Debug, Clone, Copy)]
+pub struct GpuTimes {
+    pub world: Duration,
+    pub hud: Duration,
+}
+
+impl GpuTimes {
+    pub fn total(&self) -> Duration {
+        self.world + self.hud
+    }
+}
+
+/// Measures the passes of a frame with timestamp queries. Only available when the device
+/// supports `Features::TIMESTAMP_QUERY`.
+///
+/// Reading the results back takes a few frames, and frames that are rendered while an earlier
+/// frame's results are still being read aren't measured.
+pub struct GpuTimer {
+    query_set: QuerySet,
+    buffer: Buffer,
+    /// Nanoseconds per timestamp tick.
+    period: f32,
+
+    /// Whether the frame being encoded is measured.
+    measuring: bool,
+    /// Resolves once the timestamps of the last measured frame can be read.
+    mapping: Option<MapFuture>,
+}
+
+impl GpuTimer {
+    pub fn new(render_context: &RenderContext) -> Option<Self> {
+        let device = &render_context.device;
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("GPU timer query set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: TIMESTAMP_COUNT,
+        });
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("GPU timer buffer"),
+            size: TIMESTAMP_COUNT as u64 * 8,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        Some(Self {
+            query_set,
+            buffer,
+            period: render_context.queue.get_timestamp_period(),
+
+            measuring: false,
+            mapping: None,
+        })
+    }
+
+    /// Starts a frame. It's only measured if the buffer isn't still busy with an earlier one.
+    pub fn begin_frame(&mut self) {
+        self.measuring = self.mapping.is_none();
+    }
+
+    pub fn write_timestamp(&self, encoder: &mut CommandEncoder, index: u32) {
+        if self.measuring {
+            encoder.write_timestamp(&self.query_set, index);
+        }
+    }
+
+    /// Copies the frame's timestamps to the buffer. Call after the last timestamp is written.
+    pub fn resolve(&self, encoder: &mut CommandEncoder) {
+        if self.measuring {
+            encoder.resolve_query_set(&self.query_set, 0..TIMESTAMP_COUNT, &self.buffer, 0);
+        }
+    }
+
+    /// Starts reading the timestamps back. Call after the frame's commands are submitted.
+    pub fn end_frame(&mut self) {
+        if self.measuring {
+            self.mapping = Some(Box::pin(
+                self.buffer.slice(..).map_async(wgpu::MapMode::Read),
+            ));
+            self.measuring = false;
+        }
+    }
+
+    /// Returns the times of the last measured frame, once they've been read back.
+    pub fn poll(&mut self, render_context: &RenderContext) -> Option<GpuTimes> {
+        render_context.device.poll(wgpu::Maintain::Poll);
+
+        let result = self.mapping.as_mut()?.now_or_never()?;
+        self.mapping = None;
+        if let Err(error) = result {
+            eprintln!("Failed to read GPU timestamps: {:?}", error);
+            return None;
+        }
+
+        let timestamps: Vec<u64> = {
+            let view = self.buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice(&view).to_vec()
+        };
+        self.buffer.unmap();
+
+        let duration = |from: usize, to: usize| {
+            let ticks = timestamps[to].saturating_sub(timestamps[from]);
+            Duration::from_nanos((ticks as f64 * self.period as f64) as u64)
+        };
+        Some(GpuTimes {
+            world: duration(TIMESTAMP_WORLD as usize, TIMESTAMP_HUD as usize),
+            hud: duration(TIMESTAMP_HUD as usize, TIMESTAMP_END as usize),
+        })
+    }
+}
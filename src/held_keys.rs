@@ -0,0 +1,66 @@
+use fxhash::FxHashSet;
+use winit::event::{ElementState, VirtualKeyCode};
+
+/// The keys that are being held down, to tell pressing a key apart from the operating system
+/// repeating it while it's held.
+#[derive(Default)]
+pub struct HeldKeys {
+    keys: FxHashSet<VirtualKeyCode>,
+}
+
+impl HeldKeys {
+    /// Updates the held keys with a keyboard event. Returns whether the key was just pressed,
+    /// which is only true on the transition from released to pressed, so toggles can ignore key
+    /// repeats.
+    pub fn update(&mut self, key_code: VirtualKeyCode, state: ElementState) -> bool {
+        match state {
+            ElementState::Pressed => self.keys.insert(key_code),
+            ElementState::Released => {
+                self.keys.remove(&key_code);
+                false
+            }
+        }
+    }
+
+    pub fn is_held(&self, key_code: VirtualKeyCode) -> bool {
+        self.keys.contains(&key_code)
+    }
+
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn toggles_once_while_a_key_repeats() {
+        let mut held_keys = HeldKeys::default();
+        let mut toggled = false;
+        for _ in 0..5 {
+            if held_keys.update(VirtualKeyCode::F2, ElementState::Pressed) {
+                toggled ^= true;
+            }
+        }
+        assert!(toggled);
+        assert!(held_keys.is_held(VirtualKeyCode::F2));
+
+        held_keys.update(VirtualKeyCode::F2, ElementState::Released);
+        assert!(!held_keys.is_held(VirtualKeyCode::F2));
+        assert!(held_keys.update(VirtualKeyCode::F2, ElementState::Pressed));
+    }
+
+    #[test]
+    fn tracks_keys_separately() {
+        let mut held_keys = HeldKeys::default();
+        assert!(held_keys.update(VirtualKeyCode::F3, ElementState::Pressed));
+        assert!(held_keys.update(VirtualKeyCode::C, ElementState::Pressed));
+        assert!(!held_keys.update(VirtualKeyCode::F3, ElementState::Pressed));
+
+        held_keys.clear();
+        assert!(!held_keys.is_held(VirtualKeyCode::F3));
+        assert!(!held_keys.is_held(VirtualKeyCode::C));
+    }
+}
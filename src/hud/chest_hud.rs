@@ -0,0 +1,301 @@
+use wgpu::{BindGroup, BufferUsages, RenderPass};
+
+use crate::{
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    hud::{
+        hotbar_hud::block_icon_geometry, hotbar_slot_x, widgets_hud::WidgetsHud, UI_SCALE_X,
+        UI_SCALE_Y,
+    },
+    render_context::RenderContext,
+    text_renderer::{self, TextRenderer},
+    vertex::HudVertex,
+    world::{
+        block::{BlockType, Tool},
+        block_entity::{Chest, ItemStack, CHEST_SLOTS},
+    },
+};
+
+const COLUMNS: usize = 9;
+const ROWS: usize = CHEST_SLOTS / COLUMNS;
+
+/// The bottom of the lowest row of chest slots, in UI units from the bottom of the screen.
+const CHEST_BOTTOM: f32 = 32.0;
+/// The height of a row of slots, in UI units.
+const ROW_HEIGHT: f32 = 22.0;
+
+/// How far the tooltip is from the cursor, in normalized device coordinates.
+const TOOLTIP_OFFSET: f32 = text_renderer::DX * 0.5;
+/// How far the tooltip's background extends past its text.
+const TOOLTIP_PADDING: f32 = text_renderer::DX * 0.25;
+const TOOLTIP_COLOR: [f32; 4] = [0.1, 0.0, 0.2, 0.85];
+
+/// A slot of the chest screen, as returned by `ChestHud::slot_at`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChestHudSlot {
+    Chest(usize),
+    Hotbar(usize),
+}
+
+/// Shows the contents of an open chest above the hotbar.
+pub struct ChestHud {
+    widgets_bind_group: BindGroup,
+    text_renderer: TextRenderer,
+
+    background_buffers: GeometryBuffers<u16>,
+    icon_buffers: GeometryBuffers<u16>,
+    count_buffers: GeometryBuffers<u16>,
+    /// Describes the block in the slot under the cursor, if there is one.
+    tooltip_buffers: Option<GeometryBuffers<u16>>,
+    /// The block and cursor position `tooltip_buffers` were built for.
+    tooltip: Option<(BlockType, (f32, f32))>,
+
+    /// The chest contents the buffers were built for, or `None` if no chest is open.
+    shown: Option<[Option<ItemStack>; CHEST_SLOTS]>,
+}
+
+impl ChestHud {
+    pub fn new(render_context: &RenderContext) -> Self {
+        let (_, widgets_bind_group) = WidgetsHud::create_textures(render_context);
+        let empty = || {
+            GeometryBuffers::from_geometry(
+                render_context,
+                &Geometry::<HudVertex, _>::default(),
+                BufferUsages::empty(),
+            )
+        };
+
+        Self {
+            widgets_bind_group,
+            text_renderer: TextRenderer::new(render_context).unwrap(),
+
+            background_buffers: GeometryBuffers::from_geometry(
+                render_context,
+                &Self::background_geometry(),
+                BufferUsages::empty(),
+            ),
+            icon_buffers: empty(),
+            count_buffers: empty(),
+            tooltip_buffers: None,
+            tooltip: None,
+
+            shown: None,
+        }
+    }
+
+    /// The bottom of the given row of chest slots, in UI units. Row 0 is the top one.
+    fn row_bottom(row: usize) -> f32 {
+        CHEST_BOTTOM + (ROWS - 1 - row) as f32 * ROW_HEIGHT
+    }
+
+    /// Reuses the hotbar's texture for every row of the chest.
+    fn background_geometry() -> Geometry<HudVertex, u16> {
+        let texture_index = 0;
+        let color = [1.0; 4];
+
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        for row in 0..ROWS {
+            let bottom = -1.0 + UI_SCALE_Y * Self::row_bottom(row);
+            let top = bottom + UI_SCALE_Y * ROW_HEIGHT;
+
+            #[rustfmt::skip]
+            vertices.extend([
+                HudVertex { position: [UI_SCALE_X * -91.0, top   ], texture_coordinates: [  0.0 / 256.0,  0.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X *  91.0, top   ], texture_coordinates: [182.0 / 256.0,  0.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X *  91.0, bottom], texture_coordinates: [182.0 / 256.0, 22.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X * -91.0, bottom], texture_coordinates: [  0.0 / 256.0, 22.0 / 256.0], texture_index, color },
+            ]);
+
+            let i = (row * 4) as u16;
+            indices.extend([1 + i, i, 3 + i, 1 + i, 3 + i, 2 + i]);
+        }
+
+        Geometry::new(vertices, indices)
+    }
+
+    /// Rebuilds the slots if the contents of the chest changed. Pass `None` when no chest is open.
+    pub fn update(&mut self, render_context: &RenderContext, chest: Option<&Chest>) {
+        let slots = chest.map(|chest| chest.slots);
+        if slots == self.shown {
+            return;
+        }
+        self.shown = slots;
+
+        let slots = match slots {
+            Some(slots) => slots,
+            None => {
+                self.set_tooltip(render_context, None);
+                return;
+            }
+        };
+
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        let mut icons = Geometry::default();
+        let mut counts = Geometry::<HudVertex, u16>::default();
+        for (slot, stack) in slots.iter().enumerate() {
+            let stack = match stack {
+                Some(stack) => stack,
+                None => continue,
+            };
+
+            let x = (-92 + 20 * (slot % COLUMNS) as i32) as f32;
+            let y = Self::row_bottom(slot / COLUMNS);
+            let index_offset = icons.vertices.len() as u16;
+            icons.append(&mut block_icon_geometry(
+                texture_manager,
+                stack.block_type,
+                x,
+                y,
+                index_offset,
+            ));
+
+            if stack.count > 1 {
+                let index_offset = counts.vertices.len() as u16;
+                let mut count = self.text_renderer.string_geometry(
+                    UI_SCALE_X * (x + 11.0),
+                    -1.0 + UI_SCALE_Y * (y + 9.0),
+                    &stack.count.to_string(),
+                );
+                count.indices.iter_mut().for_each(|i| *i += index_offset);
+                counts.append(&mut count);
+            }
+        }
+
+        self.icon_buffers =
+            GeometryBuffers::from_geometry(render_context, &icons, BufferUsages::empty());
+        self.count_buffers =
+            GeometryBuffers::from_geometry(render_context, &counts, BufferUsages::empty());
+    }
+
+    /// Shows a tooltip describing the given block next to the cursor, which is in normalized
+    /// device coordinates, or hides it if `None` is given.
+    pub fn set_tooltip(
+        &mut self,
+        render_context: &RenderContext,
+        tooltip: Option<(BlockType, (f32, f32))>,
+    ) {
+        if tooltip == self.tooltip {
+            return;
+        }
+        self.tooltip = tooltip;
+
+        self.tooltip_buffers = tooltip.map(|(block_type, cursor)| {
+            let geometry = self.tooltip_geometry(block_type, cursor);
+            GeometryBuffers::from_geometry(render_context, &geometry, BufferUsages::empty())
+        });
+    }
+
+    /// Builds the text of the tooltip over a dark background, to the top right of the cursor. It's
+    /// moved to the left of the cursor if it would go off the right edge of the screen.
+    fn tooltip_geometry(
+        &self,
+        block_type: BlockType,
+        (x, y): (f32, f32),
+    ) -> Geometry<HudVertex, u16> {
+        let mut string = if block_type.is_breakable() {
+            format!("{} - hardness {}", block_type.name(), block_type.hardness())
+        } else {
+            format!("{} - unbreakable", block_type.name())
+        };
+        if block_type.preferred_tool() != Tool::None {
+            let tool = format!("{:?}", block_type.preferred_tool()).to_lowercase();
+            string += &format!(" - {}", tool);
+        }
+
+        let width = self.text_renderer.string_width(&string);
+        let left = if x + TOOLTIP_OFFSET + width + TOOLTIP_PADDING > 1.0 {
+            x - TOOLTIP_OFFSET - width
+        } else {
+            x + TOOLTIP_OFFSET
+        };
+        let top = y + TOOLTIP_OFFSET + text_renderer::DY;
+
+        // A negative texture index makes the UI shader use the vertex color as is
+        let vertex = |x, y| HudVertex {
+            position: [x, y],
+            texture_coordinates: [0.0, 0.0],
+            texture_index: -1,
+            color: TOOLTIP_COLOR,
+        };
+        let (background_left, background_right) =
+            (left - TOOLTIP_PADDING, left + width + TOOLTIP_PADDING);
+        let (background_top, background_bottom) = (
+            top + TOOLTIP_PADDING,
+            top - text_renderer::DY - TOOLTIP_PADDING,
+        );
+        let mut geometry = Geometry::new(
+            vec![
+                vertex(background_left, background_top),
+                vertex(background_right, background_top),
+                vertex(background_right, background_bottom),
+                vertex(background_left, background_bottom),
+            ],
+            vec![1, 0, 3, 1, 3, 2],
+        );
+
+        let mut text = self.text_renderer.string_geometry(left, top, &string);
+        text.indices.iter_mut().for_each(|i| *i += 4);
+        geometry.append(&mut text);
+        geometry
+    }
+
+    /// Returns the slot at the given position in normalized device coordinates, if any.
+    pub fn slot_at(x: f32, y: f32, hotbar_size: usize) -> Option<ChestHudSlot> {
+        let x = x / UI_SCALE_X;
+        let y = (y + 1.0) / UI_SCALE_Y;
+
+        if (0.0..ROW_HEIGHT).contains(&y) {
+            let slot = ((x - hotbar_slot_x(hotbar_size, 0) - 2.0) / 20.0).floor();
+            return (0.0..hotbar_size as f32)
+                .contains(&slot)
+                .then_some(ChestHudSlot::Hotbar(slot as usize));
+        }
+
+        let column = ((x + 91.0) / 20.0).floor();
+        if !(0.0..COLUMNS as f32).contains(&column) {
+            return None;
+        }
+        let column = column as usize;
+
+        let rows_from_bottom = ((y - CHEST_BOTTOM) / ROW_HEIGHT).floor();
+        if (0.0..ROWS as f32).contains(&rows_from_bottom) {
+            let row = ROWS - 1 - rows_from_bottom as usize;
+            return Some(ChestHudSlot::Chest(row * COLUMNS + column));
+        }
+
+        None
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_context: &'a RenderContext,
+        render_pass: &mut RenderPass<'a>,
+    ) -> usize {
+        if self.shown.is_none() {
+            return 0;
+        }
+
+        let mut triangle_count = 0;
+
+        render_pass.set_bind_group(0, &self.widgets_bind_group, &[]);
+        self.background_buffers.apply_buffers(render_pass);
+        triangle_count += self.background_buffers.draw_indexed(render_pass);
+
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
+        self.icon_buffers.apply_buffers(render_pass);
+        triangle_count += self.icon_buffers.draw_indexed(render_pass);
+
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        self.count_buffers.apply_buffers(render_pass);
+        triangle_count += self.count_buffers.draw_indexed(render_pass);
+
+        if let Some(tooltip_buffers) = &self.tooltip_buffers {
+            tooltip_buffers.apply_buffers(render_pass);
+            triangle_count += tooltip_buffers.draw_indexed(render_pass);
+        }
+
+        triangle_count
+    }
+}
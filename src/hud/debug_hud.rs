@@ -1,12 +1,20 @@
-use std::time::{Duration, Instant};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
 
 use cgmath::Point3;
 use wgpu::RenderPass;
 
 use crate::{
+    camera::Direction,
+    geometry::Geometry,
     geometry_buffers::GeometryBuffers,
+    gpu_timer::GpuTimes,
     render_context::RenderContext,
+    settings::LightingMode,
     text_renderer::{self, TextRenderer},
+    vertex::HudVertex,
 };
 
 pub struct DebugHud {
@@ -17,16 +25,118 @@ pub struct DebugHud {
     fps_frames: u32,
     fps_geometry_buffers: GeometryBuffers<u16>,
 
+    /// The time the most recent frames took in seconds, oldest first, if the graph is enabled.
+    frame_times: Option<VecDeque<f32>>,
+    /// The frames since the graph was last rebuilt.
+    graph_frames: u32,
+    graph_geometry_buffers: Option<GeometryBuffers<u16>>,
+    background: bool,
+
     coordinates_last: Point3<f32>,
     coordinates_geometry_buffers: GeometryBuffers<u16>,
+
+    /// The last GPU times read back, if the GPU can be measured.
+    gpu_times: Option<GpuTimes>,
+    /// How many draw calls the chunks took in the last frame.
+    chunk_draw_calls: usize,
+    /// How many fewer triangles the chunks have thanks to their level of detail, if it's enabled.
+    lod_triangles_saved: Option<usize>,
+    render_time_geometry_buffers: GeometryBuffers<u16>,
+
+    lighting_last: Option<LightingMode>,
+    /// Whether back faces are culled, shown after the lighting mode.
+    cull_back_faces: bool,
+    /// The name of the debug view the world is drawn with, shown after the lighting mode.
+    debug_view: Option<&'static str>,
+    lighting_geometry_buffers: GeometryBuffers<u16>,
+
+    facing_last: Option<Direction>,
+    facing_geometry_buffers: GeometryBuffers<u16>,
+
+    render_distance_geometry_buffers: GeometryBuffers<u16>,
+
+    /// The clock time and whether time was frozen, as last shown.
+    time_of_day_last: Option<((u32, u32), bool)>,
+    time_of_day_geometry_buffers: GeometryBuffers<u16>,
+
+    /// The distance between the points marked with the measuring tool, once both are marked.
+    measurement_geometry_buffers: Option<GeometryBuffers<u16>>,
+
+    /// The width of every line of text, to size the background to.
+    line_widths: [f32; LINE_COUNT],
+    /// A dark panel behind the text, if enabled in the settings.
+    background_geometry_buffers: Option<GeometryBuffers<u16>>,
 }
 
+/// The number of lines of text: FPS, coordinates, render time, lighting, facing, render distance,
+/// time of day and measurement.
+const LINE_COUNT: usize = 8;
+/// How far down every line is from the first, in lines.
+const LINE_OFFSETS: [f32; LINE_COUNT] = [0.0, 1.3, 2.6, 3.9, 5.2, 6.5, 7.8, 9.1];
+const RENDER_DISTANCE_LINE: usize = 5;
+const TIME_OF_DAY_LINE: usize = 6;
+const MEASUREMENT_LINE: usize = 7;
+/// How far the background extends past the text on every side.
+const BACKGROUND_PADDING: f32 = text_renderer::DX * 0.25;
+const BACKGROUND_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 0.5];
+
+/// The number of frames shown in the frame time graph, one bar each.
+const GRAPH_SAMPLES: usize = 120;
+/// How many frames pass between rebuilding the graph.
+const GRAPH_INTERVAL: u32 = 4;
+const GRAPH_LEFT: f32 = -0.98;
+const GRAPH_BOTTOM: f32 = -0.97;
+const GRAPH_BAR_WIDTH: f32 = 0.004;
+const GRAPH_HEIGHT: f32 = 0.25;
+/// The frame time, in seconds, that fills the graph's full height. Slower frames are cut off.
+const GRAPH_MAX_FRAME_TIME: f32 = 1.0 / 20.0;
+/// Frames up to 60 FPS are drawn green, up to 30 FPS yellow, and anything slower red.
+const GRAPH_GOOD_FRAME_TIME: f32 = 1.0 / 60.0;
+const GRAPH_OK_FRAME_TIME: f32 = 1.0 / 30.0;
+const GRAPH_GOOD_COLOR: [f32; 4] = [0.2, 0.8, 0.2, 0.8];
+const GRAPH_OK_COLOR: [f32; 4] = [0.9, 0.8, 0.1, 0.8];
+const GRAPH_BAD_COLOR: [f32; 4] = [0.9, 0.2, 0.1, 0.8];
+/// The line marking the 60 FPS frame time.
+const GRAPH_TARGET_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 0.4];
+
 impl DebugHud {
-    pub fn new(render_context: &RenderContext) -> Self {
+    pub fn new(render_context: &RenderContext, background: bool, frame_time_graph: bool) -> Self {
         let text_renderer = TextRenderer::new(render_context).unwrap();
         let fps_geometry_buffers = text_renderer.string_to_buffers(render_context, -0.98, 0.97, "");
         let coordinates_geometry_buffers =
             text_renderer.string_to_buffers(render_context, -0.98, 0.97 - text_renderer::DY, "");
+        let render_time_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 2.6,
+            "",
+        );
+        let lighting_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 3.9,
+            "",
+        );
+        let facing_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * 5.2,
+            "",
+        );
+        let render_distance_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * LINE_OFFSETS[RENDER_DISTANCE_LINE],
+            "",
+        );
+        let time_of_day_geometry_buffers = text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * LINE_OFFSETS[TIME_OF_DAY_LINE],
+            "",
+        );
+        let background_geometry_buffers =
+            background.then(|| Self::background_to_buffers(render_context, (0.0, 0)));
 
         Self {
             text_renderer,
@@ -36,17 +146,286 @@ impl DebugHud {
             fps_frames: 0,
             fps_geometry_buffers,
 
+            frame_times: frame_time_graph.then(|| VecDeque::with_capacity(GRAPH_SAMPLES)),
+            graph_frames: 0,
+            graph_geometry_buffers: None,
+            background,
+
             coordinates_last: Point3::new(0.0, 0.0, 0.0),
             coordinates_geometry_buffers,
+
+            gpu_times: None,
+            chunk_draw_calls: 0,
+            lod_triangles_saved: None,
+            render_time_geometry_buffers,
+
+            lighting_last: None,
+            cull_back_faces: true,
+            debug_view: None,
+            lighting_geometry_buffers,
+
+            facing_last: None,
+            facing_geometry_buffers,
+
+            render_distance_geometry_buffers,
+
+            time_of_day_last: None,
+            time_of_day_geometry_buffers,
+
+            measurement_geometry_buffers: None,
+
+            line_widths: [0.0; LINE_COUNT],
+            background_geometry_buffers,
         }
     }
 
-    pub fn update(&mut self, render_context: &RenderContext, position: &Point3<f32>) {
+    /// Builds a panel that fits behind text with the given extent, as returned by
+    /// `background_extent`.
+    fn background_to_buffers(
+        render_context: &RenderContext,
+        (width, last_line): (f32, usize),
+    ) -> GeometryBuffers<u16> {
+        let left = -0.98 - BACKGROUND_PADDING;
+        let right = -0.98 + width + BACKGROUND_PADDING;
+        let top = 0.97 + BACKGROUND_PADDING;
+        let bottom =
+            0.97 - text_renderer::DY * (LINE_OFFSETS[last_line] + 1.0) - BACKGROUND_PADDING;
+
+        // A negative texture index makes the UI shader use the vertex color as is
+        let vertex = |x, y| HudVertex {
+            position: [x, y],
+            texture_coordinates: [0.0, 0.0],
+            texture_index: -1,
+            color: BACKGROUND_COLOR,
+        };
+        let geometry = Geometry::new(
+            vec![
+                vertex(left, top),
+                vertex(right, top),
+                vertex(right, bottom),
+                vertex(left, bottom),
+            ],
+            vec![1, 0, 3, 1, 3, 2],
+        );
+        GeometryBuffers::from_geometry(render_context, &geometry, wgpu::BufferUsages::empty())
+    }
+
+    /// Builds a bar for every frame time, scrolling in from the right, over a panel if the
+    /// background is enabled.
+    fn graph_to_buffers(
+        render_context: &RenderContext,
+        frame_times: &VecDeque<f32>,
+        background: bool,
+    ) -> GeometryBuffers<u16> {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut quad = |left: f32, right: f32, bottom: f32, top: f32, color: [f32; 4]| {
+            let index = vertices.len() as u16;
+            // A negative texture index makes the UI shader use the vertex color as is
+            let vertex = |x, y| HudVertex {
+                position: [x, y],
+                texture_coordinates: [0.0, 0.0],
+                texture_index: -1,
+                color,
+            };
+            vertices.extend(vec![
+                vertex(left, top),
+                vertex(right, top),
+                vertex(right, bottom),
+                vertex(left, bottom),
+            ]);
+            indices.extend(vec![1, 0, 3, 1, 3, 2].into_iter().map(|i| index + i));
+        };
+
+        let right = GRAPH_LEFT + GRAPH_BAR_WIDTH * GRAPH_SAMPLES as f32;
+        let top = GRAPH_BOTTOM + GRAPH_HEIGHT;
+        if background {
+            quad(
+                GRAPH_LEFT - BACKGROUND_PADDING,
+                right + BACKGROUND_PADDING,
+                GRAPH_BOTTOM - BACKGROUND_PADDING,
+                top + BACKGROUND_PADDING,
+                BACKGROUND_COLOR,
+            );
+        }
+
+        let height = |frame_time: f32| GRAPH_HEIGHT * (frame_time / GRAPH_MAX_FRAME_TIME).min(1.0);
+        let first_bar = GRAPH_SAMPLES - frame_times.len();
+        for (i, &frame_time) in frame_times.iter().enumerate() {
+            let left = GRAPH_LEFT + GRAPH_BAR_WIDTH * (first_bar + i) as f32;
+            let color = if frame_time <= GRAPH_GOOD_FRAME_TIME {
+                GRAPH_GOOD_COLOR
+            } else if frame_time <= GRAPH_OK_FRAME_TIME {
+                GRAPH_OK_COLOR
+            } else {
+                GRAPH_BAD_COLOR
+            };
+            quad(
+                left,
+                left + GRAPH_BAR_WIDTH,
+                GRAPH_BOTTOM,
+                GRAPH_BOTTOM + height(frame_time),
+                color,
+            );
+        }
+
+        let target = GRAPH_BOTTOM + height(GRAPH_GOOD_FRAME_TIME);
+        quad(
+            GRAPH_LEFT,
+            right,
+            target,
+            target + GRAPH_BAR_WIDTH / 2.0,
+            GRAPH_TARGET_COLOR,
+        );
+
+        let geometry = Geometry::new(vertices, indices);
+        GeometryBuffers::from_geometry(render_context, &geometry, wgpu::BufferUsages::empty())
+    }
+
+    /// Returns the width of the widest line, and the index of the last line that isn't empty.
+    fn background_extent(&self) -> (f32, usize) {
+        let widest = self.line_widths.iter().copied().fold(0.0, f32::max);
+        let last_line = self.line_widths.iter().rposition(|&width| width > 0.0);
+        (widest, last_line.unwrap_or(0))
+    }
+
+    /// Records the width of a line of text that changed, and resizes the background if the text's
+    /// extent changed with it.
+    fn set_line_width(&mut self, render_context: &RenderContext, line: usize, string: &str) {
+        let extent = self.background_extent();
+        self.line_widths[line] = self.text_renderer.string_width(string);
+        let new_extent = self.background_extent();
+
+        if self.background_geometry_buffers.is_some() && new_extent != extent {
+            self.background_geometry_buffers =
+                Some(Self::background_to_buffers(render_context, new_extent));
+        }
+    }
+
+    /// Shows the render distance, and whether it's being adjusted automatically.
+    pub fn set_render_distance(
+        &mut self,
+        render_context: &RenderContext,
+        distance: usize,
+        auto: bool,
+    ) {
+        let string = if auto {
+            format!("render distance {} (auto)", distance)
+        } else {
+            format!("render distance {}", distance)
+        };
+        self.render_distance_geometry_buffers = self.text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * LINE_OFFSETS[RENDER_DISTANCE_LINE],
+            &string,
+        );
+        self.set_line_width(render_context, RENDER_DISTANCE_LINE, &string);
+    }
+
+    /// Shows the time of day as a clock, and whether it's frozen. Only rebuilds the text when the
+    /// shown minute changes.
+    pub fn set_time_of_day(
+        &mut self,
+        render_context: &RenderContext,
+        (hours, minutes): (u32, u32),
+        frozen: bool,
+    ) {
+        if self.time_of_day_last == Some(((hours, minutes), frozen)) {
+            return;
+        }
+        self.time_of_day_last = Some(((hours, minutes), frozen));
+
+        let string = if frozen {
+            format!("time {:02}:{:02} (frozen)", hours, minutes)
+        } else {
+            format!("time {:02}:{:02}", hours, minutes)
+        };
+        self.time_of_day_geometry_buffers = self.text_renderer.string_to_buffers(
+            render_context,
+            -0.98,
+            0.97 - text_renderer::DY * LINE_OFFSETS[TIME_OF_DAY_LINE],
+            &string,
+        );
+        self.set_line_width(render_context, TIME_OF_DAY_LINE, &string);
+    }
+
+    /// Shows how many draw calls the chunks took, along with the render time.
+    pub fn set_chunk_draw_calls(&mut self, chunk_draw_calls: usize) {
+        self.chunk_draw_calls = chunk_draw_calls;
+    }
+
+    /// Shows how many triangles the chunks' level of detail saves, after the draw calls.
+    pub fn set_lod_triangles_saved(&mut self, lod_triangles_saved: Option<usize>) {
+        self.lod_triangles_saved = lod_triangles_saved;
+    }
+
+    /// Shows whether back faces are culled, on the line with the lighting mode.
+    pub fn set_cull_back_faces(&mut self, cull_back_faces: bool) {
+        self.cull_back_faces = cull_back_faces;
+        // Rebuild the line on the next update
+        self.lighting_last = None;
+    }
+
+    /// Shows which debug view the world is drawn with, on the line with the lighting mode. `None`
+    /// means the regular colors.
+    pub fn set_debug_view(&mut self, debug_view: Option<&'static str>) {
+        self.debug_view = debug_view;
+        // Rebuild the line on the next update
+        self.lighting_last = None;
+    }
+
+    /// Shows a line describing a measurement below the rest, or hides it.
+    pub fn set_measurement(&mut self, render_context: &RenderContext, measurement: Option<&str>) {
+        let string = measurement.unwrap_or("");
+        self.measurement_geometry_buffers = measurement.map(|string| {
+            self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * LINE_OFFSETS[MEASUREMENT_LINE],
+                string,
+            )
+        });
+        self.set_line_width(render_context, MEASUREMENT_LINE, string);
+    }
+
+    /// Shows the GPU times once any have been read back, and the CPU time spent encoding the
+    /// frame until then, or if the GPU can't be measured.
+    pub fn update(
+        &mut self,
+        render_context: &RenderContext,
+        position: &Point3<f32>,
+        facing: Direction,
+        render_time: Duration,
+        gpu_times: Option<GpuTimes>,
+        lighting: LightingMode,
+    ) {
+        if gpu_times.is_some() {
+            self.gpu_times = gpu_times;
+        }
+
         let elapsed = self.fps_instant.elapsed();
         self.fps_instant = Instant::now();
         self.fps_elapsed += elapsed;
         self.fps_frames += 1;
 
+        if let Some(frame_times) = &mut self.frame_times {
+            if frame_times.len() == GRAPH_SAMPLES {
+                frame_times.pop_front();
+            }
+            frame_times.push_back(elapsed.as_secs_f32());
+
+            self.graph_frames += 1;
+            if self.graph_frames >= GRAPH_INTERVAL {
+                self.graph_frames = 0;
+                self.graph_geometry_buffers = Some(Self::graph_to_buffers(
+                    render_context,
+                    frame_times,
+                    self.background,
+                ));
+            }
+        }
+
         if self.fps_elapsed.as_millis() >= 500 {
             let frametime = self.fps_elapsed / self.fps_frames;
             let fps = 1.0 / frametime.as_secs_f32();
@@ -55,6 +434,32 @@ impl DebugHud {
             self.fps_geometry_buffers =
                 self.text_renderer
                     .string_to_buffers(render_context, -0.98, 0.97, &string);
+            self.set_line_width(render_context, 0, &string);
+
+            let mut string = match self.gpu_times {
+                Some(gpu_times) => format!(
+                    "gpu {:.2}ms (world {:.2}ms, hud {:.2}ms), {} chunk draws",
+                    gpu_times.total().as_secs_f32() * 1000.0,
+                    gpu_times.world.as_secs_f32() * 1000.0,
+                    gpu_times.hud.as_secs_f32() * 1000.0,
+                    self.chunk_draw_calls,
+                ),
+                None => format!(
+                    "cpu {:.2}ms, {} chunk draws",
+                    render_time.as_secs_f32() * 1000.0,
+                    self.chunk_draw_calls,
+                ),
+            };
+            if let Some(saved) = self.lod_triangles_saved {
+                string += &format!(", {} tris saved by LOD", saved);
+            }
+            self.render_time_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 2.6,
+                &string,
+            );
+            self.set_line_width(render_context, 2, &string);
 
             self.fps_elapsed = Duration::from_secs(0);
             self.fps_frames = 0;
@@ -68,12 +473,54 @@ impl DebugHud {
                 0.97 - text_renderer::DY * 1.3,
                 &string,
             );
+            self.set_line_width(render_context, 1, &string);
+        }
+
+        if self.lighting_last != Some(lighting) {
+            self.lighting_last = Some(lighting);
+            let mut string = match lighting {
+                LightingMode::Flat => "flat lighting",
+                LightingMode::Smooth => "smooth lighting",
+            }
+            .to_string();
+            if !self.cull_back_faces {
+                string += ", no culling";
+            }
+            if let Some(debug_view) = self.debug_view {
+                string += &format!(", showing {}", debug_view);
+            }
+            self.lighting_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 3.9,
+                &string,
+            );
+            self.set_line_width(render_context, 3, &string);
+        }
+
+        if self.facing_last != Some(facing) {
+            self.facing_last = Some(facing);
+            let string = format!("facing {} ({})", facing.name(), facing.axes());
+            self.facing_geometry_buffers = self.text_renderer.string_to_buffers(
+                render_context,
+                -0.98,
+                0.97 - text_renderer::DY * 5.2,
+                &string,
+            );
+            self.set_line_width(render_context, 4, &string);
         }
     }
 
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
         let mut triangle_count = 0;
 
+        // Render the background first, so the text is drawn over it
+        if let Some(background_geometry_buffers) = &self.background_geometry_buffers {
+            background_geometry_buffers.apply_buffers(render_pass);
+            render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+            triangle_count += background_geometry_buffers.draw_indexed(render_pass);
+        }
+
         // Render the FPS text
         self.fps_geometry_buffers.apply_buffers(render_pass);
         render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
@@ -84,6 +531,48 @@ impl DebugHud {
         render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
         triangle_count += self.coordinates_geometry_buffers.draw_indexed(render_pass);
 
+        // Render the render time text
+        self.render_time_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.render_time_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the lighting mode text
+        self.lighting_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.lighting_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the facing text
+        self.facing_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.facing_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the render distance text
+        self.render_distance_geometry_buffers
+            .apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self
+            .render_distance_geometry_buffers
+            .draw_indexed(render_pass);
+
+        // Render the time of day text
+        self.time_of_day_geometry_buffers.apply_buffers(render_pass);
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        triangle_count += self.time_of_day_geometry_buffers.draw_indexed(render_pass);
+
+        // Render the measurement text
+        if let Some(measurement_geometry_buffers) = &self.measurement_geometry_buffers {
+            measurement_geometry_buffers.apply_buffers(render_pass);
+            render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+            triangle_count += measurement_geometry_buffers.draw_indexed(render_pass);
+        }
+
+        // Render the frame time graph
+        if let Some(graph_geometry_buffers) = &self.graph_geometry_buffers {
+            graph_geometry_buffers.apply_buffers(render_pass);
+            render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+            triangle_count += graph_geometry_buffers.draw_indexed(render_pass);
+        }
+
         triangle_count
     }
 }
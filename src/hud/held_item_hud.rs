@@ -0,0 +1,119 @@
+use std::{f32::consts::PI, time::Duration};
+
+use cgmath::{Basis2, Deg, Point2, Rad, Rotation, Rotation2, Vector2};
+use wgpu::{BufferUsages, RenderPass};
+
+use crate::{
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    hud::{hotbar_hud::block_icon_geometry, UI_SCALE_X, UI_SCALE_Y},
+    render_context::RenderContext,
+    vertex::HudVertex,
+    world::block::BlockType,
+};
+
+/// How long a swing of the held item takes.
+const SWING_DURATION: Duration = Duration::from_millis(250);
+/// How much bigger the held item is than a hotbar icon.
+const SCALE: f32 = 4.0;
+/// Where the center of the held item is at rest, in UI units from the bottom center of the
+/// screen.
+const REST_POSITION: Point2<f32> = Point2::new(150.0, 30.0);
+/// The center of a hotbar icon, in UI units from its bottom left corner.
+const ICON_CENTER: Vector2<f32> = Vector2::new(12.0, 11.0);
+/// How far the held item moves towards the crosshair at the height of a swing, in UI units.
+const SWING_OFFSET: Vector2<f32> = Vector2::new(-40.0, 20.0);
+/// How far the held item tilts at the height of a swing.
+const SWING_ANGLE: Deg<f32> = Deg(25.0);
+
+/// The selected block, held in the bottom right corner of the screen. It swings whenever a block
+/// is broken or placed.
+pub struct HeldItemHud {
+    /// Whether the held item swings at all.
+    swing_enabled: bool,
+    /// How far along the current swing is, from 0 to 1, if the held item is swinging.
+    swing: Option<f32>,
+
+    /// The block and swing progress `geometry_buffers` was built for.
+    last: Option<(BlockType, Option<f32>)>,
+    geometry_buffers: Option<GeometryBuffers<u16>>,
+}
+
+impl HeldItemHud {
+    pub fn new(swing_enabled: bool) -> Self {
+        Self {
+            swing_enabled,
+            swing: None,
+
+            last: None,
+            geometry_buffers: None,
+        }
+    }
+
+    /// Starts swinging the held item. A swing that's already past its height starts over, so
+    /// quick actions keep it swinging without jumping back mid-swing.
+    pub fn swing(&mut self) {
+        if self.swing_enabled && self.swing.is_none_or(|progress| progress >= 0.5) {
+            self.swing = Some(0.0);
+        }
+    }
+
+    /// Advances the swing, and rebuilds the held item if it changed. Nothing is held if `block`
+    /// is `None`.
+    pub fn update(
+        &mut self,
+        render_context: &RenderContext,
+        dt: Duration,
+        block: Option<BlockType>,
+    ) {
+        self.swing = self.swing.and_then(|progress| {
+            let progress = progress + dt.as_secs_f32() / SWING_DURATION.as_secs_f32();
+            (progress < 1.0).then_some(progress)
+        });
+
+        let current = block.map(|block| (block, self.swing));
+        if current == self.last {
+            return;
+        }
+        self.last = current;
+
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        self.geometry_buffers = block.map(|block| {
+            let mut geometry = block_icon_geometry(texture_manager, block, 0.0, 0.0, 0);
+            Self::transform(&mut geometry, self.swing.unwrap_or(0.0));
+            GeometryBuffers::from_geometry(render_context, &geometry, BufferUsages::empty())
+        });
+    }
+
+    /// Moves a hotbar icon to where the held item is `progress` of the way through a swing, and
+    /// scales it up.
+    fn transform(geometry: &mut Geometry<HudVertex, u16>, progress: f32) {
+        let strength = (progress * PI).sin();
+        let rotation: Basis2<f32> = Rotation2::from_angle(Rad::from(SWING_ANGLE * strength));
+        let center = REST_POSITION + SWING_OFFSET * strength;
+
+        for vertex in &mut geometry.vertices {
+            // Back from screen coordinates to UI units, relative to the icon's center
+            let [x, y] = vertex.position;
+            let local = Vector2::new(x / UI_SCALE_X, (y + 1.0) / UI_SCALE_Y) - ICON_CENTER;
+            let position = center + rotation.rotate_vector(local * SCALE);
+            vertex.position = [UI_SCALE_X * position.x, -1.0 + UI_SCALE_Y * position.y];
+        }
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_context: &'a RenderContext,
+        render_pass: &mut RenderPass<'a>,
+    ) -> usize {
+        let geometry_buffers = match &self.geometry_buffers {
+            Some(geometry_buffers) => geometry_buffers,
+            None => return 0,
+        };
+
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
+        geometry_buffers.apply_buffers(render_pass);
+        geometry_buffers.draw_indexed(render_pass)
+    }
+}
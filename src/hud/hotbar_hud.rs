@@ -4,52 +4,81 @@ use wgpu::{BufferUsages, RenderPass};
 use crate::{
     geometry::Geometry,
     geometry_buffers::GeometryBuffers,
-    hud::{UI_SCALE_X, UI_SCALE_Y},
+    hud::{hotbar_slot_x, UI_SCALE_X, UI_SCALE_Y},
     render_context::RenderContext,
+    text_renderer::TextRenderer,
+    texture::TextureManager,
     vertex::HudVertex,
-    world::block::BlockType,
+    world::{
+        biome::Biome,
+        block::BlockType,
+        block_entity::{ItemStack, MAX_STACK_SIZE},
+    },
 };
 
 pub struct HotbarHud {
-    pub blocks: [Option<BlockType>; 9],
-    pub last_blocks: [Option<BlockType>; 9],
+    pub slots: Vec<Option<ItemStack>>,
+    pub last_slots: Vec<Option<ItemStack>>,
 
+    text_renderer: TextRenderer,
     pub geometry_buffers: GeometryBuffers<u16>,
+    count_buffers: GeometryBuffers<u16>,
 }
 
 impl HotbarHud {
-    pub fn new(render_context: &RenderContext) -> Self {
-        let hotbar_blocks = [
-            Some(BlockType::Dirt),
-            Some(BlockType::Stone),
-            Some(BlockType::Sand),
-            None,
-            Some(BlockType::Grass),
-            Some(BlockType::Cobblestone),
-            Some(BlockType::OakPlanks),
-            Some(BlockType::OakLog),
-            Some(BlockType::OakLeaves),
-        ];
+    pub fn new(render_context: &RenderContext, hotbar_size: usize) -> Self {
+        let mut slots: Vec<_> = [
+            BlockType::Dirt,
+            BlockType::Stone,
+            BlockType::Glass,
+            BlockType::StoneStairs,
+            BlockType::Chest,
+            BlockType::Cobblestone,
+            BlockType::OakPlanks,
+            BlockType::OakLog,
+            BlockType::Glowstone,
+        ]
+        .iter()
+        .map(|&block_type| {
+            Some(ItemStack {
+                block_type,
+                count: MAX_STACK_SIZE,
+            })
+        })
+        .collect();
+        slots.resize(hotbar_size, None);
 
-        Self {
-            blocks: hotbar_blocks,
-            last_blocks: [None; 9],
-
-            geometry_buffers: GeometryBuffers::from_geometry(
+        let empty = || {
+            GeometryBuffers::from_geometry(
                 render_context,
                 &Geometry::<HudVertex, _>::default(),
                 BufferUsages::empty(),
-            ),
+            )
+        };
+
+        Self {
+            slots,
+            last_slots: vec![None; hotbar_size],
+
+            text_renderer: TextRenderer::new(render_context).unwrap(),
+            geometry_buffers: empty(),
+            count_buffers: empty(),
         }
     }
 
     pub fn update(&mut self, render_context: &RenderContext) {
-        if self.blocks != self.last_blocks {
+        if self.slots != self.last_slots {
             self.geometry_buffers = GeometryBuffers::from_geometry(
                 render_context,
-                &self.block_vertices(),
+                &self.block_vertices(render_context.texture_manager.as_ref().unwrap()),
                 wgpu::BufferUsages::empty(),
             );
+            self.count_buffers = GeometryBuffers::from_geometry(
+                render_context,
+                &self.count_vertices(),
+                wgpu::BufferUsages::empty(),
+            );
+            self.last_slots = self.slots.clone();
         }
     }
 
@@ -62,125 +91,195 @@ impl HotbarHud {
 
         render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
         self.geometry_buffers.apply_buffers(render_pass);
-        self.geometry_buffers.draw_indexed(render_pass)
+        let mut triangle_count = self.geometry_buffers.draw_indexed(render_pass);
+
+        render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+        self.count_buffers.apply_buffers(render_pass);
+        triangle_count += self.count_buffers.draw_indexed(render_pass);
+        triangle_count
     }
 
-    fn block_vertices(&self) -> Geometry<HudVertex, u16> {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-
-        let mut index_offset = 0;
-        for slot in 0..9 {
-            if let Some(block) = self.blocks[slot as usize] {
-                let x = (-92 + 20 * slot as i32) as f32;
-                let texture_indices = block.texture_indices();
-                let color = block.color();
-
-                let color_left = color
-                    .mul_element_wise(Vector4::new(0.5, 0.5, 0.5, 1.0))
-                    .into();
-                let color_front = color
-                    .mul_element_wise(Vector4::new(0.15, 0.15, 0.15, 1.0))
-                    .into();
-                let color_top = color.into();
-
-                vertices.extend([
-                    // Left face
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 3.5],
-                        texture_coordinates: [1.0, 1.0],
-                        texture_index: texture_indices.0 as i32,
-                        color: color_left,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * 6.5],
-                        texture_coordinates: [0.0, 1.0],
-                        texture_index: texture_indices.0 as i32,
-                        color: color_left,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * 15.5],
-                        texture_coordinates: [0.0, 0.0],
-                        texture_index: texture_indices.0 as i32,
-                        color: color_left,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 12.5],
-                        texture_coordinates: [1.0, 0.0],
-                        texture_index: texture_indices.0 as i32,
-                        color: color_left,
-                    },
-                    // Front face
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * 15.5],
-                        texture_coordinates: [1.0, 0.0],
-                        texture_index: texture_indices.3 as i32,
-                        color: color_front,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 12.5],
-                        texture_coordinates: [0.0, 0.0],
-                        texture_index: texture_indices.3 as i32,
-                        color: color_front,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 3.5],
-                        texture_coordinates: [0.0, 1.0],
-                        texture_index: texture_indices.3 as i32,
-                        color: color_front,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * 6.5],
-                        texture_coordinates: [1.0, 1.0],
-                        texture_index: texture_indices.3 as i32,
-                        color: color_front,
-                    },
-                    // Top face
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * 15.5],
-                        texture_coordinates: [1.0, 0.0],
-                        texture_index: texture_indices.5 as i32,
-                        color: color_top,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 18.5],
-                        texture_coordinates: [0.0, 0.0],
-                        texture_index: texture_indices.5 as i32,
-                        color: color_top,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * 15.5],
-                        texture_coordinates: [0.0, 1.0],
-                        texture_index: texture_indices.5 as i32,
-                        color: color_top,
-                    },
-                    HudVertex {
-                        position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * 12.5],
-                        texture_coordinates: [1.0, 1.0],
-                        texture_index: texture_indices.5 as i32,
-                        color: color_top,
-                    },
-                ]);
-
-                #[rustfmt::skip]
-                indices.extend([
-                    // Left face
-                    2 + index_offset, index_offset, 1 + index_offset,
-                    3 + index_offset, index_offset, 2 + index_offset,
-
-                    // Right face
-                    6 + index_offset, 4 + index_offset, 5 + index_offset,
-                    7 + index_offset, 4 + index_offset, 6 + index_offset,
-
-                    // Top face
-                    10 + index_offset, 8 + index_offset, 9 + index_offset,
-                    11 + index_offset, 8 + index_offset, 10 + index_offset,
-                ]);
-
-                index_offset += 12;
+    fn block_vertices(&self, texture_manager: &TextureManager) -> Geometry<HudVertex, u16> {
+        let mut geometry = Geometry::default();
+        for (slot, stack) in self.slots.iter().enumerate() {
+            if let Some(stack) = stack {
+                let x = hotbar_slot_x(self.slots.len(), slot);
+                let index_offset = geometry.vertices.len() as u16;
+                geometry.append(&mut block_icon_geometry(
+                    texture_manager,
+                    stack.block_type,
+                    x,
+                    0.0,
+                    index_offset,
+                ));
             }
         }
+        geometry
+    }
 
-        Geometry::new(vertices, indices)
+    /// Builds the counts of the stacks of more than one block, like in the chest screen.
+    fn count_vertices(&self) -> Geometry<HudVertex, u16> {
+        let mut geometry = Geometry::default();
+        for (slot, stack) in self.slots.iter().enumerate() {
+            if let Some(stack) = stack.filter(|stack| stack.count > 1) {
+                let x = hotbar_slot_x(self.slots.len(), slot);
+                let index_offset = geometry.vertices.len() as u16;
+                let mut count = self.text_renderer.string_geometry(
+                    UI_SCALE_X * (x + 11.0),
+                    -1.0 + UI_SCALE_Y * 9.0,
+                    &stack.count.to_string(),
+                );
+                count.indices.iter_mut().for_each(|i| *i += index_offset);
+                geometry.append(&mut count);
+            }
+        }
+        geometry
     }
 }
+
+/// Builds the isometric icon of a block, as shown in a hotbar slot.
+///
+/// # Arguments
+///
+/// * `x` - The left side of the slot, in UI units from the center of the screen.
+/// * `y` - The bottom of the slot, in UI units from the bottom of the screen.
+/// * `index_offset` - Which geometry index to start at.
+pub fn block_icon_geometry(
+    texture_manager: &TextureManager,
+    block: BlockType,
+    x: f32,
+    y: f32,
+    index_offset: u16,
+) -> Geometry<HudVertex, u16> {
+    let texture_indices = texture_manager.block_textures(block);
+    let color = block.color();
+
+    let color_left = color
+        .mul_element_wise(Vector4::new(0.5, 0.5, 0.5, 1.0))
+        .into();
+    let color_front = color
+        .mul_element_wise(Vector4::new(0.15, 0.15, 0.15, 1.0))
+        .into();
+    let color_top = color.into();
+
+    let mut vertices = vec![
+        // Left face
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 3.5)],
+            texture_coordinates: [1.0, 1.0],
+            texture_index: texture_indices.0 as i32,
+            color: color_left,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * (y + 6.5)],
+            texture_coordinates: [0.0, 1.0],
+            texture_index: texture_indices.0 as i32,
+            color: color_left,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * (y + 15.5)],
+            texture_coordinates: [0.0, 0.0],
+            texture_index: texture_indices.0 as i32,
+            color: color_left,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 12.5)],
+            texture_coordinates: [1.0, 0.0],
+            texture_index: texture_indices.0 as i32,
+            color: color_left,
+        },
+        // Front face
+        HudVertex {
+            position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * (y + 15.5)],
+            texture_coordinates: [1.0, 0.0],
+            texture_index: texture_indices.3 as i32,
+            color: color_front,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 12.5)],
+            texture_coordinates: [0.0, 0.0],
+            texture_index: texture_indices.3 as i32,
+            color: color_front,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 3.5)],
+            texture_coordinates: [0.0, 1.0],
+            texture_index: texture_indices.3 as i32,
+            color: color_front,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * (y + 6.5)],
+            texture_coordinates: [1.0, 1.0],
+            texture_index: texture_indices.3 as i32,
+            color: color_front,
+        },
+        // Top face
+        HudVertex {
+            position: [UI_SCALE_X * (x + 19.0), -1.0 + UI_SCALE_Y * (y + 15.5)],
+            texture_coordinates: [1.0, 0.0],
+            texture_index: texture_indices.5 as i32,
+            color: color_top,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 18.5)],
+            texture_coordinates: [0.0, 0.0],
+            texture_index: texture_indices.5 as i32,
+            color: color_top,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 5.0), -1.0 + UI_SCALE_Y * (y + 15.5)],
+            texture_coordinates: [0.0, 1.0],
+            texture_index: texture_indices.5 as i32,
+            color: color_top,
+        },
+        HudVertex {
+            position: [UI_SCALE_X * (x + 12.0), -1.0 + UI_SCALE_Y * (y + 12.5)],
+            texture_coordinates: [1.0, 1.0],
+            texture_index: texture_indices.5 as i32,
+            color: color_top,
+        },
+    ];
+
+    #[rustfmt::skip]
+    let mut indices = vec![
+        // Left face
+        2 + index_offset, index_offset, 1 + index_offset,
+        3 + index_offset, index_offset, 2 + index_offset,
+
+        // Right face
+        6 + index_offset, 4 + index_offset, 5 + index_offset,
+        7 + index_offset, 4 + index_offset, 6 + index_offset,
+
+        // Top face
+        10 + index_offset, 8 + index_offset, 9 + index_offset,
+        11 + index_offset, 8 + index_offset, 10 + index_offset,
+    ];
+
+    // Draw the overlays on the left and front faces over them, in the plains' foliage color
+    let overlays = texture_manager.block_overlays(block);
+    let overlay_color = Biome::Plains.foliage_color();
+    for (face, overlay) in [(0, overlays[0]), (1, overlays[3])] {
+        if let Some(overlay) = overlay {
+            let start = vertices.len() as u16;
+            for i in face * 4..face * 4 + 4 {
+                let vertex = vertices[i];
+                vertices.push(HudVertex {
+                    texture_index: overlay as i32,
+                    color: overlay_color
+                        .mul_element_wise(Vector4::from(vertex.color))
+                        .into(),
+                    ..vertex
+                });
+            }
+            // The same triangles as the face, on the copied vertices
+            let face_indices = indices[face * 6..face * 6 + 6].to_vec();
+            indices.extend(
+                face_indices
+                    .iter()
+                    .map(|index| index + start - face as u16 * 4),
+            );
+        }
+    }
+
+    Geometry::new(vertices, indices)
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+
+use wgpu::RenderPass;
+
+use crate::{
+    geometry_buffers::GeometryBuffers,
+    hud::UI_SCALE_Y,
+    render_context::RenderContext,
+    text_renderer::{self, TextRenderer},
+};
+
+/// How long a message stays on screen.
+const MESSAGE_DURATION: Duration = Duration::from_secs(2);
+
+/// Shows a short message above the hotbar for a moment.
+pub struct MessageHud {
+    text_renderer: TextRenderer,
+    geometry_buffers: Option<GeometryBuffers<u16>>,
+    hide_at: Instant,
+}
+
+impl MessageHud {
+    pub fn new(render_context: &RenderContext) -> Self {
+        Self {
+            text_renderer: TextRenderer::new(render_context).unwrap(),
+            geometry_buffers: None,
+            hide_at: Instant::now(),
+        }
+    }
+
+    /// Shows the message centered above the hotbar, replacing the one that's shown.
+    pub fn show(&mut self, render_context: &RenderContext, message: &str) {
+        let x = -self.text_renderer.string_width(message) / 2.0;
+        let y = -1.0 + UI_SCALE_Y * 32.0 + text_renderer::DY;
+        self.geometry_buffers =
+            Some(
+                self.text_renderer
+                    .string_to_buffers(render_context, x, y, message),
+            );
+        self.hide_at = Instant::now() + MESSAGE_DURATION;
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
+        match &self.geometry_buffers {
+            Some(geometry_buffers) if Instant::now() < self.hide_at => {
+                geometry_buffers.apply_buffers(render_pass);
+                render_pass.set_bind_group(0, &self.text_renderer.bind_group, &[]);
+                geometry_buffers.draw_indexed(render_pass)
+            }
+            _ => 0,
+        }
+    }
+}
@@ -1,43 +1,124 @@
-use wgpu::{CommandEncoder, RenderPipeline};
+use wgpu::{CommandEncoder, RenderPipeline, ShaderModule};
 
 use crate::{
+    error,
+    gpu_timer::GpuTimes,
     render_context::RenderContext,
+    settings::{HudSettings, LightingMode},
+    shader,
     vertex::{HudVertex, Vertex},
     world::block::BlockType,
 };
 
-use self::{debug_hud::DebugHud, hotbar_hud::HotbarHud, widgets_hud::WidgetsHud};
+use self::{
+    chest_hud::ChestHud, debug_hud::DebugHud, held_item_hud::HeldItemHud, hotbar_hud::HotbarHud,
+    message_hud::MessageHud, widgets_hud::WidgetsHud,
+};
 
-use std::borrow::Cow;
+use std::{borrow::Cow, time::Duration};
 
+pub mod chest_hud;
 pub mod debug_hud;
+pub mod held_item_hud;
 pub mod hotbar_hud;
+pub mod message_hud;
 pub mod widgets_hud;
 
 // TODO update aspect ratio when resizing
 pub const UI_SCALE_X: f32 = 0.0045;
 pub const UI_SCALE_Y: f32 = 0.008;
 
+/// Returns the left side of the cursor around the given hotbar slot, in UI units from the center
+/// of the screen. The hotbar is centered horizontally, so this depends on its size.
+pub fn hotbar_slot_x(hotbar_size: usize, slot: usize) -> f32 {
+    20.0 * slot as f32 - 10.0 * hotbar_size as f32 - 2.0
+}
+
 pub struct Hud {
     pub widgets_hud: WidgetsHud,
     pub debug_hud: DebugHud,
+    pub held_item_hud: HeldItemHud,
     pub hotbar_hud: HotbarHud,
+    pub chest_hud: ChestHud,
+    pub message_hud: MessageHud,
 
     pub pipeline: RenderPipeline,
+    /// Draws the crosshair inverting the colors behind it, if `invert_crosshair` is set.
+    inverted_pipeline: RenderPipeline,
+    invert_crosshair: bool,
 }
 
 impl Hud {
-    pub fn new(render_context: &RenderContext) -> Self {
-        Self {
-            widgets_hud: WidgetsHud::new(render_context),
-            debug_hud: DebugHud::new(render_context),
-            hotbar_hud: HotbarHud::new(render_context),
+    pub fn new(render_context: &RenderContext, settings: &HudSettings) -> Self {
+        let hotbar_size = settings.hotbar_size.max(1);
+        let shader = render_context
+            .device
+            .create_shader_module(&wgpu::ShaderModuleDescriptor {
+                label: Some("UI shader"),
+                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/ui.wgsl"))),
+            });
 
-            pipeline: Self::create_render_pipeline(render_context),
+        Self {
+            widgets_hud: WidgetsHud::new(render_context, hotbar_size),
+            debug_hud: DebugHud::new(
+                render_context,
+                settings.debug_background,
+                settings.frame_time_graph,
+            ),
+            held_item_hud: HeldItemHud::new(settings.held_item_swing),
+            hotbar_hud: HotbarHud::new(render_context, hotbar_size),
+            chest_hud: ChestHud::new(render_context),
+            message_hud: MessageHud::new(render_context),
+
+            pipeline: Self::create_render_pipeline(render_context, &shader),
+            inverted_pipeline: Self::create_inverted_pipeline(render_context, &shader),
+            invert_crosshair: settings.invert_crosshair,
         }
     }
 
-    fn create_render_pipeline(render_context: &RenderContext) -> wgpu::RenderPipeline {
+    /// Reads `ui.wgsl` from disk and rebuilds the pipeline with it. If the shader doesn't compile,
+    /// the error is returned and the current pipeline is kept.
+    pub fn reload_shaders(&mut self, render_context: &RenderContext) -> error::Result<()> {
+        let shader = shader::load(render_context, "ui.wgsl")?;
+        self.pipeline = Self::create_render_pipeline(render_context, &shader);
+        self.inverted_pipeline = Self::create_inverted_pipeline(render_context, &shader);
+        Ok(())
+    }
+
+    fn create_render_pipeline(
+        render_context: &RenderContext,
+        module: &ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        Self::create_pipeline(
+            render_context,
+            module,
+            "main",
+            wgpu::BlendState::ALPHA_BLENDING,
+        )
+    }
+
+    /// A pipeline that draws white as the inverse of what's behind it, blended by alpha.
+    fn create_inverted_pipeline(
+        render_context: &RenderContext,
+        module: &ShaderModule,
+    ) -> wgpu::RenderPipeline {
+        let blend = wgpu::BlendState {
+            color: wgpu::BlendComponent {
+                src_factor: wgpu::BlendFactor::OneMinusDst,
+                dst_factor: wgpu::BlendFactor::OneMinusSrcAlpha,
+                operation: wgpu::BlendOperation::Add,
+            },
+            alpha: wgpu::BlendComponent::OVER,
+        };
+        Self::create_pipeline(render_context, module, "inverted", blend)
+    }
+
+    fn create_pipeline(
+        render_context: &RenderContext,
+        module: &ShaderModule,
+        fragment_entry_point: &str,
+        blend: wgpu::BlendState,
+    ) -> wgpu::RenderPipeline {
         let bind_group_layout =
             render_context
                 .device
@@ -66,13 +147,6 @@ impl Hud {
                     ],
                 });
 
-        let module = &render_context
-            .device
-            .create_shader_module(&wgpu::ShaderModuleDescriptor {
-                label: Some("UI shader"),
-                source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/ui.wgsl"))),
-            });
-
         let pipeline_layout =
             render_context
                 .device
@@ -94,10 +168,10 @@ impl Hud {
                 },
                 fragment: Some(wgpu::FragmentState {
                     module,
-                    entry_point: "main",
+                    entry_point: fragment_entry_point,
                     targets: &[wgpu::ColorTargetState {
                         format: render_context.format,
-                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        blend: Some(blend),
                         write_mask: wgpu::ColorWrites::ALL,
                     }],
                 }),
@@ -111,13 +185,23 @@ impl Hud {
         &mut self,
         render_context: &crate::render_context::RenderContext,
         camera: &crate::camera::Camera,
+        render_time: Duration,
+        gpu_times: Option<GpuTimes>,
+        lighting: LightingMode,
     ) {
-        self.debug_hud.update(render_context, &camera.position);
+        self.debug_hud.update(
+            render_context,
+            &camera.position,
+            camera.facing(),
+            render_time,
+            gpu_times,
+            lighting,
+        );
         self.hotbar_hud.update(render_context);
     }
 
-    pub fn render<'a>(
-        &'a self,
+    pub fn render(
+        &self,
         render_context: &RenderContext,
         encoder: &mut CommandEncoder,
         texture_view: &wgpu::TextureView,
@@ -134,15 +218,28 @@ impl Hud {
             }],
             depth_stencil_attachment: None,
         });
+
+        // The crosshair goes first, so it only inverts the world and not the rest of the HUD
+        let mut triangle_count = 0;
+        if self.invert_crosshair {
+            render_pass.set_pipeline(&self.inverted_pipeline);
+            triangle_count += self.widgets_hud.render_crosshair(&mut render_pass);
+        }
         render_pass.set_pipeline(&self.pipeline);
 
-        self.widgets_hud.render(&mut render_pass)
+        triangle_count
+            + self.held_item_hud.render(render_context, &mut render_pass)
+            + self
+                .widgets_hud
+                .render(&mut render_pass, !self.invert_crosshair)
             + self.debug_hud.render(&mut render_pass)
             + self.hotbar_hud.render(render_context, &mut render_pass)
+            + self.chest_hud.render(render_context, &mut render_pass)
+            + self.message_hud.render(&mut render_pass)
     }
 
     pub fn selected_block(&self) -> Option<BlockType> {
         // TODO The hotbar widget should be rendered by HotbarHud
-        self.hotbar_hud.blocks[self.widgets_hud.hotbar_cursor_position]
+        self.hotbar_hud.slots[self.widgets_hud.hotbar_cursor_position].map(|stack| stack.block_type)
     }
 }
@@ -1,10 +1,12 @@
 // TODO Might want to move the hotbar outside
+use std::ops::Range;
+
 use wgpu::{BindGroup, BufferUsages, RenderPass};
 
 use crate::{
     geometry::Geometry,
     geometry_buffers::GeometryBuffers,
-    hud::{UI_SCALE_X, UI_SCALE_Y},
+    hud::{hotbar_slot_x, UI_SCALE_X, UI_SCALE_Y},
     render_context::RenderContext,
     texture::Texture,
     vertex::{HudVertex, Vertex},
@@ -14,16 +16,14 @@ pub struct WidgetsHud {
     texture_bind_group: BindGroup,
     geometry_buffers: GeometryBuffers<u16>,
     pub hotbar_cursor_position: usize,
+    hotbar_size: usize,
 }
 
 impl WidgetsHud {
-    pub fn new(render_context: &RenderContext) -> Self {
+    pub fn new(render_context: &RenderContext, hotbar_size: usize) -> Self {
         let (_, texture_bind_group) = Self::create_textures(render_context);
 
-        let geometry = Geometry {
-            vertices: VERTICES.to_vec(),
-            indices: INDICES.to_vec(),
-        };
+        let geometry = Self::geometry(hotbar_size);
         let geometry_buffers =
             GeometryBuffers::from_geometry(render_context, &geometry, BufferUsages::COPY_DST);
 
@@ -31,10 +31,67 @@ impl WidgetsHud {
             texture_bind_group,
             geometry_buffers,
             hotbar_cursor_position: 0,
+            hotbar_size,
+        }
+    }
+
+    /// Builds the crosshair, the hotbar background stretched to `hotbar_size` slots and the
+    /// hotbar cursor, which `redraw_hotbar_cursor` moves around.
+    fn geometry(hotbar_size: usize) -> Geometry<HudVertex, u16> {
+        let mut vertices = CROSSHAIR_VERTICES.to_vec();
+        vertices.extend(Self::hotbar_cursor_vertices(hotbar_size, 0));
+
+        // The hotbar texture has a 1 pixel border on either side of 9 slots of 20 pixels each.
+        // Stretch it by repeating the slots, with the borders as caps on the ends.
+        let left = hotbar_slot_x(hotbar_size, 0) + 1.0;
+        let mut strips = vec![(left, left + 1.0, 0.0, 1.0)];
+        for slot in 0..hotbar_size {
+            let x = left + 1.0 + 20.0 * slot as f32;
+            let u = 1.0 + 20.0 * (slot % 9) as f32;
+            strips.push((x, x + 20.0, u, u + 20.0));
         }
+        let right = left + 1.0 + 20.0 * hotbar_size as f32;
+        strips.push((right, right + 1.0, 181.0, 182.0));
+
+        let texture_index = 0;
+        let color = [1.0; 4];
+        let mut indices = CROSSHAIR_INDICES.to_vec();
+        for (x0, x1, u0, u1) in strips {
+            let i = vertices.len() as u16;
+            #[rustfmt::skip]
+            vertices.extend([
+                HudVertex { position: [UI_SCALE_X * x0, -1.0 + UI_SCALE_Y * 22.0], texture_coordinates: [u0 / 256.0,  0.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X * x1, -1.0 + UI_SCALE_Y * 22.0], texture_coordinates: [u1 / 256.0,  0.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X * x1, -1.0                    ], texture_coordinates: [u1 / 256.0, 22.0 / 256.0], texture_index, color },
+                HudVertex { position: [UI_SCALE_X * x0, -1.0                    ], texture_coordinates: [u0 / 256.0, 22.0 / 256.0], texture_index, color },
+            ]);
+            indices.extend([1 + i, i, 3 + i, 1 + i, 3 + i, 2 + i]);
+        }
+
+        // Draw the cursor last, over the hotbar
+        indices.extend([5, 4, 7, 5, 7, 6]);
+
+        Geometry::new(vertices, indices)
     }
 
-    fn create_textures(render_context: &RenderContext) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
+    fn hotbar_cursor_vertices(hotbar_size: usize, position: usize) -> [HudVertex; 4] {
+        let x = hotbar_slot_x(hotbar_size, position);
+        let texture_index = 0;
+        let color = [1.0; 4];
+
+        #[rustfmt::skip]
+        let vertices = [
+            HudVertex { position: [UI_SCALE_X * (x       ), -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [  0.0 / 256.0,  22.0 / 256.0], texture_index, color },
+            HudVertex { position: [UI_SCALE_X * (x + 24.0), -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [ 24.0 / 256.0,  22.0 / 256.0], texture_index, color },
+            HudVertex { position: [UI_SCALE_X * (x + 24.0), -1.0 - UI_SCALE_Y *  1.0], texture_coordinates: [ 24.0 / 256.0,  46.0 / 256.0], texture_index, color },
+            HudVertex { position: [UI_SCALE_X * (x       ), -1.0 - UI_SCALE_Y *  1.0], texture_coordinates: [  0.0 / 256.0,  46.0 / 256.0], texture_index, color },
+        ];
+        vertices
+    }
+
+    pub fn create_textures(
+        render_context: &RenderContext,
+    ) -> (wgpu::BindGroupLayout, wgpu::BindGroup) {
         let bytes = std::fs::read("assets/gui/widgets.png").unwrap();
         let texture = Texture::from_bytes(render_context, &bytes, "Texture GUI widgets").unwrap();
 
@@ -101,74 +158,57 @@ impl WidgetsHud {
     }
 
     pub fn move_hotbar_cursor(&mut self, render_context: &RenderContext, delta: i32) {
-        self.hotbar_cursor_position =
-            (self.hotbar_cursor_position as i32 + delta).rem_euclid(9) as usize;
+        self.hotbar_cursor_position = (self.hotbar_cursor_position as i32 + delta)
+            .rem_euclid(self.hotbar_size as i32) as usize;
         self.redraw_hotbar_cursor(render_context);
     }
 
     pub fn redraw_hotbar_cursor(&self, render_context: &RenderContext) {
-        let x = (-92 + 20 * self.hotbar_cursor_position as i32) as f32;
-        let texture_index = 0;
-        let color = [1.0; 4];
-
-        #[rustfmt::skip]
-        let vertices = [
-            HudVertex { position: [UI_SCALE_X * (x       ), -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [  0.0 / 256.0,  22.0 / 256.0], texture_index, color },
-            HudVertex { position: [UI_SCALE_X * (x + 24.0), -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [ 24.0 / 256.0,  22.0 / 256.0], texture_index, color },
-            HudVertex { position: [UI_SCALE_X * (x + 24.0), -1.0 + UI_SCALE_Y * -1.0], texture_coordinates: [ 24.0 / 256.0,  46.0 / 256.0], texture_index, color },
-            HudVertex { position: [UI_SCALE_X * (x       ), -1.0 + UI_SCALE_Y * -1.0], texture_coordinates: [  0.0 / 256.0,  46.0 / 256.0], texture_index, color },
-        ];
-
+        let vertices = Self::hotbar_cursor_vertices(self.hotbar_size, self.hotbar_cursor_position);
         render_context.queue.write_buffer(
             &self.geometry_buffers.vertices,
-            HudVertex::descriptor().array_stride * 8,
+            HudVertex::descriptor().array_stride * CROSSHAIR_VERTICES.len() as u64,
             bytemuck::cast_slice(&vertices),
         );
     }
 
-    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
-        // Render the HUD elements
+    /// Draws the hotbar and, if `crosshair` is set, the crosshair.
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, crosshair: bool) -> usize {
+        let start = if crosshair {
+            0
+        } else {
+            CROSSHAIR_INDICES.len()
+        };
+        self.draw_indices(render_pass, start..self.geometry_buffers.index_count)
+    }
+
+    /// Draws only the crosshair, so it can be drawn with a different pipeline.
+    pub fn render_crosshair<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
+        self.draw_indices(render_pass, 0..CROSSHAIR_INDICES.len())
+    }
+
+    fn draw_indices<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        indices: Range<usize>,
+    ) -> usize {
         self.geometry_buffers.apply_buffers(render_pass);
         render_pass.set_bind_group(0, &self.texture_bind_group, &[]);
-        self.geometry_buffers.draw_indexed(render_pass);
-        render_pass.draw_indexed(0..self.geometry_buffers.index_count as u32, 0, 0..1);
-
-        INDICES.len() / 3
+        render_pass.draw_indexed(indices.start as u32..indices.end as u32, 0, 0..1);
+        indices.len() / 3
     }
 }
 
 #[rustfmt::skip]
-pub const VERTICES: [HudVertex; 12] = [
-    // Crosshair
+pub const CROSSHAIR_VERTICES: [HudVertex; 4] = [
     HudVertex { position: [UI_SCALE_X *  -8.0,        UI_SCALE_Y *  8.0], texture_coordinates: [240.0 / 256.0,   0.0 / 256.0], texture_index: 0, color: [1.0; 4] },
     HudVertex { position: [UI_SCALE_X *   8.0,        UI_SCALE_Y *  8.0], texture_coordinates: [  1.0,           0.0 / 256.0], texture_index: 0, color: [1.0; 4] },
     HudVertex { position: [UI_SCALE_X *   8.0,        UI_SCALE_Y * -8.0], texture_coordinates: [  1.0,          16.0 / 256.0], texture_index: 0, color: [1.0; 4] },
     HudVertex { position: [UI_SCALE_X *  -8.0,        UI_SCALE_Y * -8.0], texture_coordinates: [240.0 / 256.0,  16.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-
-    // Hotbar
-    HudVertex { position: [UI_SCALE_X * -91.0, -1.0 + UI_SCALE_Y * 22.0], texture_coordinates: [  0.0 / 256.0,   0.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X *  91.0, -1.0 + UI_SCALE_Y * 22.0], texture_coordinates: [182.0 / 256.0,   0.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X *  91.0, -1.0                    ], texture_coordinates: [182.0 / 256.0,  22.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X * -91.0, -1.0                    ], texture_coordinates: [  0.0 / 256.0,  22.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-
-    // Hotbar cursor
-    HudVertex { position: [UI_SCALE_X * -92.0, -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [  0.0 / 256.0,  22.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X * -68.0, -1.0 + UI_SCALE_Y * 23.0], texture_coordinates: [ 24.0 / 256.0,  22.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X * -68.0, -1.0 + UI_SCALE_Y * -1.0], texture_coordinates: [ 24.0 / 256.0,  46.0 / 256.0], texture_index: 0, color: [1.0; 4] },
-    HudVertex { position: [UI_SCALE_X * -92.0, -1.0 + UI_SCALE_Y * -1.0], texture_coordinates: [  0.0 / 256.0,  46.0 / 256.0], texture_index: 0, color: [1.0; 4] },
 ];
 
 #[rustfmt::skip]
-pub const INDICES: [u16; 18] = [
-    // Crosshair
+pub const CROSSHAIR_INDICES: [u16; 6] = [
     1, 0, 3,
     1, 3, 2,
-
-    // Hotbar
-    5, 4, 7,
-    5, 7, 6,
-
-    // Hotbar cursor
-    9, 8, 11,
-    9, 11, 10,
 ];
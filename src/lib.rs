@@ -0,0 +1,24 @@
+pub mod aabb;
+pub mod args;
+pub mod camera;
+pub mod error;
+pub mod geometry;
+pub mod geometry_buffers;
+pub mod gpu_timer;
+pub mod held_keys;
+pub mod hud;
+pub mod metrics;
+pub mod player;
+pub mod render_context;
+pub mod render_distance;
+pub mod settings;
+pub mod shader;
+pub mod state;
+pub mod text_renderer;
+pub mod texture;
+pub mod time;
+pub mod upscaler;
+pub mod utils;
+pub mod vertex;
+pub mod view;
+pub mod world;
@@ -1,28 +1,37 @@
-mod aabb;
-mod camera;
-mod geometry;
-mod geometry_buffers;
-mod hud;
-mod player;
-mod render_context;
-mod state;
-mod text_renderer;
-mod texture;
-mod time;
-mod utils;
-mod vertex;
-mod view;
-mod world;
-
-use std::time::{Duration, Instant};
+use std::{
+    path::Path,
+    time::{Duration, Instant},
+};
 use winit::{
     dpi::{PhysicalSize, Size},
     event::{ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent},
     event_loop::{ControlFlow, EventLoop},
-    window::{Window, WindowBuilder},
+    window::{Fullscreen, Window, WindowBuilder},
+};
+
+use minecrab::{
+    args::Args,
+    error::MinecrabError,
+    metrics::{MetricsCsv, MetricsRow},
+    settings::Settings,
+    state::State,
+    world::{export::ExportHeader, World},
 };
 
-use crate::state::State;
+fn set_fullscreen(window: &Window, fullscreen: bool) {
+    if !fullscreen {
+        window.set_fullscreen(None);
+        return;
+    }
+
+    match window
+        .current_monitor()
+        .or_else(|| window.primary_monitor())
+    {
+        Some(monitor) => window.set_fullscreen(Some(Fullscreen::Borderless(Some(monitor)))),
+        None => eprintln!("Couldn't find a monitor to go fullscreen on"),
+    }
+}
 
 fn handle_window_event(
     event: &WindowEvent,
@@ -43,6 +52,25 @@ fn handle_window_event(
             let _ = window.set_cursor_grab(false);
             window.set_cursor_visible(true);
             state.mouse_grabbed = false;
+            state.close_chest();
+            None
+        }
+        WindowEvent::KeyboardInput {
+            input:
+                KeyboardInput {
+                    state: ElementState::Pressed,
+                    virtual_keycode: Some(VirtualKeyCode::F11),
+                    ..
+                },
+            ..
+        } => {
+            let repeat = state.is_key_held(VirtualKeyCode::F11);
+            state.window_event(event);
+
+            if !repeat {
+                set_fullscreen(window, window.fullscreen().is_none());
+                state.settings.window.fullscreen = window.fullscreen().is_some();
+            }
             None
         }
         WindowEvent::Resized(physical_size) => {
@@ -59,6 +87,7 @@ fn handle_window_event(
             ..
         } => {
             if !state.mouse_grabbed
+                && !state.is_chest_open()
                 && *button == MouseButton::Left
                 && *mouse_state == ElementState::Pressed
             {
@@ -67,6 +96,13 @@ fn handle_window_event(
                 state.mouse_grabbed = true;
             } else {
                 state.window_event(event);
+
+                // Let go of the cursor so the chest's slots can be clicked
+                if state.mouse_grabbed && state.is_chest_open() {
+                    let _ = window.set_cursor_grab(false);
+                    window.set_cursor_visible(true);
+                    state.mouse_grabbed = false;
+                }
             }
             None
         }
@@ -74,6 +110,8 @@ fn handle_window_event(
             let _ = window.set_cursor_grab(false);
             window.set_cursor_visible(true);
             state.mouse_grabbed = false;
+            // Key releases don't arrive while unfocused
+            state.clear_held_keys();
             None
         }
         event => {
@@ -83,19 +121,90 @@ fn handle_window_event(
     }
 }
 
+/// Handles `minecrab --import <file>`, which reads a world exported with F3+X into the chunk
+/// database at `database_path` before starting. Exits if that fails.
+fn import_world(path: &Path, database_path: &Path) -> ExportHeader {
+    match World::import(path, database_path) {
+        Ok(header) => {
+            println!("Imported {}", path.display());
+            header
+        }
+        Err(error) => {
+            eprintln!(
+                "Failed to import {}: {:?}",
+                path.display(),
+                anyhow::Error::from(error)
+            );
+            std::process::exit(1);
+        }
+    }
+}
+
 fn main() {
     env_logger::init();
+    let args = Args::parse();
+    let file_settings = Settings::load_or_default();
+    let mut settings = file_settings.clone();
+    args.apply(&mut settings);
+
+    let imported = args
+        .import
+        .as_deref()
+        .map(|path| import_world(path, &settings.world.path()));
+    if let Some(header) = imported {
+        // Chunks that weren't generated before the export should match the ones that were
+        settings.world.seed = header.seed;
+    }
+
+    println!(
+        "World {} with seed {}, render distance {}{}",
+        settings.world.path().display(),
+        settings.world.seed,
+        settings.graphics.render_distance,
+        if settings.window.fullscreen {
+            ", fullscreen"
+        } else {
+            ""
+        },
+    );
+
     let event_loop = EventLoop::new();
     let window = WindowBuilder::new()
         .with_title("minecrab")
         .with_inner_size(Size::Physical(PhysicalSize {
-            width: 1280,
-            height: 720,
+            width: settings.window.width,
+            height: settings.window.height,
         }))
         .build(&event_loop)
         .unwrap();
+    set_fullscreen(&window, settings.window.fullscreen);
 
-    let mut state = futures::executor::block_on(State::new(&window));
+    let mut state = match futures::executor::block_on(State::new(&window, settings)) {
+        Ok(state) => state,
+        Err(error) => {
+            // anyhow prints the whole chain of causes
+            eprintln!("Failed to start minecrab: {:?}", anyhow::Error::from(error));
+            std::process::exit(1);
+        }
+    };
+    if let Some(header) = imported {
+        state.teleport(header.player_position.into());
+    }
+
+    let mut metrics_csv =
+        args.metrics_csv
+            .as_deref()
+            .and_then(|path| match MetricsCsv::open(path) {
+                Ok(metrics_csv) => Some(metrics_csv),
+                Err(error) => {
+                    eprintln!(
+                        "Failed to open {}, not logging metrics: {:?}",
+                        path.display(),
+                        anyhow::Error::from(error)
+                    );
+                    None
+                }
+            });
 
     let mut frames = 0;
     let mut frame_instant = Instant::now();
@@ -145,11 +254,35 @@ fn main() {
                         fps, fps_min, fps_max
                     );
                     println!(
-                        "{:>8} tris | {:>5} chunks",
+                        "{:>8} tris | {:>5} chunks | {:>4} rebuilds",
                         triangle_count,
-                        state.world.chunks.len()
+                        state.world.chunks.len(),
+                        state.world.chunk_rebuilds,
                     );
 
+                    if let Some(csv) = &mut metrics_csv {
+                        let row = MetricsRow {
+                            frames,
+                            frametime_avg: frametime,
+                            frametime_min,
+                            frametime_max,
+                            fps_avg: fps,
+                            fps_min,
+                            fps_max,
+                            triangles: triangle_count,
+                            chunks: state.world.chunks.len(),
+                            rebuilds: state.world.chunk_rebuilds,
+                        };
+                        if let Err(error) = csv.write_row(&row) {
+                            eprintln!(
+                                "Failed to write metrics, not logging them anymore: {:?}",
+                                error
+                            );
+                            metrics_csv = None;
+                        }
+                    }
+                    state.world.chunk_rebuilds = 0;
+
                     elapsed = Duration::from_secs(0);
                     frames = 0;
                     frametime_min = Duration::from_secs(1000);
@@ -161,27 +294,41 @@ fn main() {
                 last_render_time = now;
 
                 let render_time = match state.render() {
-                    Err(root_cause) => {
-                        match root_cause.downcast_ref::<wgpu::SurfaceError>() {
-                            // Recreate if lost
-                            Some(wgpu::SurfaceError::Lost) => {
-                                state.resize(state.window_size);
+                    Err(MinecrabError::Surface(error)) => {
+                        match error {
+                            // Reconfigure if lost, or if the window changed under us. The
+                            // latter happens a lot while resizing, so don't log it
+                            wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated => {
+                                state.configure_surface();
                             }
                             // The system is out of memory, we should probably quit
-                            Some(wgpu::SurfaceError::OutOfMemory) => {
+                            wgpu::SurfaceError::OutOfMemory => {
+                                eprintln!("Out of memory, exiting");
                                 *control_flow = ControlFlow::Exit;
                             }
-                            // All other errors should be resolved by the next frame
-                            Some(wgpu::SurfaceError::Timeout) => {
-                                eprintln!("TIMEOUT");
+                            // Should be resolved by the next frame
+                            wgpu::SurfaceError::Timeout => {
+                                eprintln!("Timed out waiting for the next frame");
                             }
-                            Some(wgpu::SurfaceError::Outdated) => {
-                                eprintln!("OUTDATED");
-                            }
-                            None => {}
                         }
                         return;
                     }
+                    Err(MinecrabError::DeviceLost) => {
+                        eprintln!("Lost the graphics device, recreating it");
+                        let recreated = futures::executor::block_on(state.recreate(&window));
+                        if let Err(error) = recreated {
+                            eprintln!(
+                                "Failed to recreate the graphics device, exiting: {:?}",
+                                anyhow::Error::from(error)
+                            );
+                            *control_flow = ControlFlow::Exit;
+                        }
+                        return;
+                    }
+                    Err(error) => {
+                        eprintln!("Failed to render: {:?}", error);
+                        return;
+                    }
 
                     Ok((triangle_count_, render_time)) => {
                         triangle_count = triangle_count_;
@@ -196,6 +343,28 @@ fn main() {
                 // request it.
                 window.request_redraw();
             }
+            Event::LoopDestroyed => {
+                let window_settings = &mut state.settings.window;
+                window_settings.fullscreen = window.fullscreen().is_some();
+                if !window_settings.fullscreen {
+                    let size = window.inner_size();
+                    window_settings.width = size.width;
+                    window_settings.height = size.height;
+                }
+
+                args.restore(&mut state.settings, &file_settings);
+                if let Err(error) = state.settings.save() {
+                    eprintln!("Failed to save settings: {:?}", error);
+                }
+                if let Err(error) = state.world.save_entities() {
+                    eprintln!("Failed to save entities: {:?}", error);
+                }
+                if let Some(csv) = &mut metrics_csv {
+                    if let Err(error) = csv.flush() {
+                        eprintln!("Failed to write metrics: {:?}", error);
+                    }
+                }
+            }
             _ => {}
         }
     });
@@ -0,0 +1,90 @@
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use crate::error;
+
+/// How many rows are written between flushes. Rows are written once a second.
+const FLUSH_INTERVAL: usize = 10;
+
+/// The performance stats of one second of frames.
+pub struct MetricsRow {
+    pub frames: u32,
+    pub frametime_avg: Duration,
+    pub frametime_min: Duration,
+    pub frametime_max: Duration,
+    pub fps_avg: u128,
+    pub fps_min: u128,
+    pub fps_max: u128,
+    pub triangles: usize,
+    pub chunks: usize,
+    pub rebuilds: usize,
+}
+
+/// Appends the stats printed every second to a CSV file, for `--metrics-csv`.
+pub struct MetricsCsv {
+    writer: BufWriter<File>,
+    rows_since_flush: usize,
+}
+
+impl MetricsCsv {
+    /// Opens the file for appending, and writes the header if it's empty, so several runs can go
+    /// into the same file.
+    pub fn open(path: &Path) -> error::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        let is_empty = file.metadata()?.len() == 0;
+
+        let mut writer = BufWriter::new(file);
+        if is_empty {
+            writeln!(
+                writer,
+                "timestamp,frames,frametime_avg_ms,frametime_min_ms,frametime_max_ms,\
+                 fps_avg,fps_min,fps_max,triangles,chunks,rebuilds"
+            )?;
+        }
+
+        Ok(Self {
+            writer,
+            rows_since_flush: 0,
+        })
+    }
+
+    /// Writes a row, timestamped with the seconds since the Unix epoch.
+    pub fn write_row(&mut self, row: &MetricsRow) -> error::Result<()> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let milliseconds = |duration: Duration| duration.as_secs_f32() * 1000.0;
+
+        writeln!(
+            self.writer,
+            "{:.3},{},{:.3},{:.3},{:.3},{},{},{},{},{},{}",
+            timestamp.as_secs_f64(),
+            row.frames,
+            milliseconds(row.frametime_avg),
+            milliseconds(row.frametime_min),
+            milliseconds(row.frametime_max),
+            row.fps_avg,
+            row.fps_min,
+            row.fps_max,
+            row.triangles,
+            row.chunks,
+            row.rebuilds,
+        )?;
+
+        self.rows_since_flush += 1;
+        if self.rows_since_flush >= FLUSH_INTERVAL {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> error::Result<()> {
+        self.rows_since_flush = 0;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
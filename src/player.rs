@@ -1,19 +1,98 @@
-use std::time::Duration;
+use std::{f32::consts::PI, time::Duration};
 
-use cgmath::{InnerSpace, Point3, Rad, Vector3};
+use cgmath::{Deg, InnerSpace, Point3, Rad, Vector3};
 
-use crate::{aabb::Aabb, render_context::RenderContext, utils, view::View, world::World};
+use crate::{
+    aabb::Aabb,
+    camera::Camera,
+    render_context::RenderContext,
+    settings::CameraFeel,
+    utils,
+    view::{CameraMode, View},
+    world::{block_view::BlockView, raycast},
+};
+
+/// The upwards speed a jump starts with.
+pub const JUMP_SPEED: f32 = 0.6;
+/// How fast the player has to be falling for landing to kick up dust. Falling from two blocks up
+/// is just fast enough, so regular jumps don't.
+const HARD_LANDING_SPEED: f32 = 0.8;
+/// How far the player sprints between kicking up dust, in blocks.
+const SPRINT_DUST_DISTANCE: f32 = 1.5;
+/// How much dust a sprinting step kicks up.
+const SPRINT_DUST: usize = 3;
+/// The most dust landing can kick up.
+const MAX_LANDING_DUST: usize = 16;
+/// How far along a `/goto` the chunks around its destination are loaded.
+const GOTO_WARMUP_PROGRESS: f32 = 0.75;
+
+/// A smooth flight of the camera from one place to another, started with `/goto`. It goes
+/// straight through blocks, easing in at the start and out at the end.
+pub struct CameraPath {
+    from: Point3<f32>,
+    to: Point3<f32>,
+    duration: Duration,
+    elapsed: Duration,
+    warmed_up: bool,
+}
+
+impl CameraPath {
+    pub fn new(from: Point3<f32>, to: Point3<f32>, duration: Duration) -> Self {
+        Self {
+            from,
+            to,
+            duration,
+            elapsed: Duration::ZERO,
+            warmed_up: false,
+        }
+    }
+
+    /// How far along the flight is, from 0 to 1.
+    fn progress(&self) -> f32 {
+        if self.duration.is_zero() {
+            return 1.0;
+        }
+        (self.elapsed.as_secs_f32() / self.duration.as_secs_f32()).min(1.0)
+    }
+
+    fn position(&self) -> Point3<f32> {
+        let t = self.progress();
+        let eased = t * t * (3.0 - 2.0 * t);
+        self.from + (self.to - self.from) * eased
+    }
+
+    /// Returns the destination once the flight gets close to it, so the chunks around it can be
+    /// loaded before arriving. Only returns it once.
+    pub fn take_warmup(&mut self) -> Option<Point3<f32>> {
+        if self.warmed_up || self.progress() < GOTO_WARMUP_PROGRESS {
+            return None;
+        }
+        self.warmed_up = true;
+        Some(self.to)
+    }
+}
 
 pub struct Player {
     pub sprinting: bool,
     pub grounded: bool,
     pub creative: bool,
+    /// Jump automatically when walking into a block that can be stepped onto.
+    pub auto_jump: bool,
 
     pub forward_pressed: bool,
     pub backward_pressed: bool,
     pub left_pressed: bool,
     pub right_pressed: bool,
     pub up_speed: f32,
+    /// How far the player has walked on the ground, in blocks. Drives the view bobbing.
+    pub walked_distance: f32,
+    /// How much of the view bobbing is applied, from 0 to 1. Eases in and out as the player starts
+    /// and stops walking, so the view doesn't freeze halfway through a step.
+    bob_strength: f32,
+    /// How far the camera turns per pixel the mouse moves, in radians.
+    pub mouse_sensitivity: f32,
+    /// Moves the camera instead of the movement keys while it's flying somewhere.
+    pub camera_path: Option<CameraPath>,
 
     pub view: View,
 }
@@ -26,22 +105,37 @@ impl Player {
             sprinting: false,
             grounded: false,
             creative: false,
+            auto_jump: false,
 
             forward_pressed: false,
             backward_pressed: false,
             left_pressed: false,
             right_pressed: false,
             up_speed: 0.0,
+            walked_distance: 0.0,
+            bob_strength: 0.0,
+            mouse_sensitivity: 0.003,
+            camera_path: None,
 
             view,
         }
     }
 
+    /// Flies the camera to `destination` over `duration`, until it arrives or `camera_path` is
+    /// cleared.
+    pub fn fly_to(&mut self, destination: Point3<f32>, duration: Duration) {
+        self.camera_path = Some(CameraPath::new(
+            self.view.camera.position,
+            destination,
+            duration,
+        ));
+    }
+
     /// Update the camera based on mouse dx and dy.
     pub fn update_camera(&mut self, dx: f64, dy: f64) {
         let camera = &mut self.view.camera;
-        camera.yaw += Rad(dx as f32 * 0.003);
-        camera.pitch -= Rad(dy as f32 * 0.003);
+        camera.yaw += Rad(dx as f32 * self.mouse_sensitivity);
+        camera.pitch -= Rad(dy as f32 * self.mouse_sensitivity);
 
         if camera.pitch < Rad::from(cgmath::Deg(-80.0)) {
             camera.pitch = Rad::from(cgmath::Deg(-80.0));
@@ -53,15 +147,35 @@ impl Player {
     /// Updates the player's position by their velocity, checks for and
     /// resolves any subsequent collisions, and then adds the jumping speed to
     /// the velocity.
-    pub fn update_position(&mut self, dt: Duration, world: &World) {
+    ///
+    /// Returns how many particles of dust the player kicked up, if they landed hard or took a
+    /// sprinting step.
+    pub fn update_position(&mut self, dt: Duration, world: &impl BlockView) -> Option<usize> {
+        if let Some(path) = &mut self.camera_path {
+            path.elapsed += dt;
+            self.view.camera.position = path.position();
+            self.up_speed = 0.0;
+            if path.progress() >= 1.0 {
+                self.camera_path = None;
+            }
+            return None;
+        }
+
         let (yaw_sin, yaw_cos) = self.view.camera.yaw.0.sin_cos();
 
         let speed = 10.0 * (self.sprinting as i32 * 2 + 1) as f32 * dt.as_secs_f32();
 
         let forward_speed = self.forward_pressed as i32 - self.backward_pressed as i32;
-        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin) * forward_speed as f32;
-
         let right_speed = self.right_pressed as i32 - self.left_pressed as i32;
+
+        // The movement keys fly the free camera instead, and the player stays where they are
+        if self.view.camera_mode == CameraMode::Spectator {
+            self.view.spectator_position +=
+                spectator_velocity(&self.view.camera, forward_speed, right_speed) * speed;
+            return None;
+        }
+
+        let forward = Vector3::new(yaw_cos, 0.0, yaw_sin) * forward_speed as f32;
         let right = Vector3::new(-yaw_sin, 0.0, yaw_cos) * right_speed as f32;
 
         let mut velocity = forward + right;
@@ -72,46 +186,47 @@ impl Player {
         velocity.y = self.up_speed * 10.0 * dt.as_secs_f32();
 
         let mut new_position = self.view.camera.position;
+        let mut dust = None;
 
         if !self.creative {
+            if self.auto_jump && self.grounded && is_facing_step(new_position, velocity, world) {
+                self.up_speed = JUMP_SPEED;
+                velocity.y = self.up_speed * 10.0 * dt.as_secs_f32();
+            }
+
             // y component (jumping)
+            let was_grounded = self.grounded;
+            let fall_speed = -self.up_speed;
             new_position.y += velocity.y;
-            if let Some(aabb) = self.check_collision(new_position, world) {
+            if let Some(aabb) = check_collision(new_position, world) {
                 if self.up_speed < 0.0 {
-                    new_position.y = aabb.min.y.ceil() + 1.62;
+                    new_position.y = aabb.max.y + 1.62;
                     new_position.y = utils::f32_successor(new_position.y);
                 } else if self.up_speed > 0.0 {
-                    new_position.y = aabb.max.y.floor() - 0.18;
+                    new_position.y = aabb.min.y - 0.18;
                     new_position.y = utils::f32_predecessor(new_position.y);
                 }
 
                 self.up_speed = 0.0;
                 self.grounded = true;
+
+                if !was_grounded && fall_speed > HARD_LANDING_SPEED {
+                    dust = Some(((fall_speed * 10.0) as usize).min(MAX_LANDING_DUST));
+                }
             } else if self.up_speed.abs() > 0.05 {
                 self.grounded = false;
             }
 
-            // x component
-            new_position.x += velocity.x;
-            if let Some(aabb) = self.check_collision(new_position, world) {
-                if velocity.x < 0.0 {
-                    new_position.x = aabb.min.x.ceil() + 0.3;
-                    new_position.x = utils::f32_successor(new_position.x);
-                } else if velocity.x > 0.0 {
-                    new_position.x = aabb.max.x.floor() - 0.3;
-                    new_position.x = utils::f32_predecessor(new_position.x);
-                }
-            }
+            new_position = move_horizontally(new_position, velocity, world);
 
-            // z component
-            new_position.z += velocity.z;
-            if let Some(aabb) = self.check_collision(new_position, world) {
-                if velocity.z < 0.0 {
-                    new_position.z = aabb.min.z.ceil() + 0.3;
-                    new_position.z = utils::f32_successor(new_position.z);
-                } else if velocity.z > 0.0 {
-                    new_position.z = aabb.max.z.floor() - 0.3;
-                    new_position.z = utils::f32_predecessor(new_position.z);
+            if self.grounded {
+                let walked = new_position - self.view.camera.position;
+                let previous_step = (self.walked_distance / SPRINT_DUST_DISTANCE).floor();
+                self.walked_distance += Vector3::new(walked.x, 0.0, walked.z).magnitude();
+
+                let step = (self.walked_distance / SPRINT_DUST_DISTANCE).floor();
+                if self.sprinting && step != previous_step {
+                    dust = dust.or(Some(SPRINT_DUST));
                 }
             }
 
@@ -121,21 +236,277 @@ impl Player {
             new_position += velocity;
         }
         self.view.camera.position = new_position;
+        dust
+    }
+
+    /// Updates the view bobbing and the sprinting field of view. The bobbing only moves the view,
+    /// not the camera, so it doesn't affect where the player is aiming.
+    pub fn update_camera_feel(&mut self, dt: Duration, feel: &CameraFeel) {
+        let moving = self.forward_pressed
+            || self.backward_pressed
+            || self.left_pressed
+            || self.right_pressed;
+
+        // Ease towards the sprinting field of view, rather than jumping to it
+        let target_fov_delta = if self.sprinting && moving {
+            Rad::from(Deg(feel.sprint_fov_delta))
+        } else {
+            Rad(0.0)
+        };
+        let view = &mut self.view;
+        let ease = (dt.as_secs_f32() * 10.0).min(1.0);
+        view.fov_delta += (target_fov_delta - view.fov_delta) * ease;
+
+        let bobbing = feel.bob_enabled && self.grounded && !self.creative && moving;
+        let target_bob_strength = if bobbing { 1.0 } else { 0.0 };
+        self.bob_strength += (target_bob_strength - self.bob_strength) * ease;
+
+        let phase = self.walked_distance * feel.bob_frequency * 2.0 * PI;
+        let (yaw_sin, yaw_cos) = view.camera.yaw.0.sin_cos();
+        let right = Vector3::new(-yaw_sin, 0.0, yaw_cos);
+
+        // Sway from side to side once per two steps, and dip down on every step
+        let amplitude = feel.bob_amplitude * self.bob_strength;
+        view.offset = right * phase.sin() * amplitude * 0.5
+            - Vector3::unit_y() * phase.sin().abs() * amplitude;
+    }
+
+    /// The block the player's feet are in.
+    pub fn feet(&self) -> Point3<isize> {
+        let position = self.view.camera.position;
+        Point3::new(position.x, position.y - 1.62, position.z).map(|n| n.floor() as isize)
+    }
+
+    /// Moves the third person view up to `distance` away from the player's eyes, but pulls it in
+    /// in front of any blocks in the way.
+    pub fn update_third_person(&mut self, distance: f32, world: &impl BlockView) {
+        let camera = &self.view.camera;
+        let direction = match self.view.camera_mode {
+            CameraMode::FirstPerson | CameraMode::Spectator => return,
+            CameraMode::ThirdPersonBack => -camera.direction(),
+            CameraMode::ThirdPersonFront => camera.direction(),
+        };
+
+        // Keep some room between the view and the block, so the near plane doesn't clip into it
+        let max_steps = (distance.ceil() as usize + 1) * 3;
+        let clear_distance = raycast::raycast(camera.position, direction, world, max_steps, true)
+            .map(|(hit, face)| raycast::hit_distance(camera.position, direction, hit, face) - 0.3)
+            .unwrap_or(distance);
+        self.view.third_person_distance = clear_distance.clamp(0.0, distance);
     }
 
-    fn check_collision(&self, position: Point3<f32>, world: &World) -> Option<Aabb> {
-        let aabb = Aabb {
+    /// The bounds of the player's body, for drawing them in third person.
+    pub fn model_bounds(&self) -> Option<Aabb> {
+        if self.view.camera_mode == CameraMode::FirstPerson {
+            return None;
+        }
+
+        let position = self.view.camera.position;
+        Some(Aabb {
             min: position + Vector3::new(-0.3, -1.62, -0.3),
             max: position + Vector3::new(0.3, 0.18, 0.3),
-        };
+        })
+    }
+}
 
-        for corner in &aabb.get_corners() {
-            let block = world.get_block(corner.map(|x| x.floor() as isize));
-            if block.is_some() {
-                return Some(aabb);
-            }
+/// Which way the free camera flies in spectator mode, at most one block per unit of speed. Forwards
+/// follows where `camera` looks, up and down included, while sideways stays level. There's nothing
+/// to collide with, so it goes straight through blocks.
+fn spectator_velocity(camera: &Camera, forward_speed: i32, right_speed: i32) -> Vector3<f32> {
+    let (yaw_sin, yaw_cos) = camera.yaw.0.sin_cos();
+    let forward = camera.direction() * forward_speed as f32;
+    let right = Vector3::new(-yaw_sin, 0.0, yaw_cos) * right_speed as f32;
+
+    let velocity = forward + right;
+    if velocity.magnitude2() > 1.0 {
+        velocity.normalize()
+    } else {
+        velocity
+    }
+}
+
+/// Whether there's a block right in front of the feet of the player at `position` (the position
+/// of their eyes) in the direction they're moving, with room to stand on top of it.
+fn is_facing_step(position: Point3<f32>, velocity: Vector3<f32>, world: &impl BlockView) -> bool {
+    let direction = Vector3::new(velocity.x, 0.0, velocity.z);
+    if direction.magnitude2() == 0.0 {
+        return false;
+    }
+
+    // Just past the edge of the player's bounding box, at the height of their feet
+    let ahead = position + direction.normalize() * 0.4;
+    let feet = (position.y - 1.62 + 0.01).floor() as isize;
+    let block_at = |y: isize| {
+        world
+            .get_block(Point3::new(
+                ahead.x.floor() as isize,
+                y,
+                ahead.z.floor() as isize,
+            ))
+            .is_some()
+    };
+
+    block_at(feet) && !block_at(feet + 1) && !block_at(feet + 2)
+}
+
+/// Moves the player at `position` (the position of their eyes) along the horizontal part of
+/// `velocity`, one axis at a time, and stops them in front of any blocks in the way. Returns where
+/// they end up.
+fn move_horizontally(
+    mut position: Point3<f32>,
+    velocity: Vector3<f32>,
+    world: &impl BlockView,
+) -> Point3<f32> {
+    // x component
+    position.x += velocity.x;
+    if let Some(aabb) = check_collision(position, world) {
+        if velocity.x < 0.0 {
+            position.x = aabb.max.x + 0.3;
+            position.x = utils::f32_successor(position.x);
+        } else if velocity.x > 0.0 {
+            position.x = aabb.min.x - 0.3;
+            position.x = utils::f32_predecessor(position.x);
+        }
+    }
+
+    // z component
+    position.z += velocity.z;
+    if let Some(aabb) = check_collision(position, world) {
+        if velocity.z < 0.0 {
+            position.z = aabb.max.z + 0.3;
+            position.z = utils::f32_successor(position.z);
+        } else if velocity.z > 0.0 {
+            position.z = aabb.min.z - 0.3;
+            position.z = utils::f32_predecessor(position.z);
+        }
+    }
+
+    position
+}
+
+/// Returns the bounds of all blocks the player would overlap at the given position, if any.
+///
+/// Resolving a collision against all of them at once, rather than against the first one found,
+/// keeps the player from catching on the seams between blocks when sliding along a wall.
+fn check_collision(position: Point3<f32>, world: &impl BlockView) -> Option<Aabb> {
+    let aabb = Aabb {
+        min: position + Vector3::new(-0.3, -1.62, -0.3),
+        max: position + Vector3::new(0.3, 0.18, 0.3),
+    };
+
+    // Every cell the body overlaps is checked, not just the ones at its corners, since the body
+    // is taller than a block. Blocks that aren't full cubes, like stairs, still collide as if
+    // they were one.
+    let mut collision: Option<Aabb> = None;
+    for block_position in aabb.cells() {
+        if world.get_block(block_position).is_none() {
+            continue;
         }
 
-        None
+        let min = block_position.cast().unwrap();
+        let max = min + Vector3::new(1.0, 1.0, 1.0);
+        collision = Some(match collision {
+            Some(collision) => Aabb {
+                min: Point3::new(
+                    collision.min.x.min(min.x),
+                    collision.min.y.min(min.y),
+                    collision.min.z.min(min.z),
+                ),
+                max: Point3::new(
+                    collision.max.x.max(max.x),
+                    collision.max.y.max(max.y),
+                    collision.max.z.max(max.z),
+                ),
+            },
+            None => Aabb { min, max },
+        });
+    }
+
+    collision
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::Zero;
+
+    use super::*;
+    use crate::world::{block::BlockType, block_view::test_world::TestWorld};
+
+    /// The position of the eyes of a player standing on y = 0.
+    const EYE_HEIGHT: f32 = 1.62 + 0.001;
+
+    #[test]
+    fn finds_steps_to_jump_onto() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(1, 0, 0), BlockType::Stone);
+
+        // Close enough to the step that it's just past the edge of the body
+        let position = Point3::new(0.65, EYE_HEIGHT, 0.5);
+        assert!(is_facing_step(position, Vector3::unit_x(), &world));
+        assert!(!is_facing_step(position, -Vector3::unit_x(), &world));
+        assert!(!is_facing_step(position, Vector3::zero(), &world));
+
+        // Not when there's no room on top of the step
+        world.set_block(Point3::new(1, 2, 0), BlockType::Stone);
+        assert!(!is_facing_step(position, Vector3::unit_x(), &world));
+    }
+
+    #[test]
+    fn slides_along_a_wall_without_stopping() {
+        // A wall two blocks high along the Z axis, made of many separate blocks
+        let mut world = TestWorld::default();
+        for (y, z) in itertools::iproduct!(0..2, -10..10) {
+            world.set_block(Point3::new(2, y, z), BlockType::Stone);
+        }
+
+        // Walk diagonally into the wall, so the player keeps pushing against it
+        let mut position = Point3::new(1.5, EYE_HEIGHT, -5.5);
+        let velocity = Vector3::new(0.1, 0.0, 0.1);
+        for _ in 0..100 {
+            let previous = position;
+            position = move_horizontally(position, velocity, &world);
+
+            assert!(
+                position.x < 1.7 + 1e-4,
+                "walked into the wall at {:?}",
+                position
+            );
+            assert!(
+                (position.z - previous.z - velocity.z).abs() < 1e-4,
+                "caught on the wall at {:?}",
+                previous
+            );
+        }
+        assert!((position.x - 1.7).abs() < 1e-4);
+    }
+
+    #[test]
+    fn collides_with_blocks_at_the_middle_of_the_body() {
+        // Only the upper of the two blocks the body overlaps is there
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(1, 1, 0), BlockType::Stone);
+
+        let position = Point3::new(1.5, EYE_HEIGHT, 0.5);
+        assert!(check_collision(position, &world).is_some());
+
+        let position =
+            move_horizontally(Point3::new(0.5, EYE_HEIGHT, 0.5), Vector3::unit_x(), &world);
+        assert!(position.x < 0.7 + 1e-4);
+    }
+
+    #[test]
+    fn flies_the_spectator_camera_where_it_looks() {
+        let camera = Camera::new(Point3::new(0.0, 0.0, 0.0), Rad(0.0), Rad::from(Deg(45.0)));
+
+        // Forwards climbs along with the camera, sideways stays level
+        let forward = spectator_velocity(&camera, 1, 0);
+        assert!((forward - camera.direction()).magnitude() < 1e-6);
+        assert!(forward.y > 0.0);
+        assert_eq!(spectator_velocity(&camera, 0, 1), Vector3::unit_z());
+        assert!((spectator_velocity(&camera, -1, 0) + camera.direction()).magnitude() < 1e-6);
+
+        // Going diagonally isn't any faster
+        assert!((spectator_velocity(&camera, 1, 1).magnitude() - 1.0).abs() < 1e-6);
+        assert_eq!(spectator_velocity(&camera, 0, 0), Vector3::zero());
     }
 }
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use crate::settings::DynamicRenderDistanceSettings;
+
+/// How much of the smoothed frame time a new frame makes up. Lower values react slower, but
+/// aren't thrown off by a single slow frame.
+const SMOOTHING: f32 = 0.05;
+
+/// How long to wait after changing the render distance before changing it again, so the frame
+/// time has time to settle while chunks are loaded or unloaded.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// How long frames have to stay under the comfortable frame time before the render distance is
+/// increased.
+const COMFORTABLE_TIME: Duration = Duration::from_secs(5);
+
+/// Lowers the render distance when frames take too long, and raises it again once they're
+/// comfortably fast for a while.
+///
+/// There's a gap between the target and the comfortable frame time, and a distance is only
+/// increased after a while, so it doesn't go back and forth between two distances.
+pub struct DynamicRenderDistance {
+    settings: DynamicRenderDistanceSettings,
+    /// The most the render distance is increased to, in chunks.
+    max: usize,
+    /// An exponential moving average of the frame time, in seconds.
+    smoothed_frame_time: Option<f32>,
+    since_change: Duration,
+    comfortable_for: Duration,
+}
+
+impl DynamicRenderDistance {
+    pub fn new(settings: DynamicRenderDistanceSettings, max: usize) -> Self {
+        Self {
+            settings,
+            max,
+            smoothed_frame_time: None,
+            since_change: Duration::ZERO,
+            comfortable_for: Duration::ZERO,
+        }
+    }
+
+    /// Takes the time the last frame took, and returns the render distance to switch to if it
+    /// should change from `current`.
+    pub fn update(&mut self, frame_time: Duration, current: usize) -> Option<usize> {
+        let frame_time = frame_time.as_secs_f32();
+        let smoothed = match self.smoothed_frame_time {
+            Some(smoothed) => smoothed + (frame_time - smoothed) * SMOOTHING,
+            None => frame_time,
+        };
+        self.smoothed_frame_time = Some(smoothed);
+
+        self.since_change += Duration::from_secs_f32(frame_time);
+        if self.since_change < SETTLE_TIME {
+            return None;
+        }
+
+        let smoothed_ms = smoothed * 1000.0;
+        if smoothed_ms <= self.settings.comfortable_frame_time_ms {
+            self.comfortable_for += Duration::from_secs_f32(frame_time);
+        } else {
+            self.comfortable_for = Duration::ZERO;
+        }
+
+        let min = self.settings.min_render_distance.clamp(1, self.max);
+        let new = if smoothed_ms > self.settings.target_frame_time_ms && current > min {
+            current - 1
+        } else if self.comfortable_for >= COMFORTABLE_TIME && current < self.max {
+            current + 1
+        } else {
+            return None;
+        };
+
+        self.since_change = Duration::ZERO;
+        self.comfortable_for = Duration::ZERO;
+        Some(new)
+    }
+}
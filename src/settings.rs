@@ -0,0 +1,718 @@
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::world::{block::BlockType, raycast, DEFAULT_RENDER_DISTANCE};
+
+pub const SETTINGS_PATH: &str = "config.toml";
+
+/// User settings, persisted to `config.toml` in the working directory.
+///
+/// Missing fields (or a missing file altogether) fall back to their defaults.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub graphics: GraphicsSettings,
+    pub hud: HudSettings,
+    pub player: PlayerSettings,
+    pub camera: CameraFeel,
+    pub world: WorldSettings,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WindowSettings {
+    /// The width of the window when not fullscreen, in physical pixels.
+    pub width: u32,
+    /// The height of the window when not fullscreen, in physical pixels.
+    pub height: u32,
+    pub fullscreen: bool,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        Self {
+            width: 1280,
+            height: 720,
+            fullscreen: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GraphicsSettings {
+    /// The maximum anisotropic filtering ratio for block textures: 1 (off), 2, 4, 8 or 16.
+    pub anisotropy: u8,
+    /// Mailbox by default, for less input latency than vsync without tearing.
+    pub present_mode: PresentMode,
+    /// The RGBA color blended over the block under the crosshair. Alpha sets how strongly.
+    pub highlight_color: [f32; 4],
+    /// The color of the sky, as a hex code like `#80a9f6`. With `sky_gradient` enabled, this is
+    /// the color at the horizon.
+    pub sky_color: String,
+    /// Fades the sky from `sky_color` at the horizon to `sky_zenith_color` straight up, rather
+    /// than filling it with a single color.
+    pub sky_gradient: bool,
+    /// The color of the sky straight up, as a hex code. Only used with `sky_gradient` enabled.
+    pub sky_zenith_color: String,
+    /// The tint of water, as a hex code.
+    pub water_color: String,
+    /// Multiplies how opaque the water texture is. Below 1 makes water clearer, above 1 murkier,
+    /// up to fully opaque.
+    pub water_opacity: f32,
+    /// How much the water's surface wobbles, as a cheap stand-in for refraction. 0 turns it off,
+    /// 1 is a gentle ripple.
+    pub water_distortion: f32,
+    /// The resolution the world is rendered at, relative to the window, from 0.25 to 1. The HUD
+    /// is always drawn at the window's resolution.
+    pub render_scale: f32,
+    /// How much the edges of the screen are darkened, from 0 (off) to 1 (black corners). Around
+    /// 0.3 is subtle. The HUD isn't darkened.
+    pub vignette: f32,
+    /// Whether the corners of block faces are darkened by the blocks around them.
+    pub lighting: LightingMode,
+    /// How many chunks around the player are loaded in every horizontal direction.
+    pub render_distance: usize,
+    /// How many chunks away from the player the chunks are drawn with less detail, merging every
+    /// 2x2x2 blocks into one, and every 4x4x4 blocks from twice as far. 0 always draws every
+    /// block, which is the default.
+    pub lod_distance: usize,
+    /// The vertical field of view, in degrees.
+    pub fov: f32,
+    /// How close to the camera things can get before they're cut off, in blocks.
+    pub z_near: f32,
+    /// How far away things can be before they're cut off, in blocks. Always at least far enough
+    /// to see every loaded chunk.
+    pub z_far: f32,
+    /// Lets F3+T reload `world.wgsl` and `ui.wgsl` from the source tree without restarting. On by
+    /// default in debug builds.
+    pub shader_hot_reload: bool,
+    /// Draws the opaque faces of all chunks with a single draw call, rather than one per chunk,
+    /// which takes less CPU time at large render distances. Only works if the GPU supports
+    /// multi-draw-indirect; otherwise the chunks are drawn one by one as usual.
+    pub multi_draw_indirect: bool,
+    /// Lowers the render distance while frames take too long, and raises it back up to
+    /// `render_distance` once they're fast again.
+    pub dynamic_render_distance: DynamicRenderDistanceSettings,
+    /// The layer of clouds drifting high above the world.
+    pub clouds: CloudSettings,
+}
+
+impl Default for GraphicsSettings {
+    fn default() -> Self {
+        Self {
+            anisotropy: 1,
+            present_mode: PresentMode::Mailbox,
+            highlight_color: [1.0, 1.0, 1.0, 0.25],
+            sky_color: DEFAULT_SKY_COLOR.to_string(),
+            sky_gradient: true,
+            sky_zenith_color: DEFAULT_SKY_ZENITH_COLOR.to_string(),
+            water_color: DEFAULT_WATER_COLOR.to_string(),
+            water_opacity: 1.0,
+            water_distortion: 0.0,
+            render_scale: 1.0,
+            vignette: 0.0,
+            lighting: LightingMode::Smooth,
+            render_distance: DEFAULT_RENDER_DISTANCE,
+            lod_distance: 0,
+            fov: 45.0,
+            z_near: 0.1,
+            z_far: 300.0,
+            shader_hot_reload: cfg!(debug_assertions),
+            multi_draw_indirect: false,
+            dynamic_render_distance: DynamicRenderDistanceSettings::default(),
+            clouds: CloudSettings::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DynamicRenderDistanceSettings {
+    pub enabled: bool,
+    /// The render distance is lowered while the average frame takes longer than this, in
+    /// milliseconds.
+    pub target_frame_time_ms: f32,
+    /// The render distance is raised once the average frame has taken less than this for a few
+    /// seconds, in milliseconds. Keep it well below the target, or the render distance will keep
+    /// going up and down.
+    pub comfortable_frame_time_ms: f32,
+    /// The render distance isn't lowered any further than this, in chunks.
+    pub min_render_distance: usize,
+}
+
+impl Default for DynamicRenderDistanceSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            target_frame_time_ms: 20.0,
+            comfortable_frame_time_ms: 12.0,
+            min_render_distance: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CloudSettings {
+    pub enabled: bool,
+    /// The height of the clouds, in blocks.
+    pub height: f32,
+    /// How fast the clouds drift along the X axis, in blocks per second. Negative values make
+    /// them drift the other way.
+    pub speed: f32,
+}
+
+impl Default for CloudSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            height: 192.0,
+            speed: 1.0,
+        }
+    }
+}
+
+impl GraphicsSettings {
+    /// Parses `sky_color`, falling back to the default if it isn't a valid color.
+    pub fn sky_color(&self) -> wgpu::Color {
+        parse_hex_color_or("sky color", &self.sky_color, DEFAULT_SKY_COLOR)
+    }
+
+    /// Parses `water_color`, falling back to the default if it isn't a valid color.
+    pub fn water_color(&self) -> wgpu::Color {
+        parse_hex_color_or("water color", &self.water_color, DEFAULT_WATER_COLOR)
+    }
+
+    /// Parses `sky_zenith_color`, falling back to the default if it isn't a valid color.
+    pub fn sky_zenith_color(&self) -> wgpu::Color {
+        parse_hex_color_or(
+            "sky zenith color",
+            &self.sky_zenith_color,
+            DEFAULT_SKY_ZENITH_COLOR,
+        )
+    }
+}
+
+const DEFAULT_SKY_COLOR: &str = "#80a9f6";
+const DEFAULT_SKY_ZENITH_COLOR: &str = "#3f6fd8";
+const DEFAULT_WATER_COLOR: &str = "#3f76e4";
+
+/// Parses a color written as `#rrggbb`, or complains and parses `default` if it isn't valid.
+fn parse_hex_color_or(name: &str, string: &str, default: &str) -> wgpu::Color {
+    parse_hex_color(string).unwrap_or_else(|| {
+        eprintln!("Invalid {} {:?}, using {}", name, string, default);
+        parse_hex_color(default).unwrap()
+    })
+}
+
+/// Parses a color written as `#rrggbb`.
+fn parse_hex_color(string: &str) -> Option<wgpu::Color> {
+    let hex = string.strip_prefix('#')?;
+    // `from_str_radix` takes a leading sign too, which would let `+f` through
+    if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+
+    let component = |i: usize| {
+        let value = u8::from_str_radix(hex.get(i..i + 2)?, 16).ok()?;
+        Some(value as f64 / 255.0)
+    };
+    Some(wgpu::Color {
+        r: component(0)?,
+        g: component(2)?,
+        b: component(4)?,
+        a: 1.0,
+    })
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HudSettings {
+    /// The number of hotbar slots. The number keys select the first 9.
+    pub hotbar_size: usize,
+    /// Draws a dark panel behind the debug text, so it's readable against bright terrain.
+    pub debug_background: bool,
+    /// Shows a graph of how long the last couple of frames took in the bottom left corner, along
+    /// with the debug text. Makes stutters easy to spot.
+    pub frame_time_graph: bool,
+    /// Swings the block in hand whenever a block is broken or placed.
+    pub held_item_swing: bool,
+    /// Inverts the colors behind the crosshair, so it's visible on bright and dark terrain alike,
+    /// rather than drawing it white.
+    pub invert_crosshair: bool,
+}
+
+impl Default for HudSettings {
+    fn default() -> Self {
+        Self {
+            hotbar_size: 9,
+            debug_background: true,
+            frame_time_graph: true,
+            held_item_swing: true,
+            invert_crosshair: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlayerSettings {
+    /// Jump automatically when walking into a block that can be stepped onto. Toggled with F7.
+    pub auto_jump: bool,
+    /// The minimum time between breaking or placing blocks in survival, in milliseconds. Creative
+    /// has no cooldown.
+    pub action_cooldown_ms: u64,
+    /// The most cells of the block grid the ray from the crosshair passes through looking for a
+    /// block, which bounds how long targeting can take. Every block of reach takes up to 3.
+    pub raycast_max_steps: usize,
+    /// How far the camera turns per pixel the mouse moves, in radians.
+    pub mouse_sensitivity: f32,
+    /// How many edits Ctrl+Z can undo. A line of blocks placed by dragging counts as one.
+    pub undo_steps: usize,
+    /// How long `/goto` takes to fly the camera to its destination, in milliseconds.
+    pub goto_duration_ms: u64,
+}
+
+impl Default for PlayerSettings {
+    fn default() -> Self {
+        Self {
+            auto_jump: false,
+            action_cooldown_ms: 150,
+            raycast_max_steps: raycast::DEFAULT_MAX_STEPS,
+            mouse_sensitivity: 0.003,
+            undo_steps: 100,
+            goto_duration_ms: 3000,
+        }
+    }
+}
+
+/// How the camera reacts to the player's movement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraFeel {
+    /// How much wider the field of view gets while sprinting, in degrees.
+    pub sprint_fov_delta: f32,
+    /// Whether the view bobs up and down while walking.
+    pub bob_enabled: bool,
+    /// How far the view bobs, in blocks.
+    pub bob_amplitude: f32,
+    /// How many times the view sways from side to side per block walked. It dips down twice per
+    /// sway, once for every step.
+    pub bob_frequency: f32,
+    /// How far the camera is from the player's eyes in third person, in blocks. It's pulled in
+    /// closer when there are blocks in the way.
+    pub third_person_distance: f32,
+}
+
+impl Default for CameraFeel {
+    fn default() -> Self {
+        Self {
+            sprint_fov_delta: 10.0,
+            bob_enabled: true,
+            bob_amplitude: 0.05,
+            bob_frequency: 0.5,
+            third_person_distance: 4.0,
+        }
+    }
+}
+
+/// How frames are presented, trading off latency, tearing and vsync. Modes the surface doesn't
+/// support fall back to `Fifo` when the surface is configured.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PresentMode {
+    /// Vsync, supported everywhere.
+    Fifo,
+    /// No tearing, but without waiting for vsync: the latest finished frame is shown at the next
+    /// vertical blank (triple buffering).
+    Mailbox,
+    /// Presents right away, which may tear.
+    Immediate,
+}
+
+impl PresentMode {
+    /// Returns the mode after this one, for cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Fifo => Self::Mailbox,
+            Self::Mailbox => Self::Immediate,
+            Self::Immediate => Self::Fifo,
+        }
+    }
+}
+
+impl From<PresentMode> for wgpu::PresentMode {
+    fn from(present_mode: PresentMode) -> Self {
+        match present_mode {
+            PresentMode::Fifo => Self::Fifo,
+            PresentMode::Mailbox => Self::Mailbox,
+            PresentMode::Immediate => Self::Immediate,
+        }
+    }
+}
+
+/// How the faces of blocks are lit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LightingMode {
+    /// Every face is lit evenly, by its direction only.
+    Flat,
+    /// Corners next to other blocks are darkened (ambient occlusion). Faces with darkened corners
+    /// can't be merged with their neighbours, so this makes for more geometry.
+    Smooth,
+}
+
+impl LightingMode {
+    pub fn next(self) -> Self {
+        match self {
+            Self::Flat => Self::Smooth,
+            Self::Smooth => Self::Flat,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeneratorKind {
+    Noise,
+    Flat,
+}
+
+/// The noise the noise generator shapes the terrain with. The cheaper kinds generate chunks
+/// faster, at the cost of plainer terrain. Each kind always makes the same terrain for the same
+/// seed, but different kinds make different terrain. `cargo bench --bench noise` shows how fast
+/// each of them is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TerrainNoise {
+    /// Layers of Perlin noise, `octaves` of them.
+    Fbm,
+    /// Layers of Perlin noise, but no more than 3 of them.
+    FastFbm,
+    /// A single layer of Perlin noise, for smooth, rolling hills.
+    Perlin,
+    /// A single layer of OpenSimplex noise, which looks much like Perlin noise.
+    OpenSimplex,
+}
+
+impl TerrainNoise {
+    pub const ALL: [Self; 4] = [Self::Fbm, Self::FastFbm, Self::Perlin, Self::OpenSimplex];
+
+    /// The name used for it in `config.toml`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Fbm => "fbm",
+            Self::FastFbm => "fast_fbm",
+            Self::Perlin => "perlin",
+            Self::OpenSimplex => "open_simplex",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct WorldSettings {
+    /// The directory the world is stored in, which keeps separate worlds apart. Defaults to
+    /// `minecrab/world` in the platform's data directory, like `~/.local/share` on Linux.
+    pub path: Option<PathBuf>,
+    /// Seeds the terrain noise and the placement of trees.
+    pub seed: u32,
+    /// The terrain generator used for chunks that aren't in the database yet.
+    pub generator: GeneratorKind,
+    /// The layers of the flat generator, from the bottom up.
+    pub flat_layers: Vec<FlatLayer>,
+    /// The shape of the terrain of the noise generator.
+    pub terrain: TerrainParams,
+    /// The layers of the noise generator's terrain, from the surface down, like a grass layer of
+    /// 1 on a dirt layer of 3. Everything below the last layer is stone. When empty, the terrain
+    /// gets grass and dirt on land, sand and gravel under water, and stone at a varying depth.
+    pub terrain_palette: Vec<FlatLayer>,
+    /// What the noise generator places on top of the terrain.
+    pub decorations: DecorationSettings,
+    /// How many threads chunk work like meshing is spread over. Defaults to one per CPU core, and
+    /// is clamped to that.
+    pub generation_threads: Option<usize>,
+    /// The most chunks loaded, generated or saved in a single frame. Lower values make for
+    /// smoother frames, but slower loading. At least one chunk is always handled per frame.
+    pub max_chunk_updates_per_frame: usize,
+    /// The most bytes of chunk geometry uploaded to the GPU in a single frame. The rest waits for
+    /// the next frames. At least one chunk is always uploaded per frame.
+    pub max_upload_bytes_per_frame: usize,
+    /// Loads the chunks the camera is looking towards before the ones beside and behind it, so
+    /// the world fills in where the player is looking first.
+    pub load_in_view_first: bool,
+    /// Keeps hostile NPCs from spawning at all.
+    pub peaceful: bool,
+    /// The most NPCs that can be in the world at once.
+    pub max_entities: usize,
+    /// Where and how often NPCs spawn around the player.
+    pub spawning: SpawnSettings,
+}
+
+impl Default for WorldSettings {
+    fn default() -> Self {
+        Self {
+            path: None,
+            seed: 0,
+            generator: GeneratorKind::Noise,
+            flat_layers: vec![
+                FlatLayer::new(BlockType::Bedrock, 1),
+                FlatLayer::new(BlockType::Dirt, 3),
+                FlatLayer::new(BlockType::Grass, 1),
+            ],
+            terrain: TerrainParams::default(),
+            terrain_palette: Vec::new(),
+            decorations: DecorationSettings::default(),
+            generation_threads: None,
+            max_chunk_updates_per_frame: 16,
+            max_upload_bytes_per_frame: 4 * 1024 * 1024,
+            load_in_view_first: true,
+            peaceful: false,
+            max_entities: 16,
+            spawning: SpawnSettings::default(),
+        }
+    }
+}
+
+/// The shape of the terrain made by the noise generator. All heights are in blocks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TerrainParams {
+    /// How far the surface rises above and sinks below `base_height`, roughly.
+    pub amplitude: f64,
+    /// The average height of the surface.
+    pub base_height: isize,
+    /// Everything below this height that isn't terrain is filled with water.
+    pub sea_level: isize,
+    /// The kind of noise the terrain is made of. Anything but the default is faster, but makes
+    /// for plainer terrain.
+    pub noise: TerrainNoise,
+    /// The range of how many blocks below the surface the stone starts.
+    pub stone_depth: (isize, isize),
+    /// How many layers of ever finer noise make up the surface, from 1 to 32. Fewer make for
+    /// smoother, rolling hills.
+    pub octaves: usize,
+    /// How much finer every octave is than the one before it.
+    pub lacunarity: f64,
+    /// How much weaker every octave is than the one before it. Higher values make for more jagged
+    /// terrain.
+    pub persistence: f64,
+    /// Stretches the terrain along X and Z. Above 1 makes hills wider along that axis, below 1
+    /// squeezes them together. Their height is set by `amplitude`.
+    pub horizontal_scale: (f64, f64),
+    /// How many blocks a second noise pushes the terrain around by, which breaks up its
+    /// grid-aligned look. 0 turns it off.
+    pub warp_strength: f64,
+}
+
+impl Default for TerrainParams {
+    fn default() -> Self {
+        Self {
+            amplitude: 20.0,
+            base_height: 128,
+            sea_level: 128,
+            stone_depth: (3, 10),
+            noise: TerrainNoise::Fbm,
+            octaves: noise::Fbm::DEFAULT_OCTAVE_COUNT,
+            lacunarity: noise::Fbm::DEFAULT_LACUNARITY,
+            persistence: noise::Fbm::DEFAULT_PERSISTENCE,
+            horizontal_scale: (1.0, 1.0),
+            warp_strength: 0.0,
+        }
+    }
+}
+
+/// The decorations of the noise generator. Changing these only affects chunks generated
+/// afterwards.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DecorationSettings {
+    /// Leaves the bare terrain when disabled, for building or benchmarking.
+    pub enabled: bool,
+    /// Multiplies the chance of a tree growing. 0 disables trees. Every tree that grows at some
+    /// density also grows at any higher density.
+    pub tree_density: f32,
+}
+
+impl Default for DecorationSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            tree_density: 1.0,
+        }
+    }
+}
+
+/// NPCs spawn now and then in a ring around the player, out of view, until there are
+/// `max_entities` of them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpawnSettings {
+    pub enabled: bool,
+    /// How close to the player NPCs can spawn, in blocks.
+    pub min_radius: f32,
+    /// How far from the player NPCs can spawn, in blocks. Keep it within the render distance, or
+    /// there won't be any loaded ground to spawn on.
+    pub max_radius: f32,
+    /// How long to wait between trying to spawn an NPC, in milliseconds.
+    pub interval_ms: u64,
+}
+
+impl Default for SpawnSettings {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            min_radius: 24.0,
+            max_radius: 48.0,
+            interval_ms: 10_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlatLayer {
+    #[serde(with = "BlockTypeName")]
+    pub block: BlockType,
+    pub thickness: usize,
+}
+
+impl WorldSettings {
+    /// The directory the world is stored in. Falls back to `world` in the working directory if
+    /// the platform has no data directory.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| {
+            dirs::data_dir()
+                .map_or_else(|| PathBuf::from("world"), |dir| dir.join("minecrab/world"))
+        })
+    }
+
+    /// The number of chunk worker threads to use, between 1 and the number of CPU cores.
+    pub fn generation_threads(&self) -> usize {
+        let available = std::thread::available_parallelism().map_or(1, |n| n.get());
+        self.generation_threads
+            .unwrap_or(available)
+            .clamp(1, available)
+    }
+}
+
+impl FlatLayer {
+    pub fn new(block: BlockType, thickness: usize) -> Self {
+        Self { block, thickness }
+    }
+}
+
+/// Lets block types be written by name in the config file, rather than by the number they're
+/// stored as in chunks.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "BlockType", rename_all = "snake_case")]
+enum BlockTypeName {
+    Cobblestone,
+    Dirt,
+    Stone,
+    Grass,
+    Bedrock,
+    Sand,
+    Gravel,
+    Water,
+    OakLog,
+    OakPlanks,
+    OakLeaves,
+    StoneStairs,
+    Chest,
+    Glass,
+    StainedGlass,
+    Glowstone,
+}
+
+impl Settings {
+    /// Serializes the settings to TOML. They go through a `toml::Value` first, which puts every
+    /// table after the plain values, as TOML requires, whatever order the fields are in.
+    fn to_toml(&self) -> anyhow::Result<String> {
+        Ok(toml::to_string_pretty(&toml::Value::try_from(self)?)?)
+    }
+
+    pub fn load_or_default() -> Self {
+        let string = match std::fs::read_to_string(SETTINGS_PATH) {
+            Ok(string) => string,
+            Err(_) => return Self::default(),
+        };
+
+        toml::from_str(&string).unwrap_or_else(|error| {
+            eprintln!(
+                "Failed to parse {}, using default settings: {}",
+                SETTINGS_PATH, error
+            );
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        let string = self.to_toml()?;
+        std::fs::write(SETTINGS_PATH, string)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_settings_through_a_round_trip() {
+        let mut settings = Settings::default();
+        settings.graphics.render_distance = 5;
+        settings.graphics.fov = 90.0;
+        settings.player.mouse_sensitivity = 0.01;
+
+        let string = settings.to_toml().unwrap();
+        let loaded: Settings = toml::from_str(&string).unwrap();
+        assert_eq!(loaded.graphics.render_distance, 5);
+        assert_eq!(loaded.graphics.fov, 90.0);
+        assert_eq!(loaded.player.mouse_sensitivity, 0.01);
+
+        // And everything else comes back the same too
+        assert_eq!(
+            toml::Value::try_from(&loaded).unwrap(),
+            toml::Value::try_from(&settings).unwrap()
+        );
+    }
+
+    #[test]
+    fn fills_in_missing_settings_with_the_defaults() {
+        let loaded: Settings = toml::from_str("[graphics]\nfov = 90.0\n").unwrap();
+        let default = Settings::default();
+        assert_eq!(loaded.graphics.fov, 90.0);
+        assert_eq!(
+            loaded.graphics.render_distance,
+            default.graphics.render_distance
+        );
+        assert_eq!(
+            loaded.player.mouse_sensitivity,
+            default.player.mouse_sensitivity
+        );
+    }
+
+    #[test]
+    fn parses_hex_colors() {
+        let color = parse_hex_color("#4488ff").unwrap();
+        assert_eq!(color.r, 0x44 as f64 / 255.0);
+        assert_eq!(color.g, 0x88 as f64 / 255.0);
+        assert_eq!(color.b, 1.0);
+        assert_eq!(color.a, 1.0);
+    }
+
+    #[test]
+    fn falls_back_to_the_default_sky_color_if_it_isnt_valid() {
+        let default = parse_hex_color(DEFAULT_SKY_COLOR).unwrap();
+        for invalid in ["blue", "#12345", "#+f+f+f", "#gggggg"].iter() {
+            let graphics = GraphicsSettings {
+                sky_color: invalid.to_string(),
+                ..GraphicsSettings::default()
+            };
+            assert_eq!(graphics.sky_color(), default, "{}", invalid);
+        }
+    }
+}
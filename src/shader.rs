@@ -0,0 +1,42 @@
+use std::{borrow::Cow, fs, path::Path};
+
+use naga::valid::{Capabilities, ValidationFlags, Validator};
+use wgpu::ShaderModule;
+
+use crate::{
+    error::{self, MinecrabError},
+    render_context::RenderContext,
+};
+
+/// Where reloaded shaders are read from. This is the source tree the game was built from, so
+/// reloading only works on the machine that built it.
+const SHADER_DIRECTORY: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+/// Reads a shader from `src/shaders` as it is on disk right now, and compiles it.
+///
+/// Unlike the shaders embedded with `include_str!`, these may be broken halfway through an edit,
+/// so they're validated before wgpu gets to see them. Errors are returned instead of making wgpu
+/// panic.
+pub fn load(render_context: &RenderContext, name: &'static str) -> error::Result<ShaderModule> {
+    let path = Path::new(SHADER_DIRECTORY).join(name);
+    let source =
+        fs::read_to_string(path).map_err(|source| MinecrabError::ShaderRead { name, source })?;
+    validate(&source).map_err(|message| MinecrabError::ShaderCompile { name, message })?;
+
+    Ok(render_context
+        .device
+        .create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some(name),
+            source: wgpu::ShaderSource::Wgsl(Cow::Owned(source)),
+        }))
+}
+
+/// Runs the WGSL through the same parser and validator wgpu uses, and describes the first error.
+fn validate(source: &str) -> Result<(), String> {
+    let module =
+        naga::front::wgsl::parse_str(source).map_err(|error| error.emit_to_string(source))?;
+    Validator::new(ValidationFlags::all(), Capabilities::empty())
+        .validate(&module)
+        .map_err(|error| format!("{:?}", error))?;
+    Ok(())
+}
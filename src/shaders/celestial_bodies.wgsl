@@ -0,0 +1,81 @@
+[[block]]
+struct View {
+    position: vec4<f32>;
+    projection: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+};
+
+[[block]]
+struct Time {
+    time: f32;
+    time_of_day: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[group(1), binding(0)]]
+var<uniform> time: Time;
+
+[[group(2), binding(0)]] var body_sampler: sampler;
+[[group(2), binding(1)]] var body_texture: texture_2d_array<f32>;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] texture_coordinates: vec2<f32>;
+};
+
+let pi: f32 = 3.14159265359;
+
+// How far from the camera the sun and moon are drawn. They're drawn before the world and ignore
+// the depth buffer, so this only has to be within the far plane.
+let distance: f32 = 100.0;
+
+// Half the width of the sun and the moon, at that distance
+let sun_size: f32 = 15.0;
+let moon_size: f32 = 10.0;
+
+// Draws the sun as instance 0 and the moon as instance 1, as a quad on the sphere around the
+// camera facing back at it
+[[stage(vertex)]]
+fn main(
+    [[builtin(vertex_index)]] index: u32,
+    [[builtin(instance_index)]] body: u32,
+) -> VertexOutput {
+    var corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let corner = corners[index];
+
+    // The sun rises in the east (+X) at a quarter of the day, and is straight up at noon. The
+    // moon is always on the opposite side.
+    let angle = (time.time_of_day - 0.25) * 2.0 * pi;
+    var direction: vec3<f32> = vec3<f32>(cos(angle), sin(angle), 0.0);
+    var size: f32 = sun_size;
+    if (body == 1u) {
+        direction = -direction;
+        size = moon_size;
+    }
+
+    // The bodies move around the Z axis, so it's always at a right angle to the direction
+    let right = vec3<f32>(0.0, 0.0, 1.0);
+    let up = cross(right, direction);
+    let position = view.position.xyz
+        + direction * distance
+        + (right * corner.x + up * corner.y) * size;
+
+    var out: VertexOutput;
+    out.clip_position = view.projection * vec4<f32>(position, 1.0);
+    out.texture_coordinates = vec2<f32>(corner.x, -corner.y) * 0.5 + 0.5;
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return textureSample(body_texture, body_sampler, in.texture_coordinates, 0);
+}
@@ -0,0 +1,70 @@
+[[block]]
+struct View {
+    position: vec4<f32>;
+    projection: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+};
+
+[[block]]
+struct Time {
+    time: f32;
+    time_of_day: f32;
+};
+
+[[block]]
+struct Clouds {
+    height: f32;
+    speed: f32;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[group(1), binding(0)]]
+var<uniform> time: Time;
+
+[[group(2), binding(0)]] var cloud_sampler: sampler;
+[[group(2), binding(1)]] var cloud_texture: texture_2d_array<f32>;
+[[group(2), binding(2)]] var<uniform> clouds: Clouds;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] world_position: vec2<f32>;
+};
+
+// Half the width of the cloud plane, which follows the camera around
+let extent: f32 = 256.0;
+// How many blocks a pixel of the cloud texture covers
+let pixel_size: f32 = 12.0;
+
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    var corners: array<vec2<f32>, 6> = array<vec2<f32>, 6>(
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, -1.0),
+        vec2<f32>(1.0, 1.0),
+        vec2<f32>(-1.0, 1.0),
+    );
+    let world_position = view.position.xz + corners[index] * extent;
+
+    var out: VertexOutput;
+    out.clip_position = view.projection
+        * vec4<f32>(world_position.x, clouds.height, world_position.y, 1.0);
+    out.world_position = world_position;
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // The clouds drift towards +X, and the texture repeats across the sky
+    let size = vec2<f32>(textureDimensions(cloud_texture)) * pixel_size;
+    let offset = vec2<f32>(time.time * clouds.speed, 0.0);
+    let color = textureSample(cloud_texture, cloud_sampler, (in.world_position - offset) / size, 0);
+
+    // Fade out towards the edge of the plane, so it doesn't end in a straight line
+    let distance = distance(in.world_position, view.position.xz);
+    let fade = 1.0 - smoothStep(extent * 0.5, extent, distance);
+    return vec4<f32>(color.rgb, color.a * fade);
+}
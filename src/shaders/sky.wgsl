@@ -0,0 +1,46 @@
+[[block]]
+struct View {
+    position: vec4<f32>;
+    projection: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
+};
+
+[[block]]
+struct Sky {
+    horizon_color: vec4<f32>;
+    zenith_color: vec4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> view: View;
+
+[[group(1), binding(0)]]
+var<uniform> sky: Sky;
+
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] screen_position: vec2<f32>;
+};
+
+// Draws a single triangle that covers the whole screen
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let position = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u)) * 2.0 - 1.0;
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(position, 0.0, 1.0);
+    out.screen_position = position;
+    return out;
+}
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    // Find the point on the far plane behind this pixel, and look at it from the camera. The
+    // depth is reversed, so the far plane is at 0.
+    let far = view.inverse_projection * vec4<f32>(in.screen_position, 0.0, 1.0);
+    let direction = normalize(far.xyz / far.w - view.position.xyz);
+
+    // Below the horizon, the sky keeps the horizon's color
+    let height = sqrt(clamp(direction.y, 0.0, 1.0));
+    return mix(sky.horizon_color, sky.zenith_color, vec4<f32>(height));
+}
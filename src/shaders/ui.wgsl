@@ -27,6 +27,18 @@ fn main(model: VertexInput) -> VertexOutput {
 
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    return textureSample(texture, sampler, in.texture_coordinates, in.texture_index)
-        * in.color;
+    // A negative texture index means the quad isn't textured, and only has a color
+    let texture_color = textureSample(texture, sampler, in.texture_coordinates, max(in.texture_index, 0));
+    if (in.texture_index < 0) {
+        return in.color;
+    }
+    return texture_color * in.color;
+}
+
+// Premultiplies the color by its alpha, for the blend state that inverts what's behind it. Used
+// for the crosshair, so it's visible on any background.
+[[stage(fragment)]]
+fn inverted(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(texture, sampler, in.texture_coordinates, max(in.texture_index, 0)) * in.color;
+    return vec4<f32>(color.rgb * color.a, color.a);
 }
@@ -0,0 +1,37 @@
+struct VertexOutput {
+    [[builtin(position)]] clip_position: vec4<f32>;
+    [[location(0)]] texture_coordinates: vec2<f32>;
+};
+
+// Draws a single triangle that covers the whole screen
+[[stage(vertex)]]
+fn main([[builtin(vertex_index)]] index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+
+    var out: VertexOutput;
+    out.clip_position = vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates run downwards, unlike clip space
+    out.texture_coordinates = vec2<f32>(uv.x, 1.0 - uv.y);
+    return out;
+}
+
+[[group(0), binding(0)]] var sampler: sampler;
+[[group(0), binding(1)]] var texture: texture_2d<f32>;
+
+[[block]]
+struct PostProcess {
+    vignette: f32;
+};
+
+[[group(0), binding(2)]]
+var<uniform> post_process: PostProcess;
+
+[[stage(fragment)]]
+fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let color = textureSample(texture, sampler, in.texture_coordinates);
+
+    // Darkens smoothly from nothing in the middle to `vignette` in the corners
+    let distance = length(in.texture_coordinates - 0.5) / length(vec2<f32>(0.5, 0.5));
+    let darkening = post_process.vignette * smoothStep(0.4, 1.0, distance);
+    return vec4<f32>(color.rgb * (1.0 - darkening), color.a);
+}
@@ -2,11 +2,21 @@
 struct View {
     position: vec4<f32>;
     projection: mat4x4<f32>;
+    inverse_projection: mat4x4<f32>;
 };
 
 [[block]]
 struct Time {
     time: f32;
+    time_of_day: f32;
+};
+
+[[block]]
+struct RenderSettings {
+    highlight_color: vec4<f32>;
+    water_color: vec4<f32>;
+    water_distortion: f32;
+    debug_view: u32;
 };
 
 [[group(1), binding(0)]]
@@ -15,6 +25,9 @@ var<uniform> view: View;
 [[group(2), binding(0)]]
 var<uniform> time: Time;
 
+[[group(3), binding(0)]]
+var<uniform> render_settings: RenderSettings;
+
 struct VertexInput {
     [[location(0)]] position: vec3<f32>;
     [[location(1)]] texture_coordinates: vec2<f32>;
@@ -22,6 +35,8 @@ struct VertexInput {
     [[location(3)]] highlighted: i32;
     [[location(4)]] texture_id: i32;
     [[location(5)]] color: vec4<f32>;
+    [[location(6)]] overlay_texture_id: i32;
+    [[location(7)]] overlay_color: vec4<f32>;
 };
 
 struct VertexOutput {
@@ -32,30 +47,49 @@ struct VertexOutput {
     [[location(3)]] highlighted: i32;
     [[location(4)]] texture_id: i32;
     [[location(5)]] color: vec4<f32>;
+    [[location(6)]] overlay_texture_id: i32;
+    [[location(7)]] overlay_color: vec4<f32>;
 };
 
 let pi: f32 = 3.14159265359;
 
+// How far the water texture scrolls per second, along the surface and down the sides
+let water_flow: vec2<f32> = vec2<f32>(0.1, 0.05);
+let water_fall: vec2<f32> = vec2<f32>(0.0, -0.25);
+
+// The tint of the blocks of the type highlighted with F3+H
+let type_highlight_color: vec3<f32> = vec3<f32>(1.0, 0.2, 0.8);
+
 [[stage(vertex)]]
 fn main(model: VertexInput) -> VertexOutput {
     var out: VertexOutput;
 
     out.world_normal = model.normal;
     if (model.texture_id == 8) {
-        // water
+        // water, whose frames are layers 8 to 39: `WATER_TEXTURE` and `WATER_FRAMES` in texture.rs
         let offset = (sin(time.time * 0.5 + model.position.x) * cos(time.time * 0.9 + model.position.y) + 2.5) / 10.0;
         out.world_position = vec3<f32>(model.position.x, model.position.y - offset, model.position.z);
-        out.texture_coordinates = model.texture_coordinates + (time.time / 10.0);
+
+        // Texture coordinates run from 0 to 1 on every block and the sampler repeats, so the
+        // scrolled texture still lines up between neighbouring water blocks
+        var flow: vec2<f32> = water_flow;
+        if (model.normal.y == 0.0) {
+            flow = water_fall;
+        }
+        out.texture_coordinates = model.texture_coordinates + flow * time.time;
         out.texture_id = i32(8.0 + (time.time * 10.0) % 32.0);
+        out.color = render_settings.water_color;
     } else {
         out.world_position = model.position;
         out.texture_coordinates = model.texture_coordinates;
         out.texture_id = model.texture_id;
+        out.color = model.color;
     }
 
     out.clip_position = view.projection * vec4<f32>(out.world_position, 1.0);
     out.highlighted = model.highlighted;
-    out.color = model.color;
+    out.overlay_texture_id = model.overlay_texture_id;
+    out.overlay_color = model.overlay_color;
     return out;
 }
 
@@ -64,12 +98,54 @@ fn main(model: VertexInput) -> VertexOutput {
 
 [[stage(fragment)]]
 fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
-    let object_color: vec4<f32> = textureSample(
+    // The debug views, in the order of `DebugView`
+    if (render_settings.debug_view == 1u) {
+        return vec4<f32>(in.world_normal * 0.5 + 0.5, 1.0);
+    } elseif (render_settings.debug_view == 2u) {
+        return vec4<f32>(fract(in.texture_coordinates), 0.0, 1.0);
+    } elseif (render_settings.debug_view == 3u) {
+        // Spreads the texture indices over the colors, so neighbouring ones look different
+        return vec4<f32>(fract(f32(in.texture_id) * vec3<f32>(0.37, 0.61, 0.83)), 1.0);
+    } elseif (render_settings.debug_view == 4u) {
+        // White up close, fading to black 256 blocks away
+        let depth = clamp(distance(view.position.xyz, in.world_position) / 256.0, 0.0, 1.0);
+        return vec4<f32>(vec3<f32>(1.0 - depth), 1.0);
+    }
+
+    // Water uses the animation frames from texture 8 on. Its texture is pushed around a little,
+    // like the light passing through it is bent by the ripples.
+    var texture_coordinates: vec2<f32> = in.texture_coordinates;
+    if (in.texture_id >= 8 && in.texture_id < 40) {
+        let ripple = vec2<f32>(
+            sin(in.world_position.z * 3.0 + time.time * 2.0),
+            cos(in.world_position.x * 3.0 + time.time * 1.7)
+        );
+        texture_coordinates = texture_coordinates + ripple * render_settings.water_distortion * 0.05;
+    }
+
+    var texture_color: vec4<f32> = textureSample(
         texture_array,
         texture_sampler,
-        in.texture_coordinates,
+        texture_coordinates,
         in.texture_id
-    ) * in.color;
+    );
+
+    // The overlay is drawn over the face's own texture, which shows through where the overlay is
+    // transparent. A negative index means there's no overlay.
+    let overlay_color = textureSample(
+        texture_array,
+        texture_sampler,
+        in.texture_coordinates,
+        max(in.overlay_texture_id, 0)
+    ) * in.overlay_color;
+    if (in.overlay_texture_id >= 0) {
+        texture_color = vec4<f32>(
+            mix(texture_color.rgb, overlay_color.rgb, vec3<f32>(overlay_color.a)),
+            texture_color.a
+        );
+    }
+
+    let object_color: vec4<f32> = texture_color * in.color;
 
     let light_position = vec3<f32>(-100.0, 500.0, -200.0);
     let light_color = vec3<f32>(1.0, 1.0, 1.0);
@@ -87,10 +163,24 @@ fn main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
     let specular_strength = pow(max(dot(in.world_normal, half_direction), 0.0), 32.0);
     let specular_color = specular_strength * light_color;
 
+    // The highlighted face is drawn again over the chunk, and only adds the highlight color
+    if (in.highlighted == 1) {
+        let strength = render_settings.highlight_color.a + sin(time.time * pi) * 0.07;
+        let alpha = clamp(strength, 0.0, 1.0) * object_color.a;
+        return vec4<f32>(render_settings.highlight_color.rgb, alpha);
+    }
+
     var result: vec3<f32> = (ambient_color + diffuse_color + specular_color) * object_color.xyz;
-    if (in.highlighted != 0) {
-        result = result + 0.25 + sin(time.time * pi) * 0.07;
+    if (in.highlighted == 2) {
+        result = mix(result, type_highlight_color, vec3<f32>(0.6));
     }
 
-    return vec4<f32>(result, object_color.a);
+    // Water's opacity can be pushed above 1
+    return vec4<f32>(result, min(object_color.a, 1.0));
+}
+
+// Draws the edges of the triangles in a single color, over the filled geometry
+[[stage(fragment)]]
+fn wireframe(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    return vec4<f32>(0.0, 0.0, 0.0, 1.0);
 }
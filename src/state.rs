@@ -1,7 +1,17 @@
-use std::time::{Duration, Instant};
+use std::{
+    f32::consts::SQRT_2,
+    num::NonZeroU8,
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
+use cgmath::{Deg, MetricSpace, Point3, Vector3};
 use winit::{
-    dpi::PhysicalSize,
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{
         DeviceEvent, ElementState, MouseButton, MouseScrollDelta, VirtualKeyCode, WindowEvent,
     },
@@ -9,34 +19,156 @@ use winit::{
 };
 
 use crate::{
-    hud::Hud,
-    player::Player,
+    camera::Camera,
+    error::{self, MinecrabError},
+    gpu_timer::{GpuTimer, TIMESTAMP_END, TIMESTAMP_HUD, TIMESTAMP_WORLD},
+    held_keys::HeldKeys,
+    hud::{
+        chest_hud::{ChestHud, ChestHudSlot},
+        Hud,
+    },
+    player::{CameraPath, Player, JUMP_SPEED},
     render_context::RenderContext,
+    render_distance::DynamicRenderDistance,
+    settings::{PresentMode, Settings},
     texture::{Texture, TextureManager},
-    world::World,
+    time::TIMES_OF_DAY,
+    upscaler::Upscaler,
+    view::{CameraMode, SPAWN_POSITION},
+    world::{
+        block::{BlockType, BlockUse},
+        block_entity::BlockEntity,
+        block_view::BlockView,
+        chunk::CHUNK_SIZE,
+        export::ExportHeader,
+        DebugView, World,
+    },
 };
 
+/// What right clicking does, depending on the block at the crosshair and the one selected in the
+/// hotbar.
+#[derive(Debug, PartialEq, Eq)]
+enum RightClick {
+    Use(BlockUse),
+    Place(BlockType),
+    Nothing,
+}
+
+impl RightClick {
+    fn new(target: Option<BlockType>, selected: Option<BlockType>) -> Self {
+        // Interactive blocks are used rather than built against
+        match (target.and_then(BlockType::block_use), selected) {
+            (Some(block_use), _) => Self::Use(block_use),
+            (None, Some(selected)) => Self::Place(selected),
+            (None, None) => Self::Nothing,
+        }
+    }
+}
+
 pub struct State {
     pub window_size: PhysicalSize<u32>,
     pub mouse_grabbed: bool,
+    pub settings: Settings,
+    held_keys: HeldKeys,
+    /// Whether the left mouse button is being held down to break blocks.
+    mining: bool,
+    /// While placing blocks by dragging: where the last block was placed, and the normal of the
+    /// face the first one was placed against.
+    drag_placing: Option<(Point3<isize>, Vector3<i32>)>,
+    cursor_position: PhysicalPosition<f64>,
+    /// The position of the chest whose contents are being shown.
+    open_chest: Option<Point3<isize>>,
+    /// The blocks marked with the measuring tool, in the order they were marked.
+    measure_points: Vec<Point3<isize>>,
+    /// The seed of a `/newworld` that was pasted once, and when. Pasting it again within
+    /// `NEW_WORLD_CONFIRM_TIME` deletes the world.
+    pending_new_world: Option<(Option<u32>, Instant)>,
     render_context: RenderContext,
     surface_config: wgpu::SurfaceConfiguration,
 
     pub world: World,
     player: Player,
     hud: Hud,
+
+    /// Measures the render passes on the GPU, if the device supports it.
+    gpu_timer: Option<GpuTimer>,
+    /// Renders the world at a lower resolution, if `render_scale` is below 1, and draws the
+    /// vignette, if there is one.
+    upscaler: Option<Upscaler>,
+    /// Adjusts the render distance to the frame time, if enabled in the settings.
+    dynamic_render_distance: Option<DynamicRenderDistance>,
+    /// Set by the device's error handler once the device is lost, after which nothing should be
+    /// submitted to it anymore.
+    device_lost: Arc<AtomicBool>,
+}
+
+/// The render scales F8 cycles through.
+const RENDER_SCALES: [f32; 3] = [1.0, 0.75, 0.5];
+
+/// How many chunks around the destination of a teleport are loaded before arriving.
+const TELEPORT_WARMUP_RADIUS: isize = 1;
+
+/// How long `/newworld` has to be pasted again within to confirm it.
+const NEW_WORLD_CONFIRM_TIME: Duration = Duration::from_secs(5);
+
+/// Whether a wgpu error was caused by losing the device. wgpu reports those as validation errors
+/// of whatever call ran into it, so this looks for the cause among their sources.
+fn is_device_lost(error: &wgpu::Error) -> bool {
+    let mut source = std::error::Error::source(error);
+    while let Some(error) = source {
+        if error.to_string() == "parent device is lost" {
+            return true;
+        }
+        source = error.source();
+    }
+    false
+}
+
+/// A command that can be run from the clipboard with F3+V.
+#[derive(Debug, PartialEq)]
+enum Command {
+    /// `/tp <x> <y> <z>`: moves the player.
+    Teleport(Point3<f32>),
+    /// `/goto <x> <y> <z>`: flies the player there smoothly.
+    Goto(Point3<f32>),
+    /// `/newworld [seed]`: deletes the world, and starts a new one with the seed or a random one.
+    NewWorld(Option<u32>),
+    /// `/flatten <radius> <block>`: levels the ground the player is standing on, out to the radius,
+    /// with the block.
+    Flatten(isize, BlockType),
+}
+
+/// Parses a command like `/tp 1.5 64 -20`, `/newworld 1234` or `/flatten 8 oak_planks`.
+fn parse_command(command: &str) -> Option<Command> {
+    let mut words = command.split_whitespace();
+    let name = words.next()?;
+    let mut coordinate = || words.next()?.parse::<f32>().ok().filter(|x| x.is_finite());
+    let mut position = || Some(Point3::new(coordinate()?, coordinate()?, coordinate()?));
+    let parsed = match name {
+        "/tp" => Command::Teleport(position()?),
+        "/goto" => Command::Goto(position()?),
+        "/newworld" => Command::NewWorld(words.next().map(str::parse).transpose().ok()?),
+        "/flatten" => Command::Flatten(
+            words.next()?.parse::<u8>().ok()? as isize,
+            BlockType::from_name(words.next()?)?,
+        ),
+        _ => return None,
+    };
+    words.next().is_none().then_some(parsed)
 }
 
 impl State {
     async fn create_render_device(
         window: &Window,
-    ) -> (
+        present_mode: PresentMode,
+        device_lost: Arc<AtomicBool>,
+    ) -> error::Result<(
         wgpu::SurfaceConfiguration,
         wgpu::Surface,
         wgpu::Adapter,
         wgpu::Device,
         wgpu::Queue,
-    ) {
+    )> {
         let instance = wgpu::Instance::new(wgpu::Backends::PRIMARY);
         let render_surface = unsafe { instance.create_surface(window) };
 
@@ -56,7 +188,7 @@ impl State {
                 let mut adapters = instance.enumerate_adapters(wgpu::Backends::all());
                 adapters.next()
             })
-            .expect("No graphics adapter");
+            .ok_or(MinecrabError::NoAdapter)?;
 
         println!(
             "Using backend {:?} with features {:?}",
@@ -68,37 +200,84 @@ impl State {
             .request_device(
                 &wgpu::DeviceDescriptor {
                     label: Some("render_device"),
-                    features: wgpu::Features::TEXTURE_BINDING_ARRAY,
+                    // Timestamp queries and line drawing are optional, and only used for debugging
+                    features: wgpu::Features::TEXTURE_BINDING_ARRAY
+                        | (adapter.features()
+                            & (wgpu::Features::TIMESTAMP_QUERY
+                                | wgpu::Features::POLYGON_MODE_LINE
+                                | wgpu::Features::MULTI_DRAW_INDIRECT)),
                     limits: wgpu::Limits::default(),
                 },
                 None,
             )
-            .await
-            .unwrap();
+            .await?;
+
+        // Like wgpu's default handler this panics on errors, except for losing the device, which
+        // `render` recovers from
+        render_device.on_uncaptured_error(move |error| {
+            if is_device_lost(&error) {
+                eprintln!("Lost the graphics device: {}", error);
+                device_lost.store(true, Ordering::Relaxed);
+            } else {
+                panic!("wgpu error: {}\n", error);
+            }
+        });
 
         let size = window.inner_size();
 
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: render_surface.get_preferred_format(&adapter).unwrap(),
+            format: render_surface
+                .get_preferred_format(&adapter)
+                .ok_or(MinecrabError::IncompatibleSurface)?,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Immediate,
+            present_mode: present_mode.into(),
         };
 
+        // wgpu 0.11 can't list the present modes a surface supports, but configuring it with an
+        // unsupported one falls back to Fifo, which is the next best after Mailbox anyway
         render_surface.configure(&render_device, &config);
+        Self::log_present_mode(present_mode);
 
-        (config, render_surface, adapter, render_device, queue)
+        Ok((config, render_surface, adapter, render_device, queue))
     }
 
-    pub async fn new(window: &Window) -> State {
+    /// Turns the configured anisotropy into a sampler clamp, falling back to no anisotropic
+    /// filtering when the adapter doesn't support it.
+    fn anisotropy_clamp(adapter: &wgpu::Adapter, anisotropy: u8) -> Option<NonZeroU8> {
+        if anisotropy <= 1 {
+            return None;
+        }
+
+        let flags = adapter.get_downlevel_properties().flags;
+        if !flags.contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING) {
+            eprintln!("Anisotropic filtering isn't supported by this device, disabling it");
+            return None;
+        }
+
+        // The clamp has to be a power of two, and at most 16
+        let anisotropy = anisotropy.min(16);
+        NonZeroU8::new(1 << (7 - anisotropy.leading_zeros()))
+    }
+
+    pub async fn new(window: &Window, settings: Settings) -> error::Result<State> {
+        let database = World::open_database(&settings.world.path())?;
+        Self::create(window, settings, database).await
+    }
+
+    async fn create(
+        window: &Window,
+        settings: Settings,
+        chunk_database: sled::Db,
+    ) -> error::Result<State> {
+        let device_lost = Arc::new(AtomicBool::new(false));
         let (surface_config, render_surface, render_adapter, render_device, render_queue) =
-            Self::create_render_device(window).await;
+            Self::create_render_device(window, settings.graphics.present_mode, device_lost.clone())
+                .await?;
 
         let mut render_context = RenderContext {
-            format: render_surface
-                .get_preferred_format(&render_adapter)
-                .unwrap(),
+            format: surface_config.format,
             surface: render_surface,
             device: render_device,
             queue: render_queue,
@@ -106,24 +285,96 @@ impl State {
             texture_manager: None,
         };
 
-        let mut texture_manager = TextureManager::new(&render_context);
-        texture_manager.load_all(&render_context).unwrap();
+        let anisotropy_clamp =
+            Self::anisotropy_clamp(&render_adapter, settings.graphics.anisotropy);
+        let mut texture_manager = TextureManager::new(&render_context, anisotropy_clamp);
+        texture_manager.load_all(&render_context)?;
         render_context.texture_manager = Some(texture_manager);
 
-        let hud = Hud::new(&render_context);
-        let player = Player::new(&render_context);
-        let world = World::new(&render_context, &player.view);
+        let mut hud = Hud::new(&render_context, &settings.hud);
+        let mut player = Player::new(&render_context);
+        player.auto_jump = settings.player.auto_jump;
+        player.mouse_sensitivity = settings.player.mouse_sensitivity;
+        let projection = &mut player.view.projection;
+        projection.fov_y = Deg(settings.graphics.fov.clamp(10.0, 170.0)).into();
+        // Chunks are loaded in a square around the player, so its corners are the furthest away
+        let render_distance = (settings.graphics.render_distance * CHUNK_SIZE) as f32 * SQRT_2;
+        projection.z_near = settings.graphics.z_near.max(0.01);
+        projection.z_far = settings
+            .graphics
+            .z_far
+            .max(render_distance)
+            .max(projection.z_near * 2.0);
+        let world = World::new(&render_context, &player.view, &settings, chunk_database);
+        let gpu_timer = GpuTimer::new(&render_context);
+        if gpu_timer.is_none() {
+            println!("GPU timestamps aren't supported, falling back to CPU render times");
+        }
+
+        let graphics = &settings.graphics;
+        let dynamic_render_distance = graphics.dynamic_render_distance.enabled.then(|| {
+            DynamicRenderDistance::new(
+                graphics.dynamic_render_distance.clone(),
+                world.render_distance as usize,
+            )
+        });
+        hud.debug_hud.set_render_distance(
+            &render_context,
+            world.render_distance as usize,
+            dynamic_render_distance.is_some(),
+        );
 
-        Self {
+        let mut state = Self {
             window_size: window.inner_size(),
             mouse_grabbed: false,
+            settings,
+            held_keys: HeldKeys::default(),
+            mining: false,
+            drag_placing: None,
+            cursor_position: PhysicalPosition::new(0.0, 0.0),
+            open_chest: None,
+            measure_points: Vec::new(),
+            pending_new_world: None,
             render_context,
             surface_config,
 
             world,
             player,
             hud,
+
+            gpu_timer,
+            upscaler: None,
+            dynamic_render_distance,
+            device_lost,
+        };
+        state.create_render_targets();
+        Ok(state)
+    }
+
+    /// Recovers from losing the graphics device, by creating a new one and everything that lived
+    /// on the old one. Rather than rebuilding every GPU resource in place, the state is created
+    /// again from scratch, taking over what only exists in memory: the loaded chunks are saved to
+    /// the chunk database for the new world to load, and the player and the time of day are
+    /// carried over.
+    pub async fn recreate(&mut self, window: &Window) -> error::Result<()> {
+        if let Err(error) = self.world.save() {
+            eprintln!("Failed to save the world: {:?}", anyhow::Error::from(error));
         }
+
+        let chunk_database = self.world.chunk_database.clone();
+        let mut state = Self::create(window, self.settings.clone(), chunk_database).await?;
+
+        let camera = &self.player.view.camera;
+        state.player.view.camera = Camera::new(camera.position, camera.yaw, camera.pitch);
+        state.player.creative = self.player.creative;
+        state.mouse_grabbed = self.mouse_grabbed;
+        state.measure_points = std::mem::take(&mut self.measure_points);
+        state.world.time = self.world.time;
+        state.world.time_frozen = self.world.time_frozen;
+        state.world.streaming_frozen = self.world.streaming_frozen;
+
+        *self = state;
+        Ok(())
     }
 
     pub fn resize(&mut self, size: PhysicalSize<u32>) {
@@ -132,26 +383,590 @@ impl State {
         self.render_context.size = size;
         self.surface_config.width = size.width;
         self.surface_config.height = size.height;
+        self.configure_surface();
+
+        self.player.view.projection.resize(size.width, size.height);
+        self.create_render_targets();
+    }
+
+    /// The size of the textures the world is rendered to.
+    fn render_size(&self) -> PhysicalSize<u32> {
+        let scale = self.settings.graphics.render_scale.clamp(0.25, 1.0);
+        let scaled = |length: u32| ((length as f32 * scale).round() as u32).max(1);
+        PhysicalSize::new(
+            scaled(self.window_size.width),
+            scaled(self.window_size.height),
+        )
+    }
+
+    /// (Re)creates the depth texture and, when rendering below the window's resolution or with a
+    /// vignette, the texture the world is rendered to.
+    fn create_render_targets(&mut self) {
+        let size = self.render_size();
+        let vignette = self.settings.graphics.vignette;
+        self.world.depth_texture =
+            Texture::create_depth_texture(&self.render_context, size, "depth_texture");
+        self.upscaler = (size != self.window_size || vignette > 0.0)
+            .then(|| Upscaler::new(&self.render_context, size, vignette));
+    }
+
+    fn set_render_scale(&mut self, render_scale: f32) {
+        println!(
+            "Rendering the world at {}% resolution",
+            render_scale * 100.0
+        );
+        self.settings.graphics.render_scale = render_scale;
+        self.create_render_targets();
+    }
+
+    pub fn configure_surface(&mut self) {
         self.render_context
             .surface
             .configure(&self.render_context.device, &self.surface_config);
+    }
 
-        self.player.view.projection.resize(size.width, size.height);
-        self.world.depth_texture =
-            Texture::create_depth_texture(&self.render_context, "depth_texture");
+    /// Switches to another present mode. If the surface doesn't support it, wgpu falls back to
+    /// `PresentMode::Fifo`.
+    fn set_present_mode(&mut self, present_mode: PresentMode) {
+        self.settings.graphics.present_mode = present_mode;
+        self.surface_config.present_mode = present_mode.into();
+        self.configure_surface();
+        Self::log_present_mode(present_mode);
+    }
+
+    /// wgpu 0.11 doesn't tell which present mode the surface ended up with, so this says what it
+    /// might have fallen back to.
+    fn log_present_mode(present_mode: PresentMode) {
+        match present_mode {
+            PresentMode::Fifo => println!("Using present mode Fifo"),
+            _ => println!(
+                "Using present mode {:?}, or Fifo if the surface doesn't support it",
+                present_mode
+            ),
+        }
+    }
+
+    /// Copies a teleport command to the player's position to the clipboard.
+    fn copy_coordinates(&mut self) {
+        let position = self.player.view.camera.position;
+        let command = format!("/tp {:.2} {:.2} {:.2}", position.x, position.y, position.z);
+
+        let result =
+            arboard::Clipboard::new().and_then(|mut clipboard| clipboard.set_text(command));
+        match result {
+            Ok(()) => self
+                .hud
+                .message_hud
+                .show(&self.render_context, "Copied coordinates!"),
+            Err(error) => eprintln!("Failed to copy coordinates to the clipboard: {}", error),
+        }
+    }
+
+    /// Runs the command on the clipboard, like the teleport command `copy_coordinates` puts there.
+    /// The chunks around the destination of a teleport are loaded before moving there.
+    fn paste_command(&mut self) {
+        let text = match arboard::Clipboard::new().and_then(|mut clipboard| clipboard.get_text()) {
+            Ok(text) => text,
+            Err(error) => {
+                eprintln!("Failed to read the clipboard: {}", error);
+                return;
+            }
+        };
+
+        match parse_command(&text) {
+            Some(Command::Teleport(position)) => {
+                self.teleport(position);
+                println!(
+                    "Teleported to {:.2} {:.2} {:.2}",
+                    position.x, position.y, position.z
+                );
+            }
+            Some(Command::Goto(position)) => {
+                let duration = Duration::from_millis(self.settings.player.goto_duration_ms);
+                self.player.fly_to(position, duration);
+                println!(
+                    "Flying to {:.2} {:.2} {:.2}",
+                    position.x, position.y, position.z
+                );
+            }
+            Some(Command::NewWorld(seed)) => self.confirm_new_world(seed),
+            Some(Command::Flatten(radius, block_type)) => {
+                // Level with the block the player is standing on
+                let center = self.player.feet() - Vector3::unit_y();
+                let count = self.world.flatten(center, radius, block_type);
+                println!(
+                    "Flattened {} blocks within {} of {} {} {}",
+                    count, radius, center.x, center.y, center.z
+                );
+            }
+            None => self
+                .hud
+                .message_hud
+                .show(&self.render_context, "No command on the clipboard"),
+        }
+    }
+
+    /// Starts a new world once `/newworld` has been pasted twice in a row, since it deletes the
+    /// current one.
+    fn confirm_new_world(&mut self, seed: Option<u32>) {
+        let confirmed = self
+            .pending_new_world
+            .take()
+            .filter(|&(pending, pasted)| {
+                pending == seed && pasted.elapsed() < NEW_WORLD_CONFIRM_TIME
+            })
+            .is_some();
+        if !confirmed {
+            self.pending_new_world = Some((seed, Instant::now()));
+            self.hud.message_hud.show(
+                &self.render_context,
+                "Paste /newworld again to delete this world",
+            );
+            return;
+        }
+
+        self.new_world(seed.unwrap_or_else(|| {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap();
+            fxhash::hash32(&now.as_nanos())
+        }));
+    }
+
+    /// Deletes the world, and starts a new one with `seed` at the spawn.
+    fn new_world(&mut self, seed: u32) {
+        let result = self
+            .world
+            .reset(&self.render_context, &self.settings.world, seed);
+        if let Err(error) = result {
+            eprintln!(
+                "Failed to reset the world: {:?}",
+                anyhow::Error::from(error)
+            );
+            self.hud
+                .message_hud
+                .show(&self.render_context, "Failed to reset the world");
+            return;
+        }
+
+        // New chunks are generated with the new seed from now on, also after restarting
+        self.settings.world.seed = seed;
+        self.mining = false;
+        self.drag_placing = None;
+        self.open_chest = None;
+        self.measure_points.clear();
+        self.teleport(SPAWN_POSITION);
+
+        println!("Started a new world with seed {}", seed);
+        self.hud.message_hud.show(
+            &self.render_context,
+            &format!("Started a new world with seed {}", seed),
+        );
+    }
+
+    /// Moves the player to `position`, after loading the chunks around it.
+    pub fn teleport(&mut self, position: Point3<f32>) {
+        self.world
+            .warmup(&self.render_context, position, TELEPORT_WARMUP_RADIUS);
+        self.player.view.camera.position = position;
+        self.player.up_speed = 0.0;
+    }
+
+    /// Writes the world to a new file in `exports/`, named after the current time.
+    fn export_world(&mut self) {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+        let path = PathBuf::from(format!("exports/world-{}.minecrab", timestamp));
+        let header = ExportHeader {
+            seed: self.settings.world.seed,
+            player_position: self.player.view.camera.position.into(),
+        };
+
+        match self.world.export(&path, header) {
+            Ok(()) => {
+                println!("Exported the world to {}", path.display());
+                self.hud
+                    .message_hud
+                    .show(&self.render_context, "Exported world");
+            }
+            Err(error) => eprintln!("Failed to export the world: {:?}", error),
+        }
+    }
+
+    /// Marks the block under the crosshair as one end of a measurement. Once both ends are marked,
+    /// the distance between them is shown in the debug HUD, until the next press clears them.
+    fn mark_measure_point(&mut self) {
+        if self.measure_points.len() == 2 {
+            self.measure_points.clear();
+            self.hud
+                .debug_hud
+                .set_measurement(&self.render_context, None);
+            self.hud
+                .message_hud
+                .show(&self.render_context, "Cleared measurement");
+            return;
+        }
+
+        let position = match self.world.targeted_block_info() {
+            Some(info) => info.position,
+            None => {
+                self.hud
+                    .message_hud
+                    .show(&self.render_context, "No block targeted");
+                return;
+            }
+        };
+        self.measure_points.push(position);
+
+        let message = format!(
+            "Marked point {} at ({}, {}, {})",
+            self.measure_points.len(),
+            position.x,
+            position.y,
+            position.z
+        );
+        self.hud.message_hud.show(&self.render_context, &message);
+
+        if let [from, to] = self.measure_points[..] {
+            let delta = (to - from).map(isize::abs);
+            let distance = from.cast::<f32>().unwrap().distance(to.cast().unwrap());
+            let measurement = format!(
+                "distance {:.2} (x {}, y {}, z {})",
+                distance, delta.x, delta.y, delta.z
+            );
+            println!("Measured {}", measurement);
+            self.hud
+                .debug_hud
+                .set_measurement(&self.render_context, Some(&measurement));
+        }
+    }
+
+    /// Prints what's known about the block under the crosshair, and shows the gist of it.
+    fn show_targeted_block(&mut self) {
+        let info = match self.world.targeted_block_info() {
+            Some(info) => info,
+            None => {
+                self.hud
+                    .message_hud
+                    .show(&self.render_context, "No block targeted");
+                return;
+            }
+        };
+
+        let position: [isize; 3] = info.position.into();
+        let chunk: [isize; 3] = info.chunk.into();
+        let face: [i32; 3] = info.face.into();
+        let placement: [isize; 3] = info.placement.into();
+        println!(
+            "Targeted {:?} at {:?} in chunk {:?}, face {:?}, placing at {:?}",
+            info.block_type, position, chunk, face, placement,
+        );
+
+        let message = format!(
+            "{:?} ({}, {}, {})",
+            info.block_type, info.position.x, info.position.y, info.position.z
+        );
+        self.hud.message_hud.show(&self.render_context, &message);
+    }
+
+    /// Rebuilds the world and HUD pipelines from the shaders on disk. A shader that fails to
+    /// compile is reported, and its old pipelines stay in use.
+    fn reload_shaders(&mut self) {
+        if !self.settings.graphics.shader_hot_reload {
+            println!("Shader hot reloading is disabled in the settings");
+            return;
+        }
+
+        let results = vec![
+            self.world.reload_shaders(&self.render_context),
+            self.hud.reload_shaders(&self.render_context),
+        ];
+        let mut failed = false;
+        for error in results.into_iter().filter_map(Result::err) {
+            eprintln!("{}", error);
+            failed = true;
+        }
+
+        let message = if failed {
+            "Failed to reload shaders"
+        } else {
+            "Reloaded shaders"
+        };
+        println!("{}", message);
+        self.hud.message_hud.show(&self.render_context, message);
+    }
+
+    /// Highlights every loaded block of the targeted block's type, or stops highlighting them if
+    /// a type is already highlighted.
+    fn toggle_type_highlight(&mut self) {
+        let block_type = if self.world.highlighted_type.is_some() {
+            None
+        } else {
+            match self.world.targeted_block_info() {
+                Some(info) => Some(info.block_type),
+                None => {
+                    self.hud
+                        .message_hud
+                        .show(&self.render_context, "No block targeted");
+                    return;
+                }
+            }
+        };
+
+        let message = match block_type {
+            Some(block_type) => format!("Highlighting all {:?} blocks", block_type),
+            None => "Stopped highlighting blocks".to_string(),
+        };
+        println!("{}", message);
+        self.hud.message_hud.show(&self.render_context, &message);
+        self.world
+            .set_highlighted_type(&self.render_context, block_type);
+    }
+
+    /// Skips ahead to the next of midnight, dawn, noon and dusk.
+    fn skip_time_of_day(&mut self) {
+        let current = self.world.time.time_of_day;
+        let &(name, time_of_day) = TIMES_OF_DAY
+            .iter()
+            .find(|&&(_, time_of_day)| time_of_day > current)
+            .unwrap_or(&TIMES_OF_DAY[0]);
+
+        println!("Skipping to {}", name);
+        self.world
+            .set_time_of_day(&self.render_context, time_of_day);
     }
 
     fn set_hotbar_cursor(&mut self, i: usize) {
+        if i >= self.hud.hotbar_hud.slots.len() {
+            return;
+        }
+
         self.hud
             .widgets_hud
             .set_hotbar_cursor(&self.render_context, i);
     }
 
+    pub fn is_key_held(&self, key_code: VirtualKeyCode) -> bool {
+        self.held_keys.is_held(key_code)
+    }
+
+    pub fn clear_held_keys(&mut self) {
+        self.held_keys.clear();
+        self.mining = false;
+        self.stop_drag_placing();
+    }
+
+    /// Ends a line of blocks placed by dragging, making it a single step in the undo history.
+    fn stop_drag_placing(&mut self) {
+        self.drag_placing = None;
+        self.world.history.commit();
+    }
+
+    /// Switches the world to the next debug view, or back to the regular colors.
+    fn cycle_debug_view(&mut self) {
+        let debug_view = self.world.debug_view.next();
+        self.world.set_debug_view(&self.render_context, debug_view);
+        self.hud
+            .debug_hud
+            .set_debug_view((debug_view != DebugView::Color).then(|| debug_view.name()));
+        println!("Showing {}", debug_view.name());
+    }
+
+    /// Undoes the most recent edit to the world.
+    fn undo(&mut self) {
+        self.stop_drag_placing();
+        let message = match self.world.undo() {
+            0 => "Nothing to undo".to_string(),
+            1 => "Undid 1 block".to_string(),
+            count => format!("Undid {} blocks", count),
+        };
+        self.hud.message_hud.show(&self.render_context, &message);
+    }
+
+    pub fn is_chest_open(&self) -> bool {
+        self.open_chest.is_some()
+    }
+
+    pub fn close_chest(&mut self) {
+        self.open_chest = None;
+    }
+
+    /// The position of the mouse cursor, in normalized device coordinates.
+    fn cursor_ndc(&self) -> (f32, f32) {
+        let x = (self.cursor_position.x / self.window_size.width as f64) as f32 * 2.0 - 1.0;
+        let y = 1.0 - (self.cursor_position.y / self.window_size.height as f64) as f32 * 2.0;
+        (x, y)
+    }
+
+    /// Shows what's in the chest or hotbar slot under the cursor.
+    fn update_chest_tooltip(&mut self) {
+        let (x, y) = self.cursor_ndc();
+        let block_type = match ChestHud::slot_at(x, y, self.hud.hotbar_hud.slots.len()) {
+            Some(ChestHudSlot::Hotbar(slot)) => {
+                self.hud.hotbar_hud.slots[slot].map(|stack| stack.block_type)
+            }
+            Some(ChestHudSlot::Chest(slot)) => {
+                match self.open_chest.and_then(|p| self.world.get_block_entity(p)) {
+                    Some(BlockEntity::Chest(chest)) => {
+                        chest.slots[slot].map(|stack| stack.block_type)
+                    }
+                    None => None,
+                }
+            }
+            None => None,
+        };
+
+        let tooltip = block_type.map(|block_type| (block_type, (x, y)));
+        self.hud
+            .chest_hud
+            .set_tooltip(&self.render_context, tooltip);
+    }
+
+    /// Left clicking a chest slot moves its stack into the selected hotbar slot, right clicking
+    /// puts one of the selected block in it. Clicking the hotbar selects a slot.
+    fn click_chest_slot(&mut self, button: MouseButton) {
+        let (x, y) = self.cursor_ndc();
+
+        let chest_position = self.open_chest.unwrap();
+        let selected = self.hud.widgets_hud.hotbar_cursor_position;
+
+        match ChestHud::slot_at(x, y, self.hud.hotbar_hud.slots.len()) {
+            Some(ChestHudSlot::Hotbar(slot)) => self.set_hotbar_cursor(slot),
+            Some(ChestHudSlot::Chest(slot)) => {
+                let chest = match self.world.get_block_entity_mut(chest_position) {
+                    Some(BlockEntity::Chest(chest)) => chest,
+                    _ => return,
+                };
+
+                // Blocks move between the chest and the hotbar, they're never copied or lost
+                let hotbar_slot = &mut self.hud.hotbar_hud.slots[selected];
+                if button == MouseButton::Left {
+                    *hotbar_slot = chest.swap(slot, *hotbar_slot);
+                } else if button == MouseButton::Right {
+                    chest.move_one(slot, hotbar_slot);
+                }
+            }
+            None => (),
+        }
+    }
+
     fn input_keyboard(&mut self, key_code: VirtualKeyCode, state: ElementState) {
         let pressed = state == ElementState::Pressed;
+        let just_pressed = self.held_keys.update(key_code, state);
+
+        // Any movement key takes back control from a `/goto`
+        let movement = matches!(
+            key_code,
+            VirtualKeyCode::W
+                | VirtualKeyCode::S
+                | VirtualKeyCode::A
+                | VirtualKeyCode::D
+                | VirtualKeyCode::Space
+                | VirtualKeyCode::LShift
+        );
+        if just_pressed && movement {
+            self.player.camera_path = None;
+        }
 
         match key_code {
-            VirtualKeyCode::F2 if pressed => self.player.creative ^= true,
+            VirtualKeyCode::F2 if just_pressed => self.player.creative ^= true,
+            VirtualKeyCode::E if just_pressed => self.close_chest(),
+            VirtualKeyCode::Z if just_pressed && self.is_key_held(VirtualKeyCode::LControl) => {
+                self.undo()
+            }
+            VirtualKeyCode::C if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.copy_coordinates()
+            }
+            VirtualKeyCode::V if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.paste_command()
+            }
+            VirtualKeyCode::M if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.mark_measure_point()
+            }
+            VirtualKeyCode::X if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.export_world()
+            }
+            VirtualKeyCode::I if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.show_targeted_block()
+            }
+            VirtualKeyCode::T if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.reload_shaders()
+            }
+            VirtualKeyCode::H if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.toggle_type_highlight()
+            }
+            VirtualKeyCode::N if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.skip_time_of_day()
+            }
+            VirtualKeyCode::K if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.world.cull_back_faces ^= true;
+                self.hud
+                    .debug_hud
+                    .set_cull_back_faces(self.world.cull_back_faces);
+                if self.world.cull_back_faces {
+                    println!("Back-face culling enabled");
+                } else {
+                    println!("Back-face culling disabled");
+                }
+            }
+            VirtualKeyCode::G if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                self.cycle_debug_view()
+            }
+            VirtualKeyCode::P if just_pressed && self.is_key_held(VirtualKeyCode::F3) => {
+                let frozen = !self.world.time_frozen;
+                self.world.set_time_frozen(frozen);
+                if frozen {
+                    println!("Time frozen");
+                } else {
+                    println!("Time unfrozen");
+                }
+            }
+            VirtualKeyCode::F4 if just_pressed => {
+                self.world.streaming_frozen ^= true;
+                if self.world.streaming_frozen {
+                    println!("Chunk streaming frozen");
+                } else {
+                    println!("Chunk streaming unfrozen");
+                }
+            }
+            VirtualKeyCode::F5 if just_pressed => {
+                self.set_present_mode(self.settings.graphics.present_mode.next())
+            }
+            VirtualKeyCode::F6 if just_pressed => {
+                if self.world.wireframe_pipeline.is_none() {
+                    println!("Wireframes aren't supported by this device");
+                } else {
+                    self.world.wireframe_overlay ^= true;
+                }
+            }
+            VirtualKeyCode::F7 if just_pressed => {
+                self.player.auto_jump ^= true;
+                self.settings.player.auto_jump = self.player.auto_jump;
+                if self.player.auto_jump {
+                    println!("Auto-jump enabled");
+                } else {
+                    println!("Auto-jump disabled");
+                }
+            }
+            VirtualKeyCode::F8 if just_pressed => {
+                let current = self.settings.graphics.render_scale;
+                let next = RENDER_SCALES
+                    .iter()
+                    .position(|&scale| scale == current)
+                    .map_or(RENDER_SCALES[0], |i| {
+                        RENDER_SCALES[(i + 1) % RENDER_SCALES.len()]
+                    });
+                self.set_render_scale(next);
+            }
+            VirtualKeyCode::F9 if just_pressed => {
+                let view = &mut self.player.view;
+                view.set_camera_mode(view.camera_mode.next());
+            }
+            VirtualKeyCode::F10 if just_pressed => {
+                let lighting = self.world.lighting.next();
+                println!("Switching to {:?} lighting", lighting);
+                self.settings.graphics.lighting = lighting;
+                self.world.set_lighting(&self.render_context, lighting);
+            }
 
             // Hotbar
             VirtualKeyCode::Key1 if pressed => self.set_hotbar_cursor(0),
@@ -176,7 +991,7 @@ impl State {
                     (false, true) => 0.0,
 
                     // Not creative
-                    (true, false) if self.player.grounded => 0.6,
+                    (true, false) if self.player.grounded => JUMP_SPEED,
                     _ => self.player.up_speed,
                 };
             }
@@ -201,21 +1016,68 @@ impl State {
                 self.input_keyboard(input.virtual_keycode.unwrap(), input.state)
             }
 
+            WindowEvent::MouseInput {
+                button: MouseButton::Left,
+                state: ElementState::Released,
+                ..
+            } => self.mining = false,
+            WindowEvent::MouseInput {
+                button: MouseButton::Right,
+                state: ElementState::Released,
+                ..
+            } => self.stop_drag_placing(),
+
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = *position;
+                if self.is_chest_open() {
+                    self.update_chest_tooltip();
+                }
+            }
+
+            WindowEvent::MouseInput {
+                button,
+                state: ElementState::Pressed,
+                ..
+            } if self.is_chest_open() => {
+                self.click_chest_slot(*button);
+                self.update_chest_tooltip();
+            }
+
             WindowEvent::MouseInput {
                 button,
                 state: ElementState::Pressed,
                 ..
             } if self.mouse_grabbed => {
                 if button == &MouseButton::Left {
+                    self.hud.held_item_hud.swing();
+                    let creative = self.player.creative;
                     self.world
-                        .break_at_crosshair(&self.render_context, &self.player.view.camera);
+                        .break_at_crosshair(&self.player.view.camera, creative);
+                    self.mining = !creative;
                 } else if button == &MouseButton::Right {
-                    if let Some(selected) = self.hud.selected_block() {
-                        self.world.place_at_crosshair(
-                            &self.render_context,
-                            &self.player.view.camera,
-                            selected,
-                        );
+                    let target = self.world.highlighted.map(|(position, _)| position);
+                    let target_type = target
+                        .and_then(|position| self.world.get_block(position))
+                        .map(|block| block.block_type);
+
+                    match RightClick::new(target_type, self.hud.selected_block()) {
+                        RightClick::Use(BlockUse::OpenChest) => self.open_chest = target,
+                        RightClick::Place(selected) => {
+                            let placed = self.world.place_at_crosshair(
+                                &self.player.view.camera,
+                                selected,
+                                self.player.creative,
+                            );
+                            if placed.is_some() {
+                                self.hud.held_item_hud.swing();
+                            }
+
+                            // Holding left alt lays a line of blocks while the button is held
+                            if self.is_key_held(VirtualKeyCode::LAlt) {
+                                self.drag_placing = placed;
+                            }
+                        }
+                        RightClick::Nothing => (),
                     }
                 }
             }
@@ -239,20 +1101,125 @@ impl State {
     }
 
     pub fn update(&mut self, dt: Duration, render_time: Duration) {
-        self.player.update_position(dt, &self.world);
+        self.update_render_distance(dt);
+        let warmup = self
+            .player
+            .camera_path
+            .as_mut()
+            .and_then(CameraPath::take_warmup);
+        if let Some(destination) = warmup {
+            self.world
+                .warmup(&self.render_context, destination, TELEPORT_WARMUP_RADIUS);
+        }
+        if let Some(dust) = self.player.update_position(dt, &self.world) {
+            let feet = self.player.view.camera.position - Vector3::unit_y() * 1.62;
+            self.world.kick_up_dust(&self.render_context, feet, dust);
+        }
+        self.player.update_camera_feel(dt, &self.settings.camera);
+        self.player
+            .update_third_person(self.settings.camera.third_person_distance, &self.world);
+        self.world
+            .update_player_model(&self.render_context, self.player.model_bounds());
 
         let view = &mut self.player.view;
         view.update_view_projection(&self.render_context);
 
-        self.world
-            .update(&self.render_context, dt, render_time, &view.camera);
-        self.hud.update(&self.render_context, &view.camera);
+        // Chunks stream in around wherever the world is seen from, which is away from the player
+        // in third person, and wherever the free camera flew to when spectating
+        self.world.update(
+            &self.render_context,
+            dt,
+            render_time,
+            &view.camera,
+            view.position(),
+        );
+        self.world.update_breaking(
+            &self.render_context,
+            dt,
+            self.mining && !self.player.creative,
+        );
+        if let (Some((from, normal)), Some(selected)) =
+            (self.drag_placing, self.hud.selected_block())
+        {
+            let to = self.world.drag_place(&view.camera, selected, from, normal);
+            if to != from {
+                self.hud.held_item_hud.swing();
+            }
+            self.drag_placing = Some((to, normal));
+        }
+        let render_context = &self.render_context;
+        let gpu_times = self
+            .gpu_timer
+            .as_mut()
+            .and_then(|gpu_timer| gpu_timer.poll(render_context));
+        self.hud.update(
+            &self.render_context,
+            &view.camera,
+            render_time,
+            gpu_times,
+            self.world.lighting,
+        );
+        self.hud.debug_hud.set_time_of_day(
+            &self.render_context,
+            self.world.time.clock(),
+            self.world.time_frozen,
+        );
+
+        // Keep swinging for as long as a block is being mined
+        if self.mining && !self.player.creative {
+            self.hud.held_item_hud.swing();
+        }
+        let held_block = (self.player.view.camera_mode == CameraMode::FirstPerson)
+            .then(|| self.hud.selected_block())
+            .flatten();
+        self.hud
+            .held_item_hud
+            .update(&self.render_context, dt, held_block);
+
+        // Close the chest if it was broken, or its chunk got unloaded
+        let world = &self.world;
+        let chest = match self.open_chest.and_then(|p| world.get_block_entity(p)) {
+            Some(BlockEntity::Chest(chest)) => Some(chest),
+            None => {
+                self.open_chest = None;
+                None
+            }
+        };
+        self.hud.chest_hud.update(&self.render_context, chest);
     }
 
-    pub fn render(&mut self) -> anyhow::Result<(usize, Duration)> {
+    fn update_render_distance(&mut self, dt: Duration) {
+        let dynamic_render_distance = match &mut self.dynamic_render_distance {
+            Some(dynamic_render_distance) => dynamic_render_distance,
+            None => return,
+        };
+
+        let current = self.world.render_distance as usize;
+        if let Some(render_distance) = dynamic_render_distance.update(dt, current) {
+            println!("Changing the render distance to {}", render_distance);
+            self.world.set_render_distance(render_distance);
+            self.hud
+                .debug_hud
+                .set_render_distance(&self.render_context, render_distance, true);
+        }
+    }
+
+    fn write_timestamp(&self, encoder: &mut wgpu::CommandEncoder, index: u32) {
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.write_timestamp(encoder, index);
+        }
+    }
+
+    pub fn render(&mut self) -> error::Result<(usize, Duration)> {
         let render_start = Instant::now();
+        if self.device_lost.load(Ordering::Relaxed) {
+            return Err(MinecrabError::DeviceLost);
+        }
 
-        let frame = self.render_context.surface.get_current_texture().unwrap();
+        // Show the blocks changed since the last frame in this one
+        self.world.rebuild_dirty_chunks(&self.render_context);
+
+        let frame = self.render_context.surface.get_current_texture()?;
         let texture_view = frame
             .texture
             .create_view(&wgpu::TextureViewDescriptor::default());
@@ -264,23 +1231,56 @@ impl State {
                     label: Some("encoder"),
                 });
 
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_frame();
+        }
+
         let mut triangle_count = 0;
 
+        self.write_timestamp(&mut render_encoder, TIMESTAMP_WORLD);
+        let world_view = match &self.upscaler {
+            Some(upscaler) => &upscaler.view,
+            None => &texture_view,
+        };
         triangle_count += self.world.render(
             &self.render_context,
             &mut render_encoder,
-            &texture_view,
+            world_view,
             &self.player.view,
         );
+        self.hud
+            .debug_hud
+            .set_chunk_draw_calls(self.world.chunk_draw_calls);
+        self.hud
+            .debug_hud
+            .set_lod_triangles_saved(self.world.lod_triangles_saved());
+        if let Some(upscaler) = &self.upscaler {
+            upscaler.render(&mut render_encoder, &texture_view);
+        }
 
+        self.write_timestamp(&mut render_encoder, TIMESTAMP_HUD);
         triangle_count += self
             .hud
             .render(&self.render_context, &mut render_encoder, &texture_view);
+        self.write_timestamp(&mut render_encoder, TIMESTAMP_END);
+
+        if let Some(gpu_timer) = &self.gpu_timer {
+            gpu_timer.resolve(&mut render_encoder);
+        }
+
+        // Submitting to a lost device panics, so bail out if it was lost while encoding
+        if self.device_lost.load(Ordering::Relaxed) {
+            return Err(MinecrabError::DeviceLost);
+        }
 
         self.render_context
             .queue
             .submit(Some(render_encoder.finish()));
 
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.end_frame();
+        }
+
         // XXX: This deadlocks on Intel Xe with Mesa 21.2.4 (2021/10)
         // See https://github.com/gfx-rs/wgpu/issues/2070
         frame.present();
@@ -289,3 +1289,43 @@ impl State {
         Ok((triangle_count, render_time))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uses_interactive_blocks_instead_of_placing() {
+        let right_click = RightClick::new(Some(BlockType::Chest), Some(BlockType::Stone));
+        assert_eq!(right_click, RightClick::Use(BlockUse::OpenChest));
+    }
+
+    #[test]
+    fn places_against_other_blocks() {
+        let right_click = RightClick::new(Some(BlockType::Dirt), Some(BlockType::Stone));
+        assert_eq!(right_click, RightClick::Place(BlockType::Stone));
+        assert_eq!(
+            RightClick::new(None, Some(BlockType::Stone)),
+            RightClick::Place(BlockType::Stone)
+        );
+        assert_eq!(
+            RightClick::new(Some(BlockType::Dirt), None),
+            RightClick::Nothing
+        );
+    }
+
+    #[test]
+    fn parses_flatten_commands() {
+        assert_eq!(
+            parse_command("/flatten 8 oak_planks"),
+            Some(Command::Flatten(8, BlockType::OakPlanks))
+        );
+        assert_eq!(
+            parse_command("/flatten 0 Stone"),
+            Some(Command::Flatten(0, BlockType::Stone))
+        );
+        assert_eq!(parse_command("/flatten -1 stone"), None);
+        assert_eq!(parse_command("/flatten 8 lava"), None);
+        assert_eq!(parse_command("/flatten 8"), None);
+    }
+}
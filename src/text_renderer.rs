@@ -23,7 +23,6 @@ const CHARACTER_WIDTHS: [i32; 16 * 8] = [
 ];
 
 pub struct TextRenderer {
-    pub texture: Texture,
     pub bind_group: wgpu::BindGroup,
 }
 
@@ -88,10 +87,7 @@ impl TextRenderer {
                 ],
             });
 
-        Ok(Self {
-            texture,
-            bind_group,
-        })
+        Ok(Self { bind_group })
     }
 
     fn char_uv(c: u8) -> (f32, f32) {
@@ -155,6 +151,15 @@ impl TextRenderer {
         Geometry::new(vertices, indices)
     }
 
+    /// The width of the string on a single line, in normalized device coordinates.
+    pub fn string_width(&self, string: &str) -> f32 {
+        string
+            .bytes()
+            .filter(u8::is_ascii)
+            .map(|c| DX * (CHARACTER_WIDTHS[c as usize] as f32 / 8.0))
+            .sum()
+    }
+
     pub fn string_to_buffers(
         &self,
         render_context: &RenderContext,
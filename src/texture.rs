@@ -1,25 +1,39 @@
-use std::{num::NonZeroU32, ops::Range};
+use std::{
+    fmt,
+    num::{NonZeroU32, NonZeroU8},
+    ops::Range,
+};
 
 use anyhow::Context;
 use cgmath::{Vector2, Zero};
-use image::{EncodableLayout, ImageBuffer, Rgba};
+use fxhash::FxHashMap;
+use image::{EncodableLayout, ImageBuffer, Rgba, RgbaImage};
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use wgpu::Origin3d;
+use winit::dpi::PhysicalSize;
 
-use crate::render_context::RenderContext;
+use crate::{
+    error::{self, MinecrabError},
+    render_context::RenderContext,
+    world::block::BlockType,
+};
 
 pub struct Texture {
     pub texture: wgpu::Texture,
-    pub sampler: Option<wgpu::Sampler>,
     pub view: wgpu::TextureView,
 }
 
 impl Texture {
     pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
 
-    pub fn create_depth_texture(render_context: &RenderContext, label: &str) -> Self {
+    pub fn create_depth_texture(
+        render_context: &RenderContext,
+        size: PhysicalSize<u32>,
+        label: &str,
+    ) -> Self {
         let size = wgpu::Extent3d {
-            width: render_context.size.width,
-            height: render_context.size.height,
+            width: size.width,
+            height: size.height,
             depth_or_array_layers: 1,
         };
 
@@ -37,28 +51,8 @@ impl Texture {
             });
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = render_context
-            .device
-            .create_sampler(&wgpu::SamplerDescriptor {
-                label: Some("depth sampler"),
-                address_mode_u: wgpu::AddressMode::ClampToEdge,
-                address_mode_v: wgpu::AddressMode::ClampToEdge,
-                address_mode_w: wgpu::AddressMode::ClampToEdge,
-                mag_filter: wgpu::FilterMode::Linear,
-                min_filter: wgpu::FilterMode::Linear,
-                mipmap_filter: wgpu::FilterMode::Nearest,
-                lod_min_clamp: -100.0,
-                lod_max_clamp: 100.0,
-                compare: Some(wgpu::CompareFunction::LessEqual),
-                anisotropy_clamp: None,
-                border_color: None,
-            });
 
-        Self {
-            texture,
-            sampler: Some(sampler),
-            view,
-        }
+        Self { texture, view }
     }
 
     fn from_rgba8(
@@ -112,11 +106,7 @@ impl Texture {
             ..wgpu::TextureViewDescriptor::default()
         });
 
-        Ok(Self {
-            texture,
-            sampler: None,
-            view,
-        })
+        Ok(Self { texture, view })
     }
 
     pub fn from_bytes(
@@ -125,36 +115,46 @@ impl Texture {
         label: &str,
     ) -> anyhow::Result<Self> {
         let image = image::load_from_memory(bytes)?;
-        let rgba = image.into_rgba8();
+        Self::from_image(render_context, &image.into_rgba8(), label)
+    }
+
+    pub fn from_image(
+        render_context: &RenderContext,
+        rgba: &RgbaImage,
+        label: &str,
+    ) -> anyhow::Result<Self> {
         let (width, height) = rgba.dimensions();
         Self::from_rgba8(
             render_context,
-            &rgba,
+            rgba,
             Vector2::zero(),
             Vector2::new(width, height),
             label,
         )
     }
 
-    pub fn from_bytes_atlas(
+    pub fn from_image_atlas(
         render_context: &RenderContext,
-        bytes: &[u8],
+        rgba: &RgbaImage,
         tile_size: Vector2<u32>,
         label: &str,
     ) -> anyhow::Result<Vec<Self>> {
-        let image = image::load_from_memory(bytes)?;
-        let rgba = image.into_rgba8();
-
         let (width, height) = rgba.dimensions();
-        assert_eq!(width % tile_size.x, 0);
-        assert_eq!(height % tile_size.y, 0);
+        anyhow::ensure!(
+            width % tile_size.x == 0 && height % tile_size.y == 0,
+            "Atlas size {}x{} is not a multiple of the tile size {}x{}",
+            width,
+            height,
+            tile_size.x,
+            tile_size.y,
+        );
 
         let mut tiles = Vec::new();
         for y in (0..height).step_by(tile_size.y as usize) {
             for x in (0..width).step_by(tile_size.x as usize) {
                 tiles.push(Self::from_rgba8(
                     render_context,
-                    &rgba,
+                    rgba,
                     Vector2::new(x, y),
                     tile_size,
                     &format!("{}({},{})", label, x, y),
@@ -166,7 +166,116 @@ impl Texture {
     }
 }
 
-pub const TEXTURE_COUNT: usize = 44;
+/// The index of the first frame of the water animation, which `world.wgsl` has hardcoded along
+/// with `WATER_FRAMES`.
+pub const WATER_TEXTURE: usize = 8;
+pub const WATER_FRAMES: usize = 32;
+
+/// The index of the first of the block cracking textures, one for each of the `DESTROY_STAGES`.
+pub const DESTROY_STAGE_TEXTURE: usize = 44;
+pub const DESTROY_STAGES: usize = 10;
+
+const ATLAS_TILE_SIZE: Vector2<u32> = Vector2::new(16, 16);
+
+/// The name of a texture, the path of its image, and its tile size if it's an atlas.
+type TextureSource<'a> = (&'a str, &'a str, Option<Vector2<u32>>);
+
+/// The block textures in the order they end up in the texture array, together with the name blocks
+/// refer to them by and the tile size for textures that are atlases.
+#[rustfmt::skip]
+const BLOCK_TEXTURES: &[TextureSource<'static>] = &[
+    ("cobblestone",              "assets/block/cobblestone.png",              None), // 0
+    ("dirt",                     "assets/block/dirt.png",                     None), // 1
+    ("stone",                    "assets/block/stone.png",                    None), // 2
+    ("grass_block_top",          "assets/grass_block_top_plains.png",         None), // 3
+    ("grass_block_side_overlay", "assets/block/grass_block_side_overlay.png", None), // 4
+    ("bedrock",                  "assets/block/bedrock.png",                  None), // 5
+    ("sand",                     "assets/block/sand.png",                     None), // 6
+    ("gravel",                   "assets/block/gravel.png",                   None), // 7
+    ("water",                    "assets/block/water_still.png",              Some(ATLAS_TILE_SIZE)), // 8 - 39
+    ("oak_log",                  "assets/block/oak_log.png",                  None), // 40
+    ("oak_log_top",              "assets/block/oak_log_top.png",              None), // 41
+    ("oak_planks",               "assets/block/oak_planks.png",               None), // 42
+    ("oak_leaves",               "assets/block/oak_leaves.png",               None), // 43
+    ("destroy_stage_0",          "assets/block/destroy_stage_0.png",          None), // 44
+    ("destroy_stage_1",          "assets/block/destroy_stage_1.png",          None), // 45
+    ("destroy_stage_2",          "assets/block/destroy_stage_2.png",          None), // 46
+    ("destroy_stage_3",          "assets/block/destroy_stage_3.png",          None), // 47
+    ("destroy_stage_4",          "assets/block/destroy_stage_4.png",          None), // 48
+    ("destroy_stage_5",          "assets/block/destroy_stage_5.png",          None), // 49
+    ("destroy_stage_6",          "assets/block/destroy_stage_6.png",          None), // 50
+    ("destroy_stage_7",          "assets/block/destroy_stage_7.png",          None), // 51
+    ("destroy_stage_8",          "assets/block/destroy_stage_8.png",          None), // 52
+    ("destroy_stage_9",          "assets/block/destroy_stage_9.png",          None), // 53
+    ("glass",                    "assets/block/glass.png",                    None), // 54
+    ("white_stained_glass",      "assets/block/white_stained_glass.png",      None), // 55
+    ("glowstone",                "assets/block/glowstone.png",                None), // 56
+];
+
+/// Works out where the textures end up in the texture array, given how many layers each of them
+/// takes up, in order: one for a plain texture, and one per tile for an atlas. Returns the index
+/// of every texture's first layer by name, and how many layers there are in total.
+pub fn texture_layers(
+    textures: &[(&'static str, usize)],
+) -> (FxHashMap<&'static str, usize>, usize) {
+    let mut names = FxHashMap::default();
+    let mut layer_count = 0;
+    for &(name, layers) in textures {
+        names.insert(name, layer_count);
+        layer_count += layers;
+    }
+    (names, layer_count)
+}
+
+/// Lists every texture that failed to load, rather than just the first one.
+#[derive(Debug)]
+pub struct TextureLoadError {
+    pub failures: Vec<(String, anyhow::Error)>,
+}
+
+impl fmt::Display for TextureLoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Failed to load {} texture(s):", self.failures.len())?;
+        for (path, error) in &self.failures {
+            write!(f, "\n  {}: {:#}", path, error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for TextureLoadError {}
+
+fn read_image(path: &str) -> anyhow::Result<RgbaImage> {
+    let bytes = std::fs::read(path).context("Failed to read file")?;
+    let image = image::load_from_memory(&bytes).context("Failed to decode image")?;
+    Ok(image.into_rgba8())
+}
+
+/// Reads and decodes the images of `textures` on the thread pool. Every image that can't be read
+/// or decoded is reported, rather than just the first one.
+fn read_images<'a>(
+    textures: &[TextureSource<'a>],
+) -> Result<Vec<(TextureSource<'a>, RgbaImage)>, TextureLoadError> {
+    let images: Vec<_> = textures
+        .par_iter()
+        .map(|&texture| (texture, read_image(texture.1)))
+        .collect();
+
+    let mut loaded = Vec::new();
+    let mut failures = Vec::new();
+    for (texture, image) in images {
+        match image {
+            Ok(image) => loaded.push((texture, image)),
+            Err(error) => failures.push((texture.1.to_string(), error)),
+        }
+    }
+
+    if failures.is_empty() {
+        Ok(loaded)
+    } else {
+        Err(TextureLoadError { failures })
+    }
+}
 
 pub struct TextureManager {
     pub bind_group_layout: wgpu::BindGroupLayout,
@@ -174,10 +283,78 @@ pub struct TextureManager {
 
     pub textures: Vec<Texture>,
     pub bind_group: Option<wgpu::BindGroup>,
+
+    /// The index of every texture in `BLOCK_TEXTURES` by name. For atlases, this is the index of
+    /// the first tile.
+    names: FxHashMap<&'static str, usize>,
+    /// The textures of every block type. Only filled in by `load_all`.
+    pub blocks: BlockTextures,
+}
+
+/// The texture indices of the faces of a block, in the order of `BlockType::texture_names`.
+pub type FaceTextures = (usize, usize, usize, usize, usize, usize);
+/// The texture indices of the overlays on the faces of a block, in the same order.
+pub type FaceOverlays = [Option<usize>; 6];
+
+/// The texture indices of the faces of every block type, and of the overlays on them. Unlike the
+/// textures themselves, these don't need the GPU.
+#[derive(Default)]
+pub struct BlockTextures {
+    /// Indexed by block type.
+    faces: Vec<FaceTextures>,
+    /// Indexed by block type.
+    overlays: Vec<FaceOverlays>,
+}
+
+impl BlockTextures {
+    /// Looks up the textures every block type refers to by name in `names`, which holds the index
+    /// of every texture by name.
+    pub fn resolve(names: &FxHashMap<&'static str, usize>) -> error::Result<Self> {
+        let index = |block_type, name| {
+            names
+                .get(name)
+                .copied()
+                .ok_or(MinecrabError::UnknownTexture { block_type, name })
+        };
+
+        let faces = BlockType::all()
+            .iter()
+            .map(|&block_type| {
+                let [a, b, c, d, e, f] = block_type
+                    .texture_names()
+                    .map(|name| index(block_type, name));
+                Ok((a?, b?, c?, d?, e?, f?))
+            })
+            .collect::<error::Result<_>>()?;
+        let overlays = BlockType::all()
+            .iter()
+            .map(|&block_type| {
+                let mut overlays = [None; 6];
+                for (overlay, name) in overlays.iter_mut().zip(block_type.overlay_texture_names()) {
+                    *overlay = name.map(|name| index(block_type, name)).transpose()?;
+                }
+                Ok(overlays)
+            })
+            .collect::<error::Result<_>>()?;
+
+        Ok(Self { faces, overlays })
+    }
+
+    /// Returns the texture indices of the faces of a block type.
+    pub fn faces(&self, block_type: BlockType) -> FaceTextures {
+        self.faces[block_type as usize]
+    }
+
+    /// Returns the texture indices of the overlays on the faces of a block type.
+    pub fn overlays(&self, block_type: BlockType) -> FaceOverlays {
+        self.overlays[block_type as usize]
+    }
 }
 
 impl TextureManager {
-    pub fn new(render_context: &RenderContext) -> Self {
+    /// Creates the texture manager. `anisotropy_clamp` enables anisotropic filtering of the block
+    /// textures, and has to be supported by the device.
+    pub fn new(render_context: &RenderContext, anisotropy_clamp: Option<NonZeroU8>) -> Self {
         let bind_group_layout =
             render_context
                 .device
@@ -206,6 +383,13 @@ impl TextureManager {
                     ],
                 });
 
+        // Anisotropic filtering only does anything when minifying with linear filtering. Close up,
+        // the textures stay pixelated.
+        let min_filter = match anisotropy_clamp {
+            Some(_) => wgpu::FilterMode::Linear,
+            None => wgpu::FilterMode::Nearest,
+        };
+
         let sampler = render_context
             .device
             .create_sampler(&wgpu::SamplerDescriptor {
@@ -214,6 +398,9 @@ impl TextureManager {
                 address_mode_v: wgpu::AddressMode::Repeat,
                 address_mode_w: wgpu::AddressMode::Repeat,
                 mag_filter: wgpu::FilterMode::Nearest,
+                min_filter,
+                mipmap_filter: min_filter,
+                anisotropy_clamp,
                 ..wgpu::SamplerDescriptor::default()
             });
 
@@ -223,27 +410,42 @@ impl TextureManager {
 
             textures: Vec::new(),
             bind_group: None,
+
+            names: FxHashMap::default(),
+            blocks: BlockTextures::default(),
         }
     }
 
-    pub fn load_all(&mut self, render_context: &RenderContext) -> anyhow::Result<()> {
-        let tile_size = Vector2::new(16, 16);
-
-        self.load(render_context, "assets/block/cobblestone.png")?; // 0
-        self.load(render_context, "assets/block/dirt.png")?; // 1
-        self.load(render_context, "assets/block/stone.png")?; // 2
-        self.load(render_context, "assets/grass_block_top_plains.png")?; // 3
-        self.load(render_context, "assets/grass_block_side_plains.png")?; // 4
-        self.load(render_context, "assets/block/bedrock.png")?; // 5
-        self.load(render_context, "assets/block/sand.png")?; // 6
-        self.load(render_context, "assets/block/gravel.png")?; // 7
-        self.load_atlas(render_context, "assets/block/water_still.png", tile_size)?; // 8 - 39
-        self.load(render_context, "assets/block/oak_log.png")?; // 40
-        self.load(render_context, "assets/block/oak_log_top.png")?; // 41
-        self.load(render_context, "assets/block/oak_planks.png")?; // 42
-        self.load(render_context, "assets/block/oak_leaves.png")?; // 43
-        assert_eq!(TEXTURE_COUNT, self.textures.len());
+    pub fn load_all(&mut self, render_context: &RenderContext) -> error::Result<()> {
+        let images = read_images(BLOCK_TEXTURES)?;
+
+        let mut layers = Vec::new();
+        let mut failures = Vec::new();
+        for ((name, path, tile_size), image) in images {
+            let result = match tile_size {
+                Some(tile_size) => self
+                    .add_atlas(render_context, &image, tile_size, path)
+                    .map(|tiles| tiles.len()),
+                None => self.add(render_context, &image, path).map(|_| 1),
+            };
+
+            match result {
+                Ok(count) => layers.push((name, count)),
+                Err(error) => failures.push((path.to_string(), error)),
+            }
+        }
 
+        if !failures.is_empty() {
+            return Err(TextureLoadError { failures }.into());
+        }
+
+        // The array has a layer for every texture that was loaded, atlas tiles included. The world
+        // shader still finds the water frames by their layers, so textures can only be added after
+        // them without moving `WATER_TEXTURE` and `DESTROY_STAGE_TEXTURE` along
+        let (names, layer_count) = texture_layers(&layers);
+        self.names = names;
+        self.blocks = BlockTextures::resolve(&self.names)?;
+        let layer_count = layer_count as u32;
         let texture_array = render_context
             .device
             .create_texture(&wgpu::TextureDescriptor {
@@ -251,7 +453,7 @@ impl TextureManager {
                 size: wgpu::Extent3d {
                     width: 16,
                     height: 16,
-                    depth_or_array_layers: TEXTURE_COUNT as u32,
+                    depth_or_array_layers: layer_count,
                 },
                 mip_level_count: 1,
                 sample_count: 1,
@@ -300,7 +502,7 @@ impl TextureManager {
         let view = texture_array.create_view(&wgpu::TextureViewDescriptor {
             label: Some("load_all texture view"),
             dimension: Some(wgpu::TextureViewDimension::D2Array),
-            array_layer_count: NonZeroU32::new(TEXTURE_COUNT as u32),
+            array_layer_count: NonZeroU32::new(layer_count),
             ..wgpu::TextureViewDescriptor::default()
         });
 
@@ -324,10 +526,24 @@ impl TextureManager {
         Ok(())
     }
 
-    pub fn load(&mut self, render_context: &RenderContext, path: &str) -> anyhow::Result<usize> {
-        let bytes = std::fs::read(path).context(format!("Failed to load {}", path))?;
-        let texture = Texture::from_bytes(render_context, &bytes, path)
-            .context(format!("Failed to decode {}", path))?;
+    /// Returns the texture indices of the faces of a block type. Only valid after `load_all`.
+    pub fn block_textures(&self, block_type: BlockType) -> FaceTextures {
+        self.blocks.faces(block_type)
+    }
+
+    /// Returns the texture indices of the overlays on the faces of a block type. Only valid after
+    /// `load_all`.
+    pub fn block_overlays(&self, block_type: BlockType) -> FaceOverlays {
+        self.blocks.overlays(block_type)
+    }
+
+    pub fn add(
+        &mut self,
+        render_context: &RenderContext,
+        image: &RgbaImage,
+        path: &str,
+    ) -> anyhow::Result<usize> {
+        let texture = Texture::from_image(render_context, image, path)?;
 
         let id = self.textures.len();
         self.textures.push(texture);
@@ -336,15 +552,14 @@ impl TextureManager {
         Ok(id)
     }
 
-    pub fn load_atlas(
+    pub fn add_atlas(
         &mut self,
         render_context: &RenderContext,
-        path: &str,
+        image: &RgbaImage,
         tile_size: Vector2<u32>,
+        path: &str,
     ) -> anyhow::Result<Range<usize>> {
-        let bytes = std::fs::read(path).context(format!("Failed to load {}", path))?;
-        let mut textures = Texture::from_bytes_atlas(render_context, &bytes, tile_size, path)
-            .context(format!("Failed to decode {}", path))?;
+        let mut textures = Texture::from_image_atlas(render_context, image, tile_size, path)?;
 
         let start = self.textures.len();
         self.textures.append(&mut textures);
@@ -354,3 +569,107 @@ impl TextureManager {
         Ok(start..end)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lists_every_block_type_in_order() {
+        // `BlockTextures` is indexed by block type
+        for (index, &block_type) in BlockType::all().iter().enumerate() {
+            assert_eq!(block_type as usize, index);
+        }
+    }
+
+    #[test]
+    fn has_every_texture_blocks_refer_to() {
+        let names: Vec<_> = BLOCK_TEXTURES.iter().map(|&(name, _, _)| name).collect();
+        for &block_type in BlockType::all() {
+            let overlays = block_type.overlay_texture_names();
+            let textures = block_type.texture_names();
+            for name in textures.iter().chain(overlays.iter().flatten()) {
+                assert!(
+                    names.contains(name),
+                    "{:?} uses {}, which isn't in BLOCK_TEXTURES",
+                    block_type,
+                    name
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn gives_every_atlas_tile_a_layer() {
+        let (names, layer_count) = texture_layers(&[("dirt", 1), ("water", 32), ("stone", 1)]);
+        assert_eq!(names["dirt"], 0);
+        assert_eq!(names["water"], 1);
+        assert_eq!(names["stone"], 33);
+        assert_eq!(layer_count, 34);
+    }
+
+    #[test]
+    fn resolves_the_block_textures_by_name() {
+        let layers: Vec<_> = BLOCK_TEXTURES
+            .iter()
+            .map(|&(name, _, tile_size)| (name, if tile_size.is_some() { WATER_FRAMES } else { 1 }))
+            .collect();
+        let (names, layer_count) = texture_layers(&layers);
+        assert_eq!(names["glowstone"], layer_count - 1);
+        assert_eq!(names["water"], WATER_TEXTURE);
+        assert_eq!(names["oak_log"], WATER_TEXTURE + WATER_FRAMES);
+        assert_eq!(names["destroy_stage_0"], DESTROY_STAGE_TEXTURE);
+
+        let blocks = BlockTextures::resolve(&names).unwrap();
+        assert_eq!(blocks.faces(BlockType::Dirt), (1, 1, 1, 1, 1, 1));
+        assert_eq!(blocks.faces(BlockType::Grass).5, names["grass_block_top"]);
+        assert_eq!(blocks.overlays(BlockType::Grass)[0], Some(4));
+        assert_eq!(blocks.overlays(BlockType::Dirt), [None; 6]);
+    }
+
+    #[test]
+    fn refuses_to_resolve_unknown_textures() {
+        let (names, _) = texture_layers(&[("cobblestone", 1), ("dirt", 1)]);
+        match BlockTextures::resolve(&names) {
+            Err(MinecrabError::UnknownTexture { block_type, name }) => {
+                assert_eq!(block_type, BlockType::Stone);
+                assert_eq!(name, "stone");
+            }
+            other => panic!("expected an unknown texture, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn reports_every_texture_that_fails_to_load() {
+        // The block textures aren't in the repository, so make one to load
+        let directory =
+            std::env::temp_dir().join(format!("minecrab-textures-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let dirt = directory.join("dirt.png");
+        RgbaImage::new(16, 16).save(&dirt).unwrap();
+        let dirt = dirt.to_str().unwrap();
+
+        let textures = [
+            ("missing", "assets/block/missing.png", None),
+            ("dirt", dirt, None),
+            ("also_missing", "assets/block/also_missing.png", None),
+        ];
+        let error = read_images(&textures).unwrap_err();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let paths: Vec<_> = error
+            .failures
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect();
+        assert_eq!(
+            paths,
+            ["assets/block/missing.png", "assets/block/also_missing.png"]
+        );
+
+        let message = error.to_string();
+        assert!(message.starts_with("Failed to load 2 texture(s):"));
+        assert!(message.contains("assets/block/missing.png: Failed to read file"));
+        assert!(message.contains("assets/block/also_missing.png: Failed to read file"));
+    }
+}
@@ -1,11 +1,48 @@
+/// How long a full day and night take, in seconds.
+pub const DAY_LENGTH: f32 = 20.0 * 60.0;
+
+/// The time of day a new world starts at, a little after sunrise.
+const START_TIME_OF_DAY: f32 = 0.3;
+
+/// The times of day that can be skipped to, in the order they come in.
+pub const TIMES_OF_DAY: [(&str, f32); 4] = [
+    ("midnight", 0.0),
+    ("dawn", 0.25),
+    ("noon", 0.5),
+    ("dusk", 0.75),
+];
+
 #[repr(C)]
 #[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Time {
     pub time: f32,
+    /// How far along the day is, from 0 to 1. 0 is midnight, 0.25 sunrise, 0.5 noon and 0.75
+    /// sunset.
+    pub time_of_day: f32,
 }
 
 impl Time {
     pub fn new() -> Self {
-        Self { time: 0.0 }
+        Self {
+            time: 0.0,
+            time_of_day: START_TIME_OF_DAY,
+        }
+    }
+
+    /// Moves the time of day forward by `seconds`, wrapping it around at midnight.
+    pub fn advance_day(&mut self, seconds: f32) {
+        self.time_of_day = (self.time_of_day + seconds / DAY_LENGTH).rem_euclid(1.0);
+    }
+
+    /// Returns the time of day as hours and minutes on a 24-hour clock.
+    pub fn clock(&self) -> (u32, u32) {
+        let minutes = (self.time_of_day * 24.0 * 60.0) as u32 % (24 * 60);
+        (minutes / 60, minutes % 60)
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
     }
 }
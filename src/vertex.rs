@@ -40,8 +40,10 @@ impl Vertex for HudVertex {
 /// Represents a vertex in world geometry.
 ///
 /// Aside from the usual vertex position, texture coordinates and normal, this "vertex" also
-/// contains whether the block is highlighted (i.e. the player is pointing at the block), its
-/// texture index (to address the texture arrays) and a color multiplier.
+/// contains whether the block is highlighted (1 for the overlay over the face the player is
+/// pointing at, 2 if it's of the type being highlighted everywhere), its texture index (to address
+/// the texture arrays) and a color multiplier. Faces can have a second texture drawn over the
+/// first, with its own color multiplier; its index is -1 if they don't.
 #[repr(C)]
 #[derive(Copy, Clone, Debug, Default, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct BlockVertex {
@@ -51,6 +53,8 @@ pub struct BlockVertex {
     pub highlighted: i32,
     pub texture_id: i32,
     pub color: [f32; 4],
+    pub overlay_texture_id: i32,
+    pub overlay_color: [f32; 4],
 }
 
 const BLOCK_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
@@ -60,6 +64,8 @@ const BLOCK_VERTEX_ATTRIBUTES: &[VertexAttribute] = &wgpu::vertex_attr_array![
     3 => Sint32,
     4 => Sint32,
     5 => Float32x4,
+    6 => Sint32,
+    7 => Float32x4,
 ];
 
 impl Vertex for BlockVertex {
@@ -1,14 +1,41 @@
-use std::mem::size_of;
+use std::{f32::consts::PI, mem::size_of};
 
-use cgmath::{EuclideanSpace, Matrix4, Point3, SquareMatrix, Vector4, Zero};
+use cgmath::{EuclideanSpace, Matrix4, Point3, Rad, SquareMatrix, Vector3, Vector4, Zero};
 use wgpu::{BindGroup, BindGroupLayout, Buffer, BufferDescriptor, BufferUsages};
 
 use crate::{
     aabb::Aabb,
-    camera::{Camera, Projection, OPENGL_TO_WGPU_MATRIX},
+    camera::{Camera, Projection},
     render_context::RenderContext,
 };
 
+/// Where the player's eyes start out in a new world.
+pub const SPAWN_POSITION: Point3<f32> = Point3::new(10.0, 140.0, 10.0);
+
+/// Where the view is relative to the player's eyes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CameraMode {
+    FirstPerson,
+    /// Behind the player, looking the same way they are.
+    ThirdPersonBack,
+    /// In front of the player, looking back at them.
+    ThirdPersonFront,
+    /// Detached from the player, flying around on its own while they stay where they are.
+    Spectator,
+}
+
+impl CameraMode {
+    /// Returns the mode after this one, for cycling through them.
+    pub fn next(self) -> Self {
+        match self {
+            Self::FirstPerson => Self::ThirdPersonBack,
+            Self::ThirdPersonBack => Self::ThirdPersonFront,
+            Self::ThirdPersonFront => Self::Spectator,
+            Self::Spectator => Self::FirstPerson,
+        }
+    }
+}
+
 pub struct View {
     position_vector: Vector4<f32>,
     projection_matrix: Matrix4<f32>,
@@ -16,6 +43,16 @@ pub struct View {
 
     pub camera: Camera,
     pub projection: Projection,
+    /// Moves the view away from the camera without moving the camera itself, for view bobbing.
+    /// Raycasts keep using the camera's position.
+    pub offset: Vector3<f32>,
+    /// Widens the field of view on top of the projection's.
+    pub fov_delta: Rad<f32>,
+    pub camera_mode: CameraMode,
+    /// How far the view is moved from the camera in third person, in blocks.
+    pub third_person_distance: f32,
+    /// Where the free camera is in spectator mode. It looks the same way as the camera.
+    pub spectator_position: Point3<f32>,
 
     pub buffer: Buffer,
     pub bind_group_layout: BindGroupLayout,
@@ -27,12 +64,19 @@ impl View {
         ViewRaw {
             view_position: self.position_vector.into(),
             view_projection: self.projection_matrix.into(),
+            inverse_view_projection: self.projection_matrix.invert().unwrap().into(),
         }
     }
 
+    /// Where the view is drawn from: the camera, moved by the offset and the camera mode, or the
+    /// free camera in spectator mode. Only up to date after `update_view_projection`.
+    pub fn position(&self) -> Point3<f32> {
+        Point3::from_homogeneous(self.position_vector)
+    }
+
     pub fn new(render_context: &RenderContext) -> Self {
         let camera = Camera::new(
-            (10.0, 140.0, 10.0).into(),
+            SPAWN_POSITION,
             cgmath::Deg(45.0).into(),
             cgmath::Deg(-20.0).into(),
         );
@@ -86,6 +130,11 @@ impl View {
             frustrum_aabb: Aabb::default(),
             camera,
             projection,
+            offset: Vector3::zero(),
+            fov_delta: Rad(0.0),
+            camera_mode: CameraMode::FirstPerson,
+            third_person_distance: 0.0,
+            spectator_position: SPAWN_POSITION,
 
             buffer,
             bind_group_layout,
@@ -93,10 +142,38 @@ impl View {
         }
     }
 
+    /// Switches to another camera mode. Spectating starts from the player's eyes.
+    pub fn set_camera_mode(&mut self, camera_mode: CameraMode) {
+        if camera_mode == CameraMode::Spectator {
+            self.spectator_position = self.camera.position;
+        }
+        self.camera_mode = camera_mode;
+    }
+
     pub fn update_view_projection(&mut self, render_context: &RenderContext) {
-        self.position_vector = self.camera.position.to_homogeneous();
-        self.projection_matrix =
-            self.projection.calculate_matrix() * self.camera.calculate_matrix();
+        let mut camera = Camera {
+            position: self.camera.position + self.offset,
+            ..self.camera
+        };
+        match self.camera_mode {
+            CameraMode::FirstPerson => (),
+            CameraMode::ThirdPersonBack => {
+                camera.position -= camera.direction() * self.third_person_distance;
+            }
+            CameraMode::ThirdPersonFront => {
+                camera.position += camera.direction() * self.third_person_distance;
+                camera.yaw += Rad(PI);
+                camera.pitch = -camera.pitch;
+            }
+            CameraMode::Spectator => camera.position = self.spectator_position,
+        }
+        let projection = Projection {
+            fov_y: self.projection.fov_y + self.fov_delta,
+            ..self.projection
+        };
+
+        self.position_vector = camera.position.to_homogeneous();
+        self.projection_matrix = projection.calculate_matrix() * camera.calculate_matrix();
         self.frustrum_aabb = self.frustrum_aabb();
 
         render_context
@@ -105,18 +182,18 @@ impl View {
     }
 
     fn frustrum_aabb(&self) -> Aabb {
-        let projection = OPENGL_TO_WGPU_MATRIX.invert().unwrap() * self.projection_matrix;
-        let inverse_matrix = projection.invert().unwrap();
+        let inverse_matrix = self.projection_matrix.invert().unwrap();
 
+        // The corners of wgpu's clip space, where depth goes from 0 to 1
         let corners = [
             Vector4::new(-1.0, -1.0, 1.0, 1.0),
-            Vector4::new(-1.0, -1.0, -1.0, 1.0),
+            Vector4::new(-1.0, -1.0, 0.0, 1.0),
             Vector4::new(-1.0, 1.0, 1.0, 1.0),
-            Vector4::new(-1.0, 1.0, -1.0, 1.0),
+            Vector4::new(-1.0, 1.0, 0.0, 1.0),
             Vector4::new(1.0, -1.0, 1.0, 1.0),
-            Vector4::new(1.0, -1.0, -1.0, 1.0),
+            Vector4::new(1.0, -1.0, 0.0, 1.0),
             Vector4::new(1.0, 1.0, 1.0, 1.0),
-            Vector4::new(1.0, 1.0, -1.0, 1.0),
+            Vector4::new(1.0, 1.0, 0.0, 1.0),
         ];
 
         let mut min = Vector4::new(f32::INFINITY, f32::INFINITY, f32::INFINITY, 1.0);
@@ -141,4 +218,6 @@ impl View {
 pub struct ViewRaw {
     view_position: [f32; 4],
     view_projection: [[f32; 4]; 4],
+    /// Turns clip space positions back into world space, for reconstructing view rays.
+    inverse_view_projection: [[f32; 4]; 4],
 }
@@ -0,0 +1,262 @@
+use cgmath::{InnerSpace, Matrix4, Quaternion, SquareMatrix, Vector3, Vector4, Zero};
+use gltf::animation::{util::ReadOutputs, Interpolation, Property};
+
+/// A node of a glTF scene, in its rest pose.
+#[derive(Clone, Copy)]
+struct Node {
+    parent: Option<usize>,
+    translation: Vector3<f32>,
+    rotation: Quaternion<f32>,
+    scale: Vector3<f32>,
+}
+
+enum Keyframes {
+    Translation(Vec<Vector3<f32>>),
+    Rotation(Vec<Quaternion<f32>>),
+    Scale(Vec<Vector3<f32>>),
+}
+
+/// Animates one property of one node.
+struct Channel {
+    node: usize,
+    times: Vec<f32>,
+    keyframes: Keyframes,
+    step: bool,
+}
+
+impl Channel {
+    /// Returns the keyframes surrounding `time`, and how far `time` is between them.
+    fn keyframes_at(&self, time: f32) -> (usize, usize, f32) {
+        let next = self.times.iter().position(|&t| t > time);
+        match next {
+            Some(0) => (0, 0, 0.0),
+            None => {
+                let last = self.times.len() - 1;
+                (last, last, 0.0)
+            }
+            Some(next) => {
+                let previous = next - 1;
+                let span = self.times[next] - self.times[previous];
+                let t = if self.step || span <= 0.0 {
+                    0.0
+                } else {
+                    (time - self.times[previous]) / span
+                };
+                (previous, next, t)
+            }
+        }
+    }
+
+    fn apply(&self, time: f32, node: &mut Node) {
+        if self.times.is_empty() {
+            return;
+        }
+
+        let (a, b, t) = self.keyframes_at(time);
+        match &self.keyframes {
+            Keyframes::Translation(values) => {
+                node.translation = values[a] + (values[b] - values[a]) * t
+            }
+            Keyframes::Rotation(values) => node.rotation = values[a].slerp(values[b], t),
+            Keyframes::Scale(values) => node.scale = values[a] + (values[b] - values[a]) * t,
+        }
+    }
+}
+
+pub struct Animation {
+    pub name: Option<String>,
+    /// The time of the last keyframe, in seconds.
+    pub duration: f32,
+    channels: Vec<Channel>,
+}
+
+/// The joints of a skinned glTF model, together with the animations that move them.
+pub struct Skeleton {
+    nodes: Vec<Node>,
+    /// The node of every joint, in the order the vertices' joint indices refer to them.
+    joints: Vec<usize>,
+    inverse_bind_matrices: Vec<Matrix4<f32>>,
+    pub animations: Vec<Animation>,
+}
+
+impl Skeleton {
+    /// Reads the first skin of the document, and every animation. Returns `None` if the model
+    /// isn't skinned.
+    pub fn from_gltf(document: &gltf::Document, buffers: &[gltf::buffer::Data]) -> Option<Self> {
+        let skin = document.skins().next()?;
+        let get_buffer_data = |buffer: gltf::Buffer| Some(&*buffers[buffer.index()]);
+
+        let mut nodes: Vec<Node> = document
+            .nodes()
+            .map(|node| {
+                let (translation, rotation, scale) = node.transform().decomposed();
+                let [x, y, z, w] = rotation;
+                Node {
+                    parent: None,
+                    translation: translation.into(),
+                    rotation: Quaternion::new(w, x, y, z),
+                    scale: scale.into(),
+                }
+            })
+            .collect();
+        for node in document.nodes() {
+            for child in node.children() {
+                nodes[child.index()].parent = Some(node.index());
+            }
+        }
+
+        let joints: Vec<usize> = skin.joints().map(|joint| joint.index()).collect();
+        let inverse_bind_matrices = match skin.reader(get_buffer_data).read_inverse_bind_matrices()
+        {
+            Some(matrices) => matrices.map(Matrix4::from).collect(),
+            None => vec![Matrix4::identity(); joints.len()],
+        };
+
+        let animations = document
+            .animations()
+            .map(|animation| {
+                let channels: Vec<Channel> = animation
+                    .channels()
+                    .filter_map(|channel| Self::read_channel(&channel, get_buffer_data))
+                    .collect();
+                let duration = channels
+                    .iter()
+                    .filter_map(|channel| channel.times.last().copied())
+                    .fold(0.0, f32::max);
+
+                Animation {
+                    name: animation.name().map(str::to_string),
+                    duration,
+                    channels,
+                }
+            })
+            .collect();
+
+        Some(Self {
+            nodes,
+            joints,
+            inverse_bind_matrices,
+            animations,
+        })
+    }
+
+    fn read_channel<'a, 's>(
+        channel: &gltf::animation::Channel<'a>,
+        get_buffer_data: impl Clone + Fn(gltf::Buffer<'a>) -> Option<&'s [u8]>,
+    ) -> Option<Channel> {
+        let reader = channel.reader(get_buffer_data);
+        let times: Vec<f32> = reader.read_inputs()?.collect();
+
+        let interpolation = channel.sampler().interpolation();
+        let keyframes = match (channel.target().property(), reader.read_outputs()?) {
+            (Property::Translation, ReadOutputs::Translations(values)) => Keyframes::Translation(
+                values_only(values.map(Vector3::from).collect(), interpolation),
+            ),
+            (Property::Rotation, ReadOutputs::Rotations(values)) => {
+                Keyframes::Rotation(values_only(
+                    values
+                        .into_f32()
+                        .map(|[x, y, z, w]| Quaternion::new(w, x, y, z).normalize())
+                        .collect(),
+                    interpolation,
+                ))
+            }
+            (Property::Scale, ReadOutputs::Scales(values)) => Keyframes::Scale(values_only(
+                values.map(Vector3::from).collect(),
+                interpolation,
+            )),
+            // Morph targets aren't supported
+            _ => return None,
+        };
+
+        Some(Channel {
+            node: channel.target().node().index(),
+            times,
+            keyframes,
+            step: interpolation == Interpolation::Step,
+        })
+    }
+
+    /// Returns the index of the animation with the given name, if there is one.
+    pub fn find_animation(&self, name: &str) -> Option<usize> {
+        self.animations
+            .iter()
+            .position(|animation| animation.name.as_deref() == Some(name))
+    }
+
+    /// Poses the skeleton at `time` seconds into the animation, and returns the matrix of every
+    /// joint, which moves a vertex from its bind pose into the animated pose.
+    pub fn joint_matrices(&self, animation: Option<usize>, time: f32) -> Vec<Matrix4<f32>> {
+        let mut nodes = self.nodes.clone();
+        if let Some(animation) = animation.and_then(|i| self.animations.get(i)) {
+            for channel in &animation.channels {
+                channel.apply(time, &mut nodes[channel.node]);
+            }
+        }
+
+        let local: Vec<Matrix4<f32>> = nodes
+            .iter()
+            .map(|node| {
+                Matrix4::from_translation(node.translation)
+                    * Matrix4::from(node.rotation)
+                    * Matrix4::from_nonuniform_scale(node.scale.x, node.scale.y, node.scale.z)
+            })
+            .collect();
+        let global = |mut index: usize| {
+            let mut matrix = local[index];
+            while let Some(parent) = nodes[index].parent {
+                matrix = local[parent] * matrix;
+                index = parent;
+            }
+            matrix
+        };
+
+        self.joints
+            .iter()
+            .zip(&self.inverse_bind_matrices)
+            .map(|(&joint, inverse_bind_matrix)| global(joint) * inverse_bind_matrix)
+            .collect()
+    }
+}
+
+/// Cubic splines store an in-tangent, the value and an out-tangent for every keyframe. Only the
+/// values are used, and interpolated linearly instead.
+fn values_only<T>(values: Vec<T>, interpolation: Interpolation) -> Vec<T> {
+    match interpolation {
+        Interpolation::CubicSpline => values.into_iter().skip(1).step_by(3).collect(),
+        _ => values,
+    }
+}
+
+/// Blends the joint matrices by the vertex's weights, and moves the position and normal with the
+/// result. Vertices without any weight are left in place.
+pub fn skin_vertex(
+    joint_matrices: &[Matrix4<f32>],
+    joints: [u16; 4],
+    weights: [f32; 4],
+    position: [f32; 3],
+    normal: [f32; 3],
+) -> ([f32; 3], [f32; 3]) {
+    if weights.iter().sum::<f32>() <= 0.0 {
+        return (position, normal);
+    }
+
+    let mut matrix = Matrix4::zero();
+    for (&joint, &weight) in joints.iter().zip(&weights) {
+        if let Some(joint_matrix) = joint_matrices.get(joint as usize) {
+            matrix += joint_matrix * weight;
+        }
+    }
+
+    let position = matrix * Vector3::from(position).extend(1.0);
+    let normal = matrix * Vector3::from(normal).extend(0.0);
+    let normal = normal.truncate();
+    let normal = if normal.magnitude2() > 0.0 {
+        normal.normalize()
+    } else {
+        normal
+    };
+
+    let Vector4 { x, y, z, .. } = position;
+    ([x, y, z], normal.into())
+}
@@ -0,0 +1,100 @@
+use cgmath::{ElementWise, Vector4};
+use noise::{NoiseFn, OpenSimplex, Seedable};
+
+const CLIMATE_NOISE_SCALE: f64 = 1.0 / 512.0;
+
+/// How far apart the biome samples are that get averaged for a column's tint. Biome borders fade
+/// over roughly twice this distance.
+const BLEND_DISTANCE: isize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Desert,
+    Plains,
+    Forest,
+    Taiga,
+}
+
+impl Biome {
+    #[rustfmt::skip]
+    pub const fn foliage_color(self) -> Vector4<f32> {
+        match self {
+            Self::Desert => Vector4::new(0.682, 0.643, 0.165, 1.0),
+            Self::Plains => Vector4::new(0.478, 0.729, 0.126, 1.0),
+            Self::Forest => Vector4::new(0.349, 0.682, 0.188, 1.0),
+            Self::Taiga  => Vector4::new(0.408, 0.643, 0.392, 1.0),
+        }
+    }
+
+    /// The color grass and leaves are multiplied with in this biome.
+    ///
+    /// The grass textures and `BlockType::color` are made for plains, so this is relative to the
+    /// plains foliage color.
+    pub fn foliage_tint(self) -> Vector4<f32> {
+        self.foliage_color()
+            .div_element_wise(Self::Plains.foliage_color())
+    }
+}
+
+/// Decides which biome each column is in, based on temperature and humidity noise.
+pub struct BiomeMap {
+    temperature: OpenSimplex,
+    humidity: OpenSimplex,
+}
+
+impl BiomeMap {
+    /// Every seed has its own climate, so the biomes are where they are for that seed.
+    pub fn new(seed: u32) -> Self {
+        Self {
+            temperature: OpenSimplex::new().set_seed(seed),
+            humidity: OpenSimplex::new().set_seed(seed.wrapping_add(1)),
+        }
+    }
+
+    pub fn biome_at(&self, x: isize, z: isize) -> Biome {
+        let point = [
+            x as f64 * CLIMATE_NOISE_SCALE,
+            z as f64 * CLIMATE_NOISE_SCALE,
+        ];
+        let temperature = self.temperature.get(point);
+        let humidity = self.humidity.get(point);
+
+        if temperature < -0.25 {
+            Biome::Taiga
+        } else if temperature > 0.25 && humidity < 0.0 {
+            Biome::Desert
+        } else if humidity > 0.2 {
+            Biome::Forest
+        } else {
+            Biome::Plains
+        }
+    }
+
+    /// Averages the foliage tints of the biomes around a column, so borders between biomes show
+    /// a gradient instead of a hard edge.
+    pub fn foliage_tint_at(&self, x: isize, z: isize) -> Vector4<f32> {
+        let mut tint = Vector4::new(0.0, 0.0, 0.0, 0.0);
+        for (dx, dz) in itertools::iproduct!(-1..=1, -1..=1) {
+            let biome = self.biome_at(x + dx * BLEND_DISTANCE, z + dz * BLEND_DISTANCE);
+            tint += biome.foliage_tint();
+        }
+        tint / 9.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn biomes(biome_map: &BiomeMap) -> Vec<Biome> {
+        itertools::iproduct!(0..32, 0..32)
+            .map(|(x, z)| biome_map.biome_at(x * 64, z * 64))
+            .collect()
+    }
+
+    #[test]
+    fn places_the_biomes_by_seed() {
+        assert_eq!(biomes(&BiomeMap::new(7)), biomes(&BiomeMap::new(7)));
+        assert_ne!(biomes(&BiomeMap::new(7)), biomes(&BiomeMap::new(8)));
+    }
+}
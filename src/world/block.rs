@@ -1,8 +1,12 @@
-use cgmath::Vector4;
+use cgmath::{Vector3, Vector4};
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
 
-#[allow(dead_code)]
+use crate::world::{
+    block_light::MAX_LIGHT,
+    face_flags::{FaceFlags, FACE_TOP},
+};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
 #[repr(u8)]
 pub enum BlockType {
@@ -17,41 +21,335 @@ pub enum BlockType {
     OakLog,
     OakPlanks,
     OakLeaves,
+    StoneStairs,
+    Chest,
+    Glass,
+    StainedGlass,
+    Glowstone,
+}
+
+/// The kind of tool that breaks a block fastest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tool {
+    None,
+    Pickaxe,
+    Axe,
+    Shovel,
+}
+
+/// What right clicking an interactive block does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockUse {
+    OpenChest,
+}
+
+/// The shape of a block's geometry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockShape {
+    Cube,
+    /// A half-block base with a quarter-block step on the side the block is facing.
+    Stairs,
 }
 
 impl BlockType {
+    /// Every block type, in the order they're declared.
+    pub const fn all() -> &'static [BlockType] {
+        &[
+            Self::Cobblestone,
+            Self::Dirt,
+            Self::Stone,
+            Self::Grass,
+            Self::Bedrock,
+            Self::Sand,
+            Self::Gravel,
+            Self::Water,
+            Self::OakLog,
+            Self::OakPlanks,
+            Self::OakLeaves,
+            Self::StoneStairs,
+            Self::Chest,
+            Self::Glass,
+            Self::StainedGlass,
+            Self::Glowstone,
+        ]
+    }
+
+    /// Looks up a block type by the name shown to the player, ignoring case, and with underscores
+    /// instead of spaces, like `oak_planks`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::all().iter().copied().find(|block_type| {
+            block_type
+                .name()
+                .replace(' ', "_")
+                .eq_ignore_ascii_case(name)
+        })
+    }
+
+    /// The name shown to the player.
+    #[rustfmt::skip]
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::Cobblestone  => "Cobblestone",
+            Self::Dirt         => "Dirt",
+            Self::Stone        => "Stone",
+            Self::Grass        => "Grass Block",
+            Self::Bedrock      => "Bedrock",
+            Self::Sand         => "Sand",
+            Self::Gravel       => "Gravel",
+            Self::Water        => "Water",
+            Self::OakLog       => "Oak Log",
+            Self::OakPlanks    => "Oak Planks",
+            Self::OakLeaves    => "Oak Leaves",
+            Self::StoneStairs  => "Stone Stairs",
+            Self::Chest        => "Chest",
+            Self::Glass        => "Glass",
+            Self::StainedGlass => "Stained Glass",
+            Self::Glowstone    => "Glowstone",
+        }
+    }
+
+    /// The names of the textures on the block's faces, as listed in `BLOCK_TEXTURES`: the -X, +X,
+    /// -Z, +Z, bottom and top faces. They're resolved to texture indices by the `TextureManager`.
+    #[rustfmt::skip]
+    pub const fn texture_names(self) -> [&'static str; 6] {
+        match self {
+            Self::Cobblestone  => ["cobblestone"; 6],
+            Self::Dirt         => ["dirt"; 6],
+            Self::Stone        => ["stone"; 6],
+            Self::Grass        => ["dirt", "dirt", "dirt", "dirt", "dirt", "grass_block_top"],
+            Self::Bedrock      => ["bedrock"; 6],
+            Self::Sand         => ["sand"; 6],
+            Self::Gravel       => ["gravel"; 6],
+            Self::Water        => ["water"; 6],
+            Self::OakLog       => ["oak_log", "oak_log", "oak_log", "oak_log", "oak_log_top", "oak_log_top"],
+            Self::OakPlanks    => ["oak_planks"; 6],
+            Self::OakLeaves    => ["oak_leaves"; 6],
+            Self::StoneStairs  => ["stone"; 6],
+            Self::Chest        => ["oak_planks"; 6],
+            Self::Glass        => ["glass"; 6],
+            Self::StainedGlass => ["white_stained_glass"; 6],
+            Self::Glowstone    => ["glowstone"; 6],
+        }
+    }
+
+    /// The names of the textures drawn over the block's faces, in the same order as
+    /// `texture_names`. Overlays are tinted with the biome's foliage color, and the face's own
+    /// texture shows through where they're transparent.
     #[rustfmt::skip]
-    pub const fn texture_indices(self) -> (usize, usize, usize, usize, usize, usize) {
+    pub const fn overlay_texture_names(self) -> [Option<&'static str>; 6] {
+        const GRASS_SIDE: Option<&str> = Some("grass_block_side_overlay");
         match self {
-            BlockType::Cobblestone => ( 0,  0,  0,  0,  0,  0),
-            BlockType::Dirt        => ( 1,  1,  1,  1,  1,  1),
-            BlockType::Stone       => ( 2,  2,  2,  2,  2,  2),
-            BlockType::Grass       => ( 4,  4,  4,  4,  1,  3),
-            BlockType::Bedrock     => ( 5,  5,  5,  5,  5,  5),
-            BlockType::Sand        => ( 6,  6,  6,  6,  6,  6),
-            BlockType::Gravel      => ( 7,  7,  7,  7,  7,  7),
-            BlockType::Water       => ( 8,  8,  8,  8,  8,  8), // up to 39
-            BlockType::OakLog      => (40, 40, 40, 40, 41, 41),
-            BlockType::OakPlanks   => (42, 42, 42, 42, 42, 42),
-            BlockType::OakLeaves   => (43, 43, 43, 43, 43, 43),
+            Self::Grass => [GRASS_SIDE, GRASS_SIDE, GRASS_SIDE, GRASS_SIDE, None, None],
+            _           => [None; 6],
+        }
+    }
+
+    /// How hard the block is to break. Breaking takes `BREAK_TIME` per unit of hardness.
+    #[rustfmt::skip]
+    pub const fn hardness(self) -> f32 {
+        match self {
+            Self::Cobblestone  => 2.0,
+            Self::Dirt         => 0.5,
+            Self::Stone        => 1.5,
+            Self::Grass        => 0.6,
+            Self::Bedrock      => f32::INFINITY,
+            Self::Sand         => 0.5,
+            Self::Gravel       => 0.6,
+            Self::Water        => 100.0,
+            Self::OakLog       => 2.0,
+            Self::OakPlanks    => 2.0,
+            Self::OakLeaves    => 0.2,
+            Self::StoneStairs  => 1.5,
+            Self::Chest        => 2.5,
+            Self::Glass        => 0.3,
+            Self::StainedGlass => 0.3,
+            Self::Glowstone    => 0.3,
+        }
+    }
+
+    /// The tool that breaks the block fastest. There are no tools yet, so this is only shown in
+    /// tooltips.
+    #[rustfmt::skip]
+    pub const fn preferred_tool(self) -> Tool {
+        match self {
+            Self::Cobblestone  => Tool::Pickaxe,
+            Self::Dirt         => Tool::Shovel,
+            Self::Stone        => Tool::Pickaxe,
+            Self::Grass        => Tool::Shovel,
+            Self::Bedrock      => Tool::None,
+            Self::Sand         => Tool::Shovel,
+            Self::Gravel       => Tool::Shovel,
+            Self::Water        => Tool::None,
+            Self::OakLog       => Tool::Axe,
+            Self::OakPlanks    => Tool::Axe,
+            Self::OakLeaves    => Tool::None,
+            Self::StoneStairs  => Tool::Pickaxe,
+            Self::Chest        => Tool::Axe,
+            Self::Glass        => Tool::None,
+            Self::StainedGlass => Tool::None,
+            Self::Glowstone    => Tool::None,
         }
     }
 
     #[rustfmt::skip]
     pub const fn color(self) -> Vector4<f32> {
         match self {
-            Self::Water     => Vector4::new(0.247, 0.463, 0.894, 1.0),
-            Self::OakLeaves => Vector4::new(0.478, 0.729, 0.126, 1.0),
-            _               => Vector4::new(1.0, 1.0, 1.0, 1.0),
+            Self::Water        => Vector4::new(0.247, 0.463, 0.894, 1.0),
+            Self::OakLeaves    => Vector4::new(0.478, 0.729, 0.126, 1.0),
+            Self::StainedGlass => Vector4::new(0.4, 0.7, 1.0, 1.0),
+            _                  => Vector4::new(1.0, 1.0, 1.0, 1.0),
         }
     }
 
+    /// Whether the given face gets multiplied with the biome's foliage tint.
+    pub const fn is_biome_tinted(self, face: FaceFlags) -> bool {
+        match self {
+            Self::Grass => face == FACE_TOP,
+            Self::OakLeaves => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the block can be seen through. Transparent blocks are blended over the rest of
+    /// the world, and don't hide the faces of the blocks around them.
     pub const fn is_transparent(self) -> bool {
-        matches!(self, BlockType::Water)
+        matches!(self, Self::Water | Self::Glass | Self::StainedGlass)
+    }
+
+    /// How much light the block gives off, from 0 to `MAX_LIGHT`.
+    pub const fn light_emission(self) -> u8 {
+        match self {
+            Self::Glowstone => MAX_LIGHT,
+            _ => 0,
+        }
+    }
+
+    /// Whether light passes through the block.
+    pub const fn is_translucent(self) -> bool {
+        self.is_transparent() || !self.is_full_cube()
+    }
+
+    /// Whether the block is a liquid, which the crosshair looks through to the blocks behind it.
+    pub const fn is_fluid(self) -> bool {
+        matches!(self, Self::Water)
+    }
+
+    /// Whether placing a block into the block's cell replaces it, rather than placing the block
+    /// against it.
+    pub const fn is_replaceable(self) -> bool {
+        matches!(self, Self::Water)
+    }
+
+    /// Whether the block can be broken at all, even in creative.
+    pub const fn is_breakable(self) -> bool {
+        !matches!(self, Self::Bedrock)
+    }
+
+    /// What right clicking the block does instead of placing a block against it, for blocks that
+    /// are interactive.
+    pub const fn block_use(self) -> Option<BlockUse> {
+        match self {
+            Self::Chest => Some(BlockUse::OpenChest),
+            _ => None,
+        }
+    }
+
+    pub const fn shape(self) -> BlockShape {
+        match self {
+            Self::StoneStairs => BlockShape::Stairs,
+            _ => BlockShape::Cube,
+        }
+    }
+
+    /// Whether the block fills its whole cell, hiding the faces of the blocks next to it.
+    pub const fn is_full_cube(self) -> bool {
+        matches!(self.shape(), BlockShape::Cube)
+    }
+}
+
+/// The horizontal direction a block is facing.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, Serialize_repr, Deserialize_repr)]
+#[repr(u8)]
+pub enum Facing {
+    #[default]
+    North,
+    East,
+    South,
+    West,
+}
+
+impl Facing {
+    /// Returns the direction closest to the given horizontal camera yaw.
+    pub fn from_yaw(yaw: f32) -> Self {
+        let (x, z) = (yaw.cos(), yaw.sin());
+        if x.abs() > z.abs() {
+            if x > 0.0 {
+                Self::East
+            } else {
+                Self::West
+            }
+        } else if z > 0.0 {
+            Self::South
+        } else {
+            Self::North
+        }
+    }
+
+    pub const fn to_vector(self) -> Vector3<i32> {
+        match self {
+            Self::North => Vector3::new(0, 0, -1),
+            Self::East => Vector3::new(1, 0, 0),
+            Self::South => Vector3::new(0, 0, 1),
+            Self::West => Vector3::new(-1, 0, 0),
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        *self == Self::default()
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Block {
     pub block_type: BlockType,
+
+    /// Only meaningful for blocks that aren't `BlockShape::Cube`. Left out of the save data when
+    /// it's the default, so cube blocks don't take up any extra space.
+    #[serde(default, skip_serializing_if = "Facing::is_default")]
+    pub facing: Facing,
+}
+
+impl Block {
+    pub fn new(block_type: BlockType) -> Self {
+        Self {
+            block_type,
+            facing: Facing::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orders_hardness() {
+        assert!(!BlockType::Bedrock.is_breakable());
+        assert_eq!(BlockType::Bedrock.hardness(), f32::INFINITY);
+
+        let ordered = [
+            BlockType::OakLeaves,
+            BlockType::Dirt,
+            BlockType::Stone,
+            BlockType::Bedrock,
+        ];
+        for pair in ordered.windows(2) {
+            assert!(
+                pair[0].hardness() < pair[1].hardness(),
+                "{:?} should be softer than {:?}",
+                pair[0],
+                pair[1]
+            );
+        }
+    }
 }
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+
+use crate::world::block::BlockType;
+
+pub const CHEST_SLOTS: usize = 27;
+pub const MAX_STACK_SIZE: u32 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub block_type: BlockType,
+    pub count: u32,
+}
+
+/// Extra data stored for a single block, for blocks that need more than a `BlockType`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum BlockEntity {
+    Chest(Chest),
+}
+
+impl BlockEntity {
+    /// Creates the block entity that belongs with blocks of the given type, if it needs one.
+    pub fn for_block_type(block_type: BlockType) -> Option<Self> {
+        match block_type {
+            BlockType::Chest => Some(Self::Chest(Chest::default())),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Chest {
+    pub slots: [Option<ItemStack>; CHEST_SLOTS],
+}
+
+impl Chest {
+    /// Puts the stack in the slot, and returns the stack that was there before.
+    pub fn swap(&mut self, slot: usize, stack: Option<ItemStack>) -> Option<ItemStack> {
+        std::mem::replace(&mut self.slots[slot], stack)
+    }
+
+    /// Moves a single block from the stack into the slot, emptying the stack once its last block
+    /// is moved. Returns false, leaving the stack alone, if the block doesn't fit in the slot.
+    pub fn move_one(&mut self, slot: usize, from: &mut Option<ItemStack>) -> bool {
+        let stack = match from {
+            Some(stack) => stack,
+            None => return false,
+        };
+        if !self.insert_one(slot, stack.block_type) {
+            return false;
+        }
+
+        stack.count -= 1;
+        if stack.count == 0 {
+            *from = None;
+        }
+        true
+    }
+
+    /// Puts a single block in the slot. Returns false if the slot holds another block type, or if
+    /// its stack is full.
+    pub fn insert_one(&mut self, slot: usize, block_type: BlockType) -> bool {
+        match &mut self.slots[slot] {
+            None => {
+                self.slots[slot] = Some(ItemStack {
+                    block_type,
+                    count: 1,
+                });
+                true
+            }
+            Some(stack) if stack.block_type == block_type && stack.count < MAX_STACK_SIZE => {
+                stack.count += 1;
+                true
+            }
+            Some(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stack(block_type: BlockType, count: u32) -> Option<ItemStack> {
+        Some(ItemStack { block_type, count })
+    }
+
+    #[test]
+    fn swaps_stacks_without_losing_either() {
+        let mut chest = Chest::default();
+        chest.slots[3] = stack(BlockType::Stone, 12);
+
+        let taken = chest.swap(3, stack(BlockType::Dirt, 5));
+        assert_eq!(taken, stack(BlockType::Stone, 12));
+        assert_eq!(chest.slots[3], stack(BlockType::Dirt, 5));
+
+        assert_eq!(chest.swap(3, None), stack(BlockType::Dirt, 5));
+        assert_eq!(chest.slots[3], None);
+    }
+
+    #[test]
+    fn moves_one_block_at_a_time() {
+        let mut chest = Chest::default();
+        let mut hand = stack(BlockType::Stone, 2);
+
+        assert!(chest.move_one(0, &mut hand));
+        assert_eq!(hand, stack(BlockType::Stone, 1));
+        assert_eq!(chest.slots[0], stack(BlockType::Stone, 1));
+
+        assert!(chest.move_one(0, &mut hand));
+        assert_eq!(hand, None);
+        assert_eq!(chest.slots[0], stack(BlockType::Stone, 2));
+
+        assert!(!chest.move_one(0, &mut hand));
+        assert_eq!(chest.slots[0], stack(BlockType::Stone, 2));
+    }
+
+    #[test]
+    fn keeps_blocks_that_dont_fit() {
+        let mut chest = Chest::default();
+        chest.slots[0] = stack(BlockType::Dirt, 1);
+        chest.slots[1] = stack(BlockType::Stone, MAX_STACK_SIZE);
+        let mut hand = stack(BlockType::Stone, 3);
+
+        assert!(!chest.move_one(0, &mut hand));
+        assert!(!chest.move_one(1, &mut hand));
+        assert_eq!(hand, stack(BlockType::Stone, 3));
+        assert_eq!(chest.slots[0], stack(BlockType::Dirt, 1));
+        assert_eq!(chest.slots[1], stack(BlockType::Stone, MAX_STACK_SIZE));
+    }
+}
@@ -0,0 +1,286 @@
+use std::collections::VecDeque;
+
+use cgmath::{Point3, Vector3};
+use fxhash::{FxHashMap, FxHashSet};
+
+use crate::world::{
+    block::Block,
+    block_view::BlockView,
+    chunk::{world_to_chunk, CHUNK_ISIZE, CHUNK_SIZE},
+};
+
+/// The brightest a block can light up its surroundings. Light gets one level dimmer for every
+/// block it travels.
+pub const MAX_LIGHT: u8 = 15;
+
+const NEIGHBORS: [Vector3<isize>; 6] = [
+    Vector3::new(-1, 0, 0),
+    Vector3::new(1, 0, 0),
+    Vector3::new(0, -1, 0),
+    Vector3::new(0, 1, 0),
+    Vector3::new(0, 0, -1),
+    Vector3::new(0, 0, 1),
+];
+
+fn emission(block: Option<&Block>) -> u8 {
+    block.map_or(0, |block| block.block_type.light_emission())
+}
+
+fn is_translucent(block: Option<&Block>) -> bool {
+    block.is_none_or(|block| block.block_type.is_translucent())
+}
+
+/// The light given off by light-emitting blocks, flood filled through the loaded chunks. Light
+/// travels through air and translucent blocks, and crosses chunk borders like any other cell.
+///
+/// Only cells that are lit are stored, by their position in the world. Every update returns the
+/// cells whose light changed, so the chunks showing them can be rebuilt.
+#[derive(Default)]
+pub struct BlockLight {
+    levels: FxHashMap<Point3<isize>, u8>,
+}
+
+impl BlockLight {
+    pub fn get(&self, point: Point3<isize>) -> u8 {
+        self.levels.get(&point).copied().unwrap_or(0)
+    }
+
+    fn set(&mut self, point: Point3<isize>, level: u8) {
+        if level == 0 {
+            self.levels.remove(&point);
+        } else {
+            self.levels.insert(point, level);
+        }
+    }
+
+    /// Spreads the light of every cell in `queue` to its neighbors, and theirs, for as long as
+    /// that makes them brighter. `is_loaded` keeps the light from spreading into chunks that
+    /// aren't loaded.
+    fn spread(
+        &mut self,
+        blocks: &impl BlockView,
+        is_loaded: &impl Fn(Point3<isize>) -> bool,
+        mut queue: VecDeque<Point3<isize>>,
+        changed: &mut FxHashSet<Point3<isize>>,
+    ) {
+        while let Some(point) = queue.pop_front() {
+            let level = self.get(point);
+            if level <= 1 {
+                continue;
+            }
+
+            for &offset in &NEIGHBORS {
+                let neighbor = point + offset;
+                if self.get(neighbor) >= level - 1
+                    || !is_loaded(neighbor)
+                    || !is_translucent(blocks.get_block(neighbor))
+                {
+                    continue;
+                }
+
+                self.set(neighbor, level - 1);
+                changed.insert(neighbor);
+                queue.push_back(neighbor);
+            }
+        }
+    }
+
+    /// Takes away the light at `start`, and all light downstream of it. Returns the cells around
+    /// the darkened area that are still lit by something else, and the light sources inside it,
+    /// which have to spread their light again.
+    fn darken(
+        &mut self,
+        blocks: &impl BlockView,
+        start: Point3<isize>,
+        changed: &mut FxHashSet<Point3<isize>>,
+    ) -> VecDeque<Point3<isize>> {
+        let mut relight = VecDeque::new();
+        let mut queue = VecDeque::new();
+        queue.push_back((start, self.get(start)));
+        self.set(start, 0);
+
+        while let Some((point, level)) = queue.pop_front() {
+            for &offset in &NEIGHBORS {
+                let neighbor = point + offset;
+                let neighbor_level = self.get(neighbor);
+                if neighbor_level == 0 {
+                    continue;
+                }
+
+                if neighbor_level < level {
+                    // Lit by the darkened cell, so it goes dark along with it
+                    self.set(neighbor, 0);
+                    changed.insert(neighbor);
+                    queue.push_back((neighbor, neighbor_level));
+
+                    let emission = emission(blocks.get_block(neighbor));
+                    if emission > 0 {
+                        self.set(neighbor, emission);
+                        relight.push_back(neighbor);
+                    }
+                } else {
+                    relight.push_back(neighbor);
+                }
+            }
+        }
+
+        relight
+    }
+
+    /// Updates the light after the block at `point` changed, and returns every cell whose light
+    /// changed along with it.
+    pub fn update(
+        &mut self,
+        blocks: &impl BlockView,
+        is_loaded: impl Fn(Point3<isize>) -> bool,
+        point: Point3<isize>,
+    ) -> FxHashSet<Point3<isize>> {
+        let mut changed = FxHashSet::default();
+        let mut relight = self.darken(blocks, point, &mut changed);
+
+        let block = blocks.get_block(point);
+        let emission = emission(block);
+        if emission > 0 {
+            self.set(point, emission);
+            relight.push_back(point);
+        } else if is_translucent(block) {
+            // Let the light around the cell back in
+            relight.extend(NEIGHBORS.iter().map(|offset| point + offset));
+        }
+
+        self.spread(blocks, &is_loaded, relight, &mut changed);
+        changed.insert(point);
+        changed
+    }
+
+    /// Lights up a chunk that was just loaded: `sources` are the positions of the light-emitting
+    /// blocks in it, and the light of the loaded chunks around it spreads into it. Returns every
+    /// cell whose light changed, which can include cells in the chunks around it.
+    pub fn add_chunk(
+        &mut self,
+        blocks: &impl BlockView,
+        is_loaded: impl Fn(Point3<isize>) -> bool,
+        chunk_position: Point3<isize>,
+        sources: &[Point3<isize>],
+    ) -> FxHashSet<Point3<isize>> {
+        let mut changed = FxHashSet::default();
+        let mut queue = VecDeque::new();
+
+        for &source in sources {
+            let emission = emission(blocks.get_block(source));
+            if emission > self.get(source) {
+                self.set(source, emission);
+                changed.insert(source);
+            }
+            queue.push_back(source);
+        }
+
+        // The light next to the chunk's faces, which couldn't spread into it before
+        let min = chunk_position * CHUNK_ISIZE;
+        let max = min + Vector3::new(1, 1, 1) * CHUNK_ISIZE;
+        let inside = |point: Point3<isize>| {
+            (0..3).all(|axis| point[axis] >= min[axis] && point[axis] < max[axis])
+        };
+        queue.extend(self.levels.keys().copied().filter(|&point| {
+            !inside(point) && NEIGHBORS.iter().any(|offset| inside(point + offset))
+        }));
+
+        self.spread(blocks, &is_loaded, queue, &mut changed);
+        changed
+    }
+
+    /// Forgets the light inside a chunk that was unloaded. The light it spread into the chunks
+    /// around it stays, since it'll be the same once it's loaded again.
+    pub fn remove_chunk(&mut self, chunk_position: Point3<isize>) {
+        self.levels
+            .retain(|point, _| world_to_chunk(*point) != chunk_position);
+    }
+
+    /// Returns the light of the cells in a chunk and the ones right around it, by their position
+    /// relative to the chunk, as `Chunk::light` expects it.
+    pub fn chunk_levels(&self, chunk_position: Point3<isize>) -> FxHashMap<Vector3<isize>, u8> {
+        let min = chunk_position * CHUNK_ISIZE;
+        self.levels
+            .iter()
+            .map(|(point, &level)| (point - min, level))
+            .filter(|(local, _)| {
+                (0..3).all(|axis| local[axis] >= -1 && local[axis] <= CHUNK_SIZE as isize)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{block::BlockType, block_view::test_world::TestWorld};
+
+    #[test]
+    fn lights_up_around_a_placed_light_and_darkens_when_removed() {
+        // Right on the border between two chunks
+        let mut world = TestWorld::default();
+        let mut light = BlockLight::default();
+        let source = Point3::new(CHUNK_ISIZE - 1, 5, 5);
+
+        world.set_block(source, BlockType::Glowstone);
+        let changed = light.update(&world, |_| true, source);
+        assert_eq!(light.get(source), MAX_LIGHT);
+        assert_eq!(light.get(source + Vector3::new(1, 0, 0)), MAX_LIGHT - 1);
+        assert_eq!(light.get(source + Vector3::new(4, 0, 0)), MAX_LIGHT - 4);
+        assert_eq!(light.get(source + Vector3::new(-2, 3, 0)), MAX_LIGHT - 5);
+        assert_eq!(light.get(source + Vector3::new(20, 0, 0)), 0);
+        assert!(changed.contains(&(source + Vector3::new(1, 0, 0))));
+
+        world.blocks.remove(&source);
+        let changed = light.update(&world, |_| true, source);
+        assert!(light.levels.is_empty());
+        assert!(changed.contains(&(source + Vector3::new(4, 0, 0))));
+    }
+
+    #[test]
+    fn keeps_the_light_of_other_sources() {
+        let mut world = TestWorld::default();
+        let mut light = BlockLight::default();
+        let first = Point3::new(0, 0, 0);
+        let second = Point3::new(6, 0, 0);
+        for source in [first, second] {
+            world.set_block(source, BlockType::Glowstone);
+            light.update(&world, |_| true, source);
+        }
+        assert_eq!(light.get(Point3::new(3, 0, 0)), MAX_LIGHT - 3);
+
+        world.blocks.remove(&first);
+        light.update(&world, |_| true, first);
+        assert_eq!(light.get(Point3::new(3, 0, 0)), MAX_LIGHT - 3);
+        assert_eq!(light.get(first), MAX_LIGHT - 6);
+        assert_eq!(light.get(Point3::new(-3, 0, 0)), MAX_LIGHT - 9);
+    }
+
+    #[test]
+    fn goes_around_opaque_blocks() {
+        let mut world = TestWorld::default();
+        let mut light = BlockLight::default();
+        let wall = Point3::new(1, 0, 0);
+        world.set_block(wall, BlockType::Stone);
+
+        let source = Point3::new(0, 0, 0);
+        world.set_block(source, BlockType::Glowstone);
+        light.update(&world, |_| true, source);
+
+        assert_eq!(light.get(wall), 0);
+        // Going around the wall takes four steps instead of two
+        assert_eq!(light.get(Point3::new(2, 0, 0)), MAX_LIGHT - 4);
+    }
+
+    #[test]
+    fn stays_out_of_chunks_that_arent_loaded() {
+        let mut world = TestWorld::default();
+        let mut light = BlockLight::default();
+        let source = Point3::new(CHUNK_ISIZE - 1, 5, 5);
+        world.set_block(source, BlockType::Glowstone);
+
+        light.update(&world, |point| point.x < CHUNK_ISIZE, source);
+        assert_eq!(light.get(source - Vector3::new(1, 0, 0)), MAX_LIGHT - 1);
+        assert_eq!(light.get(source + Vector3::new(1, 0, 0)), 0);
+    }
+}
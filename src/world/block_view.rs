@@ -0,0 +1,67 @@
+use cgmath::Point3;
+
+use crate::world::block::Block;
+
+/// Read access to the blocks of a world.
+///
+/// Code that only needs to look at blocks, like raycasting and collision checks, takes this
+/// instead of `World`, so it can run against blocks that aren't backed by chunks, a database or
+/// a GPU.
+pub trait BlockView {
+    fn get_block(&self, point: Point3<isize>) -> Option<&Block>;
+
+    /// Whether there's a block at the point that can be broken.
+    fn is_breakable(&self, point: Point3<isize>) -> bool {
+        self.get_block(point)
+            .is_some_and(|block| block.block_type.is_breakable())
+    }
+}
+
+#[cfg(test)]
+pub mod test_world {
+    use cgmath::Point3;
+    use fxhash::FxHashMap;
+
+    use crate::world::block::{Block, BlockType};
+
+    use super::BlockView;
+
+    /// A world that only exists in memory, for testing code that takes a `BlockView`.
+    #[derive(Default)]
+    pub struct TestWorld {
+        pub blocks: FxHashMap<Point3<isize>, Block>,
+    }
+
+    impl TestWorld {
+        pub fn set_block(&mut self, point: Point3<isize>, block_type: BlockType) {
+            self.blocks.insert(point, Block::new(block_type));
+        }
+    }
+
+    impl BlockView for TestWorld {
+        fn get_block(&self, point: Point3<isize>) -> Option<&Block> {
+            self.blocks.get(&point)
+        }
+    }
+
+    #[test]
+    fn returns_the_blocks_that_were_set() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(-1, 2, 3), BlockType::Stone);
+
+        let block = world.get_block(Point3::new(-1, 2, 3)).unwrap();
+        assert_eq!(block.block_type, BlockType::Stone);
+        assert!(world.get_block(Point3::new(1, 2, 3)).is_none());
+    }
+
+    #[test]
+    fn only_breaks_breakable_blocks() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(0, 0, 0), BlockType::Bedrock);
+        world.set_block(Point3::new(0, 1, 0), BlockType::Stone);
+
+        assert!(!world.is_breakable(Point3::new(0, 0, 0)));
+        assert!(world.is_breakable(Point3::new(0, 1, 0)));
+        assert!(!world.is_breakable(Point3::new(0, 2, 0)));
+    }
+}
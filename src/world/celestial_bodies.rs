@@ -0,0 +1,210 @@
+use std::borrow::Cow;
+
+use cgmath::Vector2;
+use image::{Rgba, RgbaImage};
+use wgpu::{BindGroup, BindGroupLayout, RenderPass, RenderPipeline};
+
+use crate::{render_context::RenderContext, texture::Texture, view::View};
+
+/// Minecraft's resource pack layout: the sun on its own, and the moon as 4 by 2 phases, of which
+/// the first is the full moon.
+const SUN_TEXTURE: &str = "assets/environment/sun.png";
+const MOON_TEXTURE: &str = "assets/environment/moon_phases.png";
+const MOON_PHASES: (u32, u32) = (4, 2);
+
+/// The size of the disc drawn instead of a texture that couldn't be loaded.
+const FALLBACK_SIZE: u32 = 32;
+
+/// Draws the sun and the moon as billboards on a large sphere around the camera, on opposite
+/// sides of it, moving with the time of day.
+///
+/// They're drawn after the sky and before the world, without touching the depth buffer, so they
+/// always seem infinitely far away. Their textures are added to what's behind them, so the black
+/// around them doesn't show.
+pub struct CelestialBodies {
+    pipeline: RenderPipeline,
+    sun_bind_group: BindGroup,
+    moon_bind_group: BindGroup,
+}
+
+impl CelestialBodies {
+    pub fn new(
+        render_context: &RenderContext,
+        view: &View,
+        time_bind_group_layout: &BindGroupLayout,
+    ) -> Self {
+        let device = &render_context.device;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("celestial_bodies_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("celestial bodies sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            ..wgpu::SamplerDescriptor::default()
+        });
+
+        let sun_texture = Self::load_texture(render_context, SUN_TEXTURE, (1, 1), [255, 230, 130]);
+        let moon_texture =
+            Self::load_texture(render_context, MOON_TEXTURE, MOON_PHASES, [200, 210, 230]);
+        let bind_group = |texture: &Texture, label| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some(label),
+                layout: &bind_group_layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::Sampler(&sampler),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&texture.view),
+                    },
+                ],
+            })
+        };
+        let sun_bind_group = bind_group(&sun_texture, "sun_bind_group");
+        let moon_bind_group = bind_group(&moon_texture, "moon_bind_group");
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("celestial_bodies_pipeline_layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[
+                &view.bind_group_layout,
+                time_bind_group_layout,
+                &bind_group_layout,
+            ],
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("celestial bodies shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!(
+                "../shaders/celestial_bodies.wgsl"
+            ))),
+        });
+
+        let additive = wgpu::BlendComponent {
+            src_factor: wgpu::BlendFactor::One,
+            dst_factor: wgpu::BlendFactor::One,
+            operation: wgpu::BlendOperation::Add,
+        };
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Celestial bodies pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: render_context.format,
+                    blend: Some(wgpu::BlendState {
+                        color: additive,
+                        alpha: wgpu::BlendComponent::OVER,
+                    }),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // Like the sky, these are behind everything, so they leave the depth buffer alone
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        Self {
+            pipeline,
+            sun_bind_group,
+            moon_bind_group,
+        }
+    }
+
+    /// Loads the first tile of the texture at `path`, which is split into `tiles` columns and rows.
+    /// The textures aren't needed to play, so if it can't be loaded, a plain disc of `color` is
+    /// drawn instead.
+    fn load_texture(
+        render_context: &RenderContext,
+        path: &str,
+        tiles: (u32, u32),
+        color: [u8; 3],
+    ) -> Texture {
+        let loaded = std::fs::read(path)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(image::load_from_memory(&bytes)?.into_rgba8()))
+            .and_then(|image| {
+                let tile_size = Vector2::new(image.width() / tiles.0, image.height() / tiles.1);
+                anyhow::ensure!(tile_size.x > 0 && tile_size.y > 0, "Image is too small");
+                let tile = Texture::from_image_atlas(render_context, &image, tile_size, path)?;
+                Ok(tile.into_iter().next().unwrap())
+            });
+
+        loaded.unwrap_or_else(|error| {
+            eprintln!(
+                "Failed to load {}, drawing a disc instead: {:#}",
+                path, error
+            );
+            Texture::from_image(render_context, &Self::disc(color), path).unwrap()
+        })
+    }
+
+    fn disc(color: [u8; 3]) -> RgbaImage {
+        let radius = FALLBACK_SIZE as f32 / 4.0;
+        let center = FALLBACK_SIZE as f32 / 2.0;
+        RgbaImage::from_fn(FALLBACK_SIZE, FALLBACK_SIZE, |x, y| {
+            let distance = (x as f32 + 0.5 - center).hypot(y as f32 + 0.5 - center);
+            if distance < radius {
+                Rgba([color[0], color[1], color[2], 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        view: &'a View,
+        time_bind_group: &'a BindGroup,
+    ) -> usize {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &view.bind_group, &[]);
+        render_pass.set_bind_group(1, time_bind_group, &[]);
+
+        render_pass.set_bind_group(2, &self.sun_bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+        render_pass.set_bind_group(2, &self.moon_bind_group, &[]);
+        render_pass.draw(0..6, 1..2);
+        4
+    }
+}
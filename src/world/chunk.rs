@@ -2,20 +2,26 @@ use std::collections::VecDeque;
 
 use crate::{
     aabb::Aabb,
+    error,
     geometry::Geometry,
     geometry_buffers::GeometryBuffers,
     render_context::RenderContext,
+    settings::LightingMode,
+    texture::BlockTextures,
     vertex::BlockVertex,
     view::View,
     world::{
+        biome::BiomeMap,
         block::{Block, BlockType},
+        block_entity::BlockEntity,
+        chunk_arena::ArenaSlot,
         face_flags::*,
-        quad::Quad,
+        generator::WorldGenerator,
+        quad::{self, Quad},
     },
 };
-use cgmath::{Point3, Vector3};
+use cgmath::{Point3, Vector3, Vector4};
 use fxhash::{FxHashMap, FxHashSet};
-use noise::utils::{NoiseMapBuilder, PlaneMapBuilder};
 use rayon::iter::{IntoParallelIterator, ParallelIterator};
 use serde::{
     de::{SeqAccess, Visitor},
@@ -27,13 +33,94 @@ use wgpu::{BufferUsages, RenderPass};
 pub const CHUNK_SIZE: usize = 32;
 pub const CHUNK_ISIZE: isize = CHUNK_SIZE as isize;
 
+/// The position of the chunk a block is in. Rounds towards negative infinity, so the block at -1
+/// is in chunk -1 rather than chunk 0.
+pub fn world_to_chunk(point: Point3<isize>) -> Point3<isize> {
+    point.map(|n| n.div_euclid(CHUNK_ISIZE))
+}
+
+/// The position of a block within the chunk `world_to_chunk` puts it in.
+pub fn world_to_local(point: Point3<isize>) -> Point3<usize> {
+    point.map(|n| n.rem_euclid(CHUNK_ISIZE) as usize)
+}
+
 type CoordinateXZ = (usize, usize);
 type BlockFace = (BlockType, FaceFlags);
 
+/// The lowest level of detail a chunk can be meshed at, where every 4x4x4 blocks are merged into
+/// one.
+pub const MAX_LOD: usize = 2;
+
+/// Returns whether a face of a block of `block_type` is hidden by the block next to it. That's
+/// when the neighbour fills its whole cell, and is opaque if the block itself is. Transparent
+/// blocks are only hidden by blocks of the same type, so glass stays visible through water, and
+/// the other way around.
+fn covers(block_type: BlockType, neighbour: Option<BlockType>) -> bool {
+    neighbour.is_some_and(|neighbour| {
+        neighbour.is_full_cube()
+            && if block_type.is_transparent() {
+                neighbour == block_type
+            } else {
+                !neighbour.is_transparent()
+            }
+    })
+}
+
+/// The geometry of a chunk, split by how it's drawn.
+pub struct ChunkGeometry {
+    pub opaque: Geometry<BlockVertex, u16>,
+    /// The faces of transparent blocks, which are blended over everything opaque.
+    pub transparent: Geometry<BlockVertex, u16>,
+}
+
+/// More vertices than a chunk full of blocks would have if every one of them showed all 6 of its
+/// faces. A chunk with more than this means the mesher has gone wrong.
+pub const MAX_EXPECTED_VERTICES: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE * 6 * 4;
+
+impl ChunkGeometry {
+    pub fn size_in_bytes(&self) -> usize {
+        self.opaque.size_in_bytes() + self.transparent.size_in_bytes()
+    }
+
+    pub fn vertex_count(&self) -> usize {
+        self.opaque.vertices.len() + self.transparent.vertices.len()
+    }
+}
+
 pub struct Chunk {
     pub blocks: [[[Option<Block>; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+    /// The opaque faces of the chunk, unless they're in the world's `ChunkArena`.
     pub buffers: Option<GeometryBuffers<u16>>,
+    /// Where the opaque faces of the chunk are in the world's `ChunkArena`, if they're in there.
+    /// Set by the world before uploading the geometry.
+    pub arena_slot: Option<ArenaSlot>,
+    /// The transparent faces of the chunk, or `None` if it doesn't have any.
+    pub transparent_buffers: Option<GeometryBuffers<u16>>,
     pub full: bool,
+
+    /// The block entities in the chunk, by their position within it. Saved separately from the
+    /// blocks, under the chunk's key with a `_block_entities` suffix.
+    pub block_entities: FxHashMap<Vector3<usize>, BlockEntity>,
+
+    /// The biome tint at every block corner of the chunk, indexed by `z * (CHUNK_SIZE + 1) + x`.
+    /// Calculated the first time the geometry is built.
+    biome_tints: Vec<Vector4<f32>>,
+
+    /// The block light of the lit cells in the chunk and right around it, by their position
+    /// relative to the chunk, so from -1 to `CHUNK_SIZE`. Kept up to date by the `World`.
+    pub light: FxHashMap<Vector3<isize>, u8>,
+
+    /// Set when a block or the light in the chunk changes, until its geometry is rebuilt.
+    pub dirty: bool,
+
+    /// The level of detail the geometry was last built at. 0 is every block, and every level
+    /// above that merges twice as many blocks along each axis.
+    pub lod: usize,
+    /// How many triangles the geometry has at full detail, roughly. Counted the first time the
+    /// chunk is meshed, and again whenever its blocks change.
+    full_triangles: Option<usize>,
+    /// How many fewer triangles the geometry has than it would at full detail.
+    pub lod_triangles_saved: usize,
 }
 
 impl Default for Chunk {
@@ -41,7 +128,16 @@ impl Default for Chunk {
         Self {
             blocks: [[[None; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
             buffers: None,
+            arena_slot: None,
+            transparent_buffers: None,
             full: false,
+            block_entities: FxHashMap::default(),
+            biome_tints: Vec::new(),
+            light: FxHashMap::default(),
+            dirty: false,
+            lod: 0,
+            full_triangles: None,
+            lod_triangles_saved: 0,
         }
     }
 }
@@ -104,16 +200,35 @@ impl Chunk {
         render_pass: &mut RenderPass<'a>,
         position: &Point3<isize>,
         view: &View,
+    ) -> usize {
+        self.render_buffers(&self.buffers, render_pass, position, view)
+    }
+
+    /// Draws the chunk's transparent faces. These have to be drawn after everything opaque.
+    pub fn render_transparent<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        position: &Point3<isize>,
+        view: &View,
+    ) -> usize {
+        self.render_buffers(&self.transparent_buffers, render_pass, position, view)
+    }
+
+    fn render_buffers<'a>(
+        &'a self,
+        buffers: &'a Option<GeometryBuffers<u16>>,
+        render_pass: &mut RenderPass<'a>,
+        position: &Point3<isize>,
+        view: &View,
     ) -> usize {
         if !self.is_visible(position * CHUNK_ISIZE, view) {
             // Frustrum culling
             0
-        } else if let Some(buffers) = &self.buffers {
+        } else if let Some(buffers) = buffers {
             buffers.apply_buffers(render_pass);
             buffers.draw_indexed(render_pass)
         } else {
-            // Not loaded
-            println!("Trying to render non-loaded chunk {:?}", position);
+            // Not uploaded yet
             0
         }
     }
@@ -122,7 +237,8 @@ impl Chunk {
         for y in 0..CHUNK_SIZE {
             for z in 0..CHUNK_SIZE {
                 for x in 0..CHUNK_SIZE {
-                    if self.blocks[y][z][x].is_none() {
+                    let solid = self.blocks[y][z][x].is_some_and(|b| b.block_type.is_full_cube());
+                    if !solid {
                         self.full = false;
                         return;
                     }
@@ -133,140 +249,72 @@ impl Chunk {
         self.full = true;
     }
 
-    pub fn generate(&mut self, chunk_x: isize, chunk_y: isize, chunk_z: isize) {
-        let fbm = noise::Fbm::new();
-
-        const TERRAIN_NOISE_SCALE: f64 = 0.1 / 16.0 * CHUNK_SIZE as f64;
-        const TERRAIN_NOISE_OFFSET: f64 = 0.0 / 16.0 * CHUNK_SIZE as f64;
-        let terrain_noise = PlaneMapBuilder::new(&fbm)
-            .set_size(CHUNK_SIZE, CHUNK_SIZE)
-            .set_x_bounds(
-                chunk_x as f64 * TERRAIN_NOISE_SCALE + TERRAIN_NOISE_OFFSET,
-                chunk_x as f64 * TERRAIN_NOISE_SCALE + TERRAIN_NOISE_SCALE + TERRAIN_NOISE_OFFSET,
-            )
-            .set_y_bounds(
-                chunk_z as f64 * TERRAIN_NOISE_SCALE + TERRAIN_NOISE_OFFSET,
-                chunk_z as f64 * TERRAIN_NOISE_SCALE + TERRAIN_NOISE_SCALE + TERRAIN_NOISE_OFFSET,
-            )
-            .build();
-
-        const STONE_NOISE_SCALE: f64 = 0.07 / 16.0 * CHUNK_SIZE as f64;
-        const STONE_NOISE_OFFSET: f64 = 11239.0 / 16.0 * CHUNK_SIZE as f64;
-        let stone_noise = PlaneMapBuilder::new(&fbm)
-            .set_size(CHUNK_SIZE, CHUNK_SIZE)
-            .set_x_bounds(
-                chunk_x as f64 * STONE_NOISE_SCALE + STONE_NOISE_OFFSET,
-                chunk_x as f64 * STONE_NOISE_SCALE + STONE_NOISE_SCALE + STONE_NOISE_OFFSET,
-            )
-            .set_y_bounds(
-                chunk_z as f64 * STONE_NOISE_SCALE + STONE_NOISE_OFFSET,
-                chunk_z as f64 * STONE_NOISE_SCALE + STONE_NOISE_SCALE + STONE_NOISE_OFFSET,
-            )
-            .build();
-
-        for z in 0..CHUNK_SIZE {
-            for x in 0..CHUNK_SIZE {
-                let v = terrain_noise.get_value(x, z) * 20.0 + 128.0;
-                let v = v.round() as isize;
-
-                let s = stone_noise.get_value(x, z) * 20.0 + 4.5;
-                let s = (s.round() as isize).min(10).max(3);
-
-                let stone_max = (v - s - chunk_y * CHUNK_ISIZE).min(CHUNK_ISIZE);
-                for y in 0..stone_max {
-                    self.blocks[y as usize][z][x] = Some(Block {
-                        block_type: BlockType::Stone,
-                    });
-                }
-
-                let dirt_max = (v - chunk_y * CHUNK_ISIZE).min(CHUNK_ISIZE);
-                for y in stone_max.max(0)..dirt_max {
-                    self.blocks[y as usize][z][x] = Some(Block {
-                        block_type: BlockType::Dirt,
-                    });
-                }
-
-                if (0..CHUNK_ISIZE).contains(&dirt_max) {
-                    self.blocks[dirt_max as usize][z][x] = Some(Block {
-                        block_type: BlockType::Grass,
-                    });
-                }
-
-                if chunk_y == 0 {
-                    self.blocks[0][z][x] = Some(Block {
-                        block_type: BlockType::Bedrock,
-                    });
-                }
-                if chunk_y < 128 / CHUNK_ISIZE {
-                    for layer in self.blocks.iter_mut() {
-                        if layer[z][x].is_none() {
-                            layer[z][x] = Some(Block {
-                                block_type: BlockType::Water,
-                            });
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    pub fn block_coords_to_local(
-        chunk_coords: Point3<isize>,
-        block_coords: Point3<isize>,
-    ) -> Option<Vector3<usize>> {
-        let chunk_position = chunk_coords * CHUNK_ISIZE;
-        let position = block_coords - chunk_position;
-        if (0..CHUNK_ISIZE).contains(&position.x)
-            && (0..CHUNK_ISIZE).contains(&position.y)
-            && (0..CHUNK_ISIZE).contains(&position.z)
-        {
-            Some(position.cast().unwrap())
-        } else {
-            None
-        }
-    }
-
-    #[rustfmt::skip]
     fn check_visible_faces(&self, x: usize, y: usize, z: usize) -> FaceFlags {
         let mut visible_faces = FACE_NONE;
-        let transparent = self.blocks[y][z][x].unwrap().block_type.is_transparent();
+        let block_type = self.blocks[y][z][x].unwrap().block_type;
+        let covers = |neighbour: Option<Block>| covers(block_type, neighbour.map(|b| b.block_type));
 
-        if x == 0 || self.blocks[y][z][x - 1].is_none()
-            || transparent != self.blocks[y][z][x - 1].unwrap().block_type.is_transparent()
-        {
+        if x == 0 || !covers(self.blocks[y][z][x - 1]) {
             visible_faces |= FACE_LEFT;
         }
-        if x == CHUNK_SIZE - 1 || self.blocks[y][z][x + 1].is_none()
-            || transparent != self.blocks[y][z][x + 1].unwrap().block_type.is_transparent()
-        {
+        if x == CHUNK_SIZE - 1 || !covers(self.blocks[y][z][x + 1]) {
             visible_faces |= FACE_RIGHT;
         }
 
-        if y == 0 || self.blocks[y - 1][z][x].is_none()
-            || transparent != self.blocks[y - 1][z][x].unwrap().block_type.is_transparent()
-        {
+        if y == 0 || !covers(self.blocks[y - 1][z][x]) {
             visible_faces |= FACE_BOTTOM;
         }
-        if y == CHUNK_SIZE - 1 || self.blocks[y + 1][z][x].is_none()
-            || transparent != self.blocks[y + 1][z][x].unwrap().block_type.is_transparent()
-        {
+        if y == CHUNK_SIZE - 1 || !covers(self.blocks[y + 1][z][x]) {
             visible_faces |= FACE_TOP;
         }
 
-        if z == 0 || self.blocks[y][z - 1][x].is_none()
-            || transparent != self.blocks[y][z - 1][x].unwrap().block_type.is_transparent()
-        {
+        if z == 0 || !covers(self.blocks[y][z - 1][x]) {
             visible_faces |= FACE_BACK;
         }
-        if z == CHUNK_SIZE - 1 || self.blocks[y][z + 1][x].is_none()
-            || transparent != self.blocks[y][z + 1][x].unwrap().block_type.is_transparent()
-        {
+        if z == CHUNK_SIZE - 1 || !covers(self.blocks[y][z + 1][x]) {
             visible_faces |= FACE_FRONT;
         }
 
         visible_faces
     }
 
+    /// Returns the blocks around the given one that cast ambient occlusion, as a bitmap for
+    /// `Quad::occluders`. Blocks outside of the chunk don't occlude anything.
+    fn occluders(&self, x: usize, y: usize, z: usize) -> u32 {
+        let position = Vector3::new(x, y, z).cast::<isize>().unwrap();
+        itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+            .map(|(x, y, z)| Vector3::new(x, y, z))
+            .filter(|&offset| {
+                let neighbour = position + offset;
+                (0..3).all(|axis| (0..CHUNK_ISIZE).contains(&neighbour[axis]))
+                    && self.blocks[neighbour.y as usize][neighbour.z as usize][neighbour.x as usize]
+                        .is_some_and(|block| {
+                            block.block_type.is_full_cube() && !block.block_type.is_transparent()
+                        })
+            })
+            .fold(0, |occluders, offset| {
+                occluders | quad::occluder_bit(offset)
+            })
+    }
+
+    /// Returns the block light in front of each of the given faces of a block, for `Quad::light`,
+    /// or `None` if none of them are lit.
+    fn face_light(&self, x: usize, y: usize, z: usize, faces: FaceFlags) -> Option<[u8; 6]> {
+        if self.light.is_empty() {
+            return None;
+        }
+
+        let position = Vector3::new(x, y, z).cast::<isize>().unwrap();
+        let mut light = [0; 6];
+        for (level, &(face, normal)) in light.iter_mut().zip(&quad::FACES) {
+            if faces & face == face {
+                let neighbour = position + Vector3::from(normal).cast().unwrap();
+                *level = self.light.get(&neighbour).copied().unwrap_or(0);
+            }
+        }
+        light.iter().any(|&level| level > 0).then_some(light)
+    }
+
     fn cull_layer(&self, y: usize) -> (FxHashMap<CoordinateXZ, BlockFace>, VecDeque<CoordinateXZ>) {
         let mut culled = FxHashMap::default();
         let mut queue = VecDeque::new();
@@ -296,11 +344,11 @@ impl Chunk {
         offset: Point3<isize>,
         culled: FxHashMap<CoordinateXZ, BlockFace>,
         queue: &mut VecDeque<CoordinateXZ>,
-        highlighted: Option<(Vector3<usize>, Vector3<i32>)>,
+        highlighted_type: Option<BlockType>,
+        lighting: LightingMode,
     ) -> Vec<Quad> {
         let mut quads: Vec<Quad> = Vec::new();
         let mut visited = FxHashSet::default();
-        let hl = highlighted.map(|h| h.0);
         while let Some((x, z)) = queue.pop_front() {
             let position = offset + Vector3::new(x, y, z).cast().unwrap();
 
@@ -312,41 +360,72 @@ impl Chunk {
             if let Some(&(block_type, visible_faces)) = &culled.get(&(x, z)) {
                 let mut quad_faces = visible_faces;
 
-                if hl == Some(Vector3::new(x, y, z)) {
+                // Only blocks without any occluded corners can be merged with smooth lighting,
+                // since a merged quad only has vertices at its outer corners
+                let occluders = |x: usize, z: usize| match lighting {
+                    LightingMode::Flat => None,
+                    LightingMode::Smooth => Some(self.occluders(x, y, z)),
+                };
+                let occluded = |x: usize, z: usize, faces: FaceFlags| {
+                    occluders(x, z)
+                        .is_some_and(|occluders| quad::has_ambient_occlusion(occluders, faces))
+                };
+                // Block light is per face, so lit blocks aren't merged either
+                let lit = |x: usize, z: usize, faces: FaceFlags| self.face_light(x, y, z, faces);
+                // Merged quads are all the same type, so this holds for all of them
+                let type_highlighted = highlighted_type == Some(block_type);
+
+                if !block_type.is_full_cube()
+                    || occluded(x, z, visible_faces)
+                    || lit(x, z, visible_faces).is_some()
+                {
                     let mut quad = Quad::new(position, 1, 1);
-                    quad.highlighted_normal = highlighted.unwrap().1;
+                    quad.type_highlighted = type_highlighted;
                     quad.visible_faces = quad_faces;
                     quad.block_type = Some(block_type);
+                    quad.facing = self.blocks[y][z][x].unwrap().facing;
+                    quad.tints = self.corner_tints(x, z, x + 1, z + 1);
+                    quad.occluders = occluders(x, z);
+                    quad.light = lit(x, z, quad_faces);
                     quads.push(quad);
                     continue;
                 }
 
-                if block_type == BlockType::Water {
-                    let mut quad = Quad::new(position, 1, 1);
-                    quad.visible_faces = quad_faces;
-                    quad.block_type = Some(block_type);
-                    quads.push(quad);
-                    continue;
-                }
+                // The biome tint is blended between the corners of a quad, so a merged quad would
+                // lose the gradient in between, and leave seams where it meets smaller quads.
+                // Tinted blocks only merge where the tint is the same all over.
+                let tinted = quad::FACES
+                    .iter()
+                    .any(|&(face, _)| block_type.is_biome_tinted(face));
+                let tint = self.uniform_tint(x, z, x + 1, z + 1);
+                let same_tint = |(x, z): CoordinateXZ| {
+                    !tinted || (tint.is_some() && self.uniform_tint(x, z, x + 1, z + 1) == tint)
+                };
+
+                // Opaque blocks can share their faces, since the extra faces that come with that
+                // are hidden behind other blocks anyway. Transparent blocks (like water) would
+                // show them, so they only merge with blocks that have the exact same faces.
+                let mergeable = |position: CoordinateXZ| match culled.get(&position) {
+                    Some(&(block_type_, visible_faces_)) => {
+                        block_type_ == block_type
+                            && (!block_type.is_transparent() || visible_faces_ == visible_faces)
+                            && !occluded(position.0, position.1, visible_faces_)
+                            && lit(position.0, position.1, visible_faces_).is_none()
+                            && same_tint(position)
+                    }
+                    None => false,
+                };
 
                 // Extend along the X axis
                 let mut xmax = x + 1;
                 for x_ in x..CHUNK_SIZE {
                     xmax = x_ + 1;
 
-                    if visited.contains(&(xmax, z)) || hl == Some(Vector3::new(xmax, y, z)) {
-                        break;
-                    }
-
-                    if let Some(&(block_type_, visible_faces_)) = culled.get(&(xmax, z)) {
-                        quad_faces |= visible_faces_;
-                        if block_type != block_type_ {
-                            break;
-                        }
-                    } else {
+                    if visited.contains(&(xmax, z)) || !mergeable((xmax, z)) {
                         break;
                     }
 
+                    quad_faces |= culled[&(xmax, z)].1;
                     visited.insert((xmax, z));
                 }
 
@@ -356,86 +435,304 @@ impl Chunk {
                     zmax = z_ + 1;
 
                     for x_ in x..xmax {
-                        if visited.contains(&(x_, zmax)) || hl == Some(Vector3::new(x_, y, zmax)) {
-                            break 'z;
-                        }
-
-                        if let Some(&(block_type_, visible_faces_)) = culled.get(&(x_, zmax)) {
-                            quad_faces |= visible_faces_;
-                            if block_type != block_type_ {
-                                break 'z;
-                            }
-                        } else {
+                        if visited.contains(&(x_, zmax)) || !mergeable((x_, zmax)) {
                             break 'z;
                         }
                     }
 
                     for x_ in x..xmax {
+                        quad_faces |= culled[&(x_, zmax)].1;
                         visited.insert((x_, zmax));
                     }
                 }
 
                 let mut quad = Quad::new(position, (xmax - x) as isize, (zmax - z) as isize);
+                quad.type_highlighted = type_highlighted;
+                quad.visible_faces = quad_faces;
+                quad.block_type = Some(block_type);
+                quad.tints = self.corner_tints(x, z, xmax, zmax);
+                quads.push(quad);
+            }
+        }
+
+        quads
+    }
+
+    /// The block that stands in for the `scale`x`scale`x`scale` blocks from (x, y, z) on in a mesh
+    /// with less detail: the most common full cube among them, or `None` if less than half of
+    /// them are full cubes. Other shapes, like stairs, are too small to see from that far anyway.
+    fn dominant_block(&self, x: usize, y: usize, z: usize, scale: usize) -> Option<BlockType> {
+        let mut counts: FxHashMap<BlockType, usize> = FxHashMap::default();
+        for (y, z, x) in itertools::iproduct!(y..y + scale, z..z + scale, x..x + scale) {
+            if let Some(block) = self.blocks[y][z][x] {
+                if block.block_type.is_full_cube() {
+                    *counts.entry(block.block_type).or_default() += 1;
+                }
+            }
+        }
+
+        let filled: usize = counts.values().sum();
+        if filled * 2 < scale * scale * scale {
+            return None;
+        }
+        counts
+            .into_iter()
+            .max_by_key(|&(block_type, count)| (count, block_type as u8))
+            .map(|(block_type, _)| block_type)
+    }
+
+    /// Builds the chunk's quads at the given level of detail, above 0. Every cube of `2^lod`
+    /// blocks along each side becomes a single cell with its `dominant_block`, and the cells are
+    /// meshed without lighting, merging runs of them along the X axis.
+    fn lod_quads(
+        &self,
+        offset: Point3<isize>,
+        lod: usize,
+        highlighted_type: Option<BlockType>,
+    ) -> Vec<Quad> {
+        let scale = 1 << lod;
+        let size = CHUNK_SIZE / scale;
+        let cells: Vec<Option<BlockType>> = itertools::iproduct!(0..size, 0..size, 0..size)
+            .map(|(y, z, x)| self.dominant_block(x * scale, y * scale, z * scale, scale))
+            .collect();
+        let cell = |x: usize, y: usize, z: usize| cells[(y * size + z) * size + x];
+
+        // Like `check_visible_faces`, the faces on the edges of the chunk are always visible
+        let visible_faces = |x: usize, y: usize, z: usize, block_type: BlockType| {
+            let hidden = |neighbour: Option<(usize, usize, usize)>| {
+                neighbour.is_some_and(|(x, y, z)| covers(block_type, cell(x, y, z)))
+            };
+            let before = |n: usize| n.checked_sub(1);
+            let after = |n: usize| Some(n + 1).filter(|&n| n < size);
+
+            [
+                (FACE_LEFT, before(x).map(|x| (x, y, z))),
+                (FACE_RIGHT, after(x).map(|x| (x, y, z))),
+                (FACE_BOTTOM, before(y).map(|y| (x, y, z))),
+                (FACE_TOP, after(y).map(|y| (x, y, z))),
+                (FACE_BACK, before(z).map(|z| (x, y, z))),
+                (FACE_FRONT, after(z).map(|z| (x, y, z))),
+            ]
+            .iter()
+            .filter(|&&(_, neighbour)| !hidden(neighbour))
+            .fold(FACE_NONE, |faces, &(face, _)| faces | face)
+        };
+
+        let mut quads = Vec::new();
+        for (y, z) in itertools::iproduct!(0..size, 0..size) {
+            let mut x = 0;
+            while x < size {
+                let block_type = match cell(x, y, z) {
+                    Some(block_type) => block_type,
+                    None => {
+                        x += 1;
+                        continue;
+                    }
+                };
+                let faces = visible_faces(x, y, z, block_type);
+                if faces == FACE_NONE {
+                    x += 1;
+                    continue;
+                }
+
+                // Same as in `layer_to_quads`, transparent cells only merge if they show the
+                // same faces, and tinted cells only if the tint doesn't change
+                let tinted = quad::FACES
+                    .iter()
+                    .any(|&(face, _)| block_type.is_biome_tinted(face));
+                let cell_tint = |x: usize| {
+                    self.uniform_tint(x * scale, z * scale, (x + 1) * scale, (z + 1) * scale)
+                };
+                let tint = cell_tint(x);
+                let mut quad_faces = faces;
+                let mut xmax = x + 1;
+                while xmax < size && cell(xmax, y, z) == Some(block_type) {
+                    let faces_ = visible_faces(xmax, y, z, block_type);
+                    if faces_ == FACE_NONE
+                        || (block_type.is_transparent() && faces_ != faces)
+                        || (tinted && (tint.is_none() || cell_tint(xmax) != tint))
+                    {
+                        break;
+                    }
+                    quad_faces |= faces_;
+                    xmax += 1;
+                }
+
+                let position = offset + Vector3::new(x, y, z).cast().unwrap() * scale as isize;
+                let mut quad = Quad::new(position, ((xmax - x) * scale) as isize, scale as isize);
+                quad.dy = scale as isize;
+                quad.type_highlighted = highlighted_type == Some(block_type);
                 quad.visible_faces = quad_faces;
                 quad.block_type = Some(block_type);
+                quad.tints = self.corner_tints(x * scale, z * scale, xmax * scale, (z + 1) * scale);
                 quads.push(quad);
+
+                x = xmax;
             }
         }
 
         quads
     }
 
-    fn quads_to_geometry(quads: Vec<Quad>) -> Geometry<BlockVertex, u16> {
-        let mut geometry: Geometry<BlockVertex, u16> = Default::default();
+    /// Roughly how many triangles the quads make up: two for every visible face. Stairs have a
+    /// few more, but there aren't enough of them to matter.
+    fn triangle_count(quads: &[Quad]) -> usize {
+        quads
+            .iter()
+            .map(|quad| quad.visible_faces.count_ones() as usize * 2)
+            .sum()
+    }
+
+    /// Samples the biome tint at every block corner in the chunk's horizontal plane.
+    fn calculate_biome_tints(
+        chunk_coords: Point3<isize>,
+        biome_map: &BiomeMap,
+    ) -> Vec<Vector4<f32>> {
+        let offset = chunk_coords * CHUNK_ISIZE;
+        itertools::iproduct!(0..=CHUNK_ISIZE, 0..=CHUNK_ISIZE)
+            .map(|(z, x)| biome_map.foliage_tint_at(offset.x + x, offset.z + z))
+            .collect()
+    }
+
+    /// Returns the biome tints at the corners of the area spanning from (x, z) to (xmax, zmax), in
+    /// the order `Quad::tints` expects them.
+    fn corner_tints(&self, x: usize, z: usize, xmax: usize, zmax: usize) -> [Vector4<f32>; 4] {
+        let tint = |x: usize, z: usize| self.biome_tints[z * (CHUNK_SIZE + 1) + x];
+        [tint(x, z), tint(x, zmax), tint(xmax, zmax), tint(xmax, z)]
+    }
+
+    /// The biome tint of the area spanning from (x, z) to (xmax, zmax), if it's the same at all
+    /// of its corners.
+    fn uniform_tint(&self, x: usize, z: usize, xmax: usize, zmax: usize) -> Option<Vector4<f32>> {
+        let [tint, rest @ ..] = self.corner_tints(x, z, xmax, zmax);
+        rest.iter().all(|&corner| corner == tint).then_some(tint)
+    }
+
+    fn quads_to_geometry(quads: Vec<Quad>, block_textures: &BlockTextures) -> ChunkGeometry {
+        let mut opaque: Geometry<BlockVertex, u16> = Default::default();
+        let mut transparent: Geometry<BlockVertex, u16> = Default::default();
         for quad in quads {
-            geometry.append(&mut quad.to_geometry(geometry.vertices.len() as u16));
+            let geometry = match quad.block_type {
+                Some(block_type) if block_type.is_transparent() => &mut transparent,
+                _ => &mut opaque,
+            };
+            geometry.append(&mut quad.to_geometry(geometry.vertices.len() as u16, block_textures));
+        }
+
+        ChunkGeometry {
+            opaque,
+            transparent,
         }
-        geometry
     }
 
-    pub fn update_geometry(
+    /// Builds the chunk's geometry, without uploading it to the GPU yet. The faces of blocks of
+    /// `highlighted_type` are tinted. Above a `lod` of 0, blocks are merged together to make for
+    /// fewer triangles, up to `MAX_LOD`. Clears `dirty`.
+    pub fn build_geometry(
         &mut self,
-        render_context: &RenderContext,
         chunk_coords: Point3<isize>,
-        highlighted: Option<(Point3<isize>, Vector3<i32>)>,
-    ) {
-        let highlighted = highlighted.and_then(|(position, normal)| {
-            Self::block_coords_to_local(chunk_coords, position).map(|x| (x, normal))
-        });
+        highlighted_type: Option<BlockType>,
+        lighting: LightingMode,
+        lod: usize,
+        biome_map: &BiomeMap,
+        block_textures: &BlockTextures,
+    ) -> ChunkGeometry {
+        if std::mem::take(&mut self.dirty) {
+            self.full_triangles = None;
+        }
+        if self.biome_tints.is_empty() {
+            self.biome_tints = Self::calculate_biome_tints(chunk_coords, biome_map);
+        }
 
         let offset = chunk_coords * CHUNK_ISIZE;
-        let quads: Vec<Quad> = (0..CHUNK_SIZE)
-            .into_par_iter()
-            .flat_map(|y| {
-                let (culled, mut queue) = self.cull_layer(y);
-                self.layer_to_quads(y, offset, culled, &mut queue, highlighted)
-            })
-            .collect();
+        let lod = lod.min(MAX_LOD);
+        // The chunk is meshed at full detail once even if it isn't drawn like that, to know how
+        // many triangles the lower detail saves
+        let quads = (lod == 0 || self.full_triangles.is_none()).then(|| {
+            (0..CHUNK_SIZE)
+                .into_par_iter()
+                .flat_map(|y| {
+                    let (culled, mut queue) = self.cull_layer(y);
+                    self.layer_to_quads(y, offset, culled, &mut queue, highlighted_type, lighting)
+                })
+                .collect::<Vec<Quad>>()
+        });
+        if let Some(quads) = &quads {
+            self.full_triangles = Some(Self::triangle_count(quads));
+        }
 
-        self.buffers = Some(GeometryBuffers::from_geometry(
-            render_context,
-            &Self::quads_to_geometry(quads),
-            BufferUsages::empty(),
-        ));
+        let quads = match quads {
+            Some(quads) if lod == 0 => quads,
+            _ => self.lod_quads(offset, lod, highlighted_type),
+        };
+        self.lod = lod;
+        self.lod_triangles_saved = self
+            .full_triangles
+            .unwrap_or(0)
+            .saturating_sub(Self::triangle_count(&quads));
 
         self.update_fullness();
+        Self::quads_to_geometry(quads, block_textures)
+    }
+
+    /// Uploads the geometry to the GPU. The opaque faces are left out if they're in the arena.
+    pub fn upload_geometry(&mut self, render_context: &RenderContext, geometry: &ChunkGeometry) {
+        self.buffers = self.arena_slot.is_none().then(|| {
+            GeometryBuffers::from_geometry(render_context, &geometry.opaque, BufferUsages::empty())
+        });
+        self.transparent_buffers = (geometry.transparent.index_count() > 0).then(|| {
+            GeometryBuffers::from_geometry(
+                render_context,
+                &geometry.transparent,
+                BufferUsages::empty(),
+            )
+        });
     }
 
-    pub fn save(&self, position: Point3<isize>, store: &sled::Db) -> anyhow::Result<()> {
+    pub fn save(&self, position: Point3<isize>, store: &sled::Db) -> error::Result<()> {
         let data = rmp_serde::encode::to_vec_named(self)?;
         let key = format!("{}_{}_{}", position.x, position.y, position.z);
-        store.insert(key, data)?;
+        store.insert(&key, data)?;
+
+        let block_entities_key = format!("{}_block_entities", key);
+        if self.block_entities.is_empty() {
+            store.remove(block_entities_key)?;
+        } else {
+            let block_entities: Vec<([usize; 3], &BlockEntity)> = self
+                .block_entities
+                .iter()
+                .map(|(position, block_entity)| ((*position).into(), block_entity))
+                .collect();
+            let data = rmp_serde::encode::to_vec_named(&block_entities)?;
+            store.insert(block_entities_key, data)?;
+        }
+
         Ok(())
     }
 
-    pub fn load(&mut self, position: Point3<isize>, store: &sled::Db) -> anyhow::Result<bool> {
+    pub fn load(
+        &mut self,
+        position: Point3<isize>,
+        store: &sled::Db,
+        generator: &dyn WorldGenerator,
+    ) -> error::Result<bool> {
         let key = format!("{}_{}_{}", position.x, position.y, position.z);
 
-        if let Some(data) = store.get(key)? {
+        if let Some(data) = store.get(&key)? {
             *self = rmp_serde::decode::from_slice(&data)?;
+
+            if let Some(data) = store.get(format!("{}_block_entities", key))? {
+                let block_entities: Vec<([usize; 3], BlockEntity)> =
+                    rmp_serde::decode::from_slice(&data)?;
+                self.block_entities = block_entities
+                    .into_iter()
+                    .map(|(position, block_entity)| (position.into(), block_entity))
+                    .collect();
+            }
+
             Ok(false)
         } else {
-            self.generate(position.x, position.y, position.z);
+            generator.generate(self, position);
             Ok(true)
         }
     }
@@ -451,3 +748,129 @@ impl Chunk {
         aabb.intersects(&view.frustrum_aabb)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use cgmath::EuclideanSpace;
+
+    use super::*;
+
+    #[test]
+    fn puts_negative_blocks_in_negative_chunks() {
+        let corner = Point3::new(-1, -1, -1);
+        assert_eq!(world_to_chunk(corner), corner);
+        assert_eq!(world_to_local(corner), Point3::new(31, 31, 31));
+
+        assert_eq!(
+            world_to_chunk(Point3::new(0, -32, -33)),
+            Point3::new(0, -1, -2)
+        );
+        assert_eq!(
+            world_to_local(Point3::new(0, -32, -33)),
+            Point3::new(0, 0, 31)
+        );
+    }
+
+    #[test]
+    fn finds_every_block_back_from_its_chunk() {
+        for n in -2 * CHUNK_ISIZE..2 * CHUNK_ISIZE {
+            let point = Point3::new(n, -n, n / 3);
+            let chunk = world_to_chunk(point);
+            let local = world_to_local(point).cast::<isize>().unwrap();
+            assert_eq!(chunk * CHUNK_ISIZE + local.to_vec(), point);
+        }
+    }
+
+    /// A chunk with a single layer of grass at the bottom, and the given biome tint at every
+    /// block corner.
+    fn grass_layer(tint: impl Fn(usize, usize) -> Vector4<f32>) -> Chunk {
+        let mut chunk = Chunk::default();
+        chunk.blocks[0] = [[Some(Block::new(BlockType::Grass)); CHUNK_SIZE]; CHUNK_SIZE];
+        chunk.biome_tints = itertools::iproduct!(0..=CHUNK_SIZE, 0..=CHUNK_SIZE)
+            .map(|(z, x)| tint(x, z))
+            .collect();
+        chunk
+    }
+
+    fn layer_quads(chunk: &Chunk, y: usize) -> Vec<Quad> {
+        let (culled, mut queue) = chunk.cull_layer(y);
+        let offset = Point3::new(0, 0, 0);
+        chunk.layer_to_quads(y, offset, culled, &mut queue, None, LightingMode::Flat)
+    }
+
+    #[test]
+    fn merges_grass_with_the_same_tint() {
+        let chunk = grass_layer(|_, _| Vector4::new(0.8, 1.0, 0.8, 1.0));
+        assert_eq!(layer_quads(&chunk, 0).len(), 1);
+    }
+
+    #[test]
+    fn keeps_the_gradient_between_biomes() {
+        // The tint changes gradually from x = 8 to x = 12, like on the border of two biomes
+        let chunk = grass_layer(|x, _| {
+            let t = (x.clamp(8, 12) - 8) as f32 / 4.0;
+            Vector4::new(1.0 - t * 0.5, 1.0, 1.0, 1.0)
+        });
+        let quads = layer_quads(&chunk, 0);
+
+        // Every column in the gradient has a quad of its own, with the tints of its own corners
+        for quad in &quads {
+            let x = quad.position.x as usize;
+            let expected = chunk.corner_tints(x, 0, x + quad.dx as usize, quad.dz as usize);
+            assert_eq!(quad.tints, expected);
+            if (8..12).contains(&x) {
+                assert_eq!(quad.dx, 1);
+            } else {
+                assert!(chunk.uniform_tint(x, 0, x + quad.dx as usize, 1).is_some());
+            }
+        }
+        let covered: isize = quads.iter().map(|quad| quad.dx * quad.dz).sum();
+        assert_eq!(covered, CHUNK_ISIZE * CHUNK_ISIZE);
+    }
+
+    #[test]
+    fn merges_a_chunk_of_water_into_a_few_quads() {
+        let chunk = Chunk {
+            blocks: [[[Some(Block::new(BlockType::Water)); CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE],
+            biome_tints: vec![Vector4::new(1.0, 1.0, 1.0, 1.0); (CHUNK_SIZE + 1).pow(2)],
+            ..Chunk::default()
+        };
+
+        let quads: Vec<_> = (0..CHUNK_SIZE)
+            .flat_map(|y| layer_quads(&chunk, y))
+            .collect();
+
+        // Only the outside of the chunk is visible. Every layer gets a quad for each of its four
+        // corners and four edges, which also carry the top or bottom face at the top and bottom,
+        // and the top and bottom layer get one for their middle.
+        assert_eq!(quads.len(), CHUNK_SIZE * 8 + 2);
+        let sides = 4 + 4 * 2;
+        let top = 1 + 4 * 2 + 4 * 3;
+        assert_eq!(
+            Chunk::triangle_count(&quads),
+            2 * ((CHUNK_SIZE - 2) * sides + 2 * top)
+        );
+    }
+
+    #[test]
+    fn culls_faces_between_blocks_of_the_same_transparent_type() {
+        // A row of glass, glass, water and stone along X, in the middle of the chunk
+        let mut chunk = Chunk::default();
+        let row = [
+            BlockType::Glass,
+            BlockType::Glass,
+            BlockType::Water,
+            BlockType::Stone,
+        ];
+        for (x, &block_type) in row.iter().enumerate() {
+            chunk.blocks[8][8][8 + x] = Some(Block::new(block_type));
+        }
+        let faces = |x: usize| chunk.check_visible_faces(8 + x, 8, 8);
+
+        // The glass between them is hidden, but glass doesn't hide water, and neither hides stone
+        assert_eq!(faces(0) & (FACE_LEFT | FACE_RIGHT), FACE_LEFT);
+        assert_eq!(faces(1) & (FACE_LEFT | FACE_RIGHT), FACE_RIGHT);
+        assert_eq!(faces(2) & (FACE_LEFT | FACE_RIGHT), FACE_LEFT | FACE_RIGHT);
+        assert_eq!(faces(3) & (FACE_LEFT | FACE_RIGHT), FACE_LEFT | FACE_RIGHT);
+    }
+}
@@ -0,0 +1,252 @@
+use std::{mem::size_of, ops::Range};
+
+use wgpu::{Buffer, BufferAddress, BufferUsages, RenderPass};
+
+use crate::{geometry::Geometry, render_context::RenderContext, vertex::BlockVertex};
+
+/// How many vertices fit in the arena, about 128 MiB worth.
+const VERTEX_CAPACITY: u32 = 128 * 1024 * 1024 / size_of::<BlockVertex>() as u32;
+/// How many indices fit in the arena. Every quad has 4 vertices and 6 indices.
+const INDEX_CAPACITY: u32 = VERTEX_CAPACITY / 4 * 6;
+/// How many draws the indirect buffer starts out with room for. It grows when more chunks are
+/// visible than that.
+const INITIAL_DRAW_CAPACITY: usize = 1024;
+
+/// The arguments of one draw in an indirect buffer, as `multi_draw_indexed_indirect` expects them.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct DrawIndexedIndirect {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+/// Hands out ranges of a buffer, first fit, and takes them back.
+struct RangeAllocator {
+    /// The ranges that aren't handed out, sorted and never touching each other.
+    free: Vec<Range<u32>>,
+}
+
+impl RangeAllocator {
+    fn new(capacity: u32) -> Self {
+        Self {
+            free: std::iter::once(0..capacity).collect(),
+        }
+    }
+
+    fn allocate(&mut self, len: u32) -> Option<Range<u32>> {
+        let index = self
+            .free
+            .iter()
+            .position(|range| range.len() as u32 >= len)?;
+        let range = &mut self.free[index];
+        let allocated = range.start..range.start + len;
+        range.start += len;
+        if range.start == range.end {
+            self.free.remove(index);
+        }
+        Some(allocated)
+    }
+
+    fn free(&mut self, range: Range<u32>) {
+        if range.is_empty() {
+            return;
+        }
+
+        let index = self.free.partition_point(|free| free.start < range.start);
+        self.free.insert(index, range);
+
+        // Merge with the ranges on either side, if they touch
+        if index + 1 < self.free.len() && self.free[index].end == self.free[index + 1].start {
+            self.free[index].end = self.free.remove(index + 1).end;
+        }
+        if index > 0 && self.free[index - 1].end == self.free[index].start {
+            self.free[index - 1].end = self.free.remove(index).end;
+        }
+    }
+}
+
+/// Where a chunk's geometry is in the arena.
+#[derive(Debug, Clone, Default)]
+pub struct ArenaSlot {
+    vertices: Range<u32>,
+    indices: Range<u32>,
+    /// Can be less than the length of `indices`, which is rounded up to keep the copies aligned.
+    index_count: u32,
+}
+
+/// One big vertex and index buffer that the opaque geometry of many chunks is copied into, so
+/// all of it can be drawn with a single `multi_draw_indexed_indirect`. Only used if the device
+/// supports `Features::MULTI_DRAW_INDIRECT`.
+pub struct ChunkArena {
+    vertices: Buffer,
+    indices: Buffer,
+    vertex_allocator: RangeAllocator,
+    index_allocator: RangeAllocator,
+
+    /// The draws of the chunks that are visible this frame, one per chunk.
+    indirect: Buffer,
+    indirect_capacity: usize,
+    draw_count: u32,
+    triangle_count: usize,
+}
+
+impl ChunkArena {
+    /// Creates the arena, if the device supports drawing from it.
+    pub fn new(render_context: &RenderContext) -> Option<Self> {
+        let features = render_context.device.features();
+        if !features.contains(wgpu::Features::MULTI_DRAW_INDIRECT) {
+            return None;
+        }
+
+        let create_buffer = |label, size, usage| {
+            render_context
+                .device
+                .create_buffer(&wgpu::BufferDescriptor {
+                    label: Some(label),
+                    size,
+                    usage: usage | BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                })
+        };
+
+        Some(Self {
+            vertices: create_buffer(
+                "chunk arena vertex buffer",
+                VERTEX_CAPACITY as BufferAddress * size_of::<BlockVertex>() as BufferAddress,
+                BufferUsages::VERTEX,
+            ),
+            indices: create_buffer(
+                "chunk arena index buffer",
+                INDEX_CAPACITY as BufferAddress * size_of::<u16>() as BufferAddress,
+                BufferUsages::INDEX,
+            ),
+            vertex_allocator: RangeAllocator::new(VERTEX_CAPACITY),
+            index_allocator: RangeAllocator::new(INDEX_CAPACITY),
+
+            indirect: Self::create_indirect_buffer(render_context, INITIAL_DRAW_CAPACITY),
+            indirect_capacity: INITIAL_DRAW_CAPACITY,
+            draw_count: 0,
+            triangle_count: 0,
+        })
+    }
+
+    fn create_indirect_buffer(render_context: &RenderContext, capacity: usize) -> Buffer {
+        render_context
+            .device
+            .create_buffer(&wgpu::BufferDescriptor {
+                label: Some("chunk arena indirect buffer"),
+                size: (capacity * size_of::<DrawIndexedIndirect>()) as BufferAddress,
+                usage: BufferUsages::INDIRECT | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            })
+    }
+
+    /// Copies the geometry into the arena. Returns `None` if it doesn't fit, in which case it has
+    /// to be drawn by itself.
+    pub fn allocate(
+        &mut self,
+        render_context: &RenderContext,
+        geometry: &Geometry<BlockVertex, u16>,
+    ) -> Option<ArenaSlot> {
+        if geometry.indices.is_empty() {
+            return Some(ArenaSlot::default());
+        }
+
+        // Copies have to be a multiple of 4 bytes, so an odd number of indices gets padded
+        let mut indices = geometry.indices.clone();
+        if indices.len() % 2 == 1 {
+            indices.push(0);
+        }
+
+        let vertices = self
+            .vertex_allocator
+            .allocate(geometry.vertices.len() as u32)?;
+        let index_range = match self.index_allocator.allocate(indices.len() as u32) {
+            Some(index_range) => index_range,
+            None => {
+                self.vertex_allocator.free(vertices);
+                return None;
+            }
+        };
+
+        let queue = &render_context.queue;
+        queue.write_buffer(
+            &self.vertices,
+            vertices.start as BufferAddress * size_of::<BlockVertex>() as BufferAddress,
+            bytemuck::cast_slice(&geometry.vertices),
+        );
+        queue.write_buffer(
+            &self.indices,
+            index_range.start as BufferAddress * size_of::<u16>() as BufferAddress,
+            bytemuck::cast_slice(&indices),
+        );
+
+        Some(ArenaSlot {
+            vertices,
+            indices: index_range,
+            index_count: geometry.indices.len() as u32,
+        })
+    }
+
+    /// Makes room for other geometry where the slot's geometry was.
+    pub fn free(&mut self, slot: ArenaSlot) {
+        self.vertex_allocator.free(slot.vertices);
+        self.index_allocator.free(slot.indices);
+    }
+
+    /// Frees every slot at once.
+    pub fn clear(&mut self) {
+        self.vertex_allocator = RangeAllocator::new(VERTEX_CAPACITY);
+        self.index_allocator = RangeAllocator::new(INDEX_CAPACITY);
+        self.draw_count = 0;
+        self.triangle_count = 0;
+    }
+
+    /// Sets up the draws of the slots to draw this frame. Has to be called before the render pass
+    /// the arena is drawn in is started.
+    pub fn prepare<'a>(
+        &mut self,
+        render_context: &RenderContext,
+        slots: impl Iterator<Item = &'a ArenaSlot>,
+    ) {
+        let draws: Vec<DrawIndexedIndirect> = slots
+            .filter(|slot| slot.index_count > 0)
+            .map(|slot| DrawIndexedIndirect {
+                index_count: slot.index_count,
+                instance_count: 1,
+                first_index: slot.indices.start,
+                base_vertex: slot.vertices.start as i32,
+                first_instance: 0,
+            })
+            .collect();
+
+        if draws.len() > self.indirect_capacity {
+            self.indirect_capacity = draws.len().next_power_of_two();
+            self.indirect = Self::create_indirect_buffer(render_context, self.indirect_capacity);
+        }
+        if !draws.is_empty() {
+            render_context
+                .queue
+                .write_buffer(&self.indirect, 0, bytemuck::cast_slice(&draws));
+        }
+
+        self.draw_count = draws.len() as u32;
+        self.triangle_count = draws.iter().map(|draw| draw.index_count as usize / 3).sum();
+    }
+
+    /// Draws the slots passed to the last `prepare` with a single draw call. Returns the number of
+    /// triangles drawn.
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
+        if self.draw_count == 0 {
+            return 0;
+        }
+
+        render_pass.set_vertex_buffer(0, self.vertices.slice(..));
+        render_pass.set_index_buffer(self.indices.slice(..), wgpu::IndexFormat::Uint16);
+        render_pass.multi_draw_indexed_indirect(&self.indirect, 0, self.draw_count);
+        self.triangle_count
+    }
+}
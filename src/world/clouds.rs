@@ -0,0 +1,236 @@
+use std::borrow::Cow;
+
+use cgmath::Point3;
+use image::{Rgba, RgbaImage};
+use wgpu::{util::DeviceExt, BindGroup, BindGroupLayout, RenderPass, RenderPipeline};
+
+use crate::{
+    render_context::RenderContext, settings::CloudSettings, texture::Texture, view::View,
+    world::random::ChunkRng,
+};
+
+/// Minecraft's resource pack layout: a single tiling texture, white where there are clouds and
+/// transparent where there aren't.
+const CLOUD_TEXTURE: &str = "assets/environment/clouds.png";
+
+/// The size of the texture generated instead of one that couldn't be loaded.
+const GENERATED_SIZE: u32 = 64;
+/// How many times the random pixels of the generated texture are smoothed into blobs.
+const GENERATED_SMOOTHING: usize = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct CloudsUniform {
+    height: f32,
+    /// How fast the clouds drift, in blocks per second.
+    speed: f32,
+    _padding: [f32; 2],
+}
+
+/// A flat layer of clouds high above the world, drifting along with the time. It's a single quad
+/// following the camera around, with a repeating texture scrolling over it.
+///
+/// They're drawn after the opaque geometry, blended over it, and without culling, so they can be
+/// seen from both above and below.
+pub struct Clouds {
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+}
+
+impl Clouds {
+    pub fn new(
+        render_context: &RenderContext,
+        view: &View,
+        time_bind_group_layout: &BindGroupLayout,
+        settings: &CloudSettings,
+    ) -> Self {
+        let device = &render_context.device;
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("clouds_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler {
+                        comparison: false,
+                        filtering: true,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        // Repeating, and with sharp edges, like the blocks below
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("clouds sampler"),
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            ..wgpu::SamplerDescriptor::default()
+        });
+
+        let texture = Self::load_texture(render_context);
+
+        let uniform = CloudsUniform {
+            height: settings.height,
+            speed: settings.speed,
+            _padding: [0.0; 2],
+        };
+        let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("clouds uniform buffer"),
+            contents: bytemuck::cast_slice(&[uniform]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("clouds_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::Sampler(&sampler),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&texture.view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 2,
+                    resource: uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("clouds_pipeline_layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[
+                &view.bind_group_layout,
+                time_bind_group_layout,
+                &bind_group_layout,
+            ],
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("clouds shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/clouds.wgsl"))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Clouds pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: render_context.format,
+                    blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState {
+                cull_mode: None,
+                ..wgpu::PrimitiveState::default()
+            },
+            // Hidden behind terrain, like mountains reaching into them, but like other blended
+            // geometry they don't hide anything themselves
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Greater,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+        }
+    }
+
+    /// Loads the cloud texture. The clouds aren't needed to play, so if it can't be loaded, a
+    /// pattern of clouds is generated instead.
+    fn load_texture(render_context: &RenderContext) -> Texture {
+        let loaded = std::fs::read(CLOUD_TEXTURE)
+            .map_err(anyhow::Error::from)
+            .and_then(|bytes| Ok(image::load_from_memory(&bytes)?.into_rgba8()))
+            .and_then(|image| Texture::from_image(render_context, &image, CLOUD_TEXTURE));
+
+        loaded.unwrap_or_else(|error| {
+            eprintln!(
+                "Failed to load {}, generating clouds instead: {:#}",
+                CLOUD_TEXTURE, error
+            );
+            Texture::from_image(render_context, &Self::generate(), CLOUD_TEXTURE).unwrap()
+        })
+    }
+
+    /// Generates a tiling texture of clouds: random pixels, smoothed into blobs by turning every
+    /// pixel into whatever most of its neighbours are, wrapping around the edges.
+    fn generate() -> RgbaImage {
+        let size = GENERATED_SIZE as usize;
+        let mut rng = ChunkRng::new(0, Point3::new(0, 0, 0));
+        let mut cloudy: Vec<bool> = (0..size * size).map(|_| rng.next_f32() < 0.45).collect();
+
+        for _ in 0..GENERATED_SMOOTHING {
+            cloudy = (0..size * size)
+                .map(|index| {
+                    let (x, y) = (index % size, index / size);
+                    let neighbours = itertools::iproduct!([size - 1, 0, 1], [size - 1, 0, 1])
+                        .filter(|&(dx, dy)| cloudy[(y + dy) % size * size + (x + dx) % size])
+                        .count();
+                    neighbours >= 5
+                })
+                .collect();
+        }
+
+        RgbaImage::from_fn(GENERATED_SIZE, GENERATED_SIZE, |x, y| {
+            if cloudy[y as usize * size + x as usize] {
+                Rgba([255, 255, 255, 204])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        })
+    }
+
+    pub fn render<'a>(
+        &'a self,
+        render_pass: &mut RenderPass<'a>,
+        view: &'a View,
+        time_bind_group: &'a BindGroup,
+    ) -> usize {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &view.bind_group, &[]);
+        render_pass.set_bind_group(1, time_bind_group, &[]);
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+        render_pass.draw(0..6, 0..1);
+        2
+    }
+}
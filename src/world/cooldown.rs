@@ -0,0 +1,83 @@
+use std::time::{Duration, Instant};
+
+/// Limits how often blocks can be broken or placed in survival. The current time is passed in,
+/// rather than read from the clock, so it's the caller that decides what "now" is.
+pub struct ActionCooldown {
+    pub duration: Duration,
+    last_action: Option<Instant>,
+}
+
+impl ActionCooldown {
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            duration,
+            last_action: None,
+        }
+    }
+
+    /// Returns whether the cooldown since the last action has passed at `now`.
+    pub fn passed(&self, now: Instant) -> bool {
+        self.last_action
+            .is_none_or(|last_action| now.saturating_duration_since(last_action) >= self.duration)
+    }
+
+    /// Starts the cooldown again from `now`.
+    pub fn restart(&mut self, now: Instant) {
+        self.last_action = Some(now);
+    }
+
+    /// Returns whether an action can be taken at `now`, and if so, starts the cooldown again.
+    /// Creative actions don't have a cooldown, and don't start it either.
+    pub fn try_start(&mut self, now: Instant, creative: bool) -> bool {
+        if creative {
+            return true;
+        }
+        if !self.passed(now) {
+            return false;
+        }
+
+        self.restart(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COOLDOWN: Duration = Duration::from_millis(150);
+
+    #[test]
+    fn allows_one_action_within_the_cooldown() {
+        let mut cooldown = ActionCooldown::new(COOLDOWN);
+        let start = Instant::now();
+
+        let actions = [start, start + Duration::from_millis(100)]
+            .iter()
+            .filter(|&&now| cooldown.try_start(now, false))
+            .count();
+        assert_eq!(actions, 1);
+    }
+
+    #[test]
+    fn allows_another_action_once_the_cooldown_has_passed() {
+        let mut cooldown = ActionCooldown::new(COOLDOWN);
+        let start = Instant::now();
+
+        assert!(cooldown.try_start(start, false));
+        assert!(!cooldown.passed(start + COOLDOWN - Duration::from_millis(1)));
+        assert!(cooldown.try_start(start + COOLDOWN, false));
+        // That one started the cooldown again
+        assert!(!cooldown.try_start(start + COOLDOWN + Duration::from_millis(1), false));
+    }
+
+    #[test]
+    fn ignores_the_cooldown_in_creative() {
+        let mut cooldown = ActionCooldown::new(COOLDOWN);
+        let start = Instant::now();
+
+        assert!(cooldown.try_start(start, false));
+        assert!(cooldown.try_start(start, true));
+        assert!(cooldown.try_start(start, true));
+    }
+}
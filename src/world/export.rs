@@ -0,0 +1,157 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::{self, MinecrabError};
+
+/// Bumped whenever the layout of an export changes, so old exports are refused instead of
+/// misread.
+const EXPORT_VERSION: u32 = 1;
+
+/// A world packed into a single file, to share it. Holds everything in the chunk database: the
+/// chunks, their block entities and the NPCs.
+#[derive(Serialize, Deserialize)]
+struct WorldExport {
+    version: u32,
+    header: ExportHeader,
+    entries: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+/// What's needed to pick up where an exported world left off, besides its chunks.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ExportHeader {
+    /// The seed the world was generated with, so the chunks that weren't generated yet match.
+    pub seed: u32,
+    pub player_position: [f32; 3],
+}
+
+/// Writes everything in the database to a zstd-compressed file at `path`.
+pub fn export(database: &sled::Db, path: &Path, header: ExportHeader) -> error::Result<()> {
+    let entries = database
+        .iter()
+        .map(|entry| entry.map(|(key, value)| (key.to_vec(), value.to_vec())))
+        .collect::<Result<_, _>>()?;
+    let export = WorldExport {
+        version: EXPORT_VERSION,
+        header,
+        entries,
+    };
+
+    let data = rmp_serde::encode::to_vec(&export)?;
+    let data = zstd::encode_all(&data[..], zstd::DEFAULT_COMPRESSION_LEVEL)?;
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, data)?;
+    Ok(())
+}
+
+/// Reads an export into `database`, which is stored at `database_path`. Refuses to overwrite a
+/// database that already has anything in it.
+pub fn import(
+    path: &Path,
+    database: &sled::Db,
+    database_path: &Path,
+) -> error::Result<ExportHeader> {
+    let data = zstd::decode_all(&fs::read(path)?[..])?;
+    let export: WorldExport = rmp_serde::decode::from_slice(&data)?;
+    if export.version != EXPORT_VERSION {
+        return Err(MinecrabError::ExportVersion(export.version));
+    }
+
+    if !database.is_empty() {
+        return Err(MinecrabError::ImportTarget(
+            database_path.display().to_string(),
+        ));
+    }
+    for (key, value) in export.entries {
+        database.insert(key, value)?;
+    }
+    database.flush()?;
+
+    Ok(export.header)
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::{Point3, Vector3};
+
+    use super::*;
+    use crate::world::{
+        block::{Block, BlockType},
+        block_entity::{BlockEntity, ItemStack},
+        chunk::Chunk,
+        generator::FlatGenerator,
+    };
+
+    fn temporary_database() -> sled::Db {
+        sled::Config::new().temporary(true).open().unwrap()
+    }
+
+    #[test]
+    fn imports_the_chunks_it_exported() {
+        let positions = [Point3::new(0, 0, 0), Point3::new(-3, 2, 7)];
+        let mut chunks = Vec::new();
+        let source = temporary_database();
+        for (i, &position) in positions.iter().enumerate() {
+            let mut chunk = Chunk::default();
+            chunk.blocks[i][2][3] = Some(Block::new(BlockType::Stone));
+            chunk.blocks[5][i][1] = Some(Block::new(BlockType::Glass));
+            chunk.blocks[1][2][3] = Some(Block::new(BlockType::Chest));
+            let mut chest = BlockEntity::for_block_type(BlockType::Chest).unwrap();
+            let BlockEntity::Chest(contents) = &mut chest;
+            let stack = ItemStack {
+                block_type: BlockType::Dirt,
+                count: 12,
+            };
+            contents.swap(4, Some(stack));
+            chunk.block_entities.insert(Vector3::new(3, 1, 2), chest);
+
+            chunk.save(position, &source).unwrap();
+            chunks.push(chunk);
+        }
+
+        let path = std::env::temp_dir().join(format!("minecrab-export-{}", std::process::id()));
+        let header = ExportHeader {
+            seed: 1234,
+            player_position: [1.0, 70.0, -2.5],
+        };
+        export(&source, &path, header).unwrap();
+
+        let target = temporary_database();
+        let imported = import(&path, &target, Path::new("target"));
+        fs::remove_file(&path).unwrap();
+        let imported = imported.unwrap();
+        assert_eq!(imported.seed, 1234);
+        assert_eq!(imported.player_position, [1.0, 70.0, -2.5]);
+
+        let generator = FlatGenerator::new(&[]);
+        for (&position, expected) in positions.iter().zip(&chunks) {
+            let mut chunk = Chunk::default();
+            let generated = chunk.load(position, &target, &generator).unwrap();
+            assert!(!generated, "{:?} wasn't imported", position);
+            assert_eq!(chunk.blocks, expected.blocks);
+
+            let BlockEntity::Chest(chest) = &chunk.block_entities[&Vector3::new(3, 1, 2)];
+            let BlockEntity::Chest(expected) = &expected.block_entities[&Vector3::new(3, 1, 2)];
+            assert_eq!(chest.slots, expected.slots);
+        }
+    }
+
+    #[test]
+    fn refuses_to_import_over_a_world() {
+        let source = temporary_database();
+        let path = std::env::temp_dir().join(format!("minecrab-refuse-{}", std::process::id()));
+        let header = ExportHeader {
+            seed: 0,
+            player_position: [0.0; 3],
+        };
+        export(&source, &path, header).unwrap();
+
+        let target = temporary_database();
+        target.insert("0_0_0", vec![1]).unwrap();
+        let imported = import(&path, &target, Path::new("target"));
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(imported, Err(MinecrabError::ImportTarget(_))));
+    }
+}
@@ -0,0 +1,79 @@
+use cgmath::Point3;
+
+use crate::world::{block::BlockType, block_view::BlockView, chunk::CHUNK_ISIZE, WORLD_HEIGHT};
+
+/// Works out what flattening the square of columns within `radius` of `center` horizontally
+/// changes: everything above `center.y` is cleared, and everything at or below it is filled with
+/// `block_type`. Returns every block that isn't what it should be yet, with what it should become.
+/// Blocks that can't be broken, like bedrock, are left alone.
+pub fn flatten_edits(
+    center: Point3<isize>,
+    radius: isize,
+    block_type: BlockType,
+    blocks: &impl BlockView,
+) -> Vec<(Point3<isize>, Option<BlockType>)> {
+    let columns = itertools::iproduct!(
+        center.x - radius..=center.x + radius,
+        center.z - radius..=center.z + radius
+    );
+    columns
+        .flat_map(|(x, z)| (0..WORLD_HEIGHT * CHUNK_ISIZE).map(move |y| Point3::new(x, y, z)))
+        .filter_map(|point| {
+            let current = blocks.get_block(point).map(|block| block.block_type);
+            let target = (point.y <= center.y).then_some(block_type);
+            let keep = current == target || current.is_some_and(|block| !block.is_breakable());
+            (!keep).then_some((point, target))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block_view::test_world::TestWorld;
+
+    #[test]
+    fn levels_every_column_in_the_square() {
+        // Uneven terrain on bedrock, crossing the chunk borders at x = 0 and z = 0
+        let mut world = TestWorld::default();
+        for (x, z) in itertools::iproduct!(-4..4, -4..4) {
+            world.set_block(Point3::new(x, 0, z), BlockType::Bedrock);
+            for y in 1..60 + x * 3 - z {
+                world.set_block(Point3::new(x, y, z), BlockType::Stone);
+            }
+        }
+
+        let center = Point3::new(0, 58, 0);
+        for (point, block_type) in flatten_edits(center, 2, BlockType::Dirt, &world) {
+            match block_type {
+                Some(block_type) => world.set_block(point, block_type),
+                None => {
+                    world.blocks.remove(&point);
+                }
+            }
+        }
+
+        for (x, z) in itertools::iproduct!(-4..4, -4..4) {
+            let profile: Vec<_> = (0..80)
+                .map(|y| {
+                    world
+                        .get_block(Point3::new(x, y, z))
+                        .map(|block| block.block_type)
+                })
+                .collect();
+
+            if (-2..=2).contains(&x) && (-2..=2).contains(&z) {
+                assert_eq!(profile[0], Some(BlockType::Bedrock));
+                assert!(profile[1..=58].iter().all(|&b| b == Some(BlockType::Dirt)));
+                assert!(profile[59..].iter().all(Option::is_none));
+            } else {
+                // Columns outside of the square keep their height and blocks
+                let height = (60 + x * 3 - z) as usize;
+                assert!(profile[1..height]
+                    .iter()
+                    .all(|&b| b == Some(BlockType::Stone)));
+                assert!(profile[height..].iter().all(Option::is_none));
+            }
+        }
+    }
+}
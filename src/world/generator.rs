@@ -0,0 +1,622 @@
+use cgmath::Point3;
+use noise::utils::{NoiseMap, NoiseMapBuilder, PlaneMapBuilder};
+
+use noise::{MultiFractal, NoiseFn, Seedable};
+
+use crate::{
+    settings::{
+        DecorationSettings, FlatLayer, GeneratorKind, TerrainNoise, TerrainParams, WorldSettings,
+    },
+    world::{
+        block::{Block, BlockType},
+        chunk::{Chunk, CHUNK_ISIZE, CHUNK_SIZE},
+        random::ChunkRng,
+    },
+};
+
+/// Fills freshly created chunks with terrain.
+pub trait WorldGenerator: Send + Sync {
+    fn generate(&self, chunk: &mut Chunk, position: Point3<isize>);
+}
+
+pub fn from_settings(settings: &WorldSettings) -> Box<dyn WorldGenerator> {
+    match settings.generator {
+        GeneratorKind::Noise => Box::new(NoiseGenerator::new(
+            settings.seed,
+            settings.terrain.clone(),
+            &settings.terrain_palette,
+            settings.decorations.clone(),
+        )),
+        GeneratorKind::Flat => Box::new(FlatGenerator::new(&settings.flat_layers)),
+    }
+}
+
+type Noise = Box<dyn NoiseFn<[f64; 3]> + Send + Sync>;
+
+/// The most octaves `TerrainNoise::FastFbm` has.
+const FAST_FBM_OCTAVES: usize = 3;
+
+/// Creates noise of the given kind. The octaves, lacunarity and persistence only apply to the
+/// kinds made of layers of noise.
+fn create_noise(
+    kind: TerrainNoise,
+    seed: u32,
+    octaves: usize,
+    lacunarity: f64,
+    persistence: f64,
+) -> Noise {
+    let fbm = |octaves| {
+        noise::Fbm::new()
+            .set_octaves(octaves)
+            .set_lacunarity(lacunarity)
+            .set_persistence(persistence)
+            .set_seed(seed)
+    };
+
+    match kind {
+        TerrainNoise::Fbm => Box::new(fbm(octaves)),
+        TerrainNoise::FastFbm => Box::new(fbm(octaves.min(FAST_FBM_OCTAVES))),
+        // A single layer of fractal noise is just Perlin noise. `noise` exports two kinds of
+        // Perlin noise under the same name, so it can't be named directly anyway.
+        TerrainNoise::Perlin => Box::new(fbm(1)),
+        TerrainNoise::OpenSimplex => Box::new(noise::OpenSimplex::new().set_seed(seed)),
+    }
+}
+
+/// The chance of a tree growing on a grass block that's exposed to the air, at a tree density of 1.
+const TREE_CHANCE: f32 = 0.005;
+
+const TERRAIN_NOISE_SCALE: f64 = 0.1 / 16.0 * CHUNK_SIZE as f64;
+const TERRAIN_NOISE_OFFSET: f64 = 0.0 / 16.0 * CHUNK_SIZE as f64;
+
+/// The scale of the noise that warps the terrain, per block. The warp changes direction about
+/// every 64 blocks.
+const WARP_NOISE_SCALE: f64 = 1.0 / 64.0;
+/// Moves the warp along Z to a different part of the noise than the warp along X.
+const WARP_NOISE_OFFSET: f64 = 5381.0;
+
+/// The default generator, producing hilly terrain with water below the sea level, dotted with
+/// trees.
+pub struct NoiseGenerator {
+    seed: u32,
+    params: TerrainParams,
+    /// The block type at every depth below the surface, starting at the surface, down to the
+    /// bottom of the palette's last layer. Empty if there's no palette.
+    palette: Vec<BlockType>,
+    decorations: DecorationSettings,
+    /// Shapes the surface, with the kind, octaves, lacunarity and persistence from `params`.
+    terrain_noise: Noise,
+    /// Used for everything else: the depth of the stone and the blocks of the sea floor. Of the
+    /// same kind as the terrain noise, with the default octaves, lacunarity and persistence.
+    noise: Noise,
+}
+
+impl NoiseGenerator {
+    pub fn new(
+        seed: u32,
+        params: TerrainParams,
+        palette: &[FlatLayer],
+        decorations: DecorationSettings,
+    ) -> Self {
+        let terrain_noise = create_noise(
+            params.noise,
+            seed,
+            params.octaves,
+            params.lacunarity,
+            params.persistence,
+        );
+        let noise = create_noise(
+            params.noise,
+            seed,
+            noise::Fbm::DEFAULT_OCTAVE_COUNT,
+            noise::Fbm::DEFAULT_LACUNARITY,
+            noise::Fbm::DEFAULT_PERSISTENCE,
+        );
+
+        let palette = palette
+            .iter()
+            .flat_map(|layer| vec![layer.block; layer.thickness])
+            .collect();
+
+        Self {
+            seed,
+            params,
+            palette,
+            decorations,
+            terrain_noise,
+            noise,
+        }
+    }
+
+    /// Samples the terrain noise over the horizontal plane of the chunk, like `noise_map` does,
+    /// but stretched by `horizontal_scale` and warped by `warp_strength`. Both work in world
+    /// space, so neighbouring chunks still line up. Without stretching or warping, the samples
+    /// are exactly the ones `noise_map` would take.
+    fn terrain_noise_map(&self, position: Point3<isize>) -> NoiseMap {
+        let params = &self.params;
+        let (scale_x, scale_z) = params.horizontal_scale;
+        let scale_x = TERRAIN_NOISE_SCALE / scale_x;
+        let scale_z = TERRAIN_NOISE_SCALE / scale_z;
+
+        // The same arithmetic as PlaneMapBuilder, so the samples match to the bit
+        let x_start = position.x as f64 * scale_x + TERRAIN_NOISE_OFFSET;
+        let x_end = position.x as f64 * scale_x + scale_x + TERRAIN_NOISE_OFFSET;
+        let x_step = (x_end - x_start) / CHUNK_SIZE as f64;
+        let z_start = position.z as f64 * scale_z + TERRAIN_NOISE_OFFSET;
+        let z_end = position.z as f64 * scale_z + scale_z + TERRAIN_NOISE_OFFSET;
+        let z_step = (z_end - z_start) / CHUNK_SIZE as f64;
+
+        let mut map = NoiseMap::new(CHUNK_SIZE, CHUNK_SIZE);
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let mut sample_x = x_start + x_step * x as f64;
+                let mut sample_z = z_start + z_step * z as f64;
+
+                if params.warp_strength != 0.0 {
+                    let world_x = (position.x * CHUNK_ISIZE + x as isize) as f64 * WARP_NOISE_SCALE;
+                    let world_z = (position.z * CHUNK_ISIZE + z as isize) as f64 * WARP_NOISE_SCALE;
+                    let warp_x = self.noise.get([world_x, world_z, 0.0]);
+                    let warp_z = self.noise.get([world_x, world_z + WARP_NOISE_OFFSET, 0.0]);
+
+                    // The warp is in blocks, and the steps are how far apart blocks are in noise
+                    sample_x += warp_x * params.warp_strength * x_step;
+                    sample_z += warp_z * params.warp_strength * z_step;
+                }
+
+                map.set_value(x, z, self.terrain_noise.get([sample_x, sample_z, 0.0]));
+            }
+        }
+        map
+    }
+
+    /// Fills a column of the chunk from the palette, with the surface at `surface`, relative to
+    /// the bottom of the chunk. Only depends on the depth below the surface, so the layers line
+    /// up across chunks.
+    fn fill_from_palette(&self, chunk: &mut Chunk, x: usize, z: usize, surface: isize) {
+        for y in 0..(surface + 1).min(CHUNK_ISIZE) {
+            let depth = (surface - y) as usize;
+            let block_type = self.palette.get(depth).copied().unwrap_or(BlockType::Stone);
+            chunk.blocks[y as usize][z][x] = Some(Block::new(block_type));
+        }
+    }
+
+    /// Picks the soil and surface blocks of a column below the sea level from a sample of the
+    /// floor noise, giving patches of sand, gravel and the occasional bit of regular dirt.
+    fn floor_blocks(noise: f64) -> (BlockType, BlockType) {
+        if noise > 0.25 {
+            (BlockType::Gravel, BlockType::Gravel)
+        } else if noise > -0.35 {
+            (BlockType::Sand, BlockType::Sand)
+        } else {
+            (BlockType::Dirt, BlockType::Dirt)
+        }
+    }
+
+    /// Plants trees on the chunk's grass. Trees that wouldn't fit inside the chunk are left out,
+    /// so a chunk never has to touch its neighbours.
+    ///
+    /// Every grass block rolls the same numbers whatever the density is, so a chunk always gets
+    /// the same trees for the same seed and settings.
+    fn decorate(&self, chunk: &mut Chunk, position: Point3<isize>) {
+        let mut rng = ChunkRng::new(self.seed, position);
+        let tree_chance = TREE_CHANCE * self.decorations.tree_density.max(0.0);
+
+        for z in 2..CHUNK_SIZE - 2 {
+            for x in 2..CHUNK_SIZE - 2 {
+                let ground = (0..CHUNK_SIZE - 1).rev().find(|&y| {
+                    chunk.blocks[y + 1][z][x].is_none()
+                        && matches!(chunk.blocks[y][z][x], Some(b) if b.block_type == BlockType::Grass)
+                });
+
+                if let Some(ground) = ground {
+                    // Always roll, so the rest of the chunk doesn't depend on whether it fits
+                    let grow = rng.next_f32() < tree_chance;
+                    let height = 4 + rng.below(2);
+                    if grow && ground + height + 2 < CHUNK_SIZE {
+                        Self::plant_tree(chunk, x, ground + 1, z, height);
+                    }
+                }
+            }
+        }
+    }
+
+    fn plant_tree(chunk: &mut Chunk, x: usize, y: usize, z: usize, height: usize) {
+        let top = y + height - 1;
+        // The radius of every layer of leaves, relative to the top of the trunk
+        let leaf_layers: [(isize, isize); 4] = [(-2, 2), (-1, 2), (0, 1), (1, 1)];
+        for (dy, radius) in leaf_layers {
+            let leaves_y = (top as isize + dy) as usize;
+            for (dz, dx) in itertools::iproduct!(-radius..=radius, -radius..=radius) {
+                // Round the corners off
+                if radius > 1 && dx.abs() == radius && dz.abs() == radius {
+                    continue;
+                }
+
+                let block = &mut chunk.blocks[leaves_y][(z as isize + dz) as usize]
+                    [(x as isize + dx) as usize];
+                if block.is_none() {
+                    *block = Some(Block::new(BlockType::OakLeaves));
+                }
+            }
+        }
+
+        for trunk_y in y..=top {
+            chunk.blocks[trunk_y][z][x] = Some(Block::new(BlockType::OakLog));
+        }
+    }
+}
+
+impl WorldGenerator for NoiseGenerator {
+    fn generate(&self, chunk: &mut Chunk, position: Point3<isize>) {
+        let noise = self.noise.as_ref();
+        let terrain_noise = self.terrain_noise_map(position);
+
+        const STONE_NOISE_SCALE: f64 = 0.07 / 16.0 * CHUNK_SIZE as f64;
+        const STONE_NOISE_OFFSET: f64 = 11239.0 / 16.0 * CHUNK_SIZE as f64;
+        let stone_noise = noise_map(noise, position, STONE_NOISE_SCALE, STONE_NOISE_OFFSET);
+
+        const FLOOR_NOISE_SCALE: f64 = 0.2 / 16.0 * CHUNK_SIZE as f64;
+        const FLOOR_NOISE_OFFSET: f64 = -7351.0 / 16.0 * CHUNK_SIZE as f64;
+        let floor_noise = noise_map(noise, position, FLOOR_NOISE_SCALE, FLOOR_NOISE_OFFSET);
+
+        let params = &self.params;
+        let (stone_depth_min, stone_depth_max) = params.stone_depth;
+        let chunk_bottom = position.y * CHUNK_ISIZE;
+
+        for z in 0..CHUNK_SIZE {
+            for x in 0..CHUNK_SIZE {
+                let v = terrain_height(params, terrain_noise.get_value(x, z));
+
+                if self.palette.is_empty() {
+                    let s = stone_noise.get_value(x, z) * 20.0 + 4.5;
+                    let s = (s.round() as isize).clamp(stone_depth_min, stone_depth_max);
+
+                    // Columns that end up under water, or right at the shore, get a floor of sand
+                    // or gravel rather than grass and dirt
+                    let (soil, surface) = if v <= params.sea_level {
+                        Self::floor_blocks(floor_noise.get_value(x, z))
+                    } else {
+                        (BlockType::Dirt, BlockType::Grass)
+                    };
+
+                    let stone_max = (v - s - chunk_bottom).min(CHUNK_ISIZE);
+                    for y in 0..stone_max {
+                        chunk.blocks[y as usize][z][x] = Some(Block::new(BlockType::Stone));
+                    }
+
+                    let dirt_max = (v - chunk_bottom).min(CHUNK_ISIZE);
+                    for y in stone_max.max(0)..dirt_max {
+                        chunk.blocks[y as usize][z][x] = Some(Block::new(soil));
+                    }
+
+                    if (0..CHUNK_ISIZE).contains(&dirt_max) {
+                        chunk.blocks[dirt_max as usize][z][x] = Some(Block::new(surface));
+                    }
+                } else {
+                    self.fill_from_palette(chunk, x, z, v - chunk_bottom);
+                }
+
+                if position.y == 0 {
+                    chunk.blocks[0][z][x] = Some(Block::new(BlockType::Bedrock));
+                }
+
+                let water_max = (params.sea_level - chunk_bottom).min(CHUNK_ISIZE);
+                for y in 0..water_max {
+                    let block = &mut chunk.blocks[y as usize][z][x];
+                    if block.is_none() {
+                        *block = Some(Block::new(BlockType::Water));
+                    }
+                }
+            }
+        }
+
+        if self.decorations.enabled {
+            self.decorate(chunk, position);
+        }
+    }
+}
+
+/// Samples the noise over the horizontal plane of the chunk. The bounds are in world space, so
+/// neighbouring chunks line up seamlessly.
+fn noise_map(
+    noise: &dyn NoiseFn<[f64; 3]>,
+    position: Point3<isize>,
+    scale: f64,
+    offset: f64,
+) -> noise::utils::NoiseMap {
+    PlaneMapBuilder::new(noise)
+        .set_size(CHUNK_SIZE, CHUNK_SIZE)
+        .set_x_bounds(
+            position.x as f64 * scale + offset,
+            position.x as f64 * scale + scale + offset,
+        )
+        .set_y_bounds(
+            position.z as f64 * scale + offset,
+            position.z as f64 * scale + scale + offset,
+        )
+        .build()
+}
+
+/// Turns a sample of the terrain noise into the height of the surface.
+fn terrain_height(params: &TerrainParams, noise: f64) -> isize {
+    (noise * params.amplitude).round() as isize + params.base_height
+}
+
+/// Generates a superflat world out of horizontal layers of blocks.
+pub struct FlatGenerator {
+    /// The block type of every y level starting from y = 0, up to the top of the highest layer.
+    levels: Vec<BlockType>,
+}
+
+impl FlatGenerator {
+    /// Creates a generator stacking `layers` on top of each other, starting at y = 0.
+    pub fn new(layers: &[FlatLayer]) -> Self {
+        let levels = layers
+            .iter()
+            .flat_map(|layer| vec![layer.block; layer.thickness])
+            .collect();
+        Self { levels }
+    }
+}
+
+impl WorldGenerator for FlatGenerator {
+    fn generate(&self, chunk: &mut Chunk, position: Point3<isize>) {
+        for (y, layer) in chunk.blocks.iter_mut().enumerate() {
+            let level = position.y * CHUNK_ISIZE + y as isize;
+            if level < 0 {
+                continue;
+            }
+
+            if let Some(&block_type) = self.levels.get(level as usize) {
+                *layer = [[Some(Block::new(block_type)); CHUNK_SIZE]; CHUNK_SIZE];
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::WORLD_HEIGHT;
+
+    /// Generates a whole column of chunks, from the bottom of the world to the top.
+    fn generate_column(generator: &dyn WorldGenerator, x: isize, z: isize) -> Vec<Box<Chunk>> {
+        (0..WORLD_HEIGHT)
+            .map(|y| {
+                let mut chunk = Box::new(Chunk::default());
+                generator.generate(&mut chunk, Point3::new(x, y, z));
+                chunk
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decorates_the_same_chunk_the_same_way() {
+        let settings = WorldSettings {
+            decorations: DecorationSettings {
+                enabled: true,
+                tree_density: 20.0,
+            },
+            ..WorldSettings::default()
+        };
+
+        let first = generate_column(from_settings(&settings).as_ref(), 3, -2);
+        let second = generate_column(from_settings(&settings).as_ref(), 3, -2);
+        for (first, second) in first.iter().zip(&second) {
+            assert_eq!(first.blocks, second.blocks);
+        }
+
+        let trees = first
+            .iter()
+            .flat_map(|chunk| chunk.blocks.iter().flatten().flatten())
+            .filter(|block| block.is_some_and(|block| block.block_type == BlockType::OakLog))
+            .count();
+        assert!(trees > 0, "no trees were planted to compare");
+    }
+
+    /// Returns the height of the highest solid block of every column of a column of chunks,
+    /// indexed by z and x.
+    fn surface_heights(column: &[Box<Chunk>]) -> Vec<Vec<isize>> {
+        (0..CHUNK_SIZE)
+            .map(|z| {
+                (0..CHUNK_SIZE)
+                    .map(|x| {
+                        (0..WORLD_HEIGHT * CHUNK_ISIZE)
+                            .rev()
+                            .find(|&y| {
+                                let chunk = &column[(y / CHUNK_ISIZE) as usize];
+                                let block = chunk.blocks[(y % CHUNK_ISIZE) as usize][z][x];
+                                block.is_some_and(|block| !block.block_type.is_fluid())
+                            })
+                            .unwrap()
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Returns the largest height difference between neighbouring columns within a chunk.
+    fn largest_step(heights: &[Vec<isize>]) -> isize {
+        let along_x = heights
+            .iter()
+            .flat_map(|row| row.windows(2).map(|pair| (pair[0] - pair[1]).abs()));
+        let along_z = heights.windows(2).flat_map(|rows| {
+            rows[0]
+                .iter()
+                .zip(&rows[1])
+                .map(|(first, second)| (first - second).abs())
+        });
+        along_x.chain(along_z).max().unwrap()
+    }
+
+    #[test]
+    fn continues_the_terrain_across_chunk_borders() {
+        let settings = WorldSettings {
+            decorations: DecorationSettings {
+                enabled: false,
+                ..DecorationSettings::default()
+            },
+            ..WorldSettings::default()
+        };
+        let generator = from_settings(&settings);
+
+        let heights = surface_heights(&generate_column(generator.as_ref(), 0, 0));
+        let east = surface_heights(&generate_column(generator.as_ref(), 1, 0));
+        let south = surface_heights(&generate_column(generator.as_ref(), 0, 1));
+
+        // A seam would show up as a step at the border that's larger than any inside the chunks
+        let step = [&heights, &east, &south]
+            .iter()
+            .map(|heights| largest_step(heights))
+            .max()
+            .unwrap();
+        let last = CHUNK_SIZE - 1;
+        for i in 0..CHUNK_SIZE {
+            assert!((heights[i][last] - east[i][0]).abs() <= step);
+            assert!((heights[last][i] - south[0][i]).abs() <= step);
+        }
+    }
+
+    #[test]
+    fn shapes_the_terrain_by_the_octaves() {
+        let settings = |octaves| WorldSettings {
+            terrain: TerrainParams {
+                octaves,
+                ..TerrainParams::default()
+            },
+            decorations: DecorationSettings {
+                enabled: false,
+                ..DecorationSettings::default()
+            },
+            ..WorldSettings::default()
+        };
+        let heights = |octaves| {
+            let generator = from_settings(&settings(octaves));
+            surface_heights(&generate_column(generator.as_ref(), -1, 2))
+        };
+
+        assert_eq!(heights(2), heights(2));
+        assert_ne!(heights(2), heights(8));
+    }
+
+    fn terrain_samples(generator: &NoiseGenerator, position: Point3<isize>) -> Vec<f64> {
+        let map = generator.terrain_noise_map(position);
+        itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE)
+            .map(|(z, x)| map.get_value(x, z))
+            .collect()
+    }
+
+    fn noise_generator(warp_strength: f64) -> NoiseGenerator {
+        let params = TerrainParams {
+            warp_strength,
+            ..TerrainParams::default()
+        };
+        NoiseGenerator::new(42, params, &[], DecorationSettings::default())
+    }
+
+    #[test]
+    fn samples_the_plain_noise_without_warping() {
+        let generator = noise_generator(0.0);
+        let position = Point3::new(-2, 0, 3);
+
+        let map = noise_map(
+            generator.terrain_noise.as_ref(),
+            position,
+            TERRAIN_NOISE_SCALE,
+            TERRAIN_NOISE_OFFSET,
+        );
+        let unwarped: Vec<_> = itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE)
+            .map(|(z, x)| map.get_value(x, z))
+            .collect();
+        assert_eq!(terrain_samples(&generator, position), unwarped);
+    }
+
+    #[test]
+    fn warps_the_same_way_every_time() {
+        let position = Point3::new(-2, 0, 3);
+        let warped = terrain_samples(&noise_generator(8.0), position);
+
+        assert_eq!(terrain_samples(&noise_generator(8.0), position), warped);
+        assert_ne!(terrain_samples(&noise_generator(0.0), position), warped);
+    }
+
+    /// Returns the positions of the logs in a column of chunks.
+    fn logs(column: &[Box<Chunk>]) -> Vec<(usize, usize, usize, usize)> {
+        column
+            .iter()
+            .enumerate()
+            .flat_map(|(chunk_y, chunk)| {
+                itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE)
+                    .filter(move |&(y, z, x)| {
+                        chunk.blocks[y][z][x]
+                            .is_some_and(|block| block.block_type == BlockType::OakLog)
+                    })
+                    .map(move |(y, z, x)| (chunk_y, y, z, x))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn plants_trees_by_the_density() {
+        let column = |enabled, tree_density| {
+            let settings = WorldSettings {
+                decorations: DecorationSettings {
+                    enabled,
+                    tree_density,
+                },
+                ..WorldSettings::default()
+            };
+            generate_column(from_settings(&settings).as_ref(), 3, -2)
+        };
+
+        assert!(logs(&column(true, 0.0)).is_empty());
+        assert!(logs(&column(false, 20.0)).is_empty());
+
+        // The same seed and density plant the same trees
+        let trees = logs(&column(true, 5.0));
+        assert!(!trees.is_empty());
+        assert_eq!(trees, logs(&column(true, 5.0)));
+    }
+
+    #[test]
+    fn layers_the_terrain_by_the_palette() {
+        let layer = |block, thickness| FlatLayer { block, thickness };
+        let settings = WorldSettings {
+            terrain_palette: vec![
+                layer(BlockType::Grass, 1),
+                layer(BlockType::Sand, 4),
+                layer(BlockType::Dirt, 2),
+            ],
+            decorations: DecorationSettings {
+                enabled: false,
+                ..DecorationSettings::default()
+            },
+            ..WorldSettings::default()
+        };
+        let column = generate_column(from_settings(&settings).as_ref(), 3, -2);
+        let heights = surface_heights(&column);
+
+        let mut expected = vec![BlockType::Grass];
+        expected.extend([BlockType::Sand; 4].iter());
+        expected.extend([BlockType::Dirt; 2].iter());
+        for (z, x) in itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE) {
+            let blocks: Vec<_> = (0..=heights[z][x])
+                .rev()
+                .map(|y| {
+                    let chunk = &column[(y / CHUNK_ISIZE) as usize];
+                    chunk.blocks[(y % CHUNK_ISIZE) as usize][z][x]
+                        .unwrap()
+                        .block_type
+                })
+                .collect();
+
+            // From the surface down: the palette, stone below that, and the bedrock floor
+            let (bedrock, rest) = blocks.split_last().unwrap();
+            assert_eq!(rest[..expected.len()], expected[..]);
+            assert!(rest[expected.len()..]
+                .iter()
+                .all(|&block_type| block_type == BlockType::Stone));
+            assert_eq!(*bedrock, BlockType::Bedrock);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+use cgmath::Point3;
+
+use crate::world::block::Block;
+
+/// A block that was changed, and what it was before.
+#[derive(Debug, Clone, Copy)]
+pub struct BlockEdit {
+    pub position: Point3<isize>,
+    pub old: Option<Block>,
+}
+
+/// The most recent edits to the world, so they can be undone. Edits are recorded one block at a
+/// time, and grouped into a single undo step with `commit`, so a line of blocks placed by
+/// dragging is undone all at once.
+pub struct EditHistory {
+    steps: VecDeque<Vec<BlockEdit>>,
+    /// The edits recorded since the last `commit`.
+    open: Vec<BlockEdit>,
+    /// The most steps kept. The oldest ones are forgotten first.
+    max_steps: usize,
+}
+
+impl EditHistory {
+    pub fn new(max_steps: usize) -> Self {
+        Self {
+            steps: VecDeque::new(),
+            open: Vec::new(),
+            max_steps,
+        }
+    }
+
+    pub fn record(&mut self, edit: BlockEdit) {
+        if self.max_steps > 0 {
+            self.open.push(edit);
+        }
+    }
+
+    /// Turns the edits recorded since the last commit into a single undo step.
+    pub fn commit(&mut self) {
+        if self.open.is_empty() {
+            return;
+        }
+
+        self.steps.push_back(std::mem::take(&mut self.open));
+        while self.steps.len() > self.max_steps {
+            self.steps.pop_front();
+        }
+    }
+
+    /// Takes the most recent step, with its edits in the order they should be undone in.
+    pub fn pop(&mut self) -> Option<impl Iterator<Item = BlockEdit>> {
+        self.commit();
+        self.steps.pop_back().map(|edits| edits.into_iter().rev())
+    }
+
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.open.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{block::BlockType, block_view::test_world::TestWorld, BlockView};
+
+    /// Changes a block the way `World` does, recording what it was before.
+    fn edit(
+        world: &mut TestWorld,
+        history: &mut EditHistory,
+        position: Point3<isize>,
+        block: Option<BlockType>,
+    ) {
+        let old = world.get_block(position).copied();
+        history.record(BlockEdit { position, old });
+        match block {
+            Some(block_type) => world.set_block(position, block_type),
+            None => {
+                world.blocks.remove(&position);
+            }
+        }
+    }
+
+    /// Undoes the most recent step the way `World` does. Returns how many blocks changed back.
+    fn undo(world: &mut TestWorld, history: &mut EditHistory) -> usize {
+        let edits = match history.pop() {
+            Some(edits) => edits,
+            None => return 0,
+        };
+        edits
+            .map(|edit| match edit.old {
+                Some(block) => world.blocks.insert(edit.position, block),
+                None => world.blocks.remove(&edit.position),
+            })
+            .count()
+    }
+
+    #[test]
+    fn brings_back_a_broken_block() {
+        let mut world = TestWorld::default();
+        let mut history = EditHistory::new(16);
+        let position = Point3::new(-1, 64, 3);
+        world.set_block(position, BlockType::Stone);
+        let original = world.get_block(position).copied();
+
+        edit(&mut world, &mut history, position, None);
+        history.commit();
+        assert_eq!(world.get_block(position), None);
+
+        assert_eq!(undo(&mut world, &mut history), 1);
+        assert_eq!(world.get_block(position).copied(), original);
+        assert_eq!(undo(&mut world, &mut history), 0);
+    }
+
+    #[test]
+    fn undoes_a_step_all_at_once() {
+        let mut world = TestWorld::default();
+        let mut history = EditHistory::new(16);
+        let position = Point3::new(0, 64, 0);
+
+        // A drag across a chunk border, which also goes over the same block twice
+        edit(&mut world, &mut history, position, Some(BlockType::Dirt));
+        for x in -2..=2 {
+            edit(
+                &mut world,
+                &mut history,
+                Point3::new(x, 64, 0),
+                Some(BlockType::Stone),
+            );
+        }
+        history.commit();
+
+        assert_eq!(undo(&mut world, &mut history), 6);
+        assert!(world.blocks.is_empty());
+    }
+
+    #[test]
+    fn forgets_the_oldest_steps() {
+        let mut world = TestWorld::default();
+        let mut history = EditHistory::new(2);
+        for y in 0..3 {
+            edit(
+                &mut world,
+                &mut history,
+                Point3::new(0, y, 0),
+                Some(BlockType::Stone),
+            );
+            history.commit();
+        }
+
+        assert_eq!(undo(&mut world, &mut history), 1);
+        assert_eq!(undo(&mut world, &mut history), 1);
+        assert_eq!(undo(&mut world, &mut history), 0);
+        // The first block was placed too long ago to undo
+        assert_eq!(world.blocks.len(), 1);
+        assert!(world.get_block(Point3::new(0, 0, 0)).is_some());
+    }
+}
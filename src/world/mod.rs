@@ -1,65 +1,313 @@
+pub mod animation;
+pub mod biome;
 pub mod block;
+pub mod block_entity;
+pub mod block_light;
+pub mod block_view;
+pub mod celestial_bodies;
 pub mod chunk;
+pub mod chunk_arena;
+pub mod clouds;
+pub mod cooldown;
+pub mod export;
 pub mod face_flags;
+pub mod flatten;
+pub mod generator;
+pub mod history;
 pub mod npc;
+pub mod particles;
 pub mod quad;
+pub mod random;
+pub mod raycast;
+pub mod render_settings;
+pub mod sky;
+pub mod spawning;
+pub mod streaming;
+pub mod upload_queue;
 
 use std::{
     borrow::Cow,
     collections::VecDeque,
+    path::Path,
     time::{Duration, Instant},
 };
 
 use crate::{
+    aabb::Aabb,
     camera::Camera,
+    error::{self, MinecrabError},
+    geometry_buffers::GeometryBuffers,
     render_context::RenderContext,
-    texture::Texture,
+    settings::{LightingMode, Settings, SpawnSettings, WorldSettings},
+    shader,
+    texture::{Texture, DESTROY_STAGES, DESTROY_STAGE_TEXTURE},
     time::Time,
     vertex::{BlockVertex, Vertex},
     view::View,
     world::{
-        block::{Block, BlockType},
-        chunk::{Chunk, CHUNK_ISIZE, CHUNK_SIZE},
-        npc::Npc,
+        biome::BiomeMap,
+        block::{Block, BlockType, Facing},
+        block_entity::BlockEntity,
+        block_light::BlockLight,
+        block_view::BlockView,
+        celestial_bodies::CelestialBodies,
+        chunk::{
+            world_to_chunk, world_to_local, Chunk, ChunkGeometry, CHUNK_ISIZE, CHUNK_SIZE,
+            MAX_EXPECTED_VERTICES, MAX_LOD,
+        },
+        chunk_arena::ChunkArena,
+        clouds::Clouds,
+        cooldown::ActionCooldown,
+        export::ExportHeader,
+        face_flags::FACE_ALL,
+        generator::WorldGenerator,
+        history::{BlockEdit, EditHistory},
+        npc::{Npc, NpcKind, NpcState},
+        particles::Particles,
+        quad::{Quad, FACES},
+        random::ChunkRng,
+        render_settings::RenderSettings,
+        sky::Sky,
+        upload_queue::UploadQueue,
     },
 };
-use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
-use fxhash::FxHashMap;
+use cgmath::{EuclideanSpace, MetricSpace, Point3, Vector3};
+use fxhash::{FxHashMap, FxHashSet};
 use wgpu::{
     util::{BufferInitDescriptor, DeviceExt},
-    BindGroup, Buffer, CommandEncoder, RenderPipeline,
+    BindGroup, Buffer, BufferUsages, CommandEncoder, PipelineLayout, RenderPipeline, ShaderModule,
 };
-use cgmath::num_traits::Inv;
 
 pub struct World {
     pub render_pipeline: RenderPipeline,
+    /// Like the main pipeline, but draws both sides of every face. Used instead of it when
+    /// `cull_back_faces` is off, which shows faces with the wrong winding inside out.
+    unculled_pipeline: RenderPipeline,
+    /// Blends the faces of transparent blocks over the rest of the world.
+    pub transparent_pipeline: RenderPipeline,
+    /// Draws the cracks over the block that's being broken, blended on top of the chunks.
+    pub crack_pipeline: RenderPipeline,
+    /// Draws the edges of the chunks' triangles over them, if the device can draw lines.
+    pub wireframe_pipeline: Option<RenderPipeline>,
+    /// Kept around to rebuild the pipelines with when the shader is reloaded.
+    pipeline_layout: PipelineLayout,
     pub depth_texture: Texture,
+    /// What the frame is cleared to before drawing the chunks.
+    pub sky_color: wgpu::Color,
+    /// Draws a gradient over the cleared frame, if enabled in the settings.
+    pub sky: Option<Sky>,
+    /// Draws the sun and the moon, where the time of day puts them.
+    celestial_bodies: CelestialBodies,
+    /// The layer of clouds, if enabled in the settings.
+    clouds: Option<Clouds>,
 
     pub time: Time,
     pub time_buffer: Buffer,
     pub time_bind_group: BindGroup,
-
-    pub npc: Npc,
+    /// Stops the time of day from moving. Animations keep playing.
+    pub time_frozen: bool,
+    /// The highlight and water colors and the debug view, for the world shader.
+    render_settings: RenderSettings,
+
+    pub npcs: Vec<Npc>,
+    /// Keeps hostile NPCs from spawning.
+    pub peaceful: bool,
+    /// The most NPCs that can be in the world at once.
+    pub max_entities: usize,
+    spawn_settings: SpawnSettings,
+    /// How long it's been since the last try at spawning an NPC.
+    spawn_timer: Duration,
+    spawn_rng: ChunkRng,
+    /// A box standing in for the player, drawn in third person.
+    player_model: Option<GeometryBuffers<u16>>,
+    particles: Particles,
 
     pub chunks: FxHashMap<Point3<isize>, Chunk>,
+    /// Runs the parallel parts of chunk work, so it doesn't take up every core.
+    thread_pool: rayon::ThreadPool,
     pub chunk_database: sled::Db,
+    pub generator: Box<dyn WorldGenerator>,
+    /// Decides the biome tints of the chunks, for the same seed as `generator`.
+    biome_map: BiomeMap,
     pub chunk_save_queue: VecDeque<(Point3<isize>, bool)>,
     pub chunk_load_queue: VecDeque<Point3<isize>>,
-    pub chunk_generate_queue: VecDeque<Point3<isize>>,
     pub chunk_occlusion_position: Option<Point3<isize>>,
     pub chunks_visible: Option<Vec<Point3<isize>>>,
+    /// Holds the opaque geometry of the chunks, so they can all be drawn at once, if enabled in
+    /// the settings and supported by the device.
+    chunk_arena: Option<ChunkArena>,
+    /// How many draw calls the chunks took in the last frame.
+    pub chunk_draw_calls: usize,
+    /// Loads the chunks in front of the camera before the ones behind it.
+    load_in_view_first: bool,
+    /// When the last warning about a chunk with too many vertices was printed.
+    last_vertex_warning: Option<Instant>,
+    /// How many chunks around the camera are loaded in every horizontal direction.
+    pub render_distance: isize,
+    /// How many chunks away from the camera the chunks start being meshed with less detail, or 0
+    /// to always mesh them at full detail. See `chunk_lod`.
+    lod_distance: isize,
+    /// The chunk the streaming center was in when the levels of detail were last checked.
+    lod_center: Option<Point3<isize>>,
+    /// Chunks that might have moved into another level of detail, waiting to be rebuilt.
+    lod_queue: VecDeque<Point3<isize>>,
+    /// The light given off by blocks like glowstone.
+    block_light: BlockLight,
+    /// The recent block edits, for `undo`.
+    pub history: EditHistory,
 
     pub highlighted: Option<(Point3<isize>, Vector3<i32>)>,
+    /// The highlighted face, drawn over the chunks so they don't have to be rebuilt when the
+    /// highlight moves.
+    highlight_buffers: Option<GeometryBuffers<u16>>,
+    /// Tints every block of this type, to see where they are. Changed with
+    /// `set_highlighted_type`, which rebuilds the chunks.
+    pub highlighted_type: Option<BlockType>,
+
+    /// Stops chunks from being loaded, generated, saved and unloaded, and keeps the set of visible
+    /// chunks as it is. Used to measure the rendering performance of a static scene.
+    pub streaming_frozen: bool,
+    /// Draws the edges of the chunk meshes over the filled geometry, to inspect the meshing.
+    pub wireframe_overlay: bool,
+    /// Skips drawing the back faces of the opaque geometry. Turned off to debug the winding of
+    /// faces.
+    pub cull_back_faces: bool,
+    /// What the chunks are drawn with: their regular color, or something to debug with. Set with
+    /// `set_debug_view`.
+    pub debug_view: DebugView,
+    /// The most chunks loaded, generated or saved per update, on top of the time budget.
+    pub max_chunk_updates: usize,
+    /// Chunk geometry that's been built, but is waiting for its turn to be uploaded.
+    chunk_upload_queue: UploadQueue<Point3<isize>, ChunkGeometry>,
+    /// The most bytes of chunk geometry uploaded per update, on top of the first chunk.
+    pub max_upload_bytes: usize,
+    /// How the chunks are lit. Changed with `set_lighting`, which rebuilds them.
+    pub lighting: LightingMode,
+
+    /// The minimum time between breaking or placing blocks in survival.
+    pub action_cooldown: ActionCooldown,
+    /// The most cells a raycast from the crosshair steps through.
+    pub raycast_max_steps: usize,
+
+    /// The block that's being broken, and how far along breaking it is (from 0 to 1).
+    pub breaking: Option<(Point3<isize>, f32)>,
+    /// The block and destroy stage `crack_buffers` were built for.
+    crack_stage: Option<(Point3<isize>, usize)>,
+    crack_buffers: Option<GeometryBuffers<u16>>,
 
     pub unload_timer: Duration,
+    /// How many times chunk geometry was built, for performance statistics. Reset by whoever
+    /// reads it.
+    pub chunk_rebuilds: usize,
+}
+
+/// Describes the block under the crosshair, for debugging.
+#[derive(Debug, Clone, Copy)]
+pub struct TargetedBlockInfo {
+    pub block_type: BlockType,
+    pub position: Point3<isize>,
+    /// The chunk the block is in.
+    pub chunk: Point3<isize>,
+    /// The normal of the face the crosshair is on.
+    pub face: Vector3<i32>,
+    /// Where a block placed against the face would end up.
+    pub placement: Point3<isize>,
 }
 
-pub const RENDER_DISTANCE: isize = 8;
+/// What the world shader draws instead of the regular colors, to debug meshing and lighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DebugView {
+    Color,
+    Normals,
+    TextureCoordinates,
+    TextureIds,
+    Depth,
+}
+
+impl DebugView {
+    /// The view after this one, going back to the regular colors after the last one.
+    pub fn next(self) -> Self {
+        match self {
+            Self::Color => Self::Normals,
+            Self::Normals => Self::TextureCoordinates,
+            Self::TextureCoordinates => Self::TextureIds,
+            Self::TextureIds => Self::Depth,
+            Self::Depth => Self::Color,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Color => "color",
+            Self::Normals => "normals",
+            Self::TextureCoordinates => "texture coordinates",
+            Self::TextureIds => "texture ids",
+            Self::Depth => "depth",
+        }
+    }
+}
+
+pub const DEFAULT_RENDER_DISTANCE: usize = 8;
+
 pub const WORLD_HEIGHT: isize = 16 * 16 / CHUNK_ISIZE;
+const _: () = assert!(
+    16 * 16 % CHUNK_ISIZE == 0,
+    "CHUNK_SIZE has to divide the world height of 256 blocks"
+);
+
+/// How long the mouse button has to be held down to break a block when not in creative, per
+/// unit of `BlockType::hardness`.
+const BREAK_TIME: Duration = Duration::from_secs(1);
+
+/// Deletes every chunk in `chunk_database`, and returns the generator and the biome map of a new
+/// world generated with `seed` and the rest of `settings`.
+fn new_world(
+    chunk_database: &sled::Db,
+    settings: &WorldSettings,
+    seed: u32,
+) -> error::Result<(Box<dyn WorldGenerator>, BiomeMap)> {
+    chunk_database.clear()?;
+    chunk_database.flush()?;
+
+    let generator = generator::from_settings(&WorldSettings {
+        seed,
+        ..settings.clone()
+    });
+    Ok((generator, BiomeMap::new(seed)))
+}
+
+/// Returns how far along breaking a block of `block_type` is after another `dt` of breaking it,
+/// starting from `progress`. It's broken at 1. In creative, every block breaks right away, however
+/// hard it is.
+fn break_progress(block_type: BlockType, progress: f32, dt: Duration, creative: bool) -> f32 {
+    if creative {
+        return 1.0;
+    }
+    progress + dt.as_secs_f32() / (BREAK_TIME.as_secs_f32() * block_type.hardness())
+}
+
+/// How long a frame can spend on loading, generating and saving chunks, including the time it
+/// took to render the last frame.
+const CHUNK_UPDATE_BUDGET: Duration = Duration::from_millis(15);
+
+/// The most chunks `World::warmup` loads at once, so a large radius can't freeze the game for
+/// long.
+const MAX_WARMUP_CHUNKS: usize = 128;
 
 const DEBUG_IO: bool = false;
 
+/// How long to wait after warning about a chunk with too many vertices before warning again, so
+/// a meshing bug doesn't flood the output.
+const VERTEX_WARNING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The key the NPCs are stored under in the chunk database. Chunks are stored under their
+/// coordinates, so this can't clash with them.
+const ENTITIES_KEY: &str = "entities";
+
 impl World {
+    /// Advances the time, the NPCs and the particles, and loads, saves and unloads chunks around
+    /// `streaming_center`. That's where the world is seen from, which isn't necessarily where the
+    /// camera is, like in third person.
     #[allow(clippy::collapsible_else_if)]
     pub fn update(
         &mut self,
@@ -67,34 +315,38 @@ impl World {
         dt: Duration,
         render_time: Duration,
         camera: &Camera,
+        streaming_center: Point3<f32>,
     ) {
         self.time.time += dt.as_secs_f32();
+        if !self.time_frozen {
+            self.time.advance_day(dt.as_secs_f32());
+        }
         render_context
             .queue
             .write_buffer(&self.time_buffer, 0, bytemuck::cast_slice(&[self.time]));
 
         self.update_highlight(render_context, camera);
-
-        // Queue up new chunks for loading, if necessary
-        let camera_pos: Point3<isize> = camera.position.cast().unwrap();
-        let camera_chunk: Point3<isize> = camera_pos.map(|n| n.div_euclid(CHUNK_ISIZE));
-        let mut load_queue = Vec::new();
-        for (x, y, z) in itertools::iproduct!(
-            -RENDER_DISTANCE..RENDER_DISTANCE,
-            0..WORLD_HEIGHT,
-            -RENDER_DISTANCE..RENDER_DISTANCE
-        ) {
-            let point: Point3<isize> = Point3::new(x + camera_chunk.x, y, z + camera_chunk.z);
-            if !self.chunks.contains_key(&point) && !self.chunk_load_queue.contains(&point) {
-                load_queue.push(point);
-            }
+        for npc in &mut self.npcs {
+            npc.update(render_context, dt);
         }
+        self.particles.update(render_context, dt, camera);
+        self.update_spawning(render_context, dt, camera);
+        self.upload_chunk_geometry(render_context, self.max_upload_bytes);
 
-        // TODO Sort based on where camera is looking
-        load_queue.sort_unstable_by_key(|f| {
-            (f.x * CHUNK_ISIZE - camera_pos.x).abs() + (f.y * CHUNK_ISIZE - camera_pos.y).abs()
-        });
+        if self.streaming_frozen {
+            return;
+        }
 
+        // Queue up new chunks for loading, if necessary
+        let center = streaming_center.map(|x| x.floor() as isize);
+        let center_chunk = world_to_chunk(center);
+        self.update_lod_center(center_chunk);
+        let mut load_queue =
+            streaming::chunks_around(center_chunk, self.render_distance, |point| {
+                !self.chunks.contains_key(&point) && !self.chunk_load_queue.contains(&point)
+            });
+        let direction = Some(camera.direction()).filter(|_| self.load_in_view_first);
+        streaming::sort_load_queue(&mut load_queue, streaming_center, direction);
         self.chunk_load_queue.extend(load_queue);
 
         // Unload chunks that are far away
@@ -102,87 +354,98 @@ impl World {
         if self.unload_timer.as_secs() >= 10 {
             self.unload_timer = Duration::ZERO;
 
-            let camera_pos = camera.position.to_vec();
-            let unload_distance = (RENDER_DISTANCE * CHUNK_ISIZE) as f32 * 1.5;
-
-            let mut unload_chunks = Vec::new();
-            for point in self.chunks.keys() {
-                let pos: Point3<f32> = (point * CHUNK_ISIZE).cast().unwrap();
-                if (pos.x - camera_pos.x).abs() > unload_distance
-                    || (pos.z - camera_pos.z).abs() > unload_distance
-                {
-                    unload_chunks.push(*point);
-                }
-            }
+            let unload_chunks = streaming::chunks_to_unload(
+                self.chunks.keys().copied(),
+                streaming_center,
+                self.render_distance,
+            );
             for point in unload_chunks {
                 self.enqueue_chunk_save(point, true);
             }
         }
 
         let start = Instant::now() - render_time;
-        let mut chunk_updates = 0;
-        while chunk_updates == 0 || start.elapsed() < Duration::from_millis(15) {
-            if let Some(position) = self.chunk_load_queue.pop_front() {
-                let chunk = self.chunks.entry(position).or_default();
-                match chunk.load(position, &self.chunk_database) {
-                    Err(error) => {
-                        eprintln!("Failed to load/generate chunk {:?}: {:?}", position, error)
-                    }
-                    Ok(true) => {
-                        self.update_chunk_geometry(render_context, position);
-                        self.enqueue_chunk_save(position, false);
-                        if DEBUG_IO {
-                            println!("Generated chunk {:?}", position);
+        let chunk_updates = streaming::run_chunk_updates(
+            self.max_chunk_updates,
+            CHUNK_UPDATE_BUDGET,
+            || start.elapsed(),
+            || self.update_next_chunk(render_context),
+        );
+
+        if chunk_updates > 0 {
+            self.chunk_occlusion_position = None;
+        }
+    }
+
+    /// Takes the next chunk off the streaming queues and loads it, rebuilds it at its new level
+    /// of detail, or saves it. Returns whether there was anything to do.
+    fn update_next_chunk(&mut self, render_context: &RenderContext) -> bool {
+        if let Some(position) = self.chunk_load_queue.pop_front() {
+            self.load_chunk(render_context, position);
+        } else if let Some(position) = self.lod_queue.pop_front() {
+            let outdated = self
+                .chunks
+                .get(&position)
+                .is_some_and(|chunk| chunk.lod != self.chunk_lod(position));
+            if outdated {
+                self.enqueue_chunk_geometry(render_context, position);
+            }
+        } else if let Some((position, unload)) = self.chunk_save_queue.pop_front() {
+            if let Some(chunk) = self.chunks.get(&position) {
+                if let Err(err) = chunk.save(position, &self.chunk_database) {
+                    eprintln!("Failed to save chunk {:?}: {:?}", position, err);
+                } else {
+                    if unload {
+                        let chunk = self.chunks.remove(&position).unwrap();
+                        if let (Some(arena), Some(slot)) = (&mut self.chunk_arena, chunk.arena_slot)
+                        {
+                            arena.free(slot);
                         }
-                    }
-                    Ok(false) => {
-                        self.update_chunk_geometry(render_context, position);
+                        self.block_light.remove_chunk(position);
+
                         if DEBUG_IO {
-                            println!("Loaded chunk {:?}", position);
+                            println!("Saved and unloaded chunk {:?}", position);
                         }
-                    }
-                }
-            } else if let Some((position, unload)) = self.chunk_save_queue.pop_front() {
-                if let Some(chunk) = self.chunks.get(&position) {
-                    if let Err(err) = chunk.save(position, &self.chunk_database) {
-                        eprintln!("Failed to save chunk {:?}: {:?}", position, err);
                     } else {
-                        if unload {
-                            self.chunks.remove(&position);
-
-                            if DEBUG_IO {
-                                println!("Saved and unloaded chunk {:?}", position);
-                            }
-                        } else {
-                            if DEBUG_IO {
-                                println!("Saved chunk {:?}", position);
-                            }
+                        if DEBUG_IO {
+                            println!("Saved chunk {:?}", position);
                         }
                     }
-                } else {
-                    eprintln!("Tried to save unloaded chunk {:?}", position);
                 }
             } else {
-                break;
+                eprintln!("Tried to save unloaded chunk {:?}", position);
             }
-
-            chunk_updates += 1;
-        }
-
-        if chunk_updates > 0 {
-            self.chunk_occlusion_position = None;
+        } else {
+            return false;
         }
+        true
     }
 
-    pub fn render<'a>(
-        &'a mut self,
+    pub fn render(
+        &mut self,
         render_context: &RenderContext,
         render_encoder: &mut CommandEncoder,
         texture_view: &wgpu::TextureView,
         view: &View,
     ) -> usize {
         // TODO Move this to update
-        self.update_occlusion(view);
+        if !self.streaming_frozen || self.chunks_visible.is_none() {
+            self.update_occlusion(view);
+        }
+
+        // The chunks in the arena are drawn all at once, and the rest one by one
+        let visible = self.chunks_visible.as_ref().unwrap();
+        if let Some(arena) = &mut self.chunk_arena {
+            let chunks = &self.chunks;
+            arena.prepare(
+                render_context,
+                visible
+                    .iter()
+                    .filter(|position| chunks[position].is_visible(*position * CHUNK_ISIZE, view))
+                    .filter_map(|position| chunks[position].arena_slot.as_ref()),
+            );
+        }
+        let mut draw_calls = 0;
 
         let mut render_pass = render_encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
             label: Some("render_pass"),
@@ -190,51 +453,328 @@ impl World {
                 view: texture_view,
                 resolve_target: None,
                 ops: wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(wgpu::Color {
-                        r: 0.502,
-                        g: 0.663,
-                        b: 0.965,
-                        a: 1.0,
-                    }),
+                    load: wgpu::LoadOp::Clear(self.sky_color),
                     store: true,
                 },
             }],
             depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
                 view: &self.depth_texture.view,
                 depth_ops: Some(wgpu::Operations {
-                    load: wgpu::LoadOp::Clear(1.0),
+                    // The depth is reversed, so 0 is the far plane
+                    load: wgpu::LoadOp::Clear(0.0),
                     store: true,
                 }),
                 stencil_ops: None,
             }),
         });
-        render_pass.set_pipeline(&self.render_pipeline);
+
+        let mut triangle_count = 0;
+        if let Some(sky) = &self.sky {
+            triangle_count += sky.render(&mut render_pass, view);
+        }
+        triangle_count +=
+            self.celestial_bodies
+                .render(&mut render_pass, view, &self.time_bind_group);
+
+        render_pass.set_pipeline(if self.cull_back_faces {
+            &self.render_pipeline
+        } else {
+            &self.unculled_pipeline
+        });
 
         let texture_manager = render_context.texture_manager.as_ref().unwrap();
         render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
         render_pass.set_bind_group(1, &view.bind_group, &[]);
         render_pass.set_bind_group(2, &self.time_bind_group, &[]);
+        render_pass.set_bind_group(3, &self.render_settings.bind_group, &[]);
 
-        let visible = self.chunks_visible.as_ref().unwrap();
-        let mut triangle_count = 0;
+        if let Some(arena) = &self.chunk_arena {
+            let triangles = arena.render(&mut render_pass);
+            draw_calls += (triangles > 0) as usize;
+            triangle_count += triangles;
+        }
         for position in visible {
-            let chunk = self.chunks.get(position).unwrap();
-            triangle_count += chunk.render(&mut render_pass, position, view);
+            let triangles = self.chunks[position].render(&mut render_pass, position, view);
+            draw_calls += (triangles > 0) as usize;
+            triangle_count += triangles;
+        }
+        for npc in &self.npcs {
+            triangle_count += npc.render(&mut render_pass);
+        }
+        if let Some(buffers) = &self.player_model {
+            buffers.apply_buffers(&mut render_pass);
+            triangle_count += buffers.draw_indexed(&mut render_pass);
+        }
+        triangle_count += self.particles.render(&mut render_pass);
+
+        // The clouds are blended over the opaque geometry, and have their own bind groups, so the
+        // world's have to be set again after them
+        if let Some(clouds) = &self.clouds {
+            triangle_count += clouds.render(&mut render_pass, view, &self.time_bind_group);
+            render_pass.set_bind_group(0, texture_manager.bind_group.as_ref().unwrap(), &[]);
+            render_pass.set_bind_group(1, &view.bind_group, &[]);
+            render_pass.set_bind_group(2, &self.time_bind_group, &[]);
+            render_pass.set_bind_group(3, &self.render_settings.bind_group, &[]);
+        }
+
+        // Transparent chunks are drawn back to front, so the ones in front blend over the ones
+        // behind them
+        let camera_position = view.camera.position;
+        let distance = |position: &Point3<isize>| {
+            let center = (position * CHUNK_ISIZE).cast::<f32>().unwrap()
+                + Vector3::new(1.0, 1.0, 1.0) * (CHUNK_SIZE as f32 / 2.0);
+            center.distance2(camera_position)
+        };
+        let mut transparent: Vec<&Point3<isize>> = visible
+            .iter()
+            .filter(|position| self.chunks[position].transparent_buffers.is_some())
+            .collect();
+        transparent.sort_unstable_by(|a, b| distance(b).total_cmp(&distance(a)));
+
+        render_pass.set_pipeline(&self.transparent_pipeline);
+        for position in transparent {
+            let triangles =
+                self.chunks[position].render_transparent(&mut render_pass, position, view);
+            draw_calls += (triangles > 0) as usize;
+            triangle_count += triangles;
         }
-        triangle_count += self.npc.render(&mut render_pass);
+        self.chunk_draw_calls = draw_calls;
+
+        if let (true, Some(pipeline)) = (self.wireframe_overlay, &self.wireframe_pipeline) {
+            render_pass.set_pipeline(pipeline);
+            if let Some(arena) = &self.chunk_arena {
+                arena.render(&mut render_pass);
+            }
+            for position in visible {
+                self.chunks[position].render(&mut render_pass, position, view);
+                self.chunks[position].render_transparent(&mut render_pass, position, view);
+            }
+        }
+
+        // The highlight is blended over the face just like the cracks are
+        render_pass.set_pipeline(&self.crack_pipeline);
+        if let Some(buffers) = &self.highlight_buffers {
+            buffers.apply_buffers(&mut render_pass);
+            triangle_count += buffers.draw_indexed(&mut render_pass);
+        }
+        if let Some(buffers) = &self.crack_buffers {
+            buffers.apply_buffers(&mut render_pass);
+            triangle_count += buffers.draw_indexed(&mut render_pass);
+        }
+
         triangle_count
     }
 
-    pub fn new(render_context: &RenderContext, view: &View) -> Self {
+    fn create_render_pipeline(
+        render_context: &RenderContext,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+        blend: wgpu::BlendState,
+        depth_write_enabled: bool,
+        depth_compare: wgpu::CompareFunction,
+        cull_mode: Option<wgpu::Face>,
+    ) -> RenderPipeline {
+        render_context
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Render Pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "main",
+                    buffers: &[BlockVertex::descriptor()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "main",
+                    targets: &[wgpu::ColorTargetState {
+                        format: render_context.format,
+                        blend: Some(blend),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode,
+                    polygon_mode: wgpu::PolygonMode::Fill,
+                    ..wgpu::PrimitiveState::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+            })
+    }
+
+    /// Like the main pipeline, but draws the outlines of triangles in a single color. A depth bias
+    /// pulls the lines towards the camera, so they aren't hidden by the faces they're drawn on.
+    fn create_wireframe_pipeline(
+        render_context: &RenderContext,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+    ) -> RenderPipeline {
+        render_context
+            .device
+            .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("Wireframe pipeline"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: shader,
+                    entry_point: "main",
+                    buffers: &[BlockVertex::descriptor()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: shader,
+                    entry_point: "wireframe",
+                    targets: &[wgpu::ColorTargetState {
+                        format: render_context.format,
+                        blend: Some(wgpu::BlendState::REPLACE),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    }],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    cull_mode: Some(wgpu::Face::Back),
+                    polygon_mode: wgpu::PolygonMode::Line,
+                    ..wgpu::PrimitiveState::default()
+                },
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: Texture::DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::GreaterEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState {
+                        constant: 2,
+                        slope_scale: 1.0,
+                        clamp: 0.0,
+                    },
+                }),
+                multisample: wgpu::MultisampleState::default(),
+            })
+    }
+
+    /// Builds the main, unculled, transparent, crack and wireframe pipelines, which all share a
+    /// shader.
+    fn create_pipelines(
+        render_context: &RenderContext,
+        layout: &PipelineLayout,
+        shader: &ShaderModule,
+    ) -> (
+        RenderPipeline,
+        RenderPipeline,
+        RenderPipeline,
+        RenderPipeline,
+        Option<RenderPipeline>,
+    ) {
+        let render_pipeline = Self::create_render_pipeline(
+            render_context,
+            layout,
+            shader,
+            wgpu::BlendState::REPLACE,
+            true,
+            wgpu::CompareFunction::Greater,
+            Some(wgpu::Face::Back),
+        );
+        let unculled_pipeline = Self::create_render_pipeline(
+            render_context,
+            layout,
+            shader,
+            wgpu::BlendState::REPLACE,
+            true,
+            wgpu::CompareFunction::Greater,
+            None,
+        );
+
+        // Transparent faces don't write to the depth buffer, so the ones behind them still show
+        let transparent_pipeline = Self::create_render_pipeline(
+            render_context,
+            layout,
+            shader,
+            wgpu::BlendState::ALPHA_BLENDING,
+            false,
+            wgpu::CompareFunction::Greater,
+            Some(wgpu::Face::Back),
+        );
+
+        // The cracks are drawn exactly over the block's faces, so they need to pass the depth test
+        // where the block itself already wrote to it
+        let crack_pipeline = Self::create_render_pipeline(
+            render_context,
+            layout,
+            shader,
+            wgpu::BlendState::ALPHA_BLENDING,
+            false,
+            wgpu::CompareFunction::GreaterEqual,
+            Some(wgpu::Face::Back),
+        );
+
+        let wireframe_pipeline = render_context
+            .device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+            .then(|| Self::create_wireframe_pipeline(render_context, layout, shader));
+
+        (
+            render_pipeline,
+            unculled_pipeline,
+            transparent_pipeline,
+            crack_pipeline,
+            wireframe_pipeline,
+        )
+    }
+
+    /// Reads `world.wgsl` from disk and rebuilds the pipelines with it. If the shader doesn't
+    /// compile, the error is returned and the current pipelines are kept.
+    pub fn reload_shaders(&mut self, render_context: &RenderContext) -> error::Result<()> {
+        let shader = shader::load(render_context, "world.wgsl")?;
+        let (
+            render_pipeline,
+            unculled_pipeline,
+            transparent_pipeline,
+            crack_pipeline,
+            wireframe_pipeline,
+        ) = Self::create_pipelines(render_context, &self.pipeline_layout, &shader);
+        self.render_pipeline = render_pipeline;
+        self.unculled_pipeline = unculled_pipeline;
+        self.transparent_pipeline = transparent_pipeline;
+        self.crack_pipeline = crack_pipeline;
+        self.wireframe_pipeline = wireframe_pipeline;
+        Ok(())
+    }
+
+    /// Opens the chunk database in the directory at `path`, creating it if it doesn't exist yet.
+    /// It can only be opened once at a time, so a world created again has to be given the handle
+    /// of the one before it.
+    pub fn open_database(path: &Path) -> error::Result<sled::Db> {
+        std::fs::create_dir_all(path)
+            .map_err(sled::Error::Io)
+            .and_then(|_| {
+                sled::Config::new()
+                    .path(path)
+                    .mode(sled::Mode::HighThroughput)
+                    .use_compression(true)
+                    .open()
+            })
+            .map_err(|source| MinecrabError::OpenWorld {
+                path: path.display().to_string(),
+                source,
+            })
+    }
+
+    pub fn new(
+        render_context: &RenderContext,
+        view: &View,
+        settings: &Settings,
+        chunk_database: sled::Db,
+    ) -> Self {
         let chunks = FxHashMap::default();
-        let mut npc = Npc::new();
-        npc.load_geometry(render_context);
 
-        let chunk_database = sled::Config::new()
-            .path("chunks")
-            .mode(sled::Mode::HighThroughput)
-            .use_compression(true)
-            .open()
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(settings.world.generation_threads())
+            .thread_name(|i| format!("chunk worker {}", i))
+            .build()
             .unwrap();
 
         let time = Time::new();
@@ -275,6 +815,8 @@ impl World {
                 label: Some("time_bind_group"),
             });
 
+        let render_settings = RenderSettings::new(render_context, &settings.graphics);
+
         let texture_manager = render_context.texture_manager.as_ref().unwrap();
         let render_pipeline_layout =
             render_context
@@ -286,6 +828,7 @@ impl World {
                         &texture_manager.bind_group_layout,
                         &view.bind_group_layout,
                         &time_bind_group_layout,
+                        &render_settings.bind_group_layout,
                     ],
                 });
 
@@ -298,113 +841,299 @@ impl World {
             }),
         );
 
-        let render_pipeline =
-            render_context
-                .device
-                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-                    label: Some("Render Pipeline"),
-                    layout: Some(&render_pipeline_layout),
-                    vertex: wgpu::VertexState {
-                        module: &shader,
-                        entry_point: "main",
-                        buffers: &[BlockVertex::descriptor()],
-                    },
-                    fragment: Some(wgpu::FragmentState {
-                        module: &shader,
-                        entry_point: "main",
-                        targets: &[wgpu::ColorTargetState {
-                            format: render_context.format,
-                            blend: Some(wgpu::BlendState {
-                                alpha: wgpu::BlendComponent::REPLACE,
-                                color: wgpu::BlendComponent::REPLACE,
-                            }),
-                            write_mask: wgpu::ColorWrites::ALL,
-                        }],
-                    }),
-                    primitive: wgpu::PrimitiveState {
-                        cull_mode: Some(wgpu::Face::Back),
-                        polygon_mode: wgpu::PolygonMode::Fill,
-                        ..wgpu::PrimitiveState::default()
-                    },
-                    depth_stencil: Some(wgpu::DepthStencilState {
-                        format: Texture::DEPTH_FORMAT,
-                        depth_write_enabled: true,
-                        depth_compare: wgpu::CompareFunction::Less,
-                        stencil: wgpu::StencilState::default(),
-                        bias: wgpu::DepthBiasState::default(),
-                    }),
-                    multisample: wgpu::MultisampleState::default(),
-                });
+        let (
+            render_pipeline,
+            unculled_pipeline,
+            transparent_pipeline,
+            crack_pipeline,
+            wireframe_pipeline,
+        ) = Self::create_pipelines(render_context, &render_pipeline_layout, &shader);
+
+        let depth_texture =
+            Texture::create_depth_texture(render_context, render_context.size, "depth_texture");
+
+        let sky_color = settings.graphics.sky_color();
+        let sky = settings.graphics.sky_gradient.then(|| {
+            Sky::new(
+                render_context,
+                view,
+                sky_color,
+                settings.graphics.sky_zenith_color(),
+            )
+        });
 
-        let depth_texture = Texture::create_depth_texture(render_context, "depth_texture");
+        let celestial_bodies = CelestialBodies::new(render_context, view, &time_bind_group_layout);
+        let clouds = settings.graphics.clouds.enabled.then(|| {
+            Clouds::new(
+                render_context,
+                view,
+                &time_bind_group_layout,
+                &settings.graphics.clouds,
+            )
+        });
 
-        Self {
+        let mut world = Self {
             render_pipeline,
+            unculled_pipeline,
+            transparent_pipeline,
+            crack_pipeline,
+            wireframe_pipeline,
+            pipeline_layout: render_pipeline_layout,
 
             time,
             time_buffer,
             time_bind_group,
+            time_frozen: false,
+            render_settings,
 
             depth_texture,
-
-            npc,
+            sky_color,
+            sky,
+            celestial_bodies,
+            clouds,
+
+            npcs: Vec::new(),
+            peaceful: settings.world.peaceful,
+            max_entities: settings.world.max_entities,
+            spawn_settings: settings.world.spawning.clone(),
+            spawn_timer: Duration::ZERO,
+            spawn_rng: ChunkRng::new(settings.world.seed, Point3::new(0, 0, 0)),
+            player_model: None,
+            particles: Particles::new(),
 
             chunks,
+            thread_pool,
             chunk_database,
+            generator: generator::from_settings(&settings.world),
+            biome_map: BiomeMap::new(settings.world.seed),
             chunk_load_queue: VecDeque::new(),
             chunk_save_queue: VecDeque::new(),
-            chunk_generate_queue: VecDeque::new(),
             chunk_occlusion_position: None,
             chunks_visible: None,
+            chunk_arena: settings
+                .graphics
+                .multi_draw_indirect
+                .then(|| ChunkArena::new(render_context))
+                .flatten(),
+            chunk_draw_calls: 0,
+            load_in_view_first: settings.world.load_in_view_first,
+            last_vertex_warning: None,
+            render_distance: settings.graphics.render_distance.max(1) as isize,
+            lod_distance: settings.graphics.lod_distance as isize,
+            lod_center: None,
+            lod_queue: VecDeque::new(),
+            block_light: BlockLight::default(),
+            history: EditHistory::new(settings.player.undo_steps),
 
             highlighted: None,
+            highlight_buffers: None,
+            highlighted_type: None,
+
+            streaming_frozen: false,
+            wireframe_overlay: false,
+            cull_back_faces: true,
+            debug_view: DebugView::Color,
+            max_chunk_updates: settings.world.max_chunk_updates_per_frame.max(1),
+            chunk_upload_queue: UploadQueue::default(),
+            max_upload_bytes: settings.world.max_upload_bytes_per_frame,
+            lighting: settings.graphics.lighting,
+            action_cooldown: ActionCooldown::new(Duration::from_millis(
+                settings.player.action_cooldown_ms,
+            )),
+            raycast_max_steps: settings.player.raycast_max_steps,
+
+            breaking: None,
+            crack_stage: None,
+            crack_buffers: None,
 
             unload_timer: Duration::ZERO,
+            chunk_rebuilds: 0,
+        };
+
+        // A new world starts out with a crab
+        world.spawn_npc(render_context, &NpcState::new(NpcKind::Crab));
+        if let Err(error) = world.load_entities(render_context) {
+            eprintln!("Failed to load entities: {:?}", error);
         }
+        world
     }
 
-    pub fn update_occlusion(&mut self, view: &View) {
-        let initial_position = view
-            .camera
-            .position
-            .map(|x| (x.floor() as isize).div_euclid(CHUNK_ISIZE));
+    /// Deletes the world and starts a new one, generated with `seed` and the rest of `settings`.
+    /// Everything in the chunk database goes, and the chunks are loaded again as the player moves
+    /// around, like in a fresh world.
+    pub fn reset(
+        &mut self,
+        render_context: &RenderContext,
+        settings: &WorldSettings,
+        seed: u32,
+    ) -> error::Result<()> {
+        let (generator, biome_map) = new_world(&self.chunk_database, settings, seed)?;
+        self.generator = generator;
+        self.biome_map = biome_map;
+
+        self.chunks.clear();
+        if let Some(arena) = &mut self.chunk_arena {
+            arena.clear();
+        }
+        self.chunk_load_queue.clear();
+        self.chunk_save_queue.clear();
+        self.chunk_upload_queue = UploadQueue::default();
+        self.chunk_occlusion_position = None;
+        self.chunks_visible = None;
+        self.block_light = BlockLight::default();
+        self.history.clear();
+
+        self.highlighted = None;
+        self.highlight_buffers = None;
+        self.breaking = None;
+        self.crack_stage = None;
+        self.crack_buffers = None;
+
+        self.npcs.clear();
+        self.spawn_npc(render_context, &NpcState::new(NpcKind::Crab));
+        Ok(())
+    }
 
-        if self.chunk_occlusion_position == Some(initial_position) {
+    /// Jumps to a time of day, from 0 to 1 as in `Time::time_of_day`. The sun and the moon move
+    /// there right away, even if time is frozen.
+    pub fn set_time_of_day(&mut self, render_context: &RenderContext, time_of_day: f32) {
+        self.time.time_of_day = time_of_day.rem_euclid(1.0);
+        render_context
+            .queue
+            .write_buffer(&self.time_buffer, 0, bytemuck::cast_slice(&[self.time]));
+    }
+
+    /// Switches what the chunks are drawn with.
+    pub fn set_debug_view(&mut self, render_context: &RenderContext, debug_view: DebugView) {
+        self.debug_view = debug_view;
+        self.render_settings
+            .set_debug_view(render_context, debug_view);
+    }
+
+    /// Stops or restarts the time of day.
+    pub fn set_time_frozen(&mut self, frozen: bool) {
+        self.time_frozen = frozen;
+    }
+
+    /// Whether an NPC of the given kind is allowed to spawn, given the settings and how many
+    /// NPCs there already are.
+    pub fn can_spawn(&self, kind: NpcKind) -> bool {
+        spawning::allows_spawn(
+            kind.is_hostile(),
+            self.npcs.len(),
+            self.peaceful,
+            self.max_entities,
+        )
+    }
+
+    /// Spawns an NPC, unless `can_spawn` says it isn't allowed to. Returns whether it spawned.
+    pub fn spawn_npc(&mut self, render_context: &RenderContext, state: &NpcState) -> bool {
+        if !self.can_spawn(state.kind) {
+            return false;
+        }
+
+        let mut npc = Npc::new();
+        npc.set_state(state);
+        npc.load_geometry(render_context);
+        self.npcs.push(npc);
+        true
+    }
+
+    /// Tries to spawn an NPC around the camera every `interval_ms`, if there's room for one.
+    fn update_spawning(&mut self, render_context: &RenderContext, dt: Duration, camera: &Camera) {
+        if !self.spawn_settings.enabled {
             return;
         }
 
-        self.chunk_occlusion_position = Some(initial_position);
-        let mut queue = VecDeque::from(vec![initial_position]);
+        self.spawn_timer += dt;
+        if self.spawn_timer < Duration::from_millis(self.spawn_settings.interval_ms) {
+            return;
+        }
+        self.spawn_timer = Duration::ZERO;
 
-        assert_eq!(CHUNK_SIZE, 32);
-        let mut visited = [0u32; CHUNK_SIZE * CHUNK_SIZE];
-        let mut render_queue = Vec::new();
+        let kind = NpcKind::Crab;
+        if !self.can_spawn(kind) {
+            return;
+        }
 
-        while !queue.is_empty() {
-            let position = queue.pop_front().unwrap();
+        // The world is borrowed to look for ground, so the generator is advanced on a copy
+        let mut rng = self.spawn_rng.clone();
+        let position = spawning::spawn_position(
+            &mut rng,
+            camera.position,
+            camera.direction(),
+            &self.spawn_settings,
+            self,
+        );
+        self.spawn_rng = rng;
 
-            let b = position.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
-            if (visited[b.x * CHUNK_SIZE + b.y] >> b.z) & 1 == 1 {
-                continue;
-            }
-            visited[b.x * CHUNK_SIZE + b.y] |= 1 << b.z;
+        if let Some(position) = position {
+            let mut state = NpcState::new(kind);
+            state.position = position.into();
+            self.spawn_npc(render_context, &state);
+        }
+    }
 
-            if let Some(chunk) = self.chunks.get(&position) {
-                render_queue.push(position);
-                if !chunk.full {
-                    queue.extend([
-                        position + Vector3::unit_x(),
-                        position - Vector3::unit_x(),
-                        position + Vector3::unit_y(),
-                        position - Vector3::unit_y(),
-                        position + Vector3::unit_z(),
-                        position - Vector3::unit_z(),
-                    ]);
-                }
-            }
+    /// Saves the NPCs to the chunk database.
+    pub fn save_entities(&self) -> error::Result<()> {
+        let states: Vec<NpcState> = self.npcs.iter().map(Npc::state).collect();
+        let data = rmp_serde::encode::to_vec_named(&states)?;
+        self.chunk_database.insert(ENTITIES_KEY, data)?;
+        self.chunk_database.flush()?;
+        Ok(())
+    }
+
+    /// Saves every loaded chunk and the NPCs to the chunk database.
+    pub fn save(&self) -> error::Result<()> {
+        for (position, chunk) in &self.chunks {
+            chunk.save(*position, &self.chunk_database)?;
         }
+        self.save_entities()
+    }
+
+    /// Saves the world, and then writes the whole chunk database to a single file at `path`,
+    /// which `World::import` can read back.
+    pub fn export(&self, path: &Path, header: ExportHeader) -> error::Result<()> {
+        self.save()?;
+        export::export(&self.chunk_database, path, header)
+    }
 
-        self.chunks_visible = Some(render_queue);
+    /// Reads a world exported with `World::export` into the chunk database at `database_path`,
+    /// which mustn't have a world in it yet. Has to be done before the world is opened.
+    pub fn import(path: &Path, database_path: &Path) -> error::Result<ExportHeader> {
+        let database = Self::open_database(database_path)?;
+        export::import(path, &database, database_path)
+    }
+
+    /// Restores the NPCs from the chunk database. A new world keeps the NPCs it was created with.
+    /// NPCs that aren't allowed to spawn anymore (because of peaceful mode or the entity cap) are
+    /// left out.
+    pub fn load_entities(&mut self, render_context: &RenderContext) -> error::Result<()> {
+        let data = match self.chunk_database.get(ENTITIES_KEY)? {
+            Some(data) => data,
+            None => return Ok(()),
+        };
+
+        let states: Vec<NpcState> = rmp_serde::decode::from_slice(&data)?;
+        self.npcs.clear();
+        for state in &states {
+            self.spawn_npc(render_context, state);
+        }
+        Ok(())
+    }
+
+    pub fn update_occlusion(&mut self, view: &View) {
+        let initial_position = world_to_chunk(view.camera.position.map(|x| x.floor() as isize));
+
+        if self.chunk_occlusion_position == Some(initial_position) {
+            return;
+        }
+
+        self.chunk_occlusion_position = Some(initial_position);
+        let chunks = &self.chunks;
+        self.chunks_visible = Some(visible_chunks(initial_position, |position| {
+            chunks.get(&position).map(|chunk| chunk.full)
+        }));
     }
 
     pub fn enqueue_chunk_save(&mut self, position: Point3<isize>, unload: bool) {
@@ -424,140 +1153,847 @@ impl World {
         render_context: &RenderContext,
         chunk_position: Point3<isize>,
     ) {
+        let geometry = self.build_chunk_geometry(render_context, chunk_position);
+        self.upload_geometry(render_context, chunk_position, &geometry);
+
+        // Whatever was still waiting to be uploaded is out of date now
+        self.chunk_upload_queue.remove(&chunk_position);
+    }
+
+    /// Builds the geometry of a loaded chunk at its level of detail, on the chunk workers, and
+    /// counts it in `chunk_rebuilds`.
+    fn build_chunk_geometry(
+        &mut self,
+        render_context: &RenderContext,
+        chunk_position: Point3<isize>,
+    ) -> ChunkGeometry {
+        let lod = self.chunk_lod(chunk_position);
         let chunk = self.chunks.get_mut(&chunk_position).unwrap();
-        chunk.update_geometry(render_context, chunk_position, self.highlighted);
+        let highlighted_type = self.highlighted_type;
+        let lighting = self.lighting;
+        let biome_map = &self.biome_map;
+        let block_textures = &render_context.texture_manager.as_ref().unwrap().blocks;
+        let geometry = self.thread_pool.install(|| {
+            chunk.build_geometry(
+                chunk_position,
+                highlighted_type,
+                lighting,
+                lod,
+                biome_map,
+                block_textures,
+            )
+        });
+        self.check_vertex_count(chunk_position, &geometry);
+        self.chunk_rebuilds += 1;
+        geometry
     }
 
-    fn update_highlight(&mut self, render_context: &RenderContext, camera: &Camera) {
-        let old = self.highlighted;
-        let new = self.raycast(camera.position, camera.direction());
+    /// The level of detail a chunk should be meshed at: 0 within `lod_distance` chunks of the
+    /// streaming center horizontally, 1 within twice that, and so on up to `MAX_LOD`.
+    fn chunk_lod(&self, position: Point3<isize>) -> usize {
+        let center = match self.lod_center {
+            Some(center) if self.lod_distance > 0 => center,
+            _ => return 0,
+        };
+        let distance = (position.x - center.x)
+            .abs()
+            .max((position.z - center.z).abs());
+        ((distance / self.lod_distance) as usize).min(MAX_LOD)
+    }
+
+    /// Queues up the chunks whose level of detail changed for rebuilding, once the streaming
+    /// center has moved into another chunk. They're rebuilt along with the chunk loading, so
+    /// crossing into another chunk doesn't rebuild a whole ring of them in one frame.
+    fn update_lod_center(&mut self, center_chunk: Point3<isize>) {
+        if self.lod_distance == 0 || self.lod_center == Some(center_chunk) {
+            return;
+        }
+        self.lod_center = Some(center_chunk);
+
+        let mut lod_queue: Vec<Point3<isize>> = self
+            .chunks
+            .iter()
+            .filter(|&(&position, chunk)| chunk.lod != self.chunk_lod(position))
+            .map(|(&position, _)| position)
+            .collect();
+        // The closest chunks are the most noticeable
+        lod_queue.sort_unstable_by_key(|position| {
+            (position.x - center_chunk.x).abs() + (position.z - center_chunk.z).abs()
+        });
+        self.lod_queue = lod_queue.into();
+    }
+
+    /// How many fewer triangles the chunks have thanks to being meshed with less detail, or
+    /// `None` if they're all meshed at full detail.
+    pub fn lod_triangles_saved(&self) -> Option<usize> {
+        (self.lod_distance > 0).then(|| {
+            self.chunks
+                .values()
+                .map(|chunk| chunk.lod_triangles_saved)
+                .sum()
+        })
+    }
+
+    /// Warns if a chunk has far more vertices than it could have, which means the mesher has a
+    /// bug. Only warns every `VERTEX_WARNING_INTERVAL`.
+    fn check_vertex_count(&mut self, chunk_position: Point3<isize>, geometry: &ChunkGeometry) {
+        let vertex_count = geometry.vertex_count();
+        if vertex_count <= MAX_EXPECTED_VERTICES {
+            return;
+        }
 
-        let old_chunk = old.map(|(pos, _)| pos.map(|n| n.div_euclid(CHUNK_ISIZE)));
-        let new_chunk = new.map(|(pos, _)| pos.map(|n| n.div_euclid(CHUNK_ISIZE)));
+        let now = Instant::now();
+        if self
+            .last_vertex_warning
+            .is_none_or(|last| now - last >= VERTEX_WARNING_INTERVAL)
+        {
+            self.last_vertex_warning = Some(now);
+            eprintln!(
+                "Chunk {:?} has {} vertices, more than the {} it could have. The mesher is \
+                 probably broken.",
+                chunk_position, vertex_count, MAX_EXPECTED_VERTICES
+            );
+        }
+    }
 
-        if old != new {
-            self.highlighted = new;
+    /// Uploads a chunk's geometry, putting its opaque faces in the arena if there is one and they
+    /// fit. Does nothing if the chunk has been unloaded.
+    fn upload_geometry(
+        &mut self,
+        render_context: &RenderContext,
+        chunk_position: Point3<isize>,
+        geometry: &ChunkGeometry,
+    ) {
+        let chunk = match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => chunk,
+            None => return,
+        };
 
-            if let Some(old_chunk_) = old_chunk {
-                self.update_chunk_geometry(render_context, old_chunk_);
+        if let Some(arena) = &mut self.chunk_arena {
+            if let Some(slot) = chunk.arena_slot.take() {
+                arena.free(slot);
             }
+            chunk.arena_slot = arena.allocate(render_context, &geometry.opaque);
+        }
+        chunk.upload_geometry(render_context, geometry);
+    }
 
-            if let Some(new_chunk_) = new_chunk {
-                // Don't update the same chunk twice
-                if old_chunk != new_chunk {
-                    self.update_chunk_geometry(render_context, new_chunk_);
+    /// Rebuilds the geometry of a chunk, but leaves uploading it to `upload_chunk_geometry`. The
+    /// chunk keeps its old geometry (if any) until then.
+    pub fn enqueue_chunk_geometry(
+        &mut self,
+        render_context: &RenderContext,
+        chunk_position: Point3<isize>,
+    ) {
+        let geometry = self.build_chunk_geometry(render_context, chunk_position);
+        let size = geometry.size_in_bytes();
+        self.chunk_upload_queue.push(chunk_position, geometry, size);
+    }
+
+    /// Uploads as much of the queued chunk geometry as fits in `budget` bytes.
+    fn upload_chunk_geometry(&mut self, render_context: &RenderContext, budget: usize) {
+        for (position, geometry) in self.chunk_upload_queue.pop_within(budget) {
+            // The chunk may have been unloaded in the meantime
+            self.upload_geometry(render_context, position, &geometry);
+        }
+    }
+
+    /// Loads a chunk from the database, or generates it if it isn't in there yet, and queues its
+    /// geometry for uploading.
+    fn load_chunk(&mut self, render_context: &RenderContext, position: Point3<isize>) {
+        let chunk = self.chunks.entry(position).or_default();
+        let result = chunk.load(position, &self.chunk_database, self.generator.as_ref());
+        if result.is_ok() {
+            self.light_chunk(render_context, position);
+        }
+
+        match result {
+            Err(error) => eprintln!("Failed to load/generate chunk {:?}: {:?}", position, error),
+            Ok(true) => {
+                self.enqueue_chunk_geometry(render_context, position);
+                self.enqueue_chunk_save(position, false);
+                if DEBUG_IO {
+                    println!("Generated chunk {:?}", position);
+                }
+            }
+            Ok(false) => {
+                self.enqueue_chunk_geometry(render_context, position);
+                if DEBUG_IO {
+                    println!("Loaded chunk {:?}", position);
                 }
             }
         }
     }
 
-    pub fn break_at_crosshair(&mut self, render_context: &RenderContext, camera: &Camera) {
-        if let Some((pos, _)) = self.raycast(camera.position, camera.direction()) {
-            self.set_block(pos.x as isize, pos.y as isize, pos.z as isize, None);
-            self.update_chunk_geometry(render_context, pos / CHUNK_ISIZE);
+    /// Lights up a chunk that was just loaded, and queues up the chunks around it whose light
+    /// changed with it to be rebuilt. The chunk itself is left to the caller.
+    fn light_chunk(&mut self, render_context: &RenderContext, position: Point3<isize>) {
+        let offset = position * CHUNK_ISIZE;
+        let chunk = &self.chunks[&position];
+        let sources: Vec<Point3<isize>> =
+            itertools::iproduct!(0..CHUNK_SIZE, 0..CHUNK_SIZE, 0..CHUNK_SIZE)
+                .filter(|&(y, z, x)| {
+                    chunk.blocks[y][z][x].is_some_and(|block| block.block_type.light_emission() > 0)
+                })
+                .map(|(y, z, x)| offset + Vector3::new(x, y, z).cast().unwrap())
+                .collect();
+
+        let mut block_light = std::mem::take(&mut self.block_light);
+        let changed =
+            block_light.add_chunk(self, |point| self.is_loaded(point), position, &sources);
+        self.block_light = block_light;
+
+        let chunk = self.chunks.get_mut(&position).unwrap();
+        chunk.light = self.block_light.chunk_levels(position);
+        for chunk_position in self.apply_light_changes(&changed) {
+            if chunk_position != position {
+                self.enqueue_chunk_geometry(render_context, chunk_position);
+            }
         }
     }
 
-    pub fn place_at_crosshair(
+    /// Updates the light after the block at `point` changed, and marks the chunks showing it as
+    /// dirty.
+    fn update_light(&mut self, point: Point3<isize>) {
+        let mut block_light = std::mem::take(&mut self.block_light);
+        let changed = block_light.update(self, |point| self.is_loaded(point), point);
+        self.block_light = block_light;
+
+        self.apply_light_changes(&changed);
+    }
+
+    /// Hands the new light to every loaded chunk that shows one of the `changed` cells, marks
+    /// them as dirty, and returns them. That's the chunk the cell is in, and the chunks of the
+    /// blocks next to it, whose faces it lights.
+    fn apply_light_changes(
+        &mut self,
+        changed: &FxHashSet<Point3<isize>>,
+    ) -> FxHashSet<Point3<isize>> {
+        let chunk_positions: FxHashSet<Point3<isize>> = changed
+            .iter()
+            .flat_map(|&point| {
+                itertools::iproduct!(-1..=1, -1..=1, -1..=1)
+                    .map(|(x, y, z)| Vector3::new(x, y, z))
+                    .filter(|offset: &Vector3<isize>| {
+                        offset.x.abs() + offset.y.abs() + offset.z.abs() <= 1
+                    })
+                    .map(move |offset| world_to_chunk(point + offset))
+            })
+            .filter(|position| self.chunks.contains_key(position))
+            .collect();
+
+        for &position in &chunk_positions {
+            let light = self.block_light.chunk_levels(position);
+            let chunk = self.chunks.get_mut(&position).unwrap();
+            chunk.light = light;
+            chunk.dirty = true;
+        }
+        chunk_positions
+    }
+
+    /// Whether the chunk the point is in is loaded.
+    fn is_loaded(&self, point: Point3<isize>) -> bool {
+        self.chunks.contains_key(&world_to_chunk(point))
+    }
+
+    /// Loads the chunks within `radius` chunks (horizontally) of `center` right away, and uploads
+    /// their geometry, so a camera moved there doesn't have to wait for them to stream in. The
+    /// closest chunks go first, and at most `MAX_WARMUP_CHUNKS` are loaded; the rest are left to
+    /// the streaming in `update`.
+    pub fn warmup(&mut self, render_context: &RenderContext, center: Point3<f32>, radius: isize) {
+        let center = world_to_chunk(center.map(|x| x.floor() as isize));
+        let radius = radius.clamp(0, self.render_distance);
+
+        let mut positions: Vec<Point3<isize>> =
+            itertools::iproduct!(-radius..=radius, 0..WORLD_HEIGHT, -radius..=radius)
+                .map(|(x, y, z)| Point3::new(center.x + x, y, center.z + z))
+                .filter(|position| !self.chunks.contains_key(position))
+                .collect();
+        positions.sort_unstable_by_key(|position| {
+            let distance = position - center;
+            distance.x.abs() + distance.y.abs() + distance.z.abs()
+        });
+        positions.truncate(MAX_WARMUP_CHUNKS);
+
+        self.chunk_load_queue
+            .retain(|position| !positions.contains(position));
+        for &position in &positions {
+            self.load_chunk(render_context, position);
+        }
+
+        self.upload_chunk_geometry(render_context, usize::MAX);
+        self.chunk_occlusion_position = None;
+    }
+
+    /// Changes how many chunks around the camera are loaded. Chunks that end up out of range are
+    /// unloaded with the rest, the next time far away chunks are unloaded.
+    pub fn set_render_distance(&mut self, render_distance: usize) {
+        self.render_distance = render_distance.max(1) as isize;
+    }
+
+    /// Switches to the given lighting mode, and rebuilds every loaded chunk with it.
+    pub fn set_lighting(&mut self, render_context: &RenderContext, lighting: LightingMode) {
+        self.lighting = lighting;
+
+        let positions: Vec<Point3<isize>> = self.chunks.keys().copied().collect();
+        for position in positions {
+            self.enqueue_chunk_geometry(render_context, position);
+        }
+    }
+
+    /// Tints every block of `block_type` in the loaded chunks, or stops tinting them if it's
+    /// `None`. Rebuilds all loaded chunks, so it's slow.
+    pub fn set_highlighted_type(
         &mut self,
         render_context: &RenderContext,
+        block_type: Option<BlockType>,
+    ) {
+        self.highlighted_type = block_type;
+
+        let positions: Vec<Point3<isize>> = self.chunks.keys().copied().collect();
+        for position in positions {
+            self.enqueue_chunk_geometry(render_context, position);
+        }
+    }
+
+    fn update_highlight(&mut self, render_context: &RenderContext, camera: &Camera) {
+        // Whatever is highlighted is what gets broken, which is what's behind water
+        let new = self.raycast(camera.position, camera.direction(), false);
+        if new == self.highlighted {
+            return;
+        }
+        self.highlighted = new;
+
+        self.highlight_buffers = new.and_then(|(position, normal)| {
+            let block = self.get_block(position)?;
+            let &(face, _) = FACES
+                .iter()
+                .find(|(_, face_normal)| Vector3::from(*face_normal) == normal)?;
+
+            // Build the face from the block's own geometry, so the highlight follows its shape
+            let mut quad = Quad::new(position, 1, 1);
+            quad.block_type = Some(block.block_type);
+            quad.facing = block.facing;
+            quad.visible_faces = face;
+            quad.highlighted_normal = normal;
+
+            let block_textures = &render_context.texture_manager.as_ref().unwrap().blocks;
+            let mut geometry = quad.to_geometry(0, block_textures);
+            // Stairs always come with some faces of their own, which aren't highlighted
+            let vertices = &geometry.vertices;
+            geometry.indices = geometry
+                .indices
+                .chunks(3)
+                .filter(|triangle| {
+                    triangle
+                        .iter()
+                        .all(|&i| vertices[i as usize].highlighted == 1)
+                })
+                .flatten()
+                .copied()
+                .collect();
+
+            Some(GeometryBuffers::from_geometry(
+                render_context,
+                &geometry,
+                BufferUsages::empty(),
+            ))
+        });
+    }
+
+    /// Rebuilds the chunks whose blocks or light changed since they were last built. Every chunk
+    /// is rebuilt once, however many of its blocks changed.
+    pub fn rebuild_dirty_chunks(&mut self, render_context: &RenderContext) {
+        let dirty: Vec<Point3<isize>> = self
+            .chunks
+            .iter()
+            .filter(|(_, chunk)| chunk.dirty)
+            .map(|(&position, _)| position)
+            .collect();
+        for position in dirty {
+            self.update_chunk_geometry(render_context, position);
+        }
+    }
+
+    /// Advances breaking the highlighted block for as long as `mining` is true, and breaks it once
+    /// it's done. Looking at another block starts over, once the cooldown since the last block
+    /// broke has passed.
+    pub fn update_breaking(&mut self, render_context: &RenderContext, dt: Duration, mining: bool) {
+        let target = self
+            .highlighted
+            .filter(|_| mining)
+            .map(|(position, _)| position)
+            .filter(|&position| self.is_breakable(position));
+        self.breaking = match (target, self.breaking) {
+            (Some(position), Some((breaking, progress))) if position == breaking => {
+                let progress = match self.get_block(position) {
+                    Some(block) => break_progress(block.block_type, progress, dt, false),
+                    None => 1.0,
+                };
+                Some((position, progress))
+            }
+            (Some(position), _) if self.action_cooldown.passed(Instant::now()) => {
+                Some((position, 0.0))
+            }
+            _ => None,
+        };
+
+        if let Some((position, progress)) = self.breaking {
+            if progress >= 1.0 {
+                self.set_block(position.x, position.y, position.z, None);
+                self.update_light(position);
+                self.breaking = None;
+                self.action_cooldown.restart(Instant::now());
+            }
+        }
+
+        self.update_crack_geometry(render_context);
+    }
+
+    /// Rebuilds the crack overlay whenever the block being broken reaches another destroy stage.
+    fn update_crack_geometry(&mut self, render_context: &RenderContext) {
+        let stage = self.breaking.map(|(position, progress)| {
+            let stage = (progress * DESTROY_STAGES as f32) as usize;
+            (position, stage.min(DESTROY_STAGES - 1))
+        });
+        if stage == self.crack_stage {
+            return;
+        }
+        self.crack_stage = stage;
+
+        self.crack_buffers = stage.and_then(|(position, stage)| {
+            let block = self.get_block(position)?;
+
+            // Build the block's own geometry, so the cracks follow its shape
+            let mut quad = Quad::new(position, 1, 1);
+            quad.block_type = Some(block.block_type);
+            quad.facing = block.facing;
+
+            let block_textures = &render_context.texture_manager.as_ref().unwrap().blocks;
+            let mut geometry = quad.to_geometry(0, block_textures);
+            for vertex in &mut geometry.vertices {
+                vertex.texture_id = (DESTROY_STAGE_TEXTURE + stage) as i32;
+                vertex.color = [1.0; 4];
+                vertex.overlay_texture_id = -1;
+            }
+
+            Some(GeometryBuffers::from_geometry(
+                render_context,
+                &geometry,
+                BufferUsages::empty(),
+            ))
+        });
+    }
+
+    /// Rebuilds the box standing in for the player, or removes it when `bounds` is `None`.
+    pub fn update_player_model(&mut self, render_context: &RenderContext, bounds: Option<Aabb>) {
+        self.player_model = bounds.map(|bounds| {
+            let mut quad = Quad::new(bounds.min.map(|n| n.floor() as isize), 1, 1);
+            quad.block_type = Some(BlockType::OakPlanks);
+
+            let block_textures = &render_context.texture_manager.as_ref().unwrap().blocks;
+            let geometry =
+                quad.cuboid_to_geometry(bounds.min, bounds.max, FACE_ALL, 0, block_textures);
+            GeometryBuffers::from_geometry(render_context, &geometry, BufferUsages::empty())
+        });
+    }
+
+    /// Kicks up `count` particles of dust from the block the player is standing on, with their feet
+    /// at `feet`. Nothing is kicked up in water, or when standing on nothing.
+    pub fn kick_up_dust(
+        &mut self,
+        render_context: &RenderContext,
+        feet: Point3<f32>,
+        count: usize,
+    ) {
+        let at = |offset: f32| {
+            let point = Point3::new(feet.x, feet.y + offset, feet.z);
+            point.map(|n| n.floor() as isize)
+        };
+        let in_water = [0.1, 1.0].iter().any(|&offset| {
+            self.get_block(at(offset)).map(|b| b.block_type) == Some(BlockType::Water)
+        });
+        let ground = match self.get_block(at(-0.1)) {
+            Some(block) if !in_water && block.block_type != BlockType::Water => block.block_type,
+            _ => return,
+        };
+
+        let texture_manager = render_context.texture_manager.as_ref().unwrap();
+        let texture_id = texture_manager.block_textures(ground).5;
+        self.particles
+            .spawn(feet, count, 2.0, texture_id, ground.color().into());
+    }
+
+    /// Breaks the block at the crosshair. In creative, it breaks right away, however hard it is.
+    /// In survival, this only starts breaking it: `update_breaking` takes it from there, for as
+    /// long as the block's hardness says, and the cooldown has to pass first.
+    pub fn break_at_crosshair(&mut self, camera: &Camera, creative: bool) {
+        if let Some((pos, _)) = self.raycast(camera.position, camera.direction(), false) {
+            let block_type = match self.get_block(pos) {
+                Some(block) if block.block_type.is_breakable() => block.block_type,
+                _ => return,
+            };
+
+            if break_progress(block_type, 0.0, Duration::ZERO, creative) >= 1.0 {
+                self.set_block(pos.x, pos.y, pos.z, None);
+                self.update_light(pos);
+            } else if self.breaking.map(|(breaking, _)| breaking) != Some(pos)
+                && self.action_cooldown.passed(Instant::now())
+            {
+                self.breaking = Some((pos, 0.0));
+            }
+        }
+    }
+
+    /// Places a block against the block at the crosshair, limited by the cooldown in survival.
+    /// Returns where it was placed, and the normal of the face it was placed against.
+    pub fn place_at_crosshair(
+        &mut self,
         camera: &Camera,
         block_type: BlockType,
-    ) {
-        if let Some((pos, face_normal)) = self.raycast(camera.position, camera.direction()) {
-            let new_pos = (pos.cast().unwrap() + face_normal).cast().unwrap();
-            self.set_block(new_pos.x, new_pos.y, new_pos.z, Some(Block { block_type }));
-            self.update_chunk_geometry(render_context, pos / CHUNK_ISIZE);
+        creative: bool,
+    ) -> Option<(Point3<isize>, Vector3<i32>)> {
+        // Blocks are placed against the first block in the way, even water
+        if let Some((pos, face_normal)) = self.raycast(camera.position, camera.direction(), true) {
+            if !self.action_cooldown.try_start(Instant::now(), creative) {
+                return None;
+            }
+
+            // Placing into water displaces it
+            let new_pos = raycast::placement_position(pos, face_normal, self);
+
+            let mut block = Block::new(block_type);
+            if !block_type.is_full_cube() {
+                block.facing = Facing::from_yaw(camera.yaw.0);
+            }
+            self.set_block(new_pos.x, new_pos.y, new_pos.z, Some(block));
+            self.update_light(new_pos);
+            return Some((new_pos, face_normal));
         }
+
+        None
     }
 
-    pub fn get_block(&self, point: Point3<isize>) -> Option<&Block> {
-        let chunk = match self.chunks.get(&point.map(|x| x.div_euclid(CHUNK_ISIZE))) {
-            Some(chunk) => chunk,
-            None => return None,
+    /// Continues a line of blocks placed by dragging, from `from` (where the last block of the
+    /// line was placed) to where a block would be placed at the crosshair. Every cell in between
+    /// gets a block, unless something's already in the way. Returns where the line ends now.
+    ///
+    /// The blocks are left open in the undo history, so the whole line can be committed as one
+    /// step once the drag ends.
+    ///
+    /// The line stays in the layer the drag started in, which is the one in front of the faces
+    /// facing `normal`. Otherwise the crosshair would hit the blocks just placed, and the line
+    /// would grow towards the camera.
+    pub fn drag_place(
+        &mut self,
+        camera: &Camera,
+        block_type: BlockType,
+        from: Point3<isize>,
+        normal: Vector3<i32>,
+    ) -> Point3<isize> {
+        let (pos, face_normal) = match self.raycast(camera.position, camera.direction(), true) {
+            Some(hit) => hit,
+            None => return from,
         };
+        let to = raycast::placement_position(pos, face_normal, self);
+        let axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+        if face_normal != normal || to[axis] != from[axis] || to == from {
+            return from;
+        }
 
-        let b = point.map(|x| x.rem_euclid(CHUNK_ISIZE) as usize);
-        chunk.blocks[b.y][b.z][b.x].as_ref()
+        let mut chunks = FxHashSet::default();
+        for point in raycast::drag_line(from, to, self) {
+            if !self.chunks.contains_key(&world_to_chunk(point)) {
+                continue;
+            }
+
+            let mut block = Block::new(block_type);
+            if !block_type.is_full_cube() {
+                block.facing = Facing::from_yaw(camera.yaw.0);
+            }
+            chunks.insert(self.edit_block(point, Some(block)));
+            self.update_light(point);
+        }
+
+        for chunk_position in chunks {
+            self.enqueue_chunk_save(chunk_position, false);
+        }
+
+        to
+    }
+
+    /// Describes the highlighted block, if there is one.
+    pub fn targeted_block_info(&self) -> Option<TargetedBlockInfo> {
+        let (position, face) = self.highlighted?;
+        let block = self.get_block(position)?;
+
+        Some(TargetedBlockInfo {
+            block_type: block.block_type,
+            position,
+            chunk: world_to_chunk(position),
+            face,
+            placement: raycast::placement_position(position, face, self),
+        })
     }
 
+    pub fn get_block_entity(&self, point: Point3<isize>) -> Option<&BlockEntity> {
+        let chunk = self.chunks.get(&world_to_chunk(point))?;
+        let local = world_to_local(point).to_vec();
+        chunk.block_entities.get(&local)
+    }
+
+    /// Returns the block entity at the point for modification, and makes sure its chunk gets saved.
+    pub fn get_block_entity_mut(&mut self, point: Point3<isize>) -> Option<&mut BlockEntity> {
+        let chunk_position = world_to_chunk(point);
+        if !self.chunks.contains_key(&chunk_position) {
+            return None;
+        }
+        self.enqueue_chunk_save(chunk_position, false);
+
+        let chunk = self.chunks.get_mut(&chunk_position)?;
+        let local = world_to_local(point).to_vec();
+        chunk.block_entities.get_mut(&local)
+    }
+
+    /// Sets a block, as a step of its own in the undo history.
     pub fn set_block(&mut self, x: isize, y: isize, z: isize, block: Option<Block>) {
-        let chunk_position = Point3::new(
-            x.div_euclid(CHUNK_ISIZE),
-            y.div_euclid(CHUNK_ISIZE),
-            z.div_euclid(CHUNK_ISIZE),
-        );
+        let chunk_position = self.edit_block(Point3::new(x, y, z), block);
+        self.history.commit();
+        self.enqueue_chunk_save(chunk_position, false);
+    }
+
+    /// Sets the block like `set_block_unsaved`, and records it in the undo history. The edit
+    /// becomes part of the next step committed to the history.
+    fn edit_block(&mut self, point: Point3<isize>, block: Option<Block>) -> Point3<isize> {
+        if self.is_loaded(point) {
+            let old = self.get_block(point).copied();
+            self.history.record(BlockEdit {
+                position: point,
+                old,
+            });
+        }
+        self.set_block_unsaved(point, block)
+    }
+
+    /// Reverts the most recent step in the undo history. Returns how many blocks changed back.
+    /// Blocks in chunks that have been unloaded since stay as they are, and the contents of
+    /// chests that are brought back are gone.
+    pub fn undo(&mut self) -> usize {
+        let edits = match self.history.pop() {
+            Some(edits) => edits,
+            None => return 0,
+        };
+
+        let mut chunks = FxHashSet::default();
+        let mut count = 0;
+        for edit in edits {
+            if !self.is_loaded(edit.position) {
+                continue;
+            }
+            chunks.insert(self.set_block_unsaved(edit.position, edit.old));
+            self.update_light(edit.position);
+            count += 1;
+        }
+
+        for chunk_position in chunks {
+            self.enqueue_chunk_save(chunk_position, false);
+        }
+        count
+    }
+
+    /// Sets the block without queueing its chunk to be saved, and returns the chunk's position.
+    /// Does nothing if the chunk isn't loaded.
+    fn set_block_unsaved(&mut self, point: Point3<isize>, block: Option<Block>) -> Point3<isize> {
+        let chunk_position = world_to_chunk(point);
 
         if let Some(chunk) = self.chunks.get_mut(&chunk_position) {
-            let bx = x.rem_euclid(CHUNK_ISIZE) as usize;
-            let by = y.rem_euclid(CHUNK_ISIZE) as usize;
-            let bz = z.rem_euclid(CHUNK_ISIZE) as usize;
-            chunk.blocks[by][bz][bx] = block;
+            let local = world_to_local(point).to_vec();
+            chunk.blocks[local.y][local.z][local.x] = block;
+            chunk.dirty = true;
+
+            // Whatever was stored in the old block (like a chest's contents) is lost
+            chunk.block_entities.remove(&local);
+            if let Some(block_entity) =
+                block.and_then(|b| BlockEntity::for_block_type(b.block_type))
+            {
+                chunk.block_entities.insert(local, block_entity);
+            }
         }
 
-        self.enqueue_chunk_save(chunk_position, false);
+        chunk_position
+    }
+
+    /// Levels the square of loaded columns within `radius` of `center` horizontally: everything
+    /// above `center.y` is cleared, and everything at or below it is filled with `block_type`.
+    /// Bedrock is left alone.
+    ///
+    /// Every affected chunk is saved once, rather than once per block. Returns how many blocks
+    /// changed.
+    pub fn flatten(
+        &mut self,
+        center: Point3<isize>,
+        radius: isize,
+        block_type: BlockType,
+    ) -> usize {
+        let edits = flatten::flatten_edits(center, radius, block_type, self);
+        let mut chunks = FxHashSet::default();
+        for &(point, target) in &edits {
+            chunks.insert(self.edit_block(point, target.map(Block::new)));
+            self.update_light(point);
+        }
+        self.history.commit();
+
+        for chunk_position in chunks {
+            if self.chunks.contains_key(&chunk_position) {
+                self.enqueue_chunk_save(chunk_position, false);
+            }
+        }
+        edits.len()
     }
 
-    #[allow(dead_code)]
     pub fn raycast(
         &self,
         origin: Point3<f32>,
         direction: Vector3<f32>,
+        stop_on_fluids: bool,
     ) -> Option<(Point3<isize>, Vector3<i32>)> {
-        let direction = direction.normalize();
-        let mut position: Point3<i32> = origin.map(|x| x.floor() as i32);
-        let step = direction.map(|x| x.signum() as i32);
-
-        // Algorithm from: http://www.cse.yorku.ca/%7Eamana/research/grid.pdf
-        fn dif_from_next(n: f32, n_step: i32) -> f32 {
-            if n_step < 0 {
-                // Difference between the next smallest integer and n
-                n.floor() - n
-            } else {
-                // Difference between the next biggest integer and n
-                (n + 1.0).floor() - n
+        raycast::raycast(
+            origin,
+            direction,
+            self,
+            self.raycast_max_steps,
+            stop_on_fluids,
+        )
+    }
+}
+
+impl BlockView for World {
+    fn get_block(&self, point: Point3<isize>) -> Option<&Block> {
+        let chunk = self.chunks.get(&world_to_chunk(point))?;
+
+        let b = world_to_local(point);
+        chunk.blocks[b.y][b.z][b.x].as_ref()
+    }
+}
+
+/// Flood fills outwards from the chunk at `start` through the loaded chunks, and returns every
+/// chunk that's reached. Full chunks are reached, but not filled through. `is_full` returns `None`
+/// for chunks that aren't loaded, which stops the fill as well.
+fn visible_chunks(
+    start: Point3<isize>,
+    is_full: impl Fn(Point3<isize>) -> Option<bool>,
+) -> Vec<Point3<isize>> {
+    let mut queue = VecDeque::from(vec![start]);
+    let mut visited = FxHashSet::default();
+    let mut visible = Vec::new();
+
+    while let Some(position) = queue.pop_front() {
+        if !visited.insert(position) {
+            continue;
+        }
+
+        if let Some(full) = is_full(position) {
+            visible.push(position);
+            if !full {
+                queue.extend([
+                    position + Vector3::unit_x(),
+                    position - Vector3::unit_x(),
+                    position + Vector3::unit_y(),
+                    position - Vector3::unit_y(),
+                    position + Vector3::unit_z(),
+                    position - Vector3::unit_z(),
+                ]);
             }
         }
+    }
 
-        let mut t_max_x = dif_from_next(origin.x, step.x) / direction.x;
-        let mut t_max_y = dif_from_next(origin.y, step.y) / direction.y;
-        let mut t_max_z = dif_from_next(origin.z, step.z) / direction.z;
+    visible
+}
 
-        let t_delta_x = direction.x.abs().inv();
-        let t_delta_y = direction.y.abs().inv();
-        let t_delta_z = direction.z.abs().inv();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stops_the_occlusion_fill_at_full_chunks() {
+        // A loaded 5x1x5 slab of chunks, with a wall of full chunks at x = 1
+        let is_full = |position: Point3<isize>| {
+            let loaded =
+                (-2..=2).contains(&position.x) && position.y == 0 && (-2..=2).contains(&position.z);
+            loaded.then_some(position.x == 1)
+        };
+        let visible: FxHashSet<_> = visible_chunks(Point3::new(0, 0, 0), is_full)
+            .into_iter()
+            .collect();
+
+        for (x, z) in itertools::iproduct!(-2..=2, -2..=2) {
+            let position = Point3::new(x, 0, z);
+            // The wall itself is visible, but not what's behind it
+            assert_eq!(visible.contains(&position), x <= 1, "{:?}", position);
+        }
+        assert_eq!(visible.len(), 4 * 5);
+    }
 
-        let mut face;
+    #[test]
+    fn only_reaches_loaded_chunks() {
+        let visible = visible_chunks(Point3::new(0, 0, 0), |position| {
+            (position == Point3::new(0, 0, 0) || position == Point3::new(0, 1, 0)).then_some(false)
+        });
+        assert_eq!(visible, vec![Point3::new(0, 0, 0), Point3::new(0, 1, 0)]);
+    }
 
-        while t_max_x.min(t_max_y).min(t_max_z) < 100.0 {
-            if t_max_x < t_max_y {
-                if t_max_x < t_max_z {
-                    t_max_x += t_delta_x;
-                    position.x += step.x;
-                    face = Vector3::unit_x() * -step.x;
-                } else {
-                    t_max_z += t_delta_z;
-                    position.z += step.z;
-                    face = Vector3::unit_z() * -step.z;
-                }
-            } else {
-                if t_max_y < t_max_z {
-                    t_max_y += t_delta_y;
-                    position.y += step.y;
-                    face = Vector3::unit_y() * -step.y;
-                } else {
-                    t_max_z += t_delta_z;
-                    position.z += step.z;
-                    face = Vector3::unit_z() * -step.z;
-                }
-            }
+    #[test]
+    fn breaks_stone_right_away_in_creative() {
+        assert!(break_progress(BlockType::Stone, 0.0, Duration::ZERO, true) >= 1.0);
+    }
 
-            if self.get_block(position.cast().unwrap()).is_some() {
-                // Intersection occurred
-                return Some((position.cast().unwrap(), face));
-            }
+    #[test]
+    fn breaks_stone_by_its_hardness_in_survival() {
+        // Stone takes one and a half seconds to break
+        assert_eq!(
+            break_progress(BlockType::Stone, 0.0, Duration::ZERO, false),
+            0.0
+        );
+
+        let step = Duration::from_millis(250);
+        let mut progress = 0.0;
+        let mut steps = 0;
+        while progress < 1.0 {
+            progress = break_progress(BlockType::Stone, progress, step, false);
+            steps += 1;
         }
+        assert_eq!(steps, 6);
 
-        None
+        // Softer blocks break sooner
+        let dirt = break_progress(BlockType::Dirt, 0.0, step, false);
+        assert!(dirt > break_progress(BlockType::Stone, 0.0, step, false));
+    }
+
+    #[test]
+    fn starts_a_new_world_from_scratch() {
+        let chunk_database = sled::Config::new().temporary(true).open().unwrap();
+        chunk_database.insert(b"chunk", b"data".as_ref()).unwrap();
+
+        let settings = WorldSettings::default();
+        let (generator, _) = new_world(&chunk_database, &settings, settings.seed + 1).unwrap();
+        assert!(chunk_database.is_empty());
+
+        // The new world is generated with the new seed, rather than the old one
+        let generate = |generator: &dyn WorldGenerator| {
+            (0..WORLD_HEIGHT)
+                .map(|y| {
+                    let mut chunk = Chunk::default();
+                    generator.generate(&mut chunk, Point3::new(0, y, 0));
+                    chunk.blocks
+                })
+                .collect::<Vec<_>>()
+        };
+        let reseeded = WorldSettings {
+            seed: settings.seed + 1,
+            ..settings.clone()
+        };
+        let chunk = generate(generator.as_ref());
+        assert!(chunk == generate(generator::from_settings(&reseeded).as_ref()));
+        assert!(chunk != generate(generator::from_settings(&settings).as_ref()));
     }
 }
@@ -1,20 +1,79 @@
 extern crate gltf;
 extern crate wgpu;
 
+use std::time::Duration;
+
 use cgmath::Vector3;
+use serde::{Deserialize, Serialize};
 use wgpu::{BufferUsages, RenderPass};
 
 use crate::{
-    geometry::Geometry, geometry_buffers::GeometryBuffers, render_context::RenderContext,
+    geometry::Geometry,
+    geometry_buffers::GeometryBuffers,
+    render_context::RenderContext,
     vertex::BlockVertex,
+    world::animation::{self, Skeleton},
 };
 
+/// The animations played by the NPC, in order of preference.
+const ANIMATION_NAMES: &[&str] = &["idle", "walk"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NpcKind {
+    Crab,
+}
+
+impl NpcKind {
+    /// Whether the NPC attacks the player. Hostile NPCs don't spawn in peaceful mode.
+    pub const fn is_hostile(self) -> bool {
+        match self {
+            Self::Crab => false,
+        }
+    }
+}
+
+/// The parts of an NPC that are saved with the world.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct NpcState {
+    pub kind: NpcKind,
+    pub position: [f32; 3],
+    pub rotation: [f32; 3],
+    pub scale: [f32; 3],
+}
+
+impl NpcState {
+    /// The state of a freshly spawned NPC of the given kind, at the origin.
+    pub fn new(kind: NpcKind) -> Self {
+        Self {
+            kind,
+            position: [0.0; 3],
+            rotation: [0.0; 3],
+            scale: [0.0; 3],
+        }
+    }
+}
+
 pub struct Npc {
     pub position: Vector3<f32>,
     pub scale: Vector3<f32>,
     pub rotation: Vector3<f32>,
     pub geometry: Geometry<BlockVertex, u32>,
     pub geometry_buffers: Option<GeometryBuffers<u32>>,
+
+    /// The skeleton of the model, if it's skinned.
+    skeleton: Option<Skeleton>,
+    /// The joints and weights of every vertex, used to skin `geometry` on the CPU.
+    skin: Vec<([u16; 4], [f32; 4])>,
+    /// The vertices in their bind pose.
+    bind_pose: Vec<BlockVertex>,
+    animation: Option<usize>,
+    animation_time: f32,
+}
+
+impl Default for Npc {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Npc {
@@ -27,11 +86,19 @@ impl Npc {
 
         let mut indices = Vec::new();
         let mut vertices = Vec::new();
+        let mut skin = Vec::new();
 
         for mesh in model.meshes() {
             for primitive in mesh.primitives() {
                 let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
-                indices = reader.read_indices().unwrap().into_u32().collect();
+                let index_offset = vertices.len() as u32;
+                indices.extend(
+                    reader
+                        .read_indices()
+                        .unwrap()
+                        .into_u32()
+                        .map(|index| index + index_offset),
+                );
 
                 // loop over all primitives and get the normals, position and color
                 let pos_iter = reader.read_positions().unwrap();
@@ -48,19 +115,63 @@ impl Npc {
                         highlighted: 0,
                         texture_id: 0,
                         color: [1.0, 1.0, 1.0, 1.0],
+                        overlay_texture_id: -1,
+                        overlay_color: [1.0, 1.0, 1.0, 1.0],
                     };
 
                     vertices.push(current_vert);
                 }
+
+                // Vertices without any joints aren't skinned
+                match (reader.read_joints(0), reader.read_weights(0)) {
+                    (Some(joints), Some(weights)) => {
+                        skin.extend(joints.into_u16().zip(weights.into_f32()))
+                    }
+                    _ => skin.resize(vertices.len(), ([0; 4], [0.0; 4])),
+                }
             }
         }
 
+        let skeleton = Skeleton::from_gltf(&model, &buffers);
+        let animation = skeleton.as_ref().and_then(|skeleton| {
+            ANIMATION_NAMES
+                .iter()
+                .find_map(|name| skeleton.find_animation(name))
+                .or_else(|| (!skeleton.animations.is_empty()).then_some(0))
+        });
+
         Self {
             position,
             scale,
             rotation,
+            bind_pose: vertices.clone(),
             geometry: Geometry::new(vertices, indices),
             geometry_buffers: None,
+
+            skeleton,
+            skin,
+            animation,
+            animation_time: 0.0,
+        }
+    }
+
+    pub fn state(&self) -> NpcState {
+        NpcState {
+            kind: NpcKind::Crab,
+            position: self.position.into(),
+            rotation: self.rotation.into(),
+            scale: self.scale.into(),
+        }
+    }
+
+    /// Moves the NPC, along with its model.
+    pub fn set_state(&mut self, state: &NpcState) {
+        self.position = state.position.into();
+        self.rotation = state.rotation.into();
+        self.scale = state.scale.into();
+
+        for (vertex, bind) in self.geometry.vertices.iter_mut().zip(&self.bind_pose) {
+            vertex.position = (Vector3::from(bind.position) + self.position).into();
         }
     }
 
@@ -68,10 +179,55 @@ impl Npc {
         self.geometry_buffers = Some(GeometryBuffers::from_geometry(
             render_context,
             &self.geometry,
-            BufferUsages::empty(),
+            BufferUsages::COPY_DST,
         ));
     }
 
+    /// Advances the animation by `dt` seconds, and skins the vertices into the new pose.
+    pub fn update(&mut self, render_context: &RenderContext, dt: Duration) {
+        let skeleton = match &self.skeleton {
+            Some(skeleton) => skeleton,
+            None => return,
+        };
+        let animation = match self.animation {
+            Some(animation) => animation,
+            None => return,
+        };
+
+        let duration = skeleton.animations[animation].duration;
+        self.animation_time += dt.as_secs_f32();
+        if duration > 0.0 {
+            self.animation_time %= duration;
+        }
+
+        let joint_matrices = skeleton.joint_matrices(Some(animation), self.animation_time);
+        for ((vertex, bind), &(joints, weights)) in self
+            .geometry
+            .vertices
+            .iter_mut()
+            .zip(&self.bind_pose)
+            .zip(&self.skin)
+        {
+            let (position, normal) = animation::skin_vertex(
+                &joint_matrices,
+                joints,
+                weights,
+                bind.position,
+                bind.normal,
+            );
+            vertex.position = (Vector3::from(position) + self.position).into();
+            vertex.normal = normal;
+        }
+
+        if let Some(buffers) = &self.geometry_buffers {
+            render_context.queue.write_buffer(
+                &buffers.vertices,
+                0,
+                bytemuck::cast_slice(&self.geometry.vertices),
+            );
+        }
+    }
+
     pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
         let buffers = self.geometry_buffers.as_ref().unwrap();
         buffers.apply_buffers(render_pass);
@@ -0,0 +1,156 @@
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+use wgpu::{BufferUsages, RenderPass};
+
+use crate::{
+    camera::Camera, geometry::Geometry, geometry_buffers::GeometryBuffers,
+    render_context::RenderContext, vertex::BlockVertex, world::random::ChunkRng,
+};
+
+/// The most particles that can be around at once. Spawning more replaces the oldest ones.
+const MAX_PARTICLES: usize = 64;
+/// How long a particle lasts, at most.
+const LIFETIME: Duration = Duration::from_millis(600);
+/// How wide a particle is, in blocks.
+const SIZE: f32 = 0.1;
+/// How much of its block's texture a particle shows, from 0 to 1.
+const TEXTURE_FRACTION: f32 = 0.25;
+/// How fast particles fall, in blocks per second squared.
+const GRAVITY: f32 = 16.0;
+
+struct Particle {
+    position: Point3<f32>,
+    velocity: Vector3<f32>,
+    /// How long the particle has left, in seconds.
+    life: f32,
+    texture_id: i32,
+    /// The corner of the part of the texture the particle shows.
+    texture_offset: [f32; 2],
+    color: [f32; 4],
+}
+
+/// Small bits of blocks that fly up and fall back down, like the dust kicked up by the player's
+/// feet. They're drawn as squares facing the camera, with a random part of their block's texture.
+pub struct Particles {
+    particles: Vec<Particle>,
+    rng: ChunkRng,
+    geometry_buffers: Option<GeometryBuffers<u16>>,
+}
+
+impl Default for Particles {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Particles {
+    pub fn new() -> Self {
+        Self {
+            particles: Vec::new(),
+            rng: ChunkRng::new(0, Point3::new(0, 0, 0)),
+            geometry_buffers: None,
+        }
+    }
+
+    /// Spawns `count` particles at `position`, flying up and outwards at about `speed` blocks per
+    /// second.
+    pub fn spawn(
+        &mut self,
+        position: Point3<f32>,
+        count: usize,
+        speed: f32,
+        texture_id: usize,
+        color: [f32; 4],
+    ) {
+        for _ in 0..count.min(MAX_PARTICLES) {
+            if self.particles.len() == MAX_PARTICLES {
+                self.particles.remove(0);
+            }
+
+            let angle = self.rng.next_f32() * std::f32::consts::TAU;
+            let outwards = Vector3::new(angle.cos(), 0.0, angle.sin()) * self.rng.next_f32();
+            let velocity = (outwards + Vector3::unit_y()) * speed * (0.5 + self.rng.next_f32());
+            let offset = 1.0 - TEXTURE_FRACTION;
+
+            self.particles.push(Particle {
+                position: position + outwards * 0.3,
+                velocity,
+                life: LIFETIME.as_secs_f32() * (0.5 + self.rng.next_f32() * 0.5),
+                texture_id: texture_id as i32,
+                texture_offset: [self.rng.next_f32() * offset, self.rng.next_f32() * offset],
+                color,
+            });
+        }
+    }
+
+    /// Moves the particles, gets rid of the ones that have run out, and rebuilds the geometry so
+    /// the rest face `camera`.
+    pub fn update(&mut self, render_context: &RenderContext, dt: Duration, camera: &Camera) {
+        let dt = dt.as_secs_f32();
+        for particle in &mut self.particles {
+            particle.velocity.y -= GRAVITY * dt;
+            particle.position += particle.velocity * dt;
+            particle.life -= dt;
+        }
+        self.particles.retain(|particle| particle.life > 0.0);
+
+        if self.particles.is_empty() {
+            self.geometry_buffers = None;
+            return;
+        }
+
+        let direction = camera.direction();
+        let right = direction.cross(Vector3::unit_y()).normalize() * (SIZE / 2.0);
+        let up = right.cross(direction).normalize() * (SIZE / 2.0);
+        let normal = (-direction).into();
+
+        let mut geometry = Geometry::<BlockVertex, u16>::default();
+        for particle in &self.particles {
+            let [u, v] = particle.texture_offset;
+            let corners = [
+                (-right - up, [u, v + TEXTURE_FRACTION]),
+                (right - up, [u + TEXTURE_FRACTION, v + TEXTURE_FRACTION]),
+                (right + up, [u + TEXTURE_FRACTION, v]),
+                (-right + up, [u, v]),
+            ];
+
+            let start = geometry.vertices.len() as u16;
+            geometry
+                .vertices
+                .extend(
+                    corners
+                        .iter()
+                        .map(|&(offset, texture_coordinates)| BlockVertex {
+                            position: (particle.position + offset).into(),
+                            texture_coordinates,
+                            normal,
+                            highlighted: 0,
+                            texture_id: particle.texture_id,
+                            color: particle.color,
+                            overlay_texture_id: -1,
+                            overlay_color: [1.0; 4],
+                        }),
+                );
+            geometry
+                .indices
+                .extend([0, 1, 2, 2, 3, 0].iter().map(|index| start + index));
+        }
+
+        self.geometry_buffers = Some(GeometryBuffers::from_geometry(
+            render_context,
+            &geometry,
+            BufferUsages::empty(),
+        ));
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>) -> usize {
+        match &self.geometry_buffers {
+            Some(geometry_buffers) => {
+                geometry_buffers.apply_buffers(render_pass);
+                geometry_buffers.draw_indexed(render_pass)
+            }
+            None => 0,
+        }
+    }
+}
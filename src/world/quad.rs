@@ -1,20 +1,115 @@
-use cgmath::{Point3, Vector3, Vector4, Zero};
+use cgmath::{ElementWise, Point3, Vector3, Vector4, Zero};
 
 use crate::{
     geometry::Geometry,
+    texture::BlockTextures,
     vertex::BlockVertex,
-    world::{block::BlockType, face_flags::*},
+    world::{
+        biome::Biome,
+        block::{BlockShape, BlockType, Facing},
+        block_light::MAX_LIGHT,
+        face_flags::*,
+    },
 };
 
+/// The height of the surface of water blocks that have nothing on top of them.
+const WATER_SURFACE_HEIGHT: f32 = 0.875;
+
 #[derive(Debug)]
 pub struct Quad {
     pub position: Point3<isize>,
     pub dx: isize,
     pub dz: isize,
+    /// How many blocks high the quad is. Only more than 1 in chunks meshed with less detail.
+    pub dy: isize,
 
+    /// The normal of the face that was highlighted.
+    ///
+    /// Set to Vector3::zero if no faces are highlighted.
     pub highlighted_normal: Vector3<i32>,
+    /// Whether the block is of the type being highlighted everywhere, which tints all its faces.
+    pub type_highlighted: bool,
+
+    /// Bitmap of the visible faces.
     pub visible_faces: FaceFlags,
+
+    /// The `BlockType` of the blocks the quad describes.
+    ///
+    /// Used for determining which texture to map to it. When `None`, texture index 0 will be used.
     pub block_type: Option<BlockType>,
+
+    /// The biome tint at each corner of the quad, in the order (x, z), (x, z + dz),
+    /// (x + dx, z + dz), (x + dx, z). Only applied to faces that are `is_biome_tinted`, and to
+    /// overlays.
+    pub tints: [Vector4<f32>; 4],
+
+    /// The direction the block is facing. Only used for blocks that aren't `BlockShape::Cube`,
+    /// which always get a 1x1 quad.
+    pub facing: Facing,
+
+    /// The blocks around a 1x1 quad's block that darken the corners of its faces, as a bitmap of
+    /// `occluder_bit`s. `None` when the quad is lit flat.
+    pub occluders: Option<u32>,
+
+    /// The block light in front of each of a 1x1 quad's faces, in the order of `FACES`. `None`
+    /// when none of them are lit.
+    pub light: Option<[u8; 6]>,
+}
+
+/// How much light reaches a corner that's occluded by 0, 1, 2 or 3 blocks.
+const AMBIENT_OCCLUSION_LEVELS: [f32; 4] = [1.0, 0.8, 0.65, 0.5];
+
+/// The color of block light at full strength. There's no darkness for it to light up yet, so it
+/// brightens faces with a warm glow instead: this is added on top of their own color.
+const BLOCK_LIGHT_COLOR: [f32; 3] = [0.6, 0.5, 0.3];
+
+/// The faces of a block, with their normals.
+pub const FACES: [(FaceFlags, [i32; 3]); 6] = [
+    (FACE_LEFT, [-1, 0, 0]),
+    (FACE_RIGHT, [1, 0, 0]),
+    (FACE_BACK, [0, 0, -1]),
+    (FACE_FRONT, [0, 0, 1]),
+    (FACE_BOTTOM, [0, -1, 0]),
+    (FACE_TOP, [0, 1, 0]),
+];
+
+/// Returns the bit of `Quad::occluders` for the block at `offset` from the quad's block, where
+/// every component of `offset` is -1, 0 or 1.
+pub fn occluder_bit(offset: Vector3<isize>) -> u32 {
+    1 << ((offset.x + 1) + (offset.y + 1) * 3 + (offset.z + 1) * 9)
+}
+
+/// Returns how many blocks occlude the corner of the face with the given normal. The corner is
+/// given by the direction from the center of the face to it, along the other two axes.
+fn corner_occlusion(occluders: u32, normal: Vector3<isize>, corner: Vector3<isize>) -> usize {
+    let solid = |offset: Vector3<isize>| occluders & occluder_bit(offset) != 0;
+
+    let axis = (0..3).find(|&axis| normal[axis] != 0).unwrap();
+    let (a, b) = ((axis + 1) % 3, (axis + 2) % 3);
+    let mut side_a = normal;
+    side_a[a] = corner[a];
+    let mut side_b = normal;
+    side_b[b] = corner[b];
+    let mut diagonal = side_a;
+    diagonal[b] = corner[b];
+
+    // Two sides already hide the diagonal block
+    if solid(side_a) && solid(side_b) {
+        3
+    } else {
+        solid(side_a) as usize + solid(side_b) as usize + solid(diagonal) as usize
+    }
+}
+
+/// Returns whether any corner of the given faces is occluded, which keeps them from being
+/// merged with other faces.
+pub fn has_ambient_occlusion(occluders: u32, faces: FaceFlags) -> bool {
+    FACES.iter().any(|&(face, normal)| {
+        let normal = Vector3::from(normal).cast().unwrap();
+        faces & face == face
+            && itertools::iproduct!([-1, 1], [-1, 1], [-1, 1])
+                .any(|(x, y, z)| corner_occlusion(occluders, normal, Vector3::new(x, y, z)) > 0)
+    })
 }
 
 impl Quad {
@@ -23,19 +118,16 @@ impl Quad {
             position,
             dx,
             dz,
+            dy: 1,
 
-            /// The normal of the face that was highlighted.
-            ///
-            /// Set to Vector3::zero if no faces are highlighted.
             highlighted_normal: Vector3::zero(),
-
-            /// Bitmap of the visible faces.
+            type_highlighted: false,
             visible_faces: FACE_ALL,
-
-            /// The `BlockType` of the blocks the quad describes.
-            ///
-            /// Used for determining which texture to map to it. When `None`, texture index 0 will be used.
             block_type: None,
+            tints: [Vector4::new(1.0, 1.0, 1.0, 1.0); 4],
+            facing: Facing::default(),
+            occluders: None,
+            light: None,
         }
     }
 
@@ -44,131 +136,275 @@ impl Quad {
     /// # Arguments
     ///
     /// * `start_index` - Which geometry index to start at.
-    #[allow(clippy::many_single_char_names)]
-    #[rustfmt::skip]
+    /// * `block_textures` - Resolves the block's textures.
     pub fn to_geometry(
         &self,
         start_index: u16,
+        block_textures: &BlockTextures,
     ) -> Geometry<BlockVertex, u16> {
-        let dx = self.dx as f32;
-        let dz = self.dz as f32;
-        let dy = 1.0;
+        let mut geometry = match self.block_type.map(BlockType::shape) {
+            Some(BlockShape::Stairs) => self.stairs_to_geometry(start_index, block_textures),
+            _ => self.faces_to_geometry(start_index, block_textures),
+        };
 
-        let x = self.position.x as f32;
-        let y = self.position.y as f32;
-        let z = self.position.z as f32;
+        if let Some(occluders) = self.occluders {
+            self.apply_ambient_occlusion(&mut geometry, occluders);
+        }
+        if let Some(light) = self.light {
+            Self::apply_block_light(&mut geometry, light);
+        }
 
-        let (t, color) =  match self.block_type {
-            Some(block_type) => (block_type.texture_indices(), block_type.color()),
-            None => ((0, 0, 0, 0, 0, 0), Vector4::new(1.0, 1.0, 1.0, 1.0)),
-        };
-        let color = color.into();
+        geometry
+    }
 
-        let mut current_index = start_index;
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
+    /// Brightens every vertex by the block light in front of the face it's on.
+    fn apply_block_light(geometry: &mut Geometry<BlockVertex, u16>, light: [u8; 6]) {
+        for vertex in &mut geometry.vertices {
+            let normal = vertex.normal.map(|n| n as i32);
+            let face = FACES.iter().position(|&(_, n)| n == normal).unwrap();
+            let strength = light[face] as f32 / MAX_LIGHT as f32;
 
-        if self.visible_faces & FACE_LEFT == FACE_LEFT {
-            let normal = Vector3::new(-1,  0,  0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
-            let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x, y,      z     ], texture_coordinates: [dz,  1.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y,      z + dz], texture_coordinates: [0.0, 1.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.0 as i32, normal, highlighted, color },
-                BlockVertex { position: [x, y + dy, z     ], texture_coordinates: [dz,  0.0], texture_id: t.0 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                2 + current_index, current_index, 1 + current_index,
-                3 + current_index, current_index, 2 + current_index,
-            ]);
-            current_index += 4;
+            for (channel, light_color) in vertex.color[..3].iter_mut().zip(BLOCK_LIGHT_COLOR) {
+                *channel *= 1.0 + light_color * strength;
+            }
         }
+    }
 
-        if self.visible_faces & FACE_RIGHT == FACE_RIGHT {
-            let normal = Vector3::new(1, 0, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
-            let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x + dx, y,      z     ], texture_coordinates: [0.0, 1.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dz,  1.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dz,  0.0], texture_id: t.1 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.1 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                1 + current_index, current_index, 2 + current_index,
-                2 + current_index, current_index, 3 + current_index,
-            ]);
-            current_index += 4;
-        }
+    /// Darkens every vertex by how many blocks occlude the corner it's closest to.
+    fn apply_ambient_occlusion(&self, geometry: &mut Geometry<BlockVertex, u16>, occluders: u32) {
+        let origin = self.position.cast::<f32>().unwrap();
+        for vertex in &mut geometry.vertices {
+            let normal = Vector3::from(vertex.normal).cast().unwrap();
+            let corner =
+                (Point3::from(vertex.position) - origin).map(|n| if n > 0.5 { 1 } else { -1 });
 
-        if self.visible_faces & FACE_BACK == FACE_BACK {
-            let normal = Vector3::new(0, 0, -1);
-            let highlighted = (self.highlighted_normal == normal) as i32;
-            let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x,      y,      z], texture_coordinates: [dx,  1.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z], texture_coordinates: [dx,  0.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z], texture_coordinates: [0.0, 0.0], texture_id: t.2 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z], texture_coordinates: [0.0, 1.0], texture_id: t.2 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                2 + current_index, current_index, 1 + current_index,
-                3 + current_index, current_index, 2 + current_index,
-            ]);
-            current_index += 4;
+            let light = AMBIENT_OCCLUSION_LEVELS[corner_occlusion(occluders, normal, corner)];
+            for channel in &mut vertex.color[..3] {
+                *channel *= light;
+            }
         }
+    }
 
-        if self.visible_faces & FACE_FRONT == FACE_FRONT {
-            let normal = Vector3::new(0, 0, 1);
-            let highlighted = (self.highlighted_normal == normal) as i32;
-            let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x,      y,      z + dz], texture_coordinates: [0.0, 1.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, 0.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  0.0], texture_id: t.3 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y,      z + dz], texture_coordinates: [dx,  1.0], texture_id: t.3 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                1 + current_index, current_index, 2 + current_index,
-                2 + current_index, current_index, 3 + current_index,
-            ]);
-            current_index += 4;
-        }
+    /// Converts the quad's blocks to a single box, lowering the top of water that has nothing on
+    /// top of it.
+    fn faces_to_geometry(
+        &self,
+        start_index: u16,
+        block_textures: &BlockTextures,
+    ) -> Geometry<BlockVertex, u16> {
+        let dy = match self.block_type {
+            Some(BlockType::Water) if self.visible_faces & FACE_TOP == FACE_TOP => {
+                self.dy as f32 - (1.0 - WATER_SURFACE_HEIGHT)
+            }
+            _ => self.dy as f32,
+        };
 
-        if self.visible_faces & FACE_BOTTOM == FACE_BOTTOM {
-            let normal = Vector3::new(0, -1, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
-            let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x,      y, z     ], texture_coordinates: [dx,  0.0], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.4 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.4 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                current_index, 2 + current_index, 1 + current_index,
-                current_index, 3 + current_index, 2 + current_index,
-            ]);
-            current_index += 4;
-        }
+        let min = self.position.cast::<f32>().unwrap();
+        let max = min + Vector3::new(self.dx as f32, dy, self.dz as f32);
+        self.cuboid_to_geometry(min, max, self.visible_faces, start_index, block_textures)
+    }
+
+    /// Converts a 1x1 stairs quad to `Geometry`: a half-block base, with a quarter-block step on
+    /// the side it's facing.
+    fn stairs_to_geometry(
+        &self,
+        start_index: u16,
+        block_textures: &BlockTextures,
+    ) -> Geometry<BlockVertex, u16> {
+        let origin = self.position.cast::<f32>().unwrap();
+        let facing = self.facing.to_vector();
+
+        let base_max = origin + Vector3::new(1.0, 0.5, 1.0);
+        let mut geometry = self.cuboid_to_geometry(
+            origin,
+            base_max,
+            self.visible_faces | FACE_TOP,
+            start_index,
+            block_textures,
+        );
+
+        // The step covers the half of the top of the base the stairs are facing
+        let step_min = origin + facing.map(|n| n.max(0) as f32 * 0.5) + Vector3::new(0.0, 0.5, 0.0);
+        let step_max = origin + Vector3::new(1.0, 1.0, 1.0) + facing.map(|n| n.min(0) as f32 * 0.5);
+        let riser = match self.facing {
+            Facing::North => FACE_FRONT,
+            Facing::East => FACE_LEFT,
+            Facing::South => FACE_BACK,
+            Facing::West => FACE_RIGHT,
+        };
+        let step_faces = (self.visible_faces & !FACE_BOTTOM) | riser;
+        let start_index = start_index + geometry.vertices.len() as u16;
+        geometry.append(&mut self.cuboid_to_geometry(
+            step_min,
+            step_max,
+            step_faces,
+            start_index,
+            block_textures,
+        ));
+
+        geometry
+    }
 
-        if self.visible_faces & FACE_TOP == FACE_TOP {
-            let normal = Vector3::new(0, 1, 0);
-            let highlighted = (self.highlighted_normal == normal) as i32;
+    /// Converts the box from `min` to `max`, which should lie within the quad, to `Geometry`. This
+    /// is what every shape of block is built from.
+    ///
+    /// The texture coordinates follow the position within the quad, so the texture repeats over
+    /// quads wider than a block, and isn't stretched over faces smaller than one. Biome tints are
+    /// taken from the quad corner closest to each vertex.
+    #[allow(clippy::many_single_char_names)]
+    #[rustfmt::skip]
+    pub fn cuboid_to_geometry(
+        &self,
+        min: Point3<f32>,
+        max: Point3<f32>,
+        faces: FaceFlags,
+        start_index: u16,
+        block_textures: &BlockTextures,
+    ) -> Geometry<BlockVertex, u16> {
+        let (t, color) = match self.block_type {
+            Some(block_type) => (block_textures.faces(block_type), block_type.color()),
+            None => ((0, 0, 0, 0, 0, 0), Vector4::new(1.0, 1.0, 1.0, 1.0)),
+        };
+        let overlays = self.block_type.map_or([None; 6], |block_type| block_textures.overlays(block_type));
+
+        let origin = self.position.cast::<f32>().unwrap();
+        let (dx, dz) = (self.dx as f32, self.dz as f32);
+        let (x0, y0, z0) = min.into();
+        let (x1, y1, z1) = max.into();
+        let (u0, v0, w0) = (min - origin).into();
+        let (u1, v1, w1) = (max - origin).into();
+
+        // Returns the tint of the corner of the quad closest to the position
+        let tint = |position: [f32; 3]| {
+            let far_x = position[0] - origin.x > dx / 2.0;
+            let far_z = position[2] - origin.z > dz / 2.0;
+            match (far_x, far_z) {
+                (false, false) => self.tints[0],
+                (false, true) => self.tints[1],
+                (true, true) => self.tints[2],
+                (true, false) => self.tints[3],
+            }
+        };
+
+        let mut geometry = Geometry::default();
+        let mut add_face = |face: FaceFlags, normal: Vector3<i32>, texture_id: usize, corners: [([f32; 3], [f32; 2]); 4], order: [u16; 6]| {
+            if faces & face != face {
+                return;
+            }
+
+            let tinted = self.block_type.is_some_and(|block_type| block_type.is_biome_tinted(face));
+            let highlighted = if self.highlighted_normal == normal {
+                1
+            } else if self.type_highlighted {
+                2
+            } else {
+                0
+            };
+            let overlay = FACES.iter().position(|&(f, _)| f == face).and_then(|index| overlays[index]);
             let normal = normal.cast().unwrap().into();
-            vertices.extend([
-                BlockVertex { position: [x,      y + dy, z     ], texture_coordinates: [0.0, 0.0], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x,      y + dy, z + dz], texture_coordinates: [0.0, dz ], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z + dz], texture_coordinates: [dx,  dz ], texture_id: t.5 as i32, normal, highlighted, color },
-                BlockVertex { position: [x + dx, y + dy, z     ], texture_coordinates: [dx,  0.0], texture_id: t.5 as i32, normal, highlighted, color },
-            ]);
-            indices.extend([
-                current_index, 1 + current_index, 2 + current_index,
-                current_index, 2 + current_index, 3 + current_index,
-            ]);
+
+            let current_index = start_index + geometry.vertices.len() as u16;
+            geometry.vertices.extend(corners.iter().map(|&(position, texture_coordinates)| BlockVertex {
+                position,
+                texture_coordinates,
+                texture_id: texture_id as i32,
+                normal,
+                highlighted,
+                color: if tinted { color.mul_element_wise(tint(position)) } else { color }.into(),
+                // Overlays are grayscale, so they get the whole foliage color rather than the
+                // tint relative to the plains
+                overlay_texture_id: overlay.map_or(-1, |overlay| overlay as i32),
+                overlay_color: Biome::Plains.foliage_color().mul_element_wise(tint(position)).into(),
+            }));
+            geometry.indices.extend(order.iter().map(|i| current_index + i));
+        };
+
+        add_face(FACE_LEFT, Vector3::new(-1, 0, 0), t.0, [
+            ([x0, y0, z0], [dz - w0, 1.0 - v0]),
+            ([x0, y0, z1], [dz - w1, 1.0 - v0]),
+            ([x0, y1, z1], [dz - w1, 1.0 - v1]),
+            ([x0, y1, z0], [dz - w0, 1.0 - v1]),
+        ], [2, 0, 1, 3, 0, 2]);
+
+        add_face(FACE_RIGHT, Vector3::new(1, 0, 0), t.1, [
+            ([x1, y0, z0], [w0, 1.0 - v0]),
+            ([x1, y0, z1], [w1, 1.0 - v0]),
+            ([x1, y1, z1], [w1, 1.0 - v1]),
+            ([x1, y1, z0], [w0, 1.0 - v1]),
+        ], [1, 0, 2, 2, 0, 3]);
+
+        add_face(FACE_BACK, Vector3::new(0, 0, -1), t.2, [
+            ([x0, y0, z0], [dx - u0, 1.0 - v0]),
+            ([x0, y1, z0], [dx - u0, 1.0 - v1]),
+            ([x1, y1, z0], [dx - u1, 1.0 - v1]),
+            ([x1, y0, z0], [dx - u1, 1.0 - v0]),
+        ], [2, 0, 1, 3, 0, 2]);
+
+        add_face(FACE_FRONT, Vector3::new(0, 0, 1), t.3, [
+            ([x0, y0, z1], [u0, 1.0 - v0]),
+            ([x0, y1, z1], [u0, 1.0 - v1]),
+            ([x1, y1, z1], [u1, 1.0 - v1]),
+            ([x1, y0, z1], [u1, 1.0 - v0]),
+        ], [1, 0, 2, 2, 0, 3]);
+
+        add_face(FACE_BOTTOM, Vector3::new(0, -1, 0), t.4, [
+            ([x0, y0, z0], [dx - u0, w0]),
+            ([x0, y0, z1], [dx - u0, w1]),
+            ([x1, y0, z1], [dx - u1, w1]),
+            ([x1, y0, z0], [dx - u1, w0]),
+        ], [0, 2, 1, 0, 3, 2]);
+
+        add_face(FACE_TOP, Vector3::new(0, 1, 0), t.5, [
+            ([x0, y1, z0], [u0, w0]),
+            ([x0, y1, z1], [u0, w1]),
+            ([x1, y1, z1], [u1, w1]),
+            ([x1, y1, z0], [u1, w0]),
+        ], [0, 1, 2, 0, 2, 3]);
+
+        geometry
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cgmath::InnerSpace;
+
+    use super::*;
+
+    fn cube_geometry() -> Geometry<BlockVertex, u16> {
+        let quad = Quad::new(Point3::new(2, -3, 5), 1, 1);
+        let min = quad.position.cast().unwrap();
+        let max = min + Vector3::new(1.0, 1.0, 1.0);
+        quad.cuboid_to_geometry(min, max, FACE_ALL, 0, &BlockTextures::default())
+    }
+
+    #[test]
+    fn builds_a_cube_out_of_six_quads() {
+        let geometry = cube_geometry();
+        assert_eq!(geometry.vertices.len(), 24);
+        assert_eq!(geometry.indices.len(), 36);
+
+        // Four vertices facing every way
+        for &(_, normal) in &FACES {
+            let normal = Vector3::from(normal).cast::<f32>().unwrap();
+            let count = geometry
+                .vertices
+                .iter()
+                .filter(|vertex| Vector3::from(vertex.normal) == normal)
+                .count();
+            assert_eq!(count, 4, "{:?}", normal);
         }
+    }
 
-        Geometry::new(vertices, indices)
+    #[test]
+    fn winds_every_face_counterclockwise_from_the_outside() {
+        let geometry = cube_geometry();
+        for triangle in geometry.indices.chunks(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| &geometry.vertices[triangle[i] as usize]);
+            let [a_, b_, c_] = [a, b, c].map(|vertex| Vector3::from(vertex.position));
+            let winding = (b_ - a_).cross(c_ - a_);
+            let normal = Vector3::from(a.normal);
+            assert!(winding.dot(normal) > 0.0, "{:?}: {:?}", normal, winding);
+        }
     }
 }
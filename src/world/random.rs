@@ -0,0 +1,76 @@
+use cgmath::Point3;
+
+/// A small deterministic random number generator (SplitMix64).
+///
+/// Seeded from the world seed and a chunk's position, so generating the same chunk twice places
+/// the same decorations.
+#[derive(Clone)]
+pub struct ChunkRng {
+    state: u64,
+}
+
+impl ChunkRng {
+    pub fn new(seed: u32, position: Point3<isize>) -> Self {
+        // Cast to fixed-size integers so the hash is the same on every platform
+        let state = fxhash::hash64(&(
+            seed,
+            position.x as i64,
+            position.y as i64,
+            position.z as i64,
+        ));
+        Self { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Returns a number in the range `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        // The top 24 bits are exactly representable in an f32
+        (self.next_u64() >> 40) as f32 / (1 << 24) as f32
+    }
+
+    /// Returns a number in the range `[0, n)`.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn numbers(mut rng: ChunkRng) -> Vec<u64> {
+        (0..16).map(|_| rng.next_u64()).collect()
+    }
+
+    #[test]
+    fn repeats_for_the_same_seed_and_chunk() {
+        let position = Point3::new(3, -1, -7);
+        assert_eq!(
+            numbers(ChunkRng::new(42, position)),
+            numbers(ChunkRng::new(42, position))
+        );
+    }
+
+    #[test]
+    fn differs_between_seeds_and_chunks() {
+        let numbers_at = |seed, x| numbers(ChunkRng::new(seed, Point3::new(x, 0, 0)));
+        assert_ne!(numbers_at(42, 0), numbers_at(43, 0));
+        assert_ne!(numbers_at(42, 0), numbers_at(42, 1));
+    }
+
+    #[test]
+    fn stays_in_range() {
+        let mut rng = ChunkRng::new(0, Point3::new(0, 0, 0));
+        for _ in 0..1000 {
+            assert!((0.0..1.0).contains(&rng.next_f32()));
+            assert!(rng.below(5) < 5);
+        }
+    }
+}
@@ -0,0 +1,400 @@
+use cgmath::{num_traits::Inv, InnerSpace, Point3, Vector3};
+
+use crate::world::block_view::BlockView;
+
+/// How far a ray is followed before giving up, in blocks.
+const MAX_DISTANCE: f32 = 100.0;
+
+/// Enough steps to follow any ray up to `MAX_DISTANCE`, since every step crosses into the next
+/// cell along one of the three axes.
+pub const DEFAULT_MAX_STEPS: usize = MAX_DISTANCE as usize * 3;
+
+/// Follows a ray through the block grid, and returns the first block it hits, together with the
+/// normal of the face the ray entered it through.
+///
+/// Fluids (like water) are passed through, unless `stop_on_fluids` is set. Other transparent
+/// blocks, like glass, stop the ray like any other block. Gives up after stepping through
+/// `max_steps` cells, or once the ray is `MAX_DISTANCE` long, whichever comes first. The block
+/// the ray starts in is never hit.
+pub fn raycast(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    blocks: &impl BlockView,
+    max_steps: usize,
+    stop_on_fluids: bool,
+) -> Option<(Point3<isize>, Vector3<i32>)> {
+    let direction = direction.normalize();
+    let mut position: Point3<i32> = origin.map(|x| x.floor() as i32);
+    let step = direction.map(|x| x.signum() as i32);
+
+    // Algorithm from: http://www.cse.yorku.ca/%7Eamana/research/grid.pdf
+    fn dif_from_next(n: f32, n_step: i32) -> f32 {
+        if n_step < 0 {
+            // Difference between the next smallest integer and n
+            n.floor() - n
+        } else {
+            // Difference between the next biggest integer and n
+            (n + 1.0).floor() - n
+        }
+    }
+
+    let mut t_max_x = dif_from_next(origin.x, step.x) / direction.x;
+    let mut t_max_y = dif_from_next(origin.y, step.y) / direction.y;
+    let mut t_max_z = dif_from_next(origin.z, step.z) / direction.z;
+
+    let t_delta_x = direction.x.abs().inv();
+    let t_delta_y = direction.y.abs().inv();
+    let t_delta_z = direction.z.abs().inv();
+
+    let mut face;
+
+    for _ in 0..max_steps {
+        if t_max_x.min(t_max_y).min(t_max_z) >= MAX_DISTANCE {
+            break;
+        }
+
+        if t_max_x < t_max_y {
+            if t_max_x < t_max_z {
+                t_max_x += t_delta_x;
+                position.x += step.x;
+                face = Vector3::unit_x() * -step.x;
+            } else {
+                t_max_z += t_delta_z;
+                position.z += step.z;
+                face = Vector3::unit_z() * -step.z;
+            }
+        } else {
+            if t_max_y < t_max_z {
+                t_max_y += t_delta_y;
+                position.y += step.y;
+                face = Vector3::unit_y() * -step.y;
+            } else {
+                t_max_z += t_delta_z;
+                position.z += step.z;
+                face = Vector3::unit_z() * -step.z;
+            }
+        }
+
+        if let Some(block) = blocks.get_block(position.cast().unwrap()) {
+            if stop_on_fluids || !block.block_type.is_fluid() {
+                // Intersection occurred
+                return Some((position.cast().unwrap(), face));
+            }
+        }
+    }
+
+    None
+}
+
+/// Returns the cell a block placed against a raycast hit ends up in: the hit block's own cell if
+/// it can be replaced (like water), or else the cell in front of the face the ray hit.
+pub fn placement_position(
+    hit: Point3<isize>,
+    face: Vector3<i32>,
+    blocks: &impl BlockView,
+) -> Point3<isize> {
+    match blocks.get_block(hit) {
+        Some(block) if block.block_type.is_replaceable() => hit,
+        _ => hit + face.cast().unwrap(),
+    }
+}
+
+/// Returns the cells on the line between the centers of `from` and `to`, including both. Every
+/// cell shares a face with the one before it, so the line has no diagonal gaps.
+pub fn voxel_line(from: Point3<isize>, to: Point3<isize>) -> Vec<Point3<isize>> {
+    let delta = to - from;
+    let step = delta.map(|n| n.signum());
+
+    // How far along the line the next border is on every axis, and how far apart the borders are
+    let t_delta = delta.map(|n| {
+        if n == 0 {
+            f32::INFINITY
+        } else {
+            (n.abs() as f32).inv()
+        }
+    });
+    let mut t_max = t_delta / 2.0;
+
+    let mut position = from;
+    let mut line = vec![from];
+    while position != to {
+        let axis = (0..3)
+            .min_by(|&a, &b| t_max[a].partial_cmp(&t_max[b]).unwrap())
+            .unwrap();
+        position[axis] += step[axis];
+        t_max[axis] += t_delta[axis];
+        line.push(position);
+    }
+
+    line
+}
+
+/// Returns the cells a drag from `from` to `to` places blocks in: every cell on the line after
+/// `from`, which already has its block, except for the ones something is already in the way of.
+pub fn drag_line(
+    from: Point3<isize>,
+    to: Point3<isize>,
+    blocks: &impl BlockView,
+) -> Vec<Point3<isize>> {
+    voxel_line(from, to)
+        .into_iter()
+        .skip(1)
+        .filter(|&point| {
+            blocks
+                .get_block(point)
+                .is_none_or(|block| block.block_type.is_replaceable())
+        })
+        .collect()
+}
+
+/// Returns how far along the ray the face of the hit block is, where `hit` and `face` are what
+/// `raycast` returned for the same ray.
+pub fn hit_distance(
+    origin: Point3<f32>,
+    direction: Vector3<f32>,
+    hit: Point3<isize>,
+    face: Vector3<i32>,
+) -> f32 {
+    let direction = direction.normalize();
+    let hit = hit.cast::<f32>().unwrap();
+
+    // The face lies on the near side of the hit block, along the axis of its normal
+    let axis = (0..3).find(|&axis| face[axis] != 0).unwrap();
+    let plane = if face[axis] > 0 {
+        hit[axis] + 1.0
+    } else {
+        hit[axis]
+    };
+    (plane - origin[axis]) / direction[axis]
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use cgmath::{Point3, Vector3};
+
+    use super::*;
+    use crate::world::{
+        block::{Block, BlockType},
+        block_view::test_world::TestWorld,
+    };
+
+    /// Counts how many cells a raycast looks at.
+    #[derive(Default)]
+    struct CountingWorld {
+        world: TestWorld,
+        lookups: Cell<usize>,
+    }
+
+    impl BlockView for CountingWorld {
+        fn get_block(&self, point: Point3<isize>) -> Option<&Block> {
+            self.lookups.set(self.lookups.get() + 1);
+            self.world.get_block(point)
+        }
+    }
+
+    fn cast(
+        world: &TestWorld,
+        origin: Point3<f32>,
+        direction: Vector3<f32>,
+    ) -> Option<(Point3<isize>, Vector3<i32>)> {
+        raycast(origin, direction, world, DEFAULT_MAX_STEPS, false)
+    }
+
+    #[test]
+    fn hits_the_block_straight_ahead() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(3, 0, 0), BlockType::Stone);
+
+        let hit = cast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::unit_x());
+        assert_eq!(hit, Some((Point3::new(3, 0, 0), Vector3::new(-1, 0, 0))));
+    }
+
+    #[test]
+    fn misses_through_a_gap() {
+        let mut world = TestWorld::default();
+        for (y, z) in [(0, 1), (0, -1), (1, 0), (-1, 0)] {
+            world.set_block(Point3::new(3, y, z), BlockType::Stone);
+        }
+
+        assert_eq!(
+            cast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::unit_x()),
+            None
+        );
+    }
+
+    #[test]
+    fn hits_along_every_axis_through_the_facing_face() {
+        let directions = [
+            Vector3::unit_x(),
+            -Vector3::unit_x(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::unit_z(),
+            -Vector3::unit_z(),
+        ];
+        for direction in directions {
+            let target = Point3::new(0, 0, 0) + direction.cast::<isize>().unwrap() * 5;
+            let mut world = TestWorld::default();
+            world.set_block(target, BlockType::Stone);
+
+            let hit = cast(&world, Point3::new(0.5, 0.5, 0.5), direction);
+            assert_eq!(hit, Some((target, -direction.cast::<i32>().unwrap())));
+        }
+    }
+
+    #[test]
+    fn follows_diagonal_rays_cell_by_cell() {
+        // From the center of (0, 0, 0), the ray crosses into (1, 0, 0), then (1, 1, 0), and then
+        // into (2, 1, 0) through its -X face. It never passes through (2, 0, 0).
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(2, 0, 0), BlockType::Stone);
+        world.set_block(Point3::new(2, 1, 0), BlockType::Dirt);
+
+        let hit = cast(
+            &world,
+            Point3::new(0.5, 0.5, 0.5),
+            Vector3::new(1.0, 0.5, 0.0),
+        );
+        assert_eq!(hit, Some((Point3::new(2, 1, 0), Vector3::new(-1, 0, 0))));
+    }
+
+    #[test]
+    fn handles_negative_coordinates() {
+        // (-0.5, -0.5, -0.5) lies in cell (-1, -1, -1), not in (0, 0, 0)
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(0, 0, -3), BlockType::Stone);
+        world.set_block(Point3::new(-1, -1, -3), BlockType::Dirt);
+
+        let hit = cast(&world, Point3::new(-0.5, -0.5, -0.5), -Vector3::unit_z());
+        assert_eq!(hit, Some((Point3::new(-1, -1, -3), Vector3::new(0, 0, 1))));
+    }
+
+    #[test]
+    fn never_hits_the_starting_block() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(0, 0, 0), BlockType::Stone);
+
+        assert_eq!(
+            cast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::unit_x()),
+            None
+        );
+    }
+
+    #[test]
+    fn gives_up_after_the_maximum_distance() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(150, 0, 0), BlockType::Stone);
+
+        assert_eq!(
+            cast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::unit_x()),
+            None
+        );
+    }
+
+    #[test]
+    fn passes_through_water_to_the_block_behind_it() {
+        let mut world = TestWorld::default();
+        for x in 1..3 {
+            for y in -2..3 {
+                world.set_block(Point3::new(x, y, 0), BlockType::Water);
+            }
+        }
+        world.set_block(Point3::new(3, 0, 0), BlockType::Stone);
+
+        let origin = Point3::new(0.5, 0.5, 0.5);
+        let hit = cast(&world, origin, Vector3::unit_x());
+        assert_eq!(hit, Some((Point3::new(3, 0, 0), Vector3::new(-1, 0, 0))));
+
+        let hit = raycast(origin, Vector3::unit_x(), &world, DEFAULT_MAX_STEPS, true);
+        assert_eq!(hit, Some((Point3::new(1, 0, 0), Vector3::new(-1, 0, 0))));
+    }
+
+    #[test]
+    fn stops_on_glass() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(2, 0, 0), BlockType::Glass);
+        world.set_block(Point3::new(3, 0, 0), BlockType::Stone);
+
+        let hit = cast(&world, Point3::new(0.5, 0.5, 0.5), Vector3::unit_x());
+        assert_eq!(hit, Some((Point3::new(2, 0, 0), Vector3::new(-1, 0, 0))));
+    }
+
+    #[test]
+    fn places_into_the_water_it_hits() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(0, -1, 0), BlockType::Sand);
+        for y in 0..3 {
+            world.set_block(Point3::new(0, y, 0), BlockType::Water);
+        }
+
+        // Looking down at the surface of the water column displaces its top block
+        let origin = Point3::new(0.5, 5.5, 0.5);
+        let (hit, face) =
+            raycast(origin, -Vector3::unit_y(), &world, DEFAULT_MAX_STEPS, true).unwrap();
+        assert_eq!((hit, face), (Point3::new(0, 2, 0), Vector3::unit_y()));
+        assert_eq!(placement_position(hit, face, &world), Point3::new(0, 2, 0));
+    }
+
+    #[test]
+    fn places_next_to_solid_blocks() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(3, 0, 0), BlockType::Stone);
+
+        let hit = Point3::new(3, 0, 0);
+        let face = Vector3::new(-1, 0, 0);
+        assert_eq!(placement_position(hit, face, &world), Point3::new(2, 0, 0));
+    }
+
+    #[test]
+    fn stops_after_the_maximum_steps() {
+        let mut world = CountingWorld::default();
+        world
+            .world
+            .set_block(Point3::new(20, 0, 0), BlockType::Stone);
+        let origin = Point3::new(0.5, 0.5, 0.5);
+
+        assert_eq!(raycast(origin, Vector3::unit_x(), &world, 10, false), None);
+        assert_eq!(world.lookups.get(), 10);
+
+        let hit = raycast(origin, Vector3::unit_x(), &world, 20, false);
+        assert_eq!(hit, Some((Point3::new(20, 0, 0), Vector3::new(-1, 0, 0))));
+    }
+
+    #[test]
+    fn bounds_rays_into_empty_space() {
+        // Nothing stops the ray, and it's nowhere near the maximum distance after 50 steps
+        let world = CountingWorld::default();
+        let direction = Vector3::new(1.0, 1e-6, 1e-6);
+
+        let hit = raycast(Point3::new(0.5, 0.5, 0.5), direction, &world, 50, false);
+        assert_eq!(hit, None);
+        assert_eq!(world.lookups.get(), 50);
+    }
+
+    #[test]
+    fn drags_a_line_through_every_cell() {
+        let mut world = TestWorld::default();
+        world.set_block(Point3::new(3, 2, 1), BlockType::Water);
+        world.set_block(Point3::new(4, 2, 1), BlockType::Stone);
+
+        let from = Point3::new(-2, 2, -1);
+        let to = Point3::new(6, 2, 2);
+        let line = voxel_line(from, to);
+        assert_eq!(line.first(), Some(&from));
+        assert_eq!(line.last(), Some(&to));
+        for cells in line.windows(2) {
+            let step = cells[1] - cells[0];
+            assert_eq!(step.x.abs() + step.y.abs() + step.z.abs(), 1);
+        }
+
+        // The water is replaced, but the stone stays where it is
+        let placed = drag_line(from, to, &world);
+        assert!(placed.contains(&Point3::new(3, 2, 1)));
+        assert!(!placed.contains(&Point3::new(4, 2, 1)));
+        assert_eq!(placed.len(), line.len() - 2);
+        assert!(!placed.contains(&from));
+        assert!(placed.contains(&to));
+    }
+}
@@ -0,0 +1,91 @@
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, BindGroupLayout, Buffer,
+};
+
+use crate::{render_context::RenderContext, settings::GraphicsSettings, world::DebugView};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct RenderSettingsRaw {
+    /// The color blended over the highlighted block. Alpha is how strongly it's blended.
+    highlight_color: [f32; 4],
+    /// The tint of water. Alpha multiplies how opaque its texture is.
+    water_color: [f32; 4],
+    /// How much the water's surface wobbles.
+    water_distortion: f32,
+    /// What the world shader shows, as a `DebugView`.
+    debug_view: u32,
+    _padding: [f32; 2],
+}
+
+/// The parts of the graphics settings the world shader reads, in a uniform of their own. They
+/// only change when the debug view does, so unlike the time they aren't written every frame.
+pub struct RenderSettings {
+    raw: RenderSettingsRaw,
+    buffer: Buffer,
+    pub bind_group_layout: BindGroupLayout,
+    pub bind_group: BindGroup,
+}
+
+impl RenderSettings {
+    pub fn new(render_context: &RenderContext, settings: &GraphicsSettings) -> Self {
+        let device = &render_context.device;
+        let water_color = settings.water_color();
+        let raw = RenderSettingsRaw {
+            highlight_color: settings.highlight_color,
+            water_color: [
+                water_color.r as f32,
+                water_color.g as f32,
+                water_color.b as f32,
+                settings.water_opacity.max(0.0),
+            ],
+            water_distortion: settings.water_distortion.max(0.0),
+            debug_view: DebugView::Color as u32,
+            _padding: [0.0; 2],
+        };
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("render_settings_buffer"),
+            contents: bytemuck::cast_slice(&[raw]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX | wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("render_settings_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("render_settings_bind_group"),
+        });
+
+        Self {
+            raw,
+            buffer,
+            bind_group_layout,
+            bind_group,
+        }
+    }
+
+    pub fn set_debug_view(&mut self, render_context: &RenderContext, debug_view: DebugView) {
+        self.raw.debug_view = debug_view as u32;
+        render_context
+            .queue
+            .write_buffer(&self.buffer, 0, bytemuck::cast_slice(&[self.raw]));
+    }
+}
@@ -0,0 +1,126 @@
+use std::borrow::Cow;
+
+use wgpu::{
+    util::{BufferInitDescriptor, DeviceExt},
+    BindGroup, RenderPass, RenderPipeline,
+};
+
+use crate::{render_context::RenderContext, texture::Texture, view::View};
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct SkyRaw {
+    horizon_color: [f32; 4],
+    zenith_color: [f32; 4],
+}
+
+/// Fills the background with a gradient from the horizon to the zenith, by drawing a triangle
+/// over the whole screen and working out which way every pixel looks.
+pub struct Sky {
+    pipeline: RenderPipeline,
+    bind_group: BindGroup,
+}
+
+impl Sky {
+    pub fn new(
+        render_context: &RenderContext,
+        view: &View,
+        horizon_color: wgpu::Color,
+        zenith_color: wgpu::Color,
+    ) -> Self {
+        let device = &render_context.device;
+        let to_array = |color: wgpu::Color| {
+            [
+                color.r as f32,
+                color.g as f32,
+                color.b as f32,
+                color.a as f32,
+            ]
+        };
+
+        let buffer = device.create_buffer_init(&BufferInitDescriptor {
+            label: Some("sky buffer"),
+            contents: bytemuck::cast_slice(&[SkyRaw {
+                horizon_color: to_array(horizon_color),
+                zenith_color: to_array(zenith_color),
+            }]),
+            usage: wgpu::BufferUsages::UNIFORM,
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+            label: Some("sky_bind_group_layout"),
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+            label: Some("sky_bind_group"),
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sky_pipeline_layout"),
+            push_constant_ranges: &[],
+            bind_group_layouts: &[&view.bind_group_layout, &bind_group_layout],
+        });
+
+        let shader = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("sky shader"),
+            source: wgpu::ShaderSource::Wgsl(Cow::Borrowed(include_str!("../shaders/sky.wgsl"))),
+        });
+
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("Sky pipeline"),
+            layout: Some(&layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "main",
+                targets: &[wgpu::ColorTargetState {
+                    format: render_context.format,
+                    blend: Some(wgpu::BlendState::REPLACE),
+                    write_mask: wgpu::ColorWrites::ALL,
+                }],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            // The sky is drawn first and behind everything, so it leaves the depth buffer alone
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: Texture::DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState::default(),
+        });
+
+        Self {
+            pipeline,
+            bind_group,
+        }
+    }
+
+    pub fn render<'a>(&'a self, render_pass: &mut RenderPass<'a>, view: &'a View) -> usize {
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &view.bind_group, &[]);
+        render_pass.set_bind_group(1, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        1
+    }
+}
@@ -0,0 +1,152 @@
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::{
+    settings::SpawnSettings,
+    world::{block_view::BlockView, random::ChunkRng},
+};
+
+/// How many positions are tried per spawn before giving up until the next one.
+const ATTEMPTS: usize = 8;
+/// NPCs don't spawn within this angle of where the player is looking, so they don't pop into
+/// view. It's the cosine of the angle, about 60 degrees.
+const VIEW_CONE_COS: f32 = 0.5;
+
+/// Whether an NPC is allowed to spawn while there are `count` NPCs: hostile ones never spawn in
+/// `peaceful` mode, and none spawn once there are `max_entities`.
+pub fn allows_spawn(hostile: bool, count: usize, peaceful: bool, max_entities: usize) -> bool {
+    !(peaceful && hostile) && count < max_entities
+}
+
+/// Picks a spot for an NPC to spawn, somewhere in the ring between `min_radius` and
+/// `max_radius` blocks around `center`, and not in front of `direction`. The spot has to be
+/// air, with air above it and a solid block below it. Returns the position of the NPC's feet, or
+/// `None` if none of the positions tried were any good.
+pub fn spawn_position(
+    rng: &mut ChunkRng,
+    center: Point3<f32>,
+    direction: Vector3<f32>,
+    settings: &SpawnSettings,
+    blocks: &impl BlockView,
+) -> Option<Point3<f32>> {
+    let min_radius = settings.min_radius.max(0.0);
+    let max_radius = settings.max_radius.max(min_radius);
+    let forward = Vector3::new(direction.x, 0.0, direction.z);
+
+    for _ in 0..ATTEMPTS {
+        // Taking the square root spreads the positions out evenly over the ring's area
+        let angle = rng.next_f32() * std::f32::consts::TAU;
+        let t = rng.next_f32();
+        let radius = (min_radius.powi(2) + t * (max_radius.powi(2) - min_radius.powi(2))).sqrt();
+        let offset = Vector3::new(angle.cos(), 0.0, angle.sin()) * radius;
+
+        if forward.magnitude2() > 0.0 && forward.normalize().dot(offset.normalize()) > VIEW_CONE_COS
+        {
+            continue;
+        }
+
+        let column = (center + offset).map(|x| x.floor() as isize);
+        if let Some(feet) = find_ground(blocks, column, max_radius.ceil() as isize) {
+            let position = Point3::new(center.x + offset.x, feet as f32, center.z + offset.z);
+            // Flooring can put the column just inside the ring's inner edge
+            if (position - center).magnitude() >= min_radius {
+                return Some(position);
+            }
+        }
+    }
+
+    None
+}
+
+/// Looks up and down from `column` for the highest spot, at most `reach` blocks away, that's
+/// standing on a solid block with room for an NPC. Returns the height of that spot.
+fn find_ground(blocks: &impl BlockView, column: Point3<isize>, reach: isize) -> Option<isize> {
+    let is_air = |y| {
+        blocks
+            .get_block(Point3::new(column.x, y, column.z))
+            .is_none()
+    };
+    ((column.y - reach)..=(column.y + reach)).rev().find(|&y| {
+        let ground = blocks.get_block(Point3::new(column.x, y - 1, column.z));
+        ground.is_some_and(|ground| {
+            ground.block_type.is_full_cube() && !ground.block_type.is_transparent()
+        }) && is_air(y)
+            && is_air(y + 1)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::{block::BlockType, block_view::test_world::TestWorld};
+
+    /// A stone floor under y = 10, reaching past the spawn ring around the origin.
+    fn floor() -> TestWorld {
+        let mut world = TestWorld::default();
+        for (x, z) in itertools::iproduct!(-50..50, -50..50) {
+            world.set_block(Point3::new(x, 9, z), BlockType::Stone);
+        }
+        world
+    }
+
+    #[test]
+    fn stops_spawning_at_the_cap() {
+        let mut count = 0;
+        for _ in 0..10 {
+            if allows_spawn(false, count, false, 4) {
+                count += 1;
+            }
+        }
+        assert_eq!(count, 4);
+    }
+
+    #[test]
+    fn spawns_no_hostile_npcs_in_peaceful_mode() {
+        assert!(!allows_spawn(true, 0, true, 16));
+        assert!(allows_spawn(false, 0, true, 16));
+        assert!(allows_spawn(true, 0, false, 16));
+    }
+
+    #[test]
+    fn spawns_on_the_ground_in_the_ring() {
+        let world = floor();
+        let settings = SpawnSettings::default();
+        let center = Point3::new(0.5, 10.0, 0.5);
+        let direction = Vector3::unit_x();
+
+        let mut spawns = 0;
+        for seed in 0..100 {
+            let mut rng = ChunkRng::new(seed, Point3::new(0, 0, 0));
+            let position = match spawn_position(&mut rng, center, direction, &settings, &world) {
+                Some(position) => position,
+                None => continue,
+            };
+            spawns += 1;
+
+            let offset = position - center;
+            assert!(offset.magnitude() >= settings.min_radius);
+            assert!(offset.magnitude() <= settings.max_radius);
+            assert!(offset.normalize().dot(direction) <= VIEW_CONE_COS);
+
+            let feet = position.map(|x| x.floor() as isize);
+            assert_eq!(feet.y, 10);
+            assert!(world.get_block(feet - Vector3::unit_y()).is_some());
+            assert!(world.get_block(feet).is_none());
+        }
+        assert!(spawns > 90, "only {} of 100 spawns found a spot", spawns);
+    }
+
+    #[test]
+    fn spawns_nowhere_without_ground() {
+        let settings = SpawnSettings::default();
+        let mut rng = ChunkRng::new(0, Point3::new(0, 0, 0));
+        let center = Point3::new(0.5, 10.0, 0.5);
+        let position = spawn_position(
+            &mut rng,
+            center,
+            Vector3::unit_x(),
+            &settings,
+            &TestWorld::default(),
+        );
+        assert_eq!(position, None);
+    }
+}
@@ -0,0 +1,242 @@
+use std::time::Duration;
+
+use cgmath::{InnerSpace, Point3, Vector3};
+
+use crate::world::{
+    chunk::{CHUNK_ISIZE, CHUNK_SIZE},
+    WORLD_HEIGHT,
+};
+
+/// Returns the chunks within `render_distance` chunks of `center_chunk` horizontally, from the
+/// bottom of the world to the top, that `is_missing` says still have to be loaded.
+pub fn chunks_around(
+    center_chunk: Point3<isize>,
+    render_distance: isize,
+    is_missing: impl Fn(Point3<isize>) -> bool,
+) -> Vec<Point3<isize>> {
+    itertools::iproduct!(
+        -render_distance..render_distance,
+        0..WORLD_HEIGHT,
+        -render_distance..render_distance
+    )
+    .map(|(x, y, z)| Point3::new(x + center_chunk.x, y, z + center_chunk.z))
+    .filter(|&point| is_missing(point))
+    .collect()
+}
+
+/// Sorts chunks into the order they're loaded in around `center`. With a view `direction`, that's
+/// by `load_priority`, so the chunks in view come first. Without one, the closest chunks come
+/// first.
+pub fn sort_load_queue(
+    queue: &mut [Point3<isize>],
+    center: Point3<f32>,
+    direction: Option<Vector3<f32>>,
+) {
+    match direction {
+        Some(direction) => queue.sort_by(|a, b| {
+            let a = load_priority(*a, center, direction);
+            let b = load_priority(*b, center, direction);
+            a.total_cmp(&b)
+        }),
+        None => {
+            let center = center.map(|x| x.floor() as isize);
+            queue.sort_unstable_by_key(|chunk| {
+                let offset = chunk * CHUNK_ISIZE - center;
+                offset.x.abs() + offset.y.abs() + offset.z.abs()
+            });
+        }
+    }
+}
+
+/// The order chunks are loaded in when the ones in view go first: lower goes first. It's the
+/// distance from the camera to the chunk's center, stretched up to three times as far for chunks
+/// behind the camera, so nearby chunks behind the player still load before far away ones in
+/// front.
+fn load_priority(chunk: Point3<isize>, camera: Point3<f32>, direction: Vector3<f32>) -> f32 {
+    let center = (chunk * CHUNK_ISIZE).cast::<f32>().unwrap()
+        + Vector3::new(1.0, 1.0, 1.0) * (CHUNK_SIZE as f32 / 2.0);
+    let offset = center - camera;
+    let distance = offset.magnitude();
+    if distance == 0.0 {
+        return 0.0;
+    }
+    let facing = offset.dot(direction) / distance;
+    distance * (2.0 - facing)
+}
+
+/// Returns the chunks of `chunks` that are far enough away from `center` to unload: more than one
+/// and a half times the render distance away along X or Z.
+pub fn chunks_to_unload(
+    chunks: impl IntoIterator<Item = Point3<isize>>,
+    center: Point3<f32>,
+    render_distance: isize,
+) -> Vec<Point3<isize>> {
+    let unload_distance = (render_distance * CHUNK_ISIZE) as f32 * 1.5;
+    chunks
+        .into_iter()
+        .filter(|point| {
+            let position = (point * CHUNK_ISIZE).cast::<f32>().unwrap();
+            (position.x - center.x).abs() > unload_distance
+                || (position.z - center.z).abs() > unload_distance
+        })
+        .collect()
+}
+
+/// Runs `update` until it runs out of work, it has run `max_updates` times, or `elapsed` reaches
+/// `budget`. The first update always runs, however long the frame has taken so far, so streaming
+/// never stalls. `update` returns whether it did anything. Returns how many updates ran.
+pub fn run_chunk_updates(
+    max_updates: usize,
+    budget: Duration,
+    elapsed: impl Fn() -> Duration,
+    mut update: impl FnMut() -> bool,
+) -> usize {
+    let mut updates = 0;
+    while updates < max_updates && (updates == 0 || elapsed() < budget) {
+        if !update() {
+            break;
+        }
+        updates += 1;
+    }
+    updates
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use super::*;
+
+    const BUDGET: Duration = Duration::from_millis(15);
+
+    /// The middle of chunk (5, 2, -7), far away from the origin.
+    const CENTER: Point3<f32> = Point3::new(176.0, 80.0, -208.0);
+
+    #[test]
+    fn loads_the_chunks_around_the_center() {
+        let center_chunk = Point3::new(5, 2, -7);
+        let loaded = Point3::new(5, 0, -7);
+        let chunks = chunks_around(center_chunk, 3, |point| point != loaded);
+
+        assert_eq!(chunks.len(), 6 * 6 * WORLD_HEIGHT as usize - 1);
+        assert!(!chunks.contains(&loaded));
+        for chunk in chunks {
+            assert!((2..8).contains(&chunk.x));
+            assert!((0..WORLD_HEIGHT).contains(&chunk.y));
+            assert!((-10..-4).contains(&chunk.z));
+        }
+    }
+
+    #[test]
+    fn loads_the_closest_chunks_first() {
+        let mut queue = chunks_around(Point3::new(5, 2, -7), 3, |_| true);
+        sort_load_queue(&mut queue, CENTER, None);
+
+        let distance = |chunk: &Point3<isize>| {
+            let offset = (chunk * CHUNK_ISIZE).cast::<f32>().unwrap() - CENTER;
+            offset.x.abs() + offset.y.abs() + offset.z.abs()
+        };
+        assert_eq!(queue[0], Point3::new(5, 2, -7));
+        assert!(queue
+            .windows(2)
+            .all(|pair| distance(&pair[0]) <= distance(&pair[1])));
+    }
+
+    #[test]
+    fn loads_the_chunks_in_view_first() {
+        let mut queue = vec![
+            Point3::new(1, 2, -7),
+            Point3::new(3, 2, -7),
+            Point3::new(4, 2, -7),
+            Point3::new(6, 2, -7),
+            Point3::new(9, 2, -7),
+        ];
+        sort_load_queue(&mut queue, CENTER, Some(Vector3::unit_x()));
+
+        // Close chunks behind the camera still go before far ones in front of it
+        let order: Vec<_> = queue.iter().map(|chunk| chunk.x).collect();
+        assert_eq!(order, [6, 4, 9, 3, 1]);
+
+        sort_load_queue(&mut queue, CENTER, Some(-Vector3::unit_x()));
+        let order: Vec<_> = queue.iter().map(|chunk| chunk.x).collect();
+        assert_eq!(order, [4, 3, 6, 1, 9]);
+    }
+
+    #[test]
+    fn unloads_the_chunks_far_from_the_center() {
+        // Chunks more than 1.5 * 4 * 32 = 192 blocks away along X or Z unload
+        let chunks = vec![
+            Point3::new(5, 0, -7),
+            Point3::new(10, 6, -12),
+            Point3::new(12, 0, -7),
+            Point3::new(5, 7, -12),
+            Point3::new(-1, 0, -7),
+            Point3::new(0, 0, 0),
+        ];
+        let unloaded = chunks_to_unload(chunks, CENTER, 4);
+        assert_eq!(
+            unloaded,
+            [
+                Point3::new(12, 0, -7),
+                Point3::new(-1, 0, -7),
+                Point3::new(0, 0, 0)
+            ]
+        );
+    }
+
+    #[test]
+    fn stops_at_the_cap() {
+        let mut pending = 10;
+        let updates = run_chunk_updates(
+            3,
+            BUDGET,
+            || Duration::ZERO,
+            || {
+                pending -= 1;
+                true
+            },
+        );
+        assert_eq!(updates, 3);
+        assert_eq!(pending, 7);
+    }
+
+    #[test]
+    fn stops_when_the_work_runs_out() {
+        let mut pending = 2;
+        let updates = run_chunk_updates(
+            10,
+            BUDGET,
+            || Duration::ZERO,
+            || {
+                if pending == 0 {
+                    return false;
+                }
+                pending -= 1;
+                true
+            },
+        );
+        assert_eq!(updates, 2);
+    }
+
+    #[test]
+    fn stops_when_the_time_is_up() {
+        // Every update takes 4 ms, so the fourth starts at 12 ms and the fifth would at 16 ms
+        let elapsed = Cell::new(Duration::ZERO);
+        let updates = run_chunk_updates(
+            10,
+            BUDGET,
+            || elapsed.get(),
+            || {
+                elapsed.set(elapsed.get() + Duration::from_millis(4));
+                true
+            },
+        );
+        assert_eq!(updates, 4);
+    }
+
+    #[test]
+    fn always_runs_one_update() {
+        let updates = run_chunk_updates(10, BUDGET, || BUDGET * 2, || true);
+        assert_eq!(updates, 1);
+    }
+}
@@ -0,0 +1,107 @@
+use std::collections::VecDeque;
+
+/// Holds data that's waiting to be uploaded to the GPU, so uploads can be spread over several
+/// frames. Every item is stored under a key (like a chunk position), and only the latest item
+/// for a key is kept.
+pub struct UploadQueue<K, T> {
+    pending: VecDeque<(K, T, usize)>,
+}
+
+impl<K: PartialEq, T> Default for UploadQueue<K, T> {
+    fn default() -> Self {
+        Self {
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+impl<K: PartialEq, T> UploadQueue<K, T> {
+    /// Queues `item`, which takes up `size` bytes. If there's already an item queued for `key`,
+    /// it's replaced, but keeps its place in the queue.
+    pub fn push(&mut self, key: K, item: T, size: usize) {
+        match self.pending.iter_mut().find(|(key_, _, _)| key_ == &key) {
+            Some(entry) => *entry = (key, item, size),
+            None => self.pending.push_back((key, item, size)),
+        }
+    }
+
+    /// Drops the item queued for `key`, if there is one.
+    pub fn remove(&mut self, key: &K) {
+        self.pending.retain(|(key_, _, _)| key_ != key);
+    }
+
+    /// Takes items off the front of the queue until the next one would bring their total size
+    /// over `budget` bytes. The first item is always taken, even if it's over budget by itself, so
+    /// the queue keeps moving.
+    pub fn pop_within(&mut self, budget: usize) -> Vec<(K, T)> {
+        let mut items = Vec::new();
+        let mut total = 0;
+        while let Some((_, _, size)) = self.pending.front() {
+            if !items.is_empty() && total + size > budget {
+                break;
+            }
+
+            let (key, item, size) = self.pending.pop_front().unwrap();
+            total += size;
+            items.push((key, item));
+        }
+        items
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn takes_items_in_order_within_the_budget() {
+        let mut queue = UploadQueue::default();
+        queue.push(1, 'a', 40);
+        queue.push(2, 'b', 40);
+        queue.push(3, 'c', 40);
+
+        assert_eq!(queue.pop_within(100), vec![(1, 'a'), (2, 'b')]);
+        assert_eq!(queue.pop_within(100), vec![(3, 'c')]);
+        assert_eq!(queue.pop_within(100), vec![]);
+    }
+
+    #[test]
+    fn always_takes_the_first_item() {
+        let mut queue = UploadQueue::default();
+        queue.push(1, 'a', 500);
+        queue.push(2, 'b', 10);
+
+        assert_eq!(queue.pop_within(100), vec![(1, 'a')]);
+        assert_eq!(queue.pop_within(100), vec![(2, 'b')]);
+    }
+
+    #[test]
+    fn replaces_items_in_place() {
+        let mut queue = UploadQueue::default();
+        queue.push(1, 'a', 10);
+        queue.push(2, 'b', 10);
+        queue.push(1, 'c', 10);
+
+        assert_eq!(queue.pop_within(usize::MAX), vec![(1, 'c'), (2, 'b')]);
+    }
+
+    #[test]
+    fn counts_the_size_of_replaced_items() {
+        let mut queue = UploadQueue::default();
+        queue.push(1, 'a', 10);
+        queue.push(2, 'b', 10);
+        queue.push(1, 'c', 95);
+
+        assert_eq!(queue.pop_within(100), vec![(1, 'c')]);
+    }
+
+    #[test]
+    fn drops_removed_items() {
+        let mut queue = UploadQueue::default();
+        queue.push(1, 'a', 10);
+        queue.push(2, 'b', 10);
+        queue.remove(&1);
+
+        assert_eq!(queue.pop_within(usize::MAX), vec![(2, 'b')]);
+    }
+}